@@ -0,0 +1,312 @@
+// Copyright © 2022 Mark Summerfield. All rights reserved.
+// License: GPLv3
+
+/*! An Appender appends records to a UXF file whose value is a table (e.g.,
+an event log) without rewriting the file.
+
+The file is parsed once, when the Appender is opened, to validate its
+header and TClass. Thereafter each append checks (and if necessary
+repairs, as per the `RepairPolicy`) the records' values against the
+TClass's field vtypes, and then writes the records' text in place of the
+table's closing `)`, followed by the `)`, matching the table's existing
+layout.
+
+For compressed files (whose codec is detected from their magic bytes,
+whatever their names; see the `codec` module), the closing `)` is kept in
+its own final compressed stream (e.g., gzip member), so each append
+replaces just that stream with a stream for the records followed by the
+closing stream. (The first append to a compressed file that wasn't
+written this way rewrites it once.) The result is a file of concatenated streams which
+`uxf::parse()` (and, e.g., `gzip -d`) read as one.
+
+An Appender isn't safe for concurrent use by several processes (use a
+lock file if necessary), and the file mustn't be changed by anything
+else while it is open.
+
+```no_run
+use uxf::append::Appender;
+
+// events.uxf contains, e.g., uxf 1\n=Event name:str when:datetime\n(Event)
+let mut appender = Appender::open("events.uxf").unwrap();
+let now = chrono::Local::now().naive_local();
+appender.append(vec!["started".into(), now.into()]).unwrap();
+```
+*/
+use crate::codec::Codec;
+use crate::event::{self, OnEventFn};
+use crate::parser;
+use crate::repair::{apply_repair, repaired, RepairPolicy};
+use crate::table::Table;
+use crate::tclass::TClass;
+use crate::util::{read_file, write_bytes};
+use crate::uxf::{ParserOptions, Uxf, WriteOptions};
+use crate::value::Record;
+use anyhow::{bail, Context, Result};
+use std::{
+    fs::{self, OpenOptions},
+    io::{Read, Seek, SeekFrom, Write},
+    rc::Rc,
+};
+
+pub struct Appender {
+    filename: String,
+    codec: Codec,
+    uxo: Uxf, // with the file's TClasses but an empty table value
+    table: Table, // empty and used for checking records
+    repair_policy: RepairPolicy,
+    on_event: OnEventFn,
+    indent: Option<String>, // Some for one record per line with this indent
+    is_empty: bool,
+    close: String, // the text from the closing ) to the end of the file
+}
+
+impl Appender {
+    /// Opens the given UXF file for appending (using the default
+    /// `RepairPolicy` and event handler) _or_ returns an Err if the file
+    /// can't be parsed or its value isn't a table with fields.
+    pub fn open(filename: &str) -> Result<Self> {
+        Appender::open_policy(filename, &RepairPolicy::default(), None)
+    }
+
+    /// This is the same as `open()` except that it uses the given
+    /// `RepairPolicy` (for parsing and for appended records) and
+    /// `on_event` handler (or the default handler if `None`).
+    pub fn open_policy(
+        filename: &str,
+        repair_policy: &RepairPolicy,
+        on_event: Option<OnEventFn>,
+    ) -> Result<Self> {
+        let on_event = on_event.unwrap_or_else(|| Rc::new(event::on_event));
+        let bytes = fs::read(filename)
+            .with_context(|| format!("E950:{filename}:0:failed to open"))?;
+        let codec = Codec::for_bytes(&bytes);
+        let text = String::from_utf8(codec.decompress(&bytes, filename)?)
+            .with_context(|| {
+            format!("E952:{filename}:0:failed to read")
+        })?;
+        let parsed = parser::parse(
+            &text,
+            filename,
+            ParserOptions::DEFAULT,
+            repair_policy,
+            Rc::clone(&on_event),
+        )?;
+        let table = match parsed.value().as_table() {
+            Some(table) if !table.is_fieldless() => table,
+            Some(_) => {
+                bail!("E861:{filename}:0:can't append to a fieldless table")
+            }
+            None => bail!(
+                "E860:{filename}:0:can only append to a file whose value \
+                is a table, got {}",
+                parsed.value().typename()
+            ),
+        };
+        let (indent, close) = layout(&text);
+        let mut uxo = Uxf::new(parsed.custom(), parsed.comment());
+        uxo.tclass_for_ttype = parsed.tclass_for_ttype.clone();
+        Ok(Appender {
+            filename: filename.to_string(),
+            codec,
+            uxo,
+            table: Table::new(table.tclass().clone(), ""),
+            repair_policy: repair_policy.clone(),
+            on_event,
+            indent,
+            is_empty: table.is_empty(),
+            close,
+        })
+    }
+
+    /// Returns the TClass of the file's table.
+    pub fn tclass(&self) -> &TClass {
+        self.table.tclass()
+    }
+
+    /// Appends the given record to the file's table _or_ returns an Err
+    /// (and appends nothing) if the record has the wrong number of values
+    /// or a value doesn't match (and can't be repaired to match) its
+    /// field's vtype, or if the file can't be written.
+    pub fn append(&mut self, record: Record) -> Result<()> {
+        self.append_many(vec![record])
+    }
+
+    /// Appends the given records to the file's table in a single write
+    /// _or_ returns an Err (and appends nothing) if any record is invalid
+    /// (see `append()`) or if the file can't be written.
+    pub fn append_many(&mut self, records: Vec<Record>) -> Result<()> {
+        let mut text = String::new();
+        let mut is_empty = self.is_empty;
+        for mut record in records {
+            self.repair(&mut record)?;
+            self.table.try_append(record, Some(&self.uxo))?;
+            // safe to unwrap since we just appended it
+            let record = self.table.remove(0).unwrap();
+            let values: Vec<String> =
+                record.iter().map(|value| value.to_string()).collect();
+            match &self.indent {
+                Some(indent) => {
+                    text.push_str(indent);
+                    text.push_str(&values.join(" "));
+                    text.push('\n');
+                }
+                None => {
+                    text.push(if is_empty { ' ' } else { '\n' });
+                    text.push_str(&values.join(" "));
+                }
+            }
+            is_empty = false;
+        }
+        if text.is_empty() {
+            return Ok(());
+        }
+        if self.codec != Codec::Plain {
+            self.write_compressed(&text)?;
+        } else {
+            self.write_plain(&text)?;
+        }
+        self.is_empty = is_empty;
+        Ok(())
+    }
+
+    fn repair(&self, record: &mut Record) -> Result<()> {
+        for (field, value) in
+            self.table.tclass().fields().iter().zip(record)
+        {
+            let vtype = field.vtype().unwrap_or_default();
+            if let Some((new_value, action)) =
+                repaired(value, vtype, &self.repair_policy)
+            {
+                apply_repair(
+                    action,
+                    value,
+                    &new_value,
+                    &self.filename,
+                    0,
+                    &self.on_event,
+                )?;
+                *value = new_value;
+            }
+        }
+        Ok(())
+    }
+
+    // Overwrites the closing text with the records' text and the closing
+    // text.
+    fn write_plain(&self, text: &str) -> Result<()> {
+        let close = self.close.as_bytes();
+        let mut file = self.open_file()?;
+        let offset = self.close_offset(&mut file, close)?;
+        file.seek(SeekFrom::Start(offset))?;
+        let mut bytes = text.as_bytes().to_vec();
+        bytes.extend_from_slice(close);
+        self.write_at_end(&mut file, &bytes)
+    }
+
+    // Overwrites the closing stream with a stream for the records' text
+    // and the closing stream; or if the file doesn't end with the closing
+    // stream, rewrites the file so that it does.
+    fn write_compressed(&self, text: &str) -> Result<()> {
+        let close = self.compressed(&self.close)?;
+        let records = self.compressed(text)?;
+        let mut file = self.open_file()?;
+        match self.close_offset(&mut file, &close) {
+            Ok(offset) => {
+                file.seek(SeekFrom::Start(offset))?;
+                let mut bytes = records;
+                bytes.extend_from_slice(&close);
+                self.write_at_end(&mut file, &bytes)
+            }
+            Err(_) => {
+                drop(file);
+                let old = read_file(&self.filename)?;
+                let body = match old.strip_suffix(&self.close) {
+                    Some(body) => body,
+                    None => bail!(self.changed_error()),
+                };
+                let mut bytes = self.compressed(body)?;
+                bytes.extend_from_slice(&records);
+                bytes.extend_from_slice(&close);
+                write_bytes(&self.filename, &bytes, WriteOptions::DEFAULT)
+            }
+        }
+    }
+
+    fn compressed(&self, text: &str) -> Result<Vec<u8>> {
+        self.codec.compress(text.as_bytes(), None, &self.filename)
+    }
+
+    fn open_file(&self) -> Result<std::fs::File> {
+        OpenOptions::new()
+            .read(true)
+            .write(true)
+            .open(&self.filename)
+            .with_context(|| {
+                format!("E950:{}:0:failed to open", self.filename)
+            })
+    }
+
+    // Returns the offset of the closing bytes _or_ an Err if the file
+    // doesn't end with them.
+    fn close_offset(
+        &self,
+        file: &mut std::fs::File,
+        close: &[u8],
+    ) -> Result<u64> {
+        let size = file.metadata()?.len();
+        let n = close.len() as u64;
+        if size >= n {
+            file.seek(SeekFrom::Start(size - n))?;
+            let mut tail = vec![0; close.len()];
+            file.read_exact(&mut tail)?;
+            if tail == close {
+                return Ok(size - n);
+            }
+        }
+        bail!(self.changed_error())
+    }
+
+    fn write_at_end(
+        &self,
+        file: &mut std::fs::File,
+        bytes: &[u8],
+    ) -> Result<()> {
+        file.write_all(bytes).with_context(|| {
+            format!("E902:{}:0:failed to write", self.filename)
+        })?;
+        file.sync_data().with_context(|| {
+            format!("E905:{}:0:failed to sync", self.filename)
+        })
+    }
+
+    fn changed_error(&self) -> String {
+        format!(
+            "E862:{}:0:can't append since the file has been changed \
+            since it was opened",
+            self.filename
+        )
+    }
+}
+
+// Returns the indent to use if the table has one record per line (or None
+// if not), and the text from the start of the closing ) (or its line if it
+// is on a line of its own) to the end.
+fn layout(text: &str) -> (Option<String>, String) {
+    // safe to unwrap since the text parsed and its value is a table
+    let end = text.rfind(')').unwrap();
+    let before = &text[..end];
+    let head = before.trim_end_matches([' ', '\t']);
+    if let Some(head) = head.strip_suffix('\n') {
+        let line = head.rsplit('\n').next().unwrap_or_default();
+        let content = line.trim_start();
+        let indent = if content.starts_with('(') {
+            // The table's opening line, so this is the first record
+            format!("{}  ", &before[head.len() + 1..])
+        } else {
+            line[..line.len() - content.len()].to_string()
+        };
+        (Some(indent), text[head.len() + 1..].to_string())
+    } else {
+        (None, text[end..].to_string())
+    }
+}
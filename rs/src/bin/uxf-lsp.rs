@@ -0,0 +1,289 @@
+// Copyright © 2022 Mark Summerfield. All rights reserved.
+// License: GPLv3
+
+/*! A Language Server Protocol server for UXF files, communicating over
+stdin/stdout. (See the `uxf::lsp` module for what it provides.) */
+use anyhow::Result;
+use lsp_server::{
+    Connection, ErrorCode, Message, Notification, Request, RequestId,
+    Response,
+};
+use lsp_types::{
+    notification::{
+        DidChangeTextDocument, DidCloseTextDocument, DidOpenTextDocument,
+        Notification as _, PublishDiagnostics,
+    },
+    request::{
+        Completion, Formatting, GotoDefinition, HoverRequest, Request as _,
+    },
+    CompletionItem, CompletionItemKind, CompletionOptions,
+    CompletionParams, DiagnosticSeverity, DidChangeTextDocumentParams,
+    DidCloseTextDocumentParams, DidOpenTextDocumentParams,
+    DocumentFormattingParams, GotoDefinitionParams, Hover, HoverContents,
+    HoverParams, HoverProviderCapability, Location, MarkupContent,
+    MarkupKind, NumberOrString, OneOf, PublishDiagnosticsParams,
+    ServerCapabilities, TextDocumentSyncCapability, TextDocumentSyncKind,
+    TextEdit, Url,
+};
+use std::collections::HashMap;
+use uxf::lsp::{self, Analysis, CompletionKind, Severity};
+
+fn main() -> Result<()> {
+    let (connection, io_threads) = Connection::stdio();
+    let capabilities = ServerCapabilities {
+        text_document_sync: Some(TextDocumentSyncCapability::Kind(
+            TextDocumentSyncKind::FULL,
+        )),
+        hover_provider: Some(HoverProviderCapability::Simple(true)),
+        definition_provider: Some(OneOf::Left(true)),
+        document_formatting_provider: Some(OneOf::Left(true)),
+        completion_provider: Some(CompletionOptions {
+            trigger_characters: Some(
+                ["(", "[", "{", ":"]
+                    .iter()
+                    .map(|c| c.to_string())
+                    .collect(),
+            ),
+            ..Default::default()
+        }),
+        ..Default::default()
+    };
+    connection.initialize(serde_json::to_value(capabilities)?)?;
+    Server::default().run(&connection)?;
+    io_threads.join()?;
+    Ok(())
+}
+
+#[derive(Default)]
+struct Server {
+    analysis_for_url: HashMap<Url, Analysis>,
+}
+
+impl Server {
+    fn run(&mut self, connection: &Connection) -> Result<()> {
+        for message in &connection.receiver {
+            match message {
+                Message::Request(request) => {
+                    if connection.handle_shutdown(&request)? {
+                        return Ok(());
+                    }
+                    let response = self.respond(request);
+                    connection.sender.send(Message::Response(response))?;
+                }
+                Message::Notification(notification) => {
+                    if let Some(url) = self.notify(notification) {
+                        connection.sender.send(Message::Notification(
+                            self.publish(url),
+                        ))?;
+                    }
+                }
+                Message::Response(_) => {}
+            }
+        }
+        Ok(())
+    }
+
+    // Returns the URL of the document whose diagnostics may have changed
+    fn notify(&mut self, notification: Notification) -> Option<Url> {
+        match notification.method.as_str() {
+            DidOpenTextDocument::METHOD => {
+                let params: DidOpenTextDocumentParams =
+                    serde_json::from_value(notification.params).ok()?;
+                let document = params.text_document;
+                self.update(document.uri.clone(), &document.text);
+                Some(document.uri)
+            }
+            DidChangeTextDocument::METHOD => {
+                let params: DidChangeTextDocumentParams =
+                    serde_json::from_value(notification.params).ok()?;
+                // We only accept full text changes
+                let change = params.content_changes.last()?;
+                self.update(params.text_document.uri.clone(), &change.text);
+                Some(params.text_document.uri)
+            }
+            DidCloseTextDocument::METHOD => {
+                let params: DidCloseTextDocumentParams =
+                    serde_json::from_value(notification.params).ok()?;
+                self.analysis_for_url.remove(&params.text_document.uri);
+                Some(params.text_document.uri)
+            }
+            _ => None,
+        }
+    }
+
+    fn update(&mut self, url: Url, text: &str) {
+        let filename = filename_for_url(&url);
+        self.analysis_for_url.insert(url, Analysis::new(&filename, text));
+    }
+
+    fn publish(&self, url: Url) -> Notification {
+        let diagnostics = match self.analysis_for_url.get(&url) {
+            Some(analysis) => analysis
+                .diagnostics()
+                .iter()
+                .map(|diagnostic| lsp_types::Diagnostic {
+                    range: range(diagnostic.range),
+                    severity: Some(match diagnostic.severity {
+                        Severity::Error => DiagnosticSeverity::ERROR,
+                        Severity::Warning => DiagnosticSeverity::WARNING,
+                        Severity::Information => {
+                            DiagnosticSeverity::INFORMATION
+                        }
+                    }),
+                    code: Some(NumberOrString::String(
+                        diagnostic.code.clone(),
+                    )),
+                    source: Some("uxf".to_string()),
+                    message: diagnostic.message.clone(),
+                    ..Default::default()
+                })
+                .collect(),
+            None => vec![], // closed: so clear its diagnostics
+        };
+        Notification::new(
+            PublishDiagnostics::METHOD.to_string(),
+            PublishDiagnosticsParams {
+                uri: url,
+                diagnostics,
+                version: None,
+            },
+        )
+    }
+
+    fn respond(&self, request: Request) -> Response {
+        let id = request.id.clone();
+        let result = match request.method.as_str() {
+            HoverRequest::METHOD => self.hover(request),
+            GotoDefinition::METHOD => self.definition(request),
+            Formatting::METHOD => self.format(request),
+            Completion::METHOD => self.completions(request),
+            method => {
+                return Response::new_err(
+                    id,
+                    ErrorCode::MethodNotFound as i32,
+                    format!("unsupported request: {method}"),
+                )
+            }
+        };
+        match result {
+            Ok(result) => Response::new_ok(id, result),
+            Err(err) => Response::new_err(
+                id,
+                ErrorCode::InvalidParams as i32,
+                err.to_string(),
+            ),
+        }
+    }
+
+    fn hover(&self, request: Request) -> Result<serde_json::Value> {
+        let (_, params): (RequestId, HoverParams) =
+            request.extract(HoverRequest::METHOD)?;
+        let params = params.text_document_position_params;
+        let hover = self
+            .analysis(&params.text_document.uri)
+            .and_then(|analysis| analysis.hover(position(params.position)))
+            .map(|text| Hover {
+                contents: HoverContents::Markup(MarkupContent {
+                    kind: MarkupKind::Markdown,
+                    value: text,
+                }),
+                range: None,
+            });
+        Ok(serde_json::to_value(hover)?)
+    }
+
+    fn definition(&self, request: Request) -> Result<serde_json::Value> {
+        let (_, params): (RequestId, GotoDefinitionParams) =
+            request.extract(GotoDefinition::METHOD)?;
+        let params = params.text_document_position_params;
+        let url = params.text_document.uri;
+        let location = self
+            .analysis(&url)
+            .and_then(|analysis| {
+                analysis.definition(position(params.position))
+            })
+            .and_then(|location| {
+                let uri = if location.filename == filename_for_url(&url) {
+                    url.clone()
+                } else {
+                    Url::from_file_path(&location.filename).ok()?
+                };
+                Some(Location { uri, range: range(location.range) })
+            });
+        Ok(serde_json::to_value(location)?)
+    }
+
+    fn format(&self, request: Request) -> Result<serde_json::Value> {
+        let (_, params): (RequestId, DocumentFormattingParams) =
+            request.extract(Formatting::METHOD)?;
+        let format = uxf::Format {
+            indent: if params.options.insert_spaces {
+                " ".repeat(params.options.tab_size.min(8) as usize)
+            } else {
+                "\t".to_string()
+            },
+            ..Default::default()
+        };
+        let edits =
+            self.analysis(&params.text_document.uri).and_then(|analysis| {
+                let text = analysis.format(&format)?;
+                Some(vec![TextEdit {
+                    range: range(lsp::Range {
+                        start: lsp::Position::default(),
+                        end: analysis.end(),
+                    }),
+                    new_text: text,
+                }])
+            });
+        Ok(serde_json::to_value(edits)?)
+    }
+
+    fn completions(&self, request: Request) -> Result<serde_json::Value> {
+        let (_, params): (RequestId, CompletionParams) =
+            request.extract(Completion::METHOD)?;
+        let params = params.text_document_position;
+        let items: Vec<CompletionItem> = self
+            .analysis(&params.text_document.uri)
+            .map(|analysis| analysis.completions(position(params.position)))
+            .unwrap_or_default()
+            .into_iter()
+            .map(|completion| CompletionItem {
+                label: completion.label,
+                kind: Some(match completion.kind {
+                    CompletionKind::TType => CompletionItemKind::CLASS,
+                    CompletionKind::VType => CompletionItemKind::KEYWORD,
+                    CompletionKind::Field => CompletionItemKind::FIELD,
+                }),
+                detail: if completion.detail.is_empty() {
+                    None
+                } else {
+                    Some(completion.detail)
+                },
+                ..Default::default()
+            })
+            .collect();
+        Ok(serde_json::to_value(items)?)
+    }
+
+    fn analysis(&self, url: &Url) -> Option<&Analysis> {
+        self.analysis_for_url.get(url)
+    }
+}
+
+fn filename_for_url(url: &Url) -> String {
+    match url.to_file_path() {
+        Ok(path) => path.to_string_lossy().to_string(),
+        Err(_) => "-".to_string(),
+    }
+}
+
+fn position(position: lsp_types::Position) -> lsp::Position {
+    lsp::Position::new(position.line, position.character)
+}
+
+fn range(range: lsp::Range) -> lsp_types::Range {
+    let position = |position: lsp::Position| {
+        lsp_types::Position::new(position.line, position.column)
+    };
+    lsp_types::Range::new(position(range.start), position(range.end))
+}
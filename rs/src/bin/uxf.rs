@@ -3,91 +3,453 @@
 
 use anyhow::{bail, Context, Result};
 use clap::{Args, Parser, Subcommand};
-use flate2::{write::GzEncoder, Compression};
 use std::{
+    cell::Cell,
     env,
-    fs::File,
-    io::Write,
+    fmt::Write as _,
+    fs,
+    io::{self, Write as _},
     path::{Path, PathBuf},
+    process,
     rc::Rc,
 };
-use uxf::util::PathBufExt;
+use uxf::event::{EventKind, OnEventFn};
+use uxf::util::split_error;
+
+const EXIT_OK: i32 = 0;
+const EXIT_UNEQUAL: i32 = 1;
+const EXIT_WARNINGS: i32 = 2;
+const EXIT_REPAIRS: i32 = 3;
+const EXIT_ERROR: i32 = 4;
+
+static UXF_SUFFIXES: [&str; 6] =
+    [".uxf", ".uxi", ".uxf.gz", ".uxf.zst", ".uxf.xz", ".uxf.bz2"];
 
 fn main() {
     let config = Config::parse();
-    if let Err(err) = match &config.command {
+    process::exit(match &config.command {
         Commands::Format(format) => handle_format(format),
         Commands::Lint(lint) => handle_lint(lint),
         Commands::Compare(compare) => handle_compare(compare),
-    } {
-        eprintln!("{err:#}");
+        Commands::Merge(merge) => handle_merge(merge),
+        Commands::Convert(convert) => handle_convert(convert),
+        Commands::Index(index) => handle_index(index),
+        Commands::Show(show) => handle_show(show),
+    });
+}
+
+fn handle_format(format: &Format) -> i32 {
+    let (infiles, outfile) = match get_format_files(format) {
+        Ok(files) => files,
+        Err(err) => {
+            Reporter::new(format.json, false).error(&err, Path::new(""));
+            return EXIT_ERROR;
+        }
+    };
+    // Keep stdout for the UXF output if that's where it is going
+    let reporter =
+        Rc::new(Reporter::new(format.json, outfile == Path::new("-")));
+    for infile in &infiles {
+        if format.check {
+            match check_file(format, infile, &reporter) {
+                Ok(true) => (),
+                Ok(false) => reporter.unformatted(infile),
+                Err(err) => reporter.error(&err, infile),
+            }
+        } else if let Err(err) =
+            format_file(format, infile, &outfile, &reporter)
+        {
+            reporter.error(&err, infile);
+        }
+    }
+    // Only lint reports warnings and repairs via its exit code
+    match reporter.exit_code() {
+        code @ (EXIT_ERROR | EXIT_UNEQUAL) => code,
+        _ => EXIT_OK,
+    }
+}
+
+// Returns the infiles (with any folders replaced by the UXF files they
+// contain) and the outfile (which is empty for --check).
+fn get_format_files(format: &Format) -> Result<(Vec<PathBuf>, PathBuf)> {
+    let mut files = format.files.clone();
+    let outfile = if format.check {
+        if files.len() > 1 && files.last() == Some(&PathBuf::from("=")) {
+            files.pop(); // --check never writes so = is redundant
+        }
+        PathBuf::new()
+    } else {
+        if files.len() < 2 {
+            bail!(
+                "E956:-:0:an infile and an outfile (or = to format in \
+                place) are required"
+            )
+        }
+        // safe to unwrap since len() >= 2
+        let outfile = files.pop().unwrap();
+        if outfile != Path::new("=")
+            && (files.len() > 1 || files[0].is_dir())
+        {
+            bail!(
+                "E957:{}:0:can only format multiple files or folders in \
+                place: use = as the outfile",
+                outfile.display()
+            )
+        }
+        outfile
+    };
+    Ok((expand_folders(&files)?, outfile))
+}
+
+// Returns the files with any folders replaced by the files with UXF
+// suffixes that they or any of their (non-hidden) subfolders contain.
+fn expand_folders(files: &[PathBuf]) -> Result<Vec<PathBuf>> {
+    let mut expanded = vec![];
+    for file in files {
+        if file.is_dir() {
+            walk_folder(file, &mut expanded)?;
+        } else {
+            expanded.push(file.clone());
+        }
     }
+    Ok(expanded)
 }
 
-fn handle_format(format: &Format) -> Result<()> {
-    let inbuf = canonicalize_file(&format.infile)?;
+fn walk_folder(folder: &Path, files: &mut Vec<PathBuf>) -> Result<()> {
+    let mut paths = vec![];
+    for entry in fs::read_dir(folder).with_context(|| {
+        format!("E958:{}:0:failed to read folder", folder.display())
+    })? {
+        paths.push(entry?.path());
+    }
+    paths.sort();
+    for path in paths {
+        let name = path.file_name().unwrap_or_default().to_string_lossy();
+        if path.is_dir() {
+            if !name.starts_with('.') {
+                walk_folder(&path, files)?;
+            }
+        } else if UXF_SUFFIXES.iter().any(|suffix| name.ends_with(suffix)) {
+            files.push(path);
+        }
+    }
+    Ok(())
+}
+
+fn format_file(
+    format: &Format,
+    infile: &Path,
+    outfile: &Path,
+    reporter: &Rc<Reporter>,
+) -> Result<uxf::Uxf> {
+    let inbuf = canonicalize_file(infile)?;
     let infile = inbuf.to_string_lossy().to_string();
-    let outfile = get_outfile(&inbuf, &format.outfile)?;
+    let outfile = get_outfile(&inbuf, outfile)?;
     let options = parser_options(format);
-    let uxo = uxf::parse_options(
+    let repair_policy = if format.strict {
+        uxf::RepairPolicy::strict()
+    } else {
+        uxf::RepairPolicy::default()
+    };
+    let on_event: OnEventFn = if format.lint {
+        let reporter = Rc::clone(reporter);
+        Rc::new(move |event: &uxf::Event| reporter.event(event))
+    } else {
+        Rc::new(uxf::ignore_event)
+    };
+    let uxo = uxf::parse_policy(
         &infile,
         options,
-        if format.lint { None } else { Some(Rc::new(uxf::ignore_event)) },
+        &repair_policy,
+        Some(on_event),
     )?;
     if !outfile.is_empty() {
         output(&outfile, format, &uxo)?;
     }
-    Ok(())
+    Ok(uxo)
+}
+
+// Returns true if formatting the infile wouldn't change it.
+fn check_file(
+    format: &Format,
+    infile: &Path,
+    reporter: &Rc<Reporter>,
+) -> Result<bool> {
+    let uxo = format_file(format, infile, Path::new(""), reporter)?;
+    let inbuf = canonicalize_file(infile)?;
+    let text = uxf::util::read_file(&inbuf.to_string_lossy())?;
+    Ok(text == formatted(format, &uxo))
 }
 
-fn handle_lint(lint: &Lint) -> Result<()> {
+fn handle_lint(lint: &Lint) -> i32 {
+    let reporter = Rc::new(Reporter::new(lint.json, false));
+    let linter = match get_linter(lint) {
+        Ok(linter) => linter,
+        Err(err) => {
+            let config = lint.config.clone().unwrap_or_default();
+            reporter.error(&err, &config);
+            return reporter.exit_code();
+        }
+    };
     for file in &lint.files {
-        if let Err(err) = handle_format(&Format::new_lint(file)) {
-            eprintln!("{err:#}");
+        let format = Format::new_lint(lint.strict);
+        match format_file(&format, file, Path::new(""), &reporter) {
+            Ok(uxo) => {
+                if let Some(linter) = &linter {
+                    let filename = canonicalize_file(file)
+                        .unwrap_or_else(|_| file.to_path_buf());
+                    for event in
+                        linter.lint(&uxo, &filename.to_string_lossy())
+                    {
+                        reporter.event(&event);
+                    }
+                }
+            }
+            Err(err) => reporter.error(&err, file),
         }
     }
-    Ok(())
+    reporter.exit_code()
 }
 
-fn handle_compare(compare: &Compare) -> Result<()> {
+// Returns a Linter if --rules or --config was used. Uses the given config
+// file or the first one found in the current folder or its ancestors;
+// otherwise the default rules.
+fn get_linter(lint: &Lint) -> Result<Option<uxf::Linter>> {
+    if !lint.rules && lint.config.is_none() {
+        return Ok(None);
+    }
+    let config = if let Some(config) = &lint.config {
+        Some(config.clone())
+    } else {
+        let cwd = env::current_dir()
+            .with_context(|| "E954:-:0:failed to find current folder")?;
+        uxf::lint::find_config(&cwd)
+    };
+    Ok(Some(if let Some(config) = config {
+        uxf::Linter::from_config(&config.to_string_lossy())?
+    } else {
+        uxf::Linter::new()
+    }))
+}
+
+fn handle_compare(compare: &Compare) -> i32 {
+    let reporter = Reporter::new(compare.json, false);
+    match compare_files(compare) {
+        Ok(eq) => {
+            reporter.compare(eq, compare);
+            if eq {
+                EXIT_OK
+            } else {
+                EXIT_UNEQUAL
+            }
+        }
+        Err(err) => {
+            reporter.error(&err, Path::new(""));
+            reporter.exit_code()
+        }
+    }
+}
+
+fn compare_files(compare: &Compare) -> Result<bool> {
+    let options = if compare.equivalent {
+        uxf::ParserOptions::AS_STANDALONE
+    } else {
+        uxf::ParserOptions::DEFAULT
+    };
     let uxo1 = uxf::parse_options(
         &compare.file1.to_string_lossy(),
-        if compare.equivalent {
-            uxf::ParserOptions::AS_STANDALONE
-        } else {
-            uxf::ParserOptions::DEFAULT
-        },
+        options,
         Some(Rc::new(uxf::ignore_event)), // ignore lints
     )?;
     let uxo2 = uxf::parse_options(
         &compare.file2.to_string_lossy(),
-        if compare.equivalent {
-            uxf::ParserOptions::AS_STANDALONE
-        } else {
-            uxf::ParserOptions::DEFAULT
-        },
+        options,
         Some(Rc::new(uxf::ignore_event)), // ignore lints
     )?;
-    let eq = if compare.equivalent {
-        if uxo1.is_equivalent(&uxo2, uxf::Compare::EQUIVALENT) {
-            "EQUIV"
-        } else {
-            "UNEQUIV"
+    Ok(if compare.equivalent {
+        uxo1.is_equivalent(&uxo2, uxf::Compare::EQUIVALENT)
+    } else {
+        uxo1 == uxo2
+    })
+}
+
+fn handle_merge(merge: &Merge) -> i32 {
+    let reporter =
+        Rc::new(Reporter::new(merge.json, merge.outfile == Path::new("-")));
+    if let Err(err) = merge_files(merge, &reporter) {
+        reporter.error(&err, Path::new(""));
+    }
+    // Renamed ttypes are reported but aren't errors
+    match reporter.exit_code() {
+        EXIT_ERROR => EXIT_ERROR,
+        _ => EXIT_OK,
+    }
+}
+
+fn merge_files(merge: &Merge, reporter: &Rc<Reporter>) -> Result<()> {
+    let on_event: OnEventFn = {
+        let reporter = Rc::clone(reporter);
+        Rc::new(move |event: &uxf::Event| reporter.event(event))
+    };
+    let options = uxf::ParserOptions::DEFAULT;
+    let uxo = if merge.include {
+        if merge.files.len() != 1 {
+            bail!("E959:-:0:--include requires exactly one include file")
         }
-    } else if uxo1 == uxo2 {
-        "EQUAL"
+        uxf::merge::include(
+            &merge.files[0].to_string_lossy(),
+            options,
+            Some(on_event),
+        )?
     } else {
-        "UNEQUAL"
+        if merge.files.len() < 2 {
+            bail!("E959:-:0:at least two infiles are required")
+        }
+        let filenames: Vec<String> = merge
+            .files
+            .iter()
+            .map(|file| file.to_string_lossy().to_string())
+            .collect();
+        let filenames: Vec<&str> =
+            filenames.iter().map(|f| f.as_str()).collect();
+        uxf::merge::merge_files(
+            &filenames,
+            if merge.list {
+                uxf::merge::MergeAs::List
+            } else {
+                uxf::merge::MergeAs::Map
+            },
+            options,
+            Some(on_event),
+        )?
     };
-    println!("{eq} {:?} {:?}", compare.file1, compare.file2);
-    Ok(())
+    let outfile = if merge.outfile == Path::new("-") {
+        "-".to_string()
+    } else {
+        for file in &merge.files {
+            check_same_file(file, &merge.outfile)?;
+        }
+        merge.outfile.to_string_lossy().to_string()
+    };
+    output(
+        &outfile,
+        &Format {
+            backup: merge.backup,
+            level: merge.level,
+            ..Format::new_default()
+        },
+        &uxo,
+    )
+}
+
+fn handle_convert(convert: &Convert) -> i32 {
+    let reporter =
+        Reporter::new(convert.json, convert.outfile == Path::new("-"));
+    if let Err(err) = convert_file(convert) {
+        reporter.error(&err, &convert.infile);
+    }
+    reporter.exit_code()
+}
+
+fn convert_file(convert: &Convert) -> Result<()> {
+    let infile = convert.infile.to_string_lossy().to_string();
+    let bytes = uxf::util::read_bytes(&infile)?;
+    let to_text = uxf::binary::is_binary(&bytes);
+    let uxo = if to_text {
+        uxf::binary::read_binary(&infile)?
+    } else {
+        uxf::parse_options(
+            &infile,
+            uxf::ParserOptions::DEFAULT,
+            Some(Rc::new(uxf::ignore_event)),
+        )?
+    };
+    let outfile = if convert.outfile == Path::new("-") {
+        "-".to_string()
+    } else {
+        check_same_file(&convert.infile, &convert.outfile)?;
+        convert.outfile.to_string_lossy().to_string()
+    };
+    if to_text {
+        output(
+            &outfile,
+            &Format {
+                backup: convert.backup,
+                level: convert.level,
+                ..Format::new_default()
+            },
+            &uxo,
+        )
+    } else if outfile == "-" {
+        io::stdout()
+            .write_all(&uxo.to_binary())
+            .with_context(|| "E902:-:0:failed to write".to_string())
+    } else {
+        let mut options = uxf::WriteOptions::DEFAULT;
+        if convert.backup {
+            options |= uxf::WriteOptions::BACKUP;
+        }
+        uxf::binary::write_binary(&outfile, &uxo, options, convert.level)
+    }
+}
+
+fn handle_index(index: &Index) -> i32 {
+    let reporter = Reporter::new(index.json, false);
+    for file in &index.files {
+        if let Err(err) = uxf::index::Index::build(
+            &file.to_string_lossy(),
+            index.key.as_deref(),
+        )
+        .and_then(|idx| idx.save())
+        {
+            reporter.error(&err, file);
+        }
+    }
+    reporter.exit_code()
 }
 
-fn get_outfile(inbuf: &Path, outfile: &PathBuf) -> Result<String> {
-    Ok(if !outfile.is_empty() {
-        if outfile == &PathBuf::from("-") {
+fn handle_show(show: &Show) -> i32 {
+    let reporter = Reporter::new(show.json, show.outfile == Path::new("-"));
+    if let Err(err) = show_file(show) {
+        reporter.error(&err, &show.infile);
+    }
+    reporter.exit_code()
+}
+
+fn show_file(show: &Show) -> Result<()> {
+    let uxo = uxf::parse_options(
+        &show.infile.to_string_lossy(),
+        uxf::ParserOptions::DEFAULT,
+        Some(Rc::new(uxf::ignore_event)),
+    )?;
+    let format =
+        uxf::Format::new(show.indent, show.wrapwidth, show.decimals);
+    let text = if show.html {
+        uxf::render::to_html(&uxo, &format)
+    } else if show.color {
+        uxf::render::to_ansi(&uxo, &format)
+    } else {
+        uxo.to_text_format(&format)
+    };
+    if show.outfile == Path::new("-") {
+        print!("{text}");
+        Ok(())
+    } else {
+        check_same_file(&show.infile, &show.outfile)?;
+        uxf::util::write_file_options(
+            &show.outfile.to_string_lossy(),
+            &text,
+            uxf::WriteOptions::DEFAULT,
+            None,
+        )
+    }
+}
+
+fn get_outfile(inbuf: &Path, outfile: &Path) -> Result<String> {
+    Ok(if !outfile.as_os_str().is_empty() {
+        if outfile == Path::new("-") {
             "-".to_string()
-        } else if outfile == &PathBuf::from("=") {
+        } else if outfile == Path::new("=") {
             inbuf.to_string_lossy().to_string()
         } else {
             check_same_file(inbuf, outfile)?;
@@ -100,39 +462,44 @@ fn get_outfile(inbuf: &Path, outfile: &PathBuf) -> Result<String> {
 }
 
 fn output(outfile: &str, format: &Format, uxo: &uxf::Uxf) -> Result<()> {
-    let text = if format.compact {
-        uxo.to_string()
-    } else {
-        uxo.to_text_format(&uxf::Format::new(
-            format.indent,
-            format.wrapwidth,
-            format.decimals,
-        ))
-    };
+    let text = formatted(format, uxo);
     if outfile == "-" {
         println!("{text}");
     } else {
-        let raw = text.as_bytes();
-        let mut file = File::create(outfile).with_context(|| {
-            format!("E913:{outfile}:0:failed to create")
-        })?;
-        if outfile.ends_with(".gz") {
-            let mut out = GzEncoder::new(&file, Compression::best());
-            out.write_all(raw).with_context(|| {
-                format!("E910:{outfile}:0:failed to write gzipped")
-            })?;
-            out.finish().with_context(|| {
-                format!("E911:{outfile}:0:failed to gzip")
-            })?;
-        } else {
-            file.write_all(raw).with_context(|| {
-                format!("E912:{outfile}:0:failed to write")
-            })?;
+        // Writes are atomic so a failure leaves any existing file intact
+        let mut options = uxf::WriteOptions::DEFAULT;
+        if format.backup {
+            options |= uxf::WriteOptions::BACKUP;
         }
+        uxf::util::write_file_options(
+            outfile,
+            &text,
+            options,
+            format.level,
+        )?;
     }
     Ok(())
 }
 
+fn formatted(format: &Format, uxo: &uxf::Uxf) -> String {
+    if format.compact {
+        uxo.to_string()
+    } else {
+        uxo.to_text_format(
+            &uxf::Format::new(
+                format.indent,
+                format.wrapwidth,
+                format.decimals,
+            )
+            .with_map_order(if format.preserveorder {
+                uxf::MapOrder::Insertion
+            } else {
+                uxf::MapOrder::Sorted
+            }),
+        )
+    }
+}
+
 fn parser_options(format: &Format) -> uxf::ParserOptions {
     let mut options = uxf::ParserOptions::DEFAULT;
     if format.standalone || format.dropunused {
@@ -145,7 +512,7 @@ fn parser_options(format: &Format) -> uxf::ParserOptions {
 }
 
 fn check_same_file(a: &Path, b: &Path) -> Result<()> {
-    if b != PathBuf::from("-") {
+    if b != "-" {
         let a = canonicalize_file(a)?;
         let b = canonicalize_file(b)?;
         if a == b {
@@ -168,8 +535,168 @@ fn canonicalize_file(p: &Path) -> Result<PathBuf> {
     Ok(p)
 }
 
+/// Reports events, errors, and comparisons either as plain text (the
+/// default) or as JSON records, one per line, and keeps count of them so as
+/// to provide a meaningful exit code.
+struct Reporter {
+    json: bool,
+    to_stderr: bool, // only applies to JSON records
+    warnings: Cell<usize>,
+    repairs: Cell<usize>,
+    unformatted: Cell<usize>,
+    errors: Cell<usize>,
+}
+
+impl Reporter {
+    fn new(json: bool, to_stderr: bool) -> Self {
+        Reporter {
+            json,
+            to_stderr,
+            warnings: Cell::new(0),
+            repairs: Cell::new(0),
+            unformatted: Cell::new(0),
+            errors: Cell::new(0),
+        }
+    }
+
+    fn event(&self, event: &uxf::Event) {
+        let kind = match event.kind {
+            EventKind::Warning => {
+                self.warnings.set(self.warnings.get() + 1);
+                "warning"
+            }
+            EventKind::Repair => {
+                self.repairs.set(self.repairs.get() + 1);
+                "repair"
+            }
+        };
+        if self.json {
+            self.record(&[
+                ("kind", json_str(kind)),
+                ("code", event.code.to_string()),
+                ("file", json_str(&event.filename)),
+                ("line", event.lino.to_string()),
+                ("message", json_str(&event.message)),
+            ]);
+        } else {
+            uxf::on_event(event);
+        }
+    }
+
+    // The file is only used if the error doesn't specify one
+    fn error(&self, err: &anyhow::Error, file: &Path) {
+        self.errors.set(self.errors.get() + 1);
+        let text = format!("{err:#}");
+        if self.json {
+            let (code, filename, lino, message) = split_error(&text);
+            let filename = if filename.is_empty() {
+                file.to_string_lossy()
+            } else {
+                filename.into()
+            };
+            self.record(&[
+                ("kind", json_str("error")),
+                ("code", code.to_string()),
+                ("file", json_str(&filename)),
+                ("line", lino.to_string()),
+                ("message", json_str(message)),
+            ]);
+        } else {
+            eprintln!("{text}");
+        }
+    }
+
+    // Used by format --check for files that formatting would change
+    fn unformatted(&self, file: &Path) {
+        self.unformatted.set(self.unformatted.get() + 1);
+        if self.json {
+            self.record(&[
+                ("kind", json_str("unformatted")),
+                ("file", json_str(&file.to_string_lossy())),
+            ]);
+        } else {
+            println!("{}", file.display());
+        }
+    }
+
+    fn compare(&self, eq: bool, compare: &Compare) {
+        let result = match (compare.equivalent, eq) {
+            (true, true) => "EQUIV",
+            (true, false) => "UNEQUIV",
+            (false, true) => "EQUAL",
+            (false, false) => "UNEQUAL",
+        };
+        if self.json {
+            self.record(&[
+                ("kind", json_str("compare")),
+                ("result", json_str(result)),
+                ("file1", json_str(&compare.file1.to_string_lossy())),
+                ("file2", json_str(&compare.file2.to_string_lossy())),
+            ]);
+        } else {
+            println!("{result} {:?} {:?}", compare.file1, compare.file2);
+        }
+    }
+
+    fn record(&self, fields: &[(&str, String)]) {
+        let mut text = String::from("{");
+        for (i, (name, value)) in fields.iter().enumerate() {
+            if i > 0 {
+                text.push_str(", ");
+            }
+            let _ = write!(text, "\"{name}\": {value}");
+        }
+        text.push('}');
+        if self.to_stderr {
+            eprintln!("{text}");
+        } else {
+            println!("{text}");
+        }
+    }
+
+    fn exit_code(&self) -> i32 {
+        if self.errors.get() > 0 {
+            EXIT_ERROR
+        } else if self.unformatted.get() > 0 {
+            EXIT_UNEQUAL
+        } else if self.repairs.get() > 0 {
+            EXIT_REPAIRS
+        } else if self.warnings.get() > 0 {
+            EXIT_WARNINGS
+        } else {
+            EXIT_OK
+        }
+    }
+}
+
+fn json_str(s: &str) -> String {
+    let mut text = String::from("\"");
+    for c in s.chars() {
+        match c {
+            '"' => text.push_str("\\\""),
+            '\\' => text.push_str("\\\\"),
+            '\n' => text.push_str("\\n"),
+            '\r' => text.push_str("\\r"),
+            '\t' => text.push_str("\\t"),
+            c if (c as u32) < 0x20 => {
+                let _ = write!(text, "\\u{:04x}", c as u32);
+            }
+            c => text.push(c),
+        }
+    }
+    text.push('"');
+    text
+}
+
 #[derive(Parser, Debug)]
-#[clap(version, about = "Compares, Formats, and Lints UXF files.")]
+#[clap(
+    version,
+    about = "Compares, Converts, Formats, Indexes, Lints, Merges, and \
+    Shows UXF files.",
+    after_help = "Exit codes: 0 success (or equal or equivalent); \
+    1 unequal (or unequivalent, or format --check found files that need \
+    formatting); 2 lint warnings; 3 lint repairs; 4 errors."
+)]
 struct Config {
     #[clap(subcommand)]
     command: Commands,
@@ -197,16 +724,71 @@ enum Commands {
     /// duplicates removed) to allow later imports to override earlier ones.
     /// The conversion will also automatically perform type repairs, e.g.,
     /// converting strings to dates or ints or reals if that is the target
-    /// type, and similar. (Use f or fmt or format)
+    /// type, and similar (unless --strict is used).
+    /// To format multiple files in place give them all followed by =;
+    /// any folders given are searched recursively for files ending .uxf,
+    /// .uxi, .uxf.gz, .uxf.zst, .uxf.xz, or .uxf.bz2. Use --check to list
+    /// the files that formatting would change (with exit code 1) without
+    /// changing them. (Use f or fmt or format)
     #[clap(alias("f"))]
     #[clap(alias("fmt"))]
     Format(Format),
 
     /// Print the repairs that formatting would apply and lint warnings (if
-    /// any) to stderr for the given file(s). (Use l or lnt or lint)
+    /// any) to stderr for the given file(s). With --strict, repairs are
+    /// treated as errors and the exit code is nonzero if there are any
+    /// errors or lint warnings. With --rules, also check the file(s)
+    /// against the lint rules (no_untyped_lists, tclass_comments,
+    /// snake_case_fields, no_date_like_str_keys, no_unused_imports,
+    /// max_depth), all of which are enabled unless configured otherwise
+    /// in a uxflint.uxf file in the current folder or one of its
+    /// ancestors (or given by --config). (Use l or lnt or lint)
     #[clap(alias("l"))]
     #[clap(alias("lnt"))]
     Lint(Lint),
+
+    /// Merge two or more UXF files into one whose value is a map of each
+    /// infile's name to its value (or with --list, a list of the infiles'
+    /// values), and whose ttypes and imports are the union of the
+    /// infiles'. If an infile defines a ttype with the same name but
+    /// different fields as an earlier infile, it is renamed (e.g., Point
+    /// becomes Point2) and the renaming is reported. With --include, the
+    /// single infile is an include file whose value is an include table,
+    /// e.g., =include filename:str (include <a.uxf> <b.uxf>), and the files
+    /// it lists are merged as a list. (Use m or merge)
+    #[clap(alias("m"))]
+    Merge(Merge),
+
+    /// Convert the infile from UXF text to the compact lossless binary
+    /// form or from the binary form to UXF text—the direction depends on
+    /// whether the infile is binary. Converting a file to binary and back
+    /// again produces the same result as formatting it. By convention
+    /// binary UXF files end .uxb. (Use v or cv or convert)
+    #[clap(alias("v"))]
+    #[clap(alias("cv"))]
+    Convert(Convert),
+
+    /// Create a sidecar index for each given uncompressed UXF file whose
+    /// value is a table, saved with .uxx appended to the file's name. An
+    /// index maps each record's number (and with --key, each record's key
+    /// field value) to the record's byte offsets so that programs using
+    /// the uxf library's index module can read individual records (or
+    /// ranges of records by key) without parsing the whole file. Rebuild
+    /// the index whenever the file changes. (Use x or idx or index)
+    #[clap(alias("x"))]
+    #[clap(alias("idx"))]
+    Index(Index),
+
+    /// Show the infile for reviewing: with --color as UXF text with ANSI
+    /// colours that distinguish ttypes, field names, vtypes, keys, strs,
+    /// numbers, dates, bytes, and comments, or with --html as a
+    /// standalone HTML page in which tables are shown as HTML tables with
+    /// their TClass definition as their caption. (Without either, the
+    /// infile is shown formatted as UXF text.) The output can't
+    /// necessarily be read back in as UXF. (Use s or shw or show)
+    #[clap(alias("s"))]
+    #[clap(alias("shw"))]
+    Show(Show),
 }
 
 #[derive(Args, Debug)]
@@ -215,8 +797,12 @@ struct Compare {
     #[clap(short, long, action)]
     equivalent: bool,
 
+    /// Output the result (or any error) as a JSON record
+    #[clap(short, long, action)]
+    json: bool,
+
     /// The first required file to compare (can have any suffix, i.e.,
-    /// not just .uxf, and be gzip-compressed if it ends with .gz)
+    /// not just .uxf, and be compressed, e.g., gzipped)
     #[clap(value_parser)]
     file1: PathBuf,
 
@@ -236,6 +822,16 @@ struct Format {
     #[clap(short, long, action)]
     standalone: bool,
 
+    /// Fail with an error rather than doing any type repairs (e.g.,
+    /// rounding a real to an int)
+    #[clap(long, action)]
+    strict: bool,
+
+    /// Output lints and errors as JSON records, one per line, to stdout
+    /// (or to stderr if the outfile is -)
+    #[clap(short, long, action)]
+    json: bool,
+
     /// Drop unused imports and ttype definitions (best to use
     /// -s|--standalone)
     #[clap(short, long, action)]
@@ -265,40 +861,200 @@ struct Format {
         value_parser=clap::value_parser!(u8).range(0..=15))]
     decimals: u8,
 
+    /// Output map items in the order they appear in the infile rather
+    /// than in sorted key order (ignored if -c|--compact used)
+    #[clap(short, long, action)]
+    preserveorder: bool,
+
     /// Use compact output format (not human friendly; ignores indent
     /// and wrapwidth)
     #[clap(short, long, action)]
     compact: bool,
 
-    /// Required infile
-    #[clap(value_parser)]
-    infile: PathBuf,
+    /// Don't write anything; instead list the files that formatting would
+    /// change and exit with code 1 if there are any
+    #[clap(long, action)]
+    check: bool,
 
-    /// Required outfile; use - to write to stdout or = to overwrite
-    /// infile
-    #[clap(value_parser)]
-    outfile: PathBuf,
+    /// Compression level for an outfile ending .gz (0-9), .zst (1-22),
+    /// .xz (0-9), or .bz2 (1-9); the default is the best compression
+    #[clap(short = 'L', long, value_parser)]
+    level: Option<u32>,
+
+    /// Copy each existing outfile to outfile.bak before replacing it
+    #[clap(short, long, action)]
+    backup: bool,
+
+    /// Required infile followed by the required outfile; use - to write to
+    /// stdout or = to overwrite the infile. Or any number of infiles
+    /// and/or folders followed by = to format them all in place. (No
+    /// outfile is needed with --check.)
+    #[clap(value_parser, required = true)]
+    files: Vec<PathBuf>,
 }
 
 impl Format {
-    fn new_lint(file: &Path) -> Self {
+    fn new_default() -> Self {
+        Format { lint: false, ..Format::new_lint(false) }
+    }
+
+    fn new_lint(strict: bool) -> Self {
         Format {
             lint: true,
             standalone: false,
+            strict,
+            json: false, // handled by lint's own Reporter
             dropunused: false,
             replaceimports: false,
             indent: 2,
             wrapwidth: 96,
             decimals: 0,
+            preserveorder: false,
             compact: false,
-            infile: file.to_path_buf(),
-            outfile: PathBuf::new(),
+            check: false,
+            backup: false,
+            level: None,
+            files: vec![],
         }
     }
 }
 
+#[derive(Args, Debug)]
+struct Merge {
+    /// Output a list of the infiles' values rather than a map
+    #[clap(short, long, action)]
+    list: bool,
+
+    /// The infile is an include file listing the files to merge (as a
+    /// list)
+    #[clap(short = 'I', long, action)]
+    include: bool,
+
+    /// Output renamings and errors as JSON records, one per line, to
+    /// stdout (or to stderr if the outfile is -)
+    #[clap(short, long, action)]
+    json: bool,
+
+    /// The outfile (use - to write to stdout, the default)
+    #[clap(short, long, value_parser, default_value = "-")]
+    outfile: PathBuf,
+
+    /// Compression level for an outfile ending .gz (0-9), .zst (1-22),
+    /// .xz (0-9), or .bz2 (1-9); the default is the best compression
+    #[clap(short = 'L', long, value_parser)]
+    level: Option<u32>,
+
+    /// Copy an existing outfile to outfile.bak before replacing it
+    #[clap(short, long, action)]
+    backup: bool,
+
+    /// The infiles to merge (or with --include, the include file)
+    #[clap(value_parser, required = true)]
+    files: Vec<PathBuf>,
+}
+
+#[derive(Args, Debug)]
+struct Convert {
+    /// Output errors as JSON records, one per line, to stdout (or to
+    /// stderr if the outfile is -)
+    #[clap(short, long, action)]
+    json: bool,
+
+    /// Compression level for an outfile ending .gz (0-9), .zst (1-22),
+    /// .xz (0-9), or .bz2 (1-9); the default is the best compression
+    #[clap(short = 'L', long, value_parser)]
+    level: Option<u32>,
+
+    /// Copy an existing outfile to outfile.bak before replacing it
+    #[clap(short, long, action)]
+    backup: bool,
+
+    /// The required infile (UXF text or binary, either of which may be
+    /// compressed, e.g., gzipped)
+    #[clap(value_parser)]
+    infile: PathBuf,
+
+    /// The required outfile (use - to write to stdout)
+    #[clap(value_parser)]
+    outfile: PathBuf,
+}
+
+#[derive(Args, Debug)]
+struct Index {
+    /// The name of a field whose values the index should map to records
+    #[clap(short, long, value_parser)]
+    key: Option<String>,
+
+    /// Output errors as JSON records, one per line, to stdout
+    #[clap(short, long, action)]
+    json: bool,
+
+    /// The file(s) to index.
+    #[clap(value_parser, required = true)]
+    files: Vec<PathBuf>,
+}
+
+#[derive(Args, Debug)]
+struct Show {
+    /// Output UXF text with ANSI colours (e.g., for a terminal)
+    #[clap(short, long, action, conflicts_with = "html")]
+    color: bool,
+
+    /// Output a standalone HTML page
+    #[clap(short = 'H', long, action)]
+    html: bool,
+
+    /// Output errors as JSON records, one per line, to stdout (or to
+    /// stderr if the outfile is -)
+    #[clap(short, long, action)]
+    json: bool,
+
+    /// Indent (0-8 spaces or 9 to use a tab)
+    #[clap(short, long, default_value_t=2,
+        value_parser=clap::value_parser!(u8).range(0..=9))]
+    indent: u8,
+
+    /// Wrapwidth (40-240)
+    #[clap(short, long, default_value_t=96,
+        value_parser=clap::value_parser!(u8).range(40..=240))]
+    wrapwidth: u8,
+
+    /// Decimal digits (0-15; 0 means use at least one (even if .0) and as
+    /// many as needed; 1-15 means used that fixed number of digits)
+    #[clap(short='D', long, default_value_t=0,
+        value_parser=clap::value_parser!(u8).range(0..=15))]
+    decimals: u8,
+
+    /// The outfile (use - to write to stdout, the default)
+    #[clap(short, long, value_parser, default_value = "-")]
+    outfile: PathBuf,
+
+    /// The required infile (which may be compressed, e.g., gzipped)
+    #[clap(value_parser)]
+    infile: PathBuf,
+}
+
 #[derive(Args, Debug)]
 struct Lint {
+    /// Treat repairs as errors and exit with a nonzero exit code if there
+    /// are any errors or lint warnings
+    #[clap(long, action)]
+    strict: bool,
+
+    /// Output lints and errors as JSON records, one per line, to stdout
+    #[clap(short, long, action)]
+    json: bool,
+
+    /// Also check the file(s) against the lint rules
+    #[clap(short, long, action)]
+    rules: bool,
+
+    /// The lint rules config file to use (implies -r|--rules); this is a
+    /// UXF file whose value is a map of rule names to yes or no (or to an
+    /// int limit for max_depth)
+    #[clap(short, long, value_parser)]
+    config: Option<PathBuf>,
+
     /// The file(s) to lint.
     #[clap(value_parser, required = true)]
     files: Vec<PathBuf>,
@@ -0,0 +1,627 @@
+// Copyright © 2022 Mark Summerfield. All rights reserved.
+// License: GPLv3
+
+/*! A compact binary encoding of the UXF data model.
+
+The binary form is lossless: `from_binary(&to_binary(&uxo))? == uxo` for
+any `Uxf` (and similarly for any `Value` using `value_to_binary()` and
+`value_from_binary()`). It preserves the custom and comment strings, the
+imports (in order), every TClass (including enums, fieldless TClasses,
+and unused ones), list and map ktypes and vtypes, every comment, table
+keys, and every scalar (including bytes, dates, and datetimes), but not
+the text form's layout.
+
+The encoding starts with the magic bytes `UXFB`, a version byte (`1`),
+and a kind byte (`U` for a `Uxf` or `V` for a standalone `Value`).
+A `Uxf` then has its custom and comment strings, its imports (each a
+ttype and the import it came from), and its TClasses. Both kinds
+then have the distinct TClasses of the value's tables (which each table
+refers to by index), followed by the value itself.
+
+Counts and lengths are unsigned LEB128 varints, ints (and date day
+numbers) are zigzag encoded varints, reals are little-endian IEEE 754
+doubles, and strs are UTF-8 preceded by their length. Each value starts
+with a one byte tag.
+
+Since the encoding may come from an untrusted source, decoding fails
+(E873) rather than overflowing the stack if collections are nested more
+than `MAX_DEPTH` deep.
+
+By convention binary UXF files have the suffix `.uxb` (or, e.g.,
+`.uxb.gz` if compressed).
+
+```
+let uxo = uxf::parse("uxf 1\n=Point x:int y:int\n(Point 1 2 3 4)").unwrap();
+let bytes = uxf::binary::to_binary(&uxo);
+assert!(uxf::binary::is_binary(&bytes));
+assert_eq!(uxf::binary::from_binary(&bytes).unwrap(), uxo);
+```
+*/
+use crate::field::Field;
+use crate::key::Key;
+use crate::list::List;
+use crate::map::Map;
+use crate::table::Table;
+use crate::tclass::TClass;
+use crate::util::{compressed, read_bytes, write_bytes};
+use crate::uxf::{Uxf, WriteOptions};
+use crate::value::{Record, Value};
+use anyhow::{bail, Result};
+use chrono::{Datelike, NaiveDate, NaiveDateTime, NaiveTime, Timelike};
+use indexmap::IndexMap;
+use std::collections::HashMap;
+
+/// The bytes every binary UXF encoding starts with.
+pub const MAGIC: &[u8; 4] = b"UXFB";
+
+/// The version of the binary encoding.
+pub const BINARY_VERSION: u8 = 1;
+
+/// The maximum depth to which decoded collections may be nested.
+pub const MAX_DEPTH: usize = 512;
+
+const KIND_UXF: u8 = b'U';
+const KIND_VALUE: u8 = b'V';
+
+const TAG_NULL: u8 = 0;
+const TAG_FALSE: u8 = 1;
+const TAG_TRUE: u8 = 2;
+const TAG_INT: u8 = 3;
+const TAG_REAL: u8 = 4;
+const TAG_STR: u8 = 5;
+const TAG_BYTES: u8 = 6;
+const TAG_DATE: u8 = 7;
+const TAG_DATETIME: u8 = 8;
+const TAG_LIST: u8 = 9;
+const TAG_MAP: u8 = 10;
+const TAG_TABLE: u8 = 11;
+
+/// Returns `true` if the given bytes start with the binary UXF magic
+/// bytes; otherwise returns `false`.
+pub fn is_binary(bytes: &[u8]) -> bool {
+    bytes.starts_with(MAGIC)
+}
+
+/// Returns the binary encoding of the given `Uxf`.
+pub fn to_binary(uxo: &Uxf) -> Vec<u8> {
+    let mut encoder = Encoder::new(KIND_UXF);
+    encoder.str(uxo.custom());
+    encoder.str(uxo.comment());
+    encoder.uint(uxo.import_for_ttype.len() as u64);
+    for (ttype, import) in &uxo.import_for_ttype {
+        encoder.str(ttype);
+        encoder.str(import);
+    }
+    let mut tclasses: Vec<&TClass> =
+        uxo.tclass_for_ttype.values().collect();
+    tclasses.sort_by(|a, b| a.ttype().cmp(b.ttype()));
+    encoder.uint(tclasses.len() as u64);
+    for tclass in tclasses {
+        encoder.tclass(tclass);
+    }
+    encoder.finish(uxo.value())
+}
+
+/// Returns the `Uxf` encoded in the given bytes _or_ returns an Err if
+/// the bytes aren't a valid binary encoding of a `Uxf`.
+pub fn from_binary(bytes: &[u8]) -> Result<Uxf> {
+    from_binary_x(bytes, "-")
+}
+
+/// Returns the binary encoding of the given `Value` (which needn't be a
+/// collection).
+pub fn value_to_binary(value: &Value) -> Vec<u8> {
+    Encoder::new(KIND_VALUE).finish(value)
+}
+
+/// Returns the `Value` encoded in the given bytes _or_ returns an Err if
+/// the bytes aren't a valid binary encoding of a `Value`.
+pub fn value_from_binary(bytes: &[u8]) -> Result<Value> {
+    let mut decoder = Decoder::new(bytes, "-", KIND_VALUE)?;
+    let value = decoder.tables_and_value()?;
+    decoder.end()?;
+    Ok(value)
+}
+
+/// Returns the `Uxf` read from the given binary UXF file (which may be
+/// compressed; see the `codec` module) _or_ returns an Err if the file
+/// can't be read or isn't a valid binary UXF file.
+pub fn read_binary(filename: &str) -> Result<Uxf> {
+    from_binary_x(&read_bytes(filename)?, filename)
+}
+
+/// Writes the binary encoding of the given `Uxf` to the given file
+/// atomically, using the given `WriteOptions` and compression `level`
+/// (see `util::write_file_options()`).
+pub fn write_binary(
+    filename: &str,
+    uxo: &Uxf,
+    options: WriteOptions,
+    level: Option<u32>,
+) -> Result<()> {
+    write_bytes(
+        filename,
+        &compressed(filename, &to_binary(uxo), level)?,
+        options,
+    )
+}
+
+fn from_binary_x(bytes: &[u8], filename: &str) -> Result<Uxf> {
+    let mut decoder = Decoder::new(bytes, filename, KIND_UXF)?;
+    let custom = decoder.str()?;
+    let comment = decoder.str()?;
+    let mut import_for_ttype = IndexMap::new();
+    for _ in 0..decoder.count()? {
+        let ttype = decoder.str()?;
+        let import = decoder.str()?;
+        import_for_ttype.insert(ttype, import);
+    }
+    let mut tclass_for_ttype = HashMap::new();
+    for _ in 0..decoder.count()? {
+        let tclass = decoder.tclass()?;
+        tclass_for_ttype.insert(tclass.ttype().to_string(), tclass);
+    }
+    let value = decoder.tables_and_value()?;
+    decoder.end()?;
+    let mut uxo = Uxf::new(&custom, &comment);
+    uxo.set_value(value).map_err(|_| {
+        decoder.invalid("a Uxf's value must be a list, map, or table")
+    })?;
+    uxo.tclass_for_ttype = tclass_for_ttype;
+    uxo.import_for_ttype = import_for_ttype;
+    Ok(uxo)
+}
+
+struct Encoder {
+    body: Vec<u8>,
+    tclasses: Vec<TClass>, // the distinct TClasses of the value's tables
+}
+
+impl Encoder {
+    fn new(kind: u8) -> Self {
+        let mut body = MAGIC.to_vec();
+        body.push(BINARY_VERSION);
+        body.push(kind);
+        Encoder { body, tclasses: vec![] }
+    }
+
+    // Encodes the value (gathering the tables' TClasses as it goes), and
+    // returns everything encoded so far followed by the TClasses and the
+    // value.
+    fn finish(mut self, value: &Value) -> Vec<u8> {
+        let mut bytes = std::mem::take(&mut self.body);
+        self.value(value);
+        let value = std::mem::take(&mut self.body);
+        let tclasses = std::mem::take(&mut self.tclasses);
+        self.uint(tclasses.len() as u64);
+        for tclass in &tclasses {
+            self.tclass(tclass);
+        }
+        bytes.append(&mut self.body);
+        bytes.extend_from_slice(&value);
+        bytes
+    }
+
+    fn uint(&mut self, mut n: u64) {
+        loop {
+            let byte = (n & 0x7F) as u8;
+            n >>= 7;
+            if n == 0 {
+                self.body.push(byte);
+                break;
+            }
+            self.body.push(byte | 0x80);
+        }
+    }
+
+    fn int(&mut self, i: i64) {
+        self.uint(((i << 1) ^ (i >> 63)) as u64);
+    }
+
+    fn str(&mut self, s: &str) {
+        self.bytes(s.as_bytes());
+    }
+
+    fn bytes(&mut self, b: &[u8]) {
+        self.uint(b.len() as u64);
+        self.body.extend_from_slice(b);
+    }
+
+    fn date(&mut self, d: &NaiveDate) {
+        self.int(d.num_days_from_ce() as i64);
+    }
+
+    fn tclass(&mut self, tclass: &TClass) {
+        self.str(tclass.ttype());
+        self.str(tclass.comment());
+        self.uint(tclass.fields().len() as u64);
+        for field in tclass.fields() {
+            self.str(field.name());
+            self.str(field.vtype().unwrap_or_default());
+        }
+        let members = tclass.members();
+        self.uint(members.len() as u64);
+        for member in members {
+            self.str(member);
+        }
+        let key = tclass.key();
+        self.uint(key.len() as u64);
+        for fieldname in key {
+            self.str(fieldname);
+        }
+    }
+
+    fn value(&mut self, value: &Value) {
+        match value {
+            Value::Null => self.body.push(TAG_NULL),
+            Value::Bool(false) => self.body.push(TAG_FALSE),
+            Value::Bool(true) => self.body.push(TAG_TRUE),
+            Value::Int(i) => {
+                self.body.push(TAG_INT);
+                self.int(*i);
+            }
+            Value::Real(r) => {
+                self.body.push(TAG_REAL);
+                self.body.extend_from_slice(&r.to_le_bytes());
+            }
+            Value::Str(s) => {
+                self.body.push(TAG_STR);
+                self.str(s);
+            }
+            Value::Bytes(b) => {
+                self.body.push(TAG_BYTES);
+                self.bytes(b);
+            }
+            Value::Date(d) => {
+                self.body.push(TAG_DATE);
+                self.date(d);
+            }
+            Value::DateTime(dt) => {
+                self.body.push(TAG_DATETIME);
+                self.date(&dt.date());
+                self.uint(dt.num_seconds_from_midnight() as u64);
+                self.uint(dt.nanosecond() as u64);
+            }
+            Value::List(lst) => {
+                self.body.push(TAG_LIST);
+                self.str(lst.vtype());
+                self.str(lst.comment());
+                self.uint(lst.len() as u64);
+                for value in lst.iter() {
+                    self.value(value);
+                }
+            }
+            Value::Map(m) => {
+                self.body.push(TAG_MAP);
+                self.str(m.ktype());
+                self.str(m.vtype());
+                self.str(m.comment());
+                self.uint(m.len() as u64);
+                for (key, value) in m.inner() {
+                    self.value(&Value::from(key.clone()));
+                    self.value(value);
+                }
+            }
+            Value::Table(t) => {
+                self.body.push(TAG_TABLE);
+                let index = self.tclass_index(t.tclass());
+                self.uint(index as u64);
+                self.str(t.comment());
+                let key = t.key();
+                self.uint(key.len() as u64);
+                for fieldname in key {
+                    self.str(fieldname);
+                }
+                self.uint(t.len() as u64);
+                for record in t.iter() {
+                    for value in record {
+                        self.value(value);
+                    }
+                }
+            }
+        }
+    }
+
+    fn tclass_index(&mut self, tclass: &TClass) -> usize {
+        match self.tclasses.iter().position(|t| {
+            t == tclass
+                && t.comment() == tclass.comment()
+                && t.key() == tclass.key()
+        }) {
+            Some(index) => index,
+            None => {
+                self.tclasses.push(tclass.clone());
+                self.tclasses.len() - 1
+            }
+        }
+    }
+}
+
+struct Decoder<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+    filename: &'a str,
+    tclasses: Vec<TClass>, // the TClasses that tables refer to by index
+    depth: usize,          // of the collection being decoded
+}
+
+impl<'a> Decoder<'a> {
+    fn new(bytes: &'a [u8], filename: &'a str, kind: u8) -> Result<Self> {
+        if !is_binary(bytes) {
+            bail!("E870:{filename}:0:not binary UXF")
+        }
+        let mut decoder = Decoder {
+            bytes,
+            pos: MAGIC.len(),
+            filename,
+            tclasses: vec![],
+            depth: 0,
+        };
+        let version = decoder.byte()?;
+        if version != BINARY_VERSION {
+            bail!(
+                "E871:{filename}:0:unsupported binary UXF version \
+                {version}"
+            )
+        }
+        let actual = decoder.byte()?;
+        if actual != kind {
+            bail!(
+                "E874:{filename}:0:expected binary UXF {} got {}",
+                kind_name(kind),
+                kind_name(actual)
+            )
+        }
+        Ok(decoder)
+    }
+
+    fn end(&self) -> Result<()> {
+        if self.pos != self.bytes.len() {
+            bail!(
+                "E875:{}:0:unexpected trailing bytes at offset {}",
+                self.filename,
+                self.pos
+            )
+        }
+        Ok(())
+    }
+
+    fn invalid(&self, message: &str) -> anyhow::Error {
+        anyhow::anyhow!(
+            "E873:{}:0:invalid binary UXF at offset {}: {message}",
+            self.filename,
+            self.pos
+        )
+    }
+
+    fn take(&mut self, n: usize) -> Result<&'a [u8]> {
+        if n > self.bytes.len() - self.pos {
+            bail!(
+                "E872:{}:0:binary UXF is truncated at offset {}",
+                self.filename,
+                self.bytes.len()
+            )
+        }
+        let bytes = &self.bytes[self.pos..self.pos + n];
+        self.pos += n;
+        Ok(bytes)
+    }
+
+    fn byte(&mut self) -> Result<u8> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn uint(&mut self) -> Result<u64> {
+        let mut n = 0u64;
+        let mut shift = 0;
+        loop {
+            let byte = self.byte()?;
+            if shift > 63 {
+                return Err(self.invalid("varint too long"));
+            }
+            n |= ((byte & 0x7F) as u64) << shift;
+            if byte & 0x80 == 0 {
+                return Ok(n);
+            }
+            shift += 7;
+        }
+    }
+
+    // Returns a count or length which can't exceed the remaining bytes
+    // (each counted item takes at least one byte).
+    fn count(&mut self) -> Result<usize> {
+        let n = self.uint()?;
+        if n > (self.bytes.len() - self.pos) as u64 {
+            bail!(
+                "E872:{}:0:binary UXF is truncated at offset {}",
+                self.filename,
+                self.bytes.len()
+            )
+        }
+        Ok(n as usize)
+    }
+
+    fn int(&mut self) -> Result<i64> {
+        let n = self.uint()?;
+        Ok((n >> 1) as i64 ^ -((n & 1) as i64))
+    }
+
+    fn bytes(&mut self) -> Result<Vec<u8>> {
+        let n = self.count()?;
+        Ok(self.take(n)?.to_vec())
+    }
+
+    fn str(&mut self) -> Result<String> {
+        let bytes = self.bytes()?;
+        String::from_utf8(bytes).map_err(|_| self.invalid("invalid UTF-8"))
+    }
+
+    fn date(&mut self) -> Result<NaiveDate> {
+        let days = self.int()?;
+        i32::try_from(days)
+            .ok()
+            .and_then(NaiveDate::from_num_days_from_ce_opt)
+            .ok_or_else(|| self.invalid("invalid date"))
+    }
+
+    fn datetime(&mut self) -> Result<NaiveDateTime> {
+        let date = self.date()?;
+        let secs = u32::try_from(self.uint()?).ok();
+        let nanos = u32::try_from(self.uint()?).ok();
+        let time = secs.zip(nanos).and_then(|(secs, nanos)| {
+            NaiveTime::from_num_seconds_from_midnight_opt(secs, nanos)
+        });
+        match time {
+            Some(time) => Ok(NaiveDateTime::new(date, time)),
+            None => Err(self.invalid("invalid datetime")),
+        }
+    }
+
+    fn tclass(&mut self) -> Result<TClass> {
+        let ttype = self.str()?;
+        let comment = self.str()?;
+        let mut fields = vec![];
+        for _ in 0..self.count()? {
+            let name = self.str()?;
+            let vtype = self.str()?;
+            fields.push(Field::new(&name, &vtype)?);
+        }
+        let mut members = vec![];
+        for _ in 0..self.count()? {
+            members.push(self.str()?);
+        }
+        let mut key = vec![];
+        for _ in 0..self.count()? {
+            key.push(self.str()?);
+        }
+        let mut tclass = if !members.is_empty() {
+            let members: Vec<&str> =
+                members.iter().map(|m| m.as_str()).collect();
+            TClass::new_enum(&ttype, &members, &comment)?
+        } else if fields.is_empty() {
+            TClass::new_fieldless(&ttype, &comment)?
+        } else {
+            TClass::new(&ttype, fields, &comment)?
+        };
+        let key: Vec<&str> = key.iter().map(|k| k.as_str()).collect();
+        tclass.set_key(&key)?;
+        Ok(tclass)
+    }
+
+    fn tables_and_value(&mut self) -> Result<Value> {
+        for _ in 0..self.count()? {
+            let tclass = self.tclass()?;
+            self.tclasses.push(tclass);
+        }
+        self.value()
+    }
+
+    // Kept small (with the scalars and collections decoded elsewhere)
+    // since it recurses once per level of nesting.
+    fn value(&mut self) -> Result<Value> {
+        let tag = self.byte()?;
+        if matches!(tag, TAG_LIST | TAG_MAP | TAG_TABLE) {
+            self.collection(tag)
+        } else {
+            self.scalar(tag)
+        }
+    }
+
+    fn scalar(&mut self, tag: u8) -> Result<Value> {
+        Ok(match tag {
+            TAG_NULL => Value::Null,
+            TAG_FALSE => Value::Bool(false),
+            TAG_TRUE => Value::Bool(true),
+            TAG_INT => Value::Int(self.int()?),
+            TAG_REAL => {
+                // safe to unwrap since take() returned 8 bytes
+                let bytes = self.take(8)?.try_into().unwrap();
+                Value::Real(f64::from_le_bytes(bytes))
+            }
+            TAG_STR => Value::Str(self.str()?),
+            TAG_BYTES => Value::Bytes(self.bytes()?),
+            TAG_DATE => Value::Date(self.date()?),
+            TAG_DATETIME => Value::DateTime(self.datetime()?),
+            tag => return Err(self.invalid(&format!("unknown tag {tag}"))),
+        })
+    }
+
+    // Fails if the collection is too deeply nested (rather than risking a
+    // stack overflow).
+    fn collection(&mut self, tag: u8) -> Result<Value> {
+        if self.depth == MAX_DEPTH {
+            return Err(self.invalid("too deeply nested"));
+        }
+        self.depth += 1;
+        let reply = match tag {
+            TAG_LIST => self.list(),
+            TAG_MAP => self.map(),
+            _ => self.table(),
+        };
+        self.depth -= 1;
+        reply
+    }
+
+    fn list(&mut self) -> Result<Value> {
+        let vtype = self.str()?;
+        let comment = self.str()?;
+        let mut lst = List::new(&vtype, &comment)?;
+        for _ in 0..self.count()? {
+            lst.push(self.value()?);
+        }
+        Ok(Value::List(lst))
+    }
+
+    fn map(&mut self) -> Result<Value> {
+        let ktype = self.str()?;
+        let vtype = self.str()?;
+        let comment = self.str()?;
+        let mut m = Map::new(&ktype, &vtype, &comment)?;
+        for _ in 0..self.count()? {
+            let key = Key::from(self.value()?)
+                .map_err(|_| self.invalid("invalid map key"))?;
+            let value = self.value()?;
+            m.inner_mut().insert(key, value);
+        }
+        Ok(Value::Map(m))
+    }
+
+    fn table(&mut self) -> Result<Value> {
+        let index = self.uint()? as usize;
+        let tclass = match self.tclasses.get(index) {
+            Some(tclass) => tclass.clone(),
+            None => return Err(self.invalid("invalid TClass index")),
+        };
+        let comment = self.str()?;
+        let mut key = vec![];
+        for _ in 0..self.count()? {
+            key.push(self.str()?);
+        }
+        let columns = tclass.len();
+        let mut table = Table::new(tclass, &comment);
+        let rows = self.count()?;
+        if columns == 0 && rows > 0 {
+            return Err(self.invalid("fieldless table with records"));
+        }
+        for _ in 0..rows {
+            let mut record = Record::with_capacity(columns);
+            for _ in 0..columns {
+                record.push(self.value()?);
+            }
+            table.inner_mut().push(record);
+        }
+        if !key.is_empty() {
+            let key: Vec<&str> = key.iter().map(|k| k.as_str()).collect();
+            table.set_key_x(&key, false, self.filename, 0)?;
+        }
+        Ok(Value::Table(table))
+    }
+}
+
+fn kind_name(kind: u8) -> &'static str {
+    match kind {
+        KIND_UXF => "Uxf",
+        KIND_VALUE => "Value",
+        _ => "unknown",
+    }
+}
@@ -3,8 +3,13 @@
 
 /*! Internal use—publically visible because I don't know how to hide it. */
 use crate::consts::*;
+use crate::uxf::Uxf;
+use crate::value::Value;
 use anyhow::{bail, Result};
 
+/// A type problem: its code and message.
+pub(crate) type Problem = (u16, String);
+
 /// Returns `Ok(())` if `ktype` is a valid ktype; otherwise `Err`.
 #[allow(dead_code)]
 pub(crate) fn check_ktype(ktype: &str) -> Result<()> {
@@ -74,6 +79,38 @@ pub(crate) fn check_name_x(
     Ok(())
 }
 
+/// Returns `true` if the (non-null) `value` is of the given built-in
+/// `vtype` (which may be one of the pseudo-vtypes `key`, `number`, or
+/// `scalar`); otherwise returns `false`.
+pub(crate) fn is_of_vtype(value: &Value, vtype: &str) -> bool {
+    let typename = value.typename();
+    if vtype == VTYPE_NAME_KEY {
+        KTYPES.contains(&typename) || typename == VTYPE_NAME_DATETIME
+    } else if vtype == VTYPE_NAME_NUMBER {
+        typename == VTYPE_NAME_INT || typename == VTYPE_NAME_REAL
+    } else if vtype == VTYPE_NAME_SCALAR {
+        !value.is_collection()
+    } else {
+        typename == vtype
+    }
+}
+
+/// Returns `true` if `vtype` is a built-in vtype or a pseudo-vtype. If
+/// `uxo` is given, a pseudo-vtype only counts if the `uxo` accepts
+/// pseudo-vtypes (see `Uxf::pseudo_vtypes()`) and doesn't define a _ttype_
+/// of the same name.
+pub(crate) fn is_builtin_vtype(vtype: &str, uxo: Option<&Uxf>) -> bool {
+    VTYPES.contains(&vtype)
+        || (PSEUDO_VTYPES.contains(&vtype)
+            && match uxo {
+                Some(uxo) => {
+                    uxo.pseudo_vtypes()
+                        && !uxo.tclass_for_ttype.contains_key(vtype)
+                }
+                None => true,
+            })
+}
+
 /// Returns `Ok(())` if `name` is a valid vtype; otherwise `Err`.
 /// Note that for lists and maps an empty vtype means any vtype is
 /// acceptable; so for these, only check the vtype if it is nonempty.
@@ -134,3 +171,182 @@ pub(crate) fn check_vtype_x(
     }
     Ok(())
 }
+
+/// Checks `value` against `vtype` (which may be empty meaning any vtype, a
+/// built-in vtype, or a ttype) and, if the `value` is a collection, checks
+/// its contents against the collection's own ktype, vtype, or TClass,
+/// recursively. Any problems found are appended to `problems`; their
+/// messages end with the `path` of the offending value.
+/// If `uxo` is given, every ttype used must be defined in it and every
+/// table's TClass must match its definition.
+pub(crate) fn typecheck(
+    value: &Value,
+    vtype: &str,
+    path: &str,
+    uxo: Option<&Uxf>,
+    problems: &mut Vec<Problem>,
+) {
+    let tclass_for_ttype = uxo.map(|uxo| &uxo.tclass_for_ttype);
+    if !vtype.is_empty() && !value.is_null() {
+        let ok = if is_builtin_vtype(vtype, uxo) {
+            is_of_vtype(value, vtype)
+        } else if let Some(table) = value.as_table() {
+            table.ttype() == vtype
+                || tclass_for_ttype
+                    .and_then(|tclass_for_ttype| {
+                        tclass_for_ttype.get(vtype)
+                    })
+                    .is_some_and(|tclass| tclass.has_member(table.ttype()))
+        } else {
+            false
+        };
+        if !ok {
+            problems.push((
+                if value.is_collection() { 506 } else { 500 },
+                format!(
+                    "expected {vtype}, got {} at {}",
+                    describe(value),
+                    at(path)
+                ),
+            ));
+        }
+    }
+    match value {
+        Value::List(lst) => {
+            typecheck_vtype(lst.vtype(), path, uxo, problems);
+            for (i, value) in lst.iter().enumerate() {
+                typecheck(
+                    value,
+                    lst.vtype(),
+                    &format!("{path}[{i}]"),
+                    uxo,
+                    problems,
+                );
+            }
+        }
+        Value::Map(m) => {
+            typecheck_vtype(m.vtype(), path, uxo, problems);
+            for key in m.sorted_keys() {
+                let key_value = Value::from(key.clone());
+                if !m.ktype().is_empty()
+                    && key_value.typename() != m.ktype()
+                {
+                    problems.push((
+                        500,
+                        format!(
+                            "expected {} key, got {} at {}",
+                            m.ktype(),
+                            describe(&key_value),
+                            at(path)
+                        ),
+                    ));
+                }
+                // safe to unwrap since key is from m
+                typecheck(
+                    m.get(key).unwrap(),
+                    m.vtype(),
+                    &format!("{path}{{{key}}}"),
+                    uxo,
+                    problems,
+                );
+            }
+        }
+        Value::Table(t) => {
+            let tclass = t.tclass();
+            if let Some(tclass_for_ttype) = tclass_for_ttype {
+                match tclass_for_ttype.get(t.ttype()) {
+                    None => problems.push((
+                        450,
+                        format!(
+                            "undefined ttype {} at {}",
+                            t.ttype(),
+                            at(path)
+                        ),
+                    )),
+                    Some(defined) if defined != tclass => problems.push((
+                        457,
+                        format!(
+                            "table of ttype {} doesn't match its TClass \
+                            definition at {}",
+                            t.ttype(),
+                            at(path)
+                        ),
+                    )),
+                    _ => (),
+                }
+            }
+            for field in tclass.fields() {
+                if let Some(vtype) = field.vtype() {
+                    typecheck_vtype(vtype, path, uxo, problems);
+                }
+            }
+            for (row, record) in t.iter().enumerate() {
+                if record.len() != tclass.len() {
+                    problems.push((
+                        736,
+                        format!(
+                            "expected {} values, got {} at {path}[{row}]",
+                            tclass.len(),
+                            record.len()
+                        ),
+                    ));
+                }
+                for (field, value) in tclass.fields().iter().zip(record) {
+                    typecheck(
+                        value,
+                        field.vtype().unwrap_or_default(),
+                        &format!("{path}[{row}].{}", field.name()),
+                        uxo,
+                        problems,
+                    );
+                }
+            }
+        }
+        _ => (),
+    }
+}
+
+/// Reports a `vtype` that is a ttype which isn't defined (if `uxo` is
+/// given).
+pub(crate) fn typecheck_vtype(
+    vtype: &str,
+    path: &str,
+    uxo: Option<&Uxf>,
+    problems: &mut Vec<Problem>,
+) {
+    if let Some(uxo) = uxo {
+        if !vtype.is_empty()
+            && !is_builtin_vtype(vtype, Some(uxo))
+            && !uxo.tclass_for_ttype.contains_key(vtype)
+        {
+            problems.push((
+                446,
+                format!("undefined ttype {vtype} used at {}", at(path)),
+            ));
+        }
+    }
+}
+
+/// Returns `Err` for the first problem (if any); otherwise `Ok(())`.
+pub(crate) fn first_problem(problems: &[Problem]) -> Result<()> {
+    if let Some((code, message)) = problems.first() {
+        bail!("E{code}:-:0:{message}")
+    }
+    Ok(())
+}
+
+fn describe(value: &Value) -> String {
+    match value {
+        Value::List(_) | Value::Map(_) => value.typename().to_string(),
+        Value::Table(t) => format!("table of ttype {}", t.ttype()),
+        _ => format!("{} {value}", value.typename()),
+    }
+}
+
+fn at(path: &str) -> &str {
+    if path.is_empty() {
+        "the top level"
+    } else {
+        path
+    }
+}
@@ -0,0 +1,222 @@
+// Copyright © 2022 Mark Summerfield. All rights reserved.
+// License: GPLv3
+
+/*! This module provides the compression codecs that UXF files (text or
+binary) may be compressed with.
+
+When reading, the codec is detected from the file's first few (magic)
+bytes, so a compressed file's name needn't have a particular suffix. When
+writing, the codec is chosen by the filename's suffix: `.gz` for gzip,
+`.zst` for zstd, `.xz` for xz, and `.bz2` for bzip2; any other suffix
+means the file is written uncompressed.
+
+Gzip is always available. Zstd, xz, and bzip2 are only available if the
+`zstd`, `xz`, or `bzip2` cargo feature respectively is enabled; reading or
+writing a file that needs an unavailable codec is an error.
+
+Every codec reads files that consist of several concatenated compressed
+streams (e.g., as written by an `Appender`) as one.
+
+```
+use uxf::codec::Codec;
+
+assert_eq!(Codec::for_filename("data.uxf.gz"), Codec::Gzip);
+assert_eq!(Codec::for_filename("data.uxf"), Codec::Plain);
+let bytes = Codec::Gzip.compress(b"uxf 1\n[]\n", None, "-").unwrap();
+assert_eq!(Codec::for_bytes(&bytes), Codec::Gzip);
+assert_eq!(Codec::Gzip.decompress(&bytes, "-").unwrap(), b"uxf 1\n[]\n");
+```
+*/
+use anyhow::{bail, Context, Result};
+use std::{fmt, io::prelude::*};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Codec {
+    Plain,
+    Gzip,
+    Zstd,
+    Xz,
+    Bzip2,
+}
+
+impl Codec {
+    /// Returns the codec for the given `filename`'s suffix (`Plain` if the
+    /// suffix isn't one used for compressed files).
+    pub fn for_filename(filename: &str) -> Codec {
+        for codec in [Codec::Gzip, Codec::Zstd, Codec::Xz, Codec::Bzip2] {
+            if filename.ends_with(codec.suffix()) {
+                return codec;
+            }
+        }
+        Codec::Plain
+    }
+
+    /// Returns the codec whose magic bytes the given `bytes` start with
+    /// (`Plain` if they don't start with any codec's magic bytes).
+    pub fn for_bytes(bytes: &[u8]) -> Codec {
+        if bytes.starts_with(&[0x1F, 0x8B]) {
+            Codec::Gzip
+        } else if bytes.starts_with(&[0x28, 0xB5, 0x2F, 0xFD]) {
+            Codec::Zstd
+        } else if bytes.starts_with(&[0xFD, b'7', b'z', b'X', b'Z', 0x00]) {
+            Codec::Xz
+        } else if bytes.starts_with(b"BZh") {
+            Codec::Bzip2
+        } else {
+            Codec::Plain
+        }
+    }
+
+    /// Returns the filename suffix for this codec (`""` for `Plain`).
+    pub fn suffix(&self) -> &'static str {
+        match self {
+            Codec::Plain => "",
+            Codec::Gzip => ".gz",
+            Codec::Zstd => ".zst",
+            Codec::Xz => ".xz",
+            Codec::Bzip2 => ".bz2",
+        }
+    }
+
+    /// Returns `true` if this codec can be used, i.e., if it is `Plain` or
+    /// `Gzip`, or if its cargo feature is enabled; otherwise returns
+    /// `false`.
+    pub fn is_available(&self) -> bool {
+        match self {
+            Codec::Plain | Codec::Gzip => true,
+            Codec::Zstd => cfg!(feature = "zstd"),
+            Codec::Xz => cfg!(feature = "xz"),
+            Codec::Bzip2 => cfg!(feature = "bzip2"),
+        }
+    }
+
+    /// Returns the range of valid compression levels for this codec
+    /// (where higher means smaller but slower).
+    pub fn levels(&self) -> std::ops::RangeInclusive<u32> {
+        match self {
+            Codec::Plain => 0..=0,
+            Codec::Gzip | Codec::Xz => 0..=9,
+            Codec::Zstd => 1..=22,
+            Codec::Bzip2 => 1..=9,
+        }
+    }
+
+    /// Returns the compression level used if none is specified, i.e., the
+    /// best compression (except for zstd, where 19 is the highest level
+    /// that doesn't need a lot of extra memory to decompress).
+    pub fn default_level(&self) -> u32 {
+        match self {
+            Codec::Zstd => 19,
+            _ => *self.levels().end(),
+        }
+    }
+
+    /// Returns the given `bytes` compressed using this codec at the given
+    /// `level` (or at the `default_level()` if `None`; an out of range
+    /// level is clamped to the nearest valid level) _or_ returns an Err
+    /// if the codec isn't available or the compression fails. The
+    /// `filename` is only used for error messages.
+    pub fn compress(
+        &self,
+        bytes: &[u8],
+        level: Option<u32>,
+        filename: &str,
+    ) -> Result<Vec<u8>> {
+        self.check_available(filename)?;
+        let levels = self.levels();
+        let level = level
+            .unwrap_or_else(|| self.default_level())
+            .clamp(*levels.start(), *levels.end());
+        let compressed = match self {
+            Codec::Plain => Ok(bytes.to_vec()),
+            Codec::Gzip => {
+                let mut out = flate2::write::GzEncoder::new(
+                    vec![],
+                    flate2::Compression::new(level),
+                );
+                out.write_all(bytes).and_then(|_| out.finish())
+            }
+            #[cfg(feature = "zstd")]
+            Codec::Zstd => zstd::stream::encode_all(bytes, level as i32),
+            #[cfg(feature = "xz")]
+            Codec::Xz => {
+                let mut out = xz2::write::XzEncoder::new(vec![], level);
+                out.write_all(bytes).and_then(|_| out.finish())
+            }
+            #[cfg(feature = "bzip2")]
+            Codec::Bzip2 => {
+                let mut out = bzip2::write::BzEncoder::new(
+                    vec![],
+                    bzip2::Compression::new(level),
+                );
+                out.write_all(bytes).and_then(|_| out.finish())
+            }
+            #[allow(unreachable_patterns)]
+            _ => unreachable!(), // check_available() ensures this
+        };
+        compressed.with_context(|| {
+            format!("E901:{filename}:0:failed to {self}-compress")
+        })
+    }
+
+    /// Returns the given `bytes` decompressed using this codec _or_
+    /// returns an Err if the codec isn't available or the bytes can't be
+    /// decompressed. The `filename` is only used for error messages.
+    pub fn decompress(
+        &self,
+        bytes: &[u8],
+        filename: &str,
+    ) -> Result<Vec<u8>> {
+        self.check_available(filename)?;
+        let mut out = vec![];
+        let decompressed = match self {
+            Codec::Plain => {
+                out.extend_from_slice(bytes);
+                Ok(0)
+            }
+            Codec::Gzip => flate2::read::MultiGzDecoder::new(bytes)
+                .read_to_end(&mut out),
+            #[cfg(feature = "zstd")]
+            Codec::Zstd => zstd::stream::read::Decoder::new(bytes)
+                .and_then(|mut decoder| decoder.read_to_end(&mut out)),
+            #[cfg(feature = "xz")]
+            Codec::Xz => xz2::read::XzDecoder::new_multi_decoder(bytes)
+                .read_to_end(&mut out),
+            #[cfg(feature = "bzip2")]
+            Codec::Bzip2 => bzip2::read::MultiBzDecoder::new(bytes)
+                .read_to_end(&mut out),
+            #[allow(unreachable_patterns)]
+            _ => unreachable!(), // check_available() ensures this
+        };
+        decompressed.with_context(|| {
+            format!("E951:{filename}:0:failed to read {self}-compressed")
+        })?;
+        Ok(out)
+    }
+
+    fn check_available(&self, filename: &str) -> Result<()> {
+        if !self.is_available() {
+            bail!(
+                "E960:{filename}:0:{self} compression isn't available \
+                (uxf must be built with the {self} feature)"
+            )
+        }
+        Ok(())
+    }
+
+    fn name(&self) -> &'static str {
+        match self {
+            Codec::Plain => "plain",
+            Codec::Gzip => "gzip",
+            Codec::Zstd => "zstd",
+            Codec::Xz => "xz",
+            Codec::Bzip2 => "bzip2",
+        }
+    }
+}
+
+impl fmt::Display for Codec {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
@@ -0,0 +1,390 @@
+// Copyright © 2022 Mark Summerfield. All rights reserved.
+// License: GPLv3
+
+/*! A Config provides layered application configuration: defaults, then
+system, user, and project UXF config files, then environment variables,
+then explicit `key.path=value` overrides (e.g., from the command line).
+
+Every layer is a `Map` (so every config file's value must be a map). The
+layers are deep-merged in `Layer` order, i.e., a later layer's value
+replaces an earlier layer's value for the same key, except that when both
+values are maps they are merged key by key. The layer that supplied each
+value is tracked, and can be queried using `layer()`.
+
+Values are accessed using dotted key paths, e.g., `"colors.bg1"` for the
+`<bg1>` key of the `<colors>` map. (So keys must be `str`s that don't
+contain `.`.)
+
+Environment variable and override values are strings converted using
+`naturalize()`, so `"22"` becomes an `int`, `"yes"` a `bool`, and so on;
+however, if the value being overridden is a `str`, the value is kept as a
+`str`.
+
+Only the user layer is ever saved. It is loaded as a `cst::Document`, and
+changes made using `set()` are applied to the document, so saving it
+preserves the user's comments and layout. (Except that adding a key
+rewrites the text of the map it is added to.)
+
+```
+use uxf::config::{Config, Layer};
+
+let defaults = uxf::parse("uxf 1\n{<fontsize> 18 <colors> \
+                          {<bg> <white> <fg> <black>}}\n").unwrap();
+let mut config =
+    Config::new(defaults.value().as_map().unwrap().clone());
+config.apply_override("colors.fg=navy").unwrap();
+config.apply_override("fontsize=22").unwrap();
+assert_eq!(config.get_as::<i64>("fontsize").unwrap(), 22);
+assert_eq!(config.get_as::<String>("colors.fg").unwrap(), "navy");
+assert_eq!(config.layer("colors.fg"), Some(Layer::Override));
+assert_eq!(config.layer("colors.bg"), Some(Layer::Default));
+```
+*/
+use crate::convert::FromValue;
+use crate::cst::{Document, Step};
+use crate::event::OnEventFn;
+use crate::key::Key;
+use crate::map::Map;
+use crate::uxf::{parse_options, ParserOptions};
+use crate::value::{naturalize, Value};
+use anyhow::{bail, Result};
+use std::{
+    collections::{BTreeMap, HashMap},
+    fmt,
+    path::Path,
+};
+
+const EMPTY_CONFIG: &str = "uxf 1\n{}\n";
+
+/// The configuration layers in order of increasing precedence.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Layer {
+    Default,
+    System,
+    User,
+    Project,
+    Env,
+    Override,
+}
+
+impl fmt::Display for Layer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}",
+            match self {
+                Layer::Default => "default",
+                Layer::System => "system",
+                Layer::User => "user",
+                Layer::Project => "project",
+                Layer::Env => "env",
+                Layer::Override => "override",
+            }
+        )
+    }
+}
+
+#[derive(Clone, Default)]
+pub struct Config {
+    layers: BTreeMap<Layer, Map>,
+    value: Map,
+    layer_for_path: HashMap<String, Layer>,
+    user_filename: String,
+    user_doc: Option<Document>,
+    on_event: Option<OnEventFn>,
+}
+
+impl Config {
+    /// Returns a new Config whose default layer is the given map.
+    pub fn new(defaults: Map) -> Self {
+        let mut config = Config::default();
+        config.layers.insert(Layer::Default, defaults);
+        config.rebuild();
+        config
+    }
+
+    /// Sets the event handler used when parsing config files (the default
+    /// handler is used if this isn't called or is given `None`).
+    pub fn set_on_event(&mut self, on_event: Option<OnEventFn>) {
+        self.on_event = on_event;
+    }
+
+    /// Loads the given UXF file as the given layer (replacing any
+    /// previous map for that layer) and returns `true` _or_ returns
+    /// `false` if the file doesn't exist _or_ returns an Err if the file
+    /// can't be parsed or its value isn't a map.
+    /// For the `Layer::User` layer the filename is remembered (even if the
+    /// file doesn't exist) since this is where `save()` writes to.
+    pub fn load(&mut self, layer: Layer, filename: &str) -> Result<bool> {
+        let exists = Path::new(filename).exists();
+        if layer == Layer::User {
+            self.user_filename = filename.to_string();
+            let doc = Document::parse_options(
+                if exists { filename } else { EMPTY_CONFIG },
+                self.on_event.clone(),
+            )?;
+            let map = config_map(doc.uxf().value(), filename)?;
+            self.layers.insert(layer, map);
+            self.user_doc = Some(doc);
+        } else if exists {
+            let uxo = parse_options(
+                filename,
+                ParserOptions::DEFAULT,
+                self.on_event.clone(),
+            )?;
+            let map = config_map(uxo.value(), filename)?;
+            self.layers.insert(layer, map);
+        }
+        self.rebuild();
+        Ok(exists)
+    }
+
+    /// Applies every environment variable whose name starts with the
+    /// given prefix followed by `_` as an override in the `Layer::Env`
+    /// layer. The rest of the name is lowercased and `__` is used to
+    /// separate keys, e.g., with prefix `MYAPP`, `MYAPP_COLORS__BG=red` is
+    /// equivalent to `colors.bg=red`.
+    pub fn apply_env(&mut self, prefix: &str) -> Result<()> {
+        let prefix = format!("{prefix}_");
+        let mut vars: Vec<(String, String)> = std::env::vars()
+            .filter(|(name, _)| name.starts_with(&prefix))
+            .collect();
+        vars.sort_unstable(); // for deterministic errors
+        for (name, text) in vars {
+            let path =
+                name[prefix.len()..].to_lowercase().replace("__", ".");
+            self.set_in(Layer::Env, &path, &text)?;
+        }
+        Ok(())
+    }
+
+    /// Applies the given `key.path=value` override in the
+    /// `Layer::Override` layer, _or_ returns an Err if it isn't of that
+    /// form.
+    pub fn apply_override(&mut self, keyvalue: &str) -> Result<()> {
+        match keyvalue.split_once('=') {
+            Some((path, text)) => {
+                self.set_in(Layer::Override, path.trim(), text)
+            }
+            None => bail!(
+                "E840:-:0:expected an override of the form \
+                key.path=value, got {keyvalue:?}"
+            ),
+        }
+    }
+
+    /// Returns the value at the given dotted key path or `None`.
+    pub fn get(&self, path: &str) -> Option<&Value> {
+        let mut keys = path.split('.');
+        let mut value = self.value.get(&str_key(keys.next()?))?;
+        for key in keys {
+            value = value.as_map()?.get(&str_key(key))?;
+        }
+        Some(value)
+    }
+
+    /// Returns the value at the given dotted key path converted to `T`,
+    /// _or_ returns an Err if there's no such value or it can't be
+    /// converted.
+    pub fn get_as<T: FromValue>(&self, path: &str) -> Result<T> {
+        match self.get(path) {
+            Some(value) => T::from_value(value),
+            None => bail!("E841:-:0:no config value for {path:?}"),
+        }
+    }
+
+    /// Returns the layer that supplied the value at the given dotted key
+    /// path or `None` if there's no such value. (For a map, this is the
+    /// highest layer that supplied any of its items.)
+    pub fn layer(&self, path: &str) -> Option<Layer> {
+        self.layer_for_path.get(path).copied()
+    }
+
+    /// Returns the merged config map.
+    pub fn value(&self) -> &Map {
+        &self.value
+    }
+
+    /// Returns the given layer's map or `None` if that layer hasn't been
+    /// set.
+    pub fn layer_map(&self, layer: Layer) -> Option<&Map> {
+        self.layers.get(&layer)
+    }
+
+    /// Sets the value at the given dotted key path in the user layer
+    /// (creating maps for any missing keys) ready to be saved, _or_
+    /// returns an Err if a key on the path has a non-map value.
+    /// (If an env or override layer also has this key path, its value
+    /// takes precedence.)
+    pub fn set(&mut self, path: &str, value: Value) -> Result<()> {
+        let keys = keys_for(path)?;
+        let map = self.layers.entry(Layer::User).or_default();
+        set_path(map, &keys, value.clone(), path)?;
+        let doc = match &mut self.user_doc {
+            Some(doc) => doc,
+            None => self.user_doc.insert(Document::parse(EMPTY_CONFIG)?),
+        };
+        // Replace the value if it is in the document; otherwise replace
+        // the deepest enclosing map that is with a copy that has the value
+        let steps: Vec<Step> =
+            keys.iter().map(|key| Step::Key(str_key(key))).collect();
+        let mut n = steps.len();
+        while n > 0 && doc.get(&steps[..n]).is_none() {
+            n -= 1;
+        }
+        if n == steps.len() {
+            doc.set(&steps, value)?;
+        } else if let Some(Value::Map(m)) = doc.get(&steps[..n]) {
+            let mut m = m.clone();
+            set_path(&mut m, &keys[n..], value, path)?;
+            doc.set(&steps[..n], Value::from(m))?;
+        }
+        self.rebuild();
+        Ok(())
+    }
+
+    /// Returns `true` if the user layer has unsaved changes.
+    pub fn is_changed(&self) -> bool {
+        self.user_doc.as_ref().is_some_and(|doc| doc.is_changed())
+    }
+
+    /// Saves the user layer to the user config file that was given to
+    /// `load()` (preserving its comments and layout), _or_ returns an Err
+    /// if no user config file was given or the file can't be written.
+    pub fn save(&self) -> Result<()> {
+        match &self.user_doc {
+            Some(doc) if !self.user_filename.is_empty() => {
+                doc.write(&self.user_filename)
+            }
+            _ => bail!("E842:-:0:no user config filename to save to"),
+        }
+    }
+
+    // Parses the text as for an override and sets it in the given layer.
+    fn set_in(
+        &mut self,
+        layer: Layer,
+        path: &str,
+        text: &str,
+    ) -> Result<()> {
+        let value = match self.get(path) {
+            Some(Value::Str(_)) => Value::Str(text.to_string()),
+            _ => naturalize(text),
+        };
+        let keys = keys_for(path)?;
+        set_path(
+            self.layers.entry(layer).or_default(),
+            &keys,
+            value,
+            path,
+        )?;
+        self.rebuild();
+        Ok(())
+    }
+
+    // Deep-merges the layers into self.value recording each value's layer.
+    fn rebuild(&mut self) {
+        self.value = Map::default();
+        self.layer_for_path.clear();
+        for (layer, map) in &self.layers {
+            merge_map(
+                &mut self.value,
+                map,
+                "",
+                *layer,
+                &mut self.layer_for_path,
+            );
+        }
+    }
+}
+
+fn config_map(value: &Value, filename: &str) -> Result<Map> {
+    match value.as_map() {
+        Some(m) => Ok(m.clone()),
+        None => bail!(
+            "E843:{filename}:0:a config file's value must be a map, got {}",
+            value.typename()
+        ),
+    }
+}
+
+fn keys_for(path: &str) -> Result<Vec<&str>> {
+    let keys: Vec<&str> = path.split('.').collect();
+    if keys.iter().any(|key| key.is_empty()) {
+        bail!("E844:-:0:invalid config key path {path:?}")
+    }
+    Ok(keys)
+}
+
+fn str_key(key: &str) -> Key {
+    Key::Str(key.to_string())
+}
+
+fn merge_map(
+    target: &mut Map,
+    source: &Map,
+    prefix: &str,
+    layer: Layer,
+    layer_for_path: &mut HashMap<String, Layer>,
+) {
+    for (key, value) in source.inner() {
+        let name = match key {
+            Key::Str(name) => name.to_string(),
+            _ => key.to_string(),
+        };
+        let path = if prefix.is_empty() {
+            name
+        } else {
+            format!("{prefix}.{name}")
+        };
+        layer_for_path.insert(path.clone(), layer);
+        let old_is_map = matches!(target.get(key), Some(Value::Map(_)));
+        if !(old_is_map && value.is_map()) {
+            // Values from lower layers under this path no longer apply
+            let sub = format!("{path}.");
+            layer_for_path.retain(|p, _| !p.starts_with(&sub));
+        }
+        if let Value::Map(new) = value {
+            if !old_is_map {
+                let m = Map::new(new.ktype(), new.vtype(), new.comment())
+                    .unwrap_or_default();
+                target.insert(key.clone(), Value::from(m));
+            }
+            if let Some(Value::Map(old)) = target.get_mut(key) {
+                merge_map(old, new, &path, layer, layer_for_path);
+            }
+        } else {
+            target.insert(key.clone(), value.clone());
+        }
+    }
+}
+
+// Sets the value at keys in map, creating maps for missing keys.
+fn set_path(
+    map: &mut Map,
+    keys: &[&str],
+    value: Value,
+    path: &str,
+) -> Result<()> {
+    match keys {
+        [] => bail!("E844:-:0:invalid config key path {path:?}"),
+        [key] => {
+            map.insert(str_key(key), value);
+        }
+        [key, rest @ ..] => {
+            let key = str_key(key);
+            if map.get(&key).is_none() {
+                map.insert(key.clone(), Value::from(Map::default()));
+            }
+            // safe to unwrap since we just ensured it exists
+            match map.get_mut(&key).unwrap() {
+                Value::Map(m) => set_path(m, rest, value, path)?,
+                _ => bail!(
+                    "E845:-:0:can't set config value {path:?} since \
+                    {key} isn't a map"
+                ),
+            }
+        }
+    }
+    Ok(())
+}
@@ -27,6 +27,14 @@ pub static VTYPE_NAME_REAL: &str = "real";
 pub static VTYPE_NAME_STR: &str = "str";
 pub static VTYPE_NAME_TABLE: &str = "table";
 
+// Pseudo-vtypes: key accepts bytes, date, datetime, int, or str; scalar
+// accepts any non-collection; and number accepts int or real. These are
+// not reserved words: the parser only recognizes them as vtypes if the
+// ParserOptions::PSEUDO_VTYPES flag is set and no ttype has the same name.
+pub static VTYPE_NAME_KEY: &str = "key";
+pub static VTYPE_NAME_NUMBER: &str = "number";
+pub static VTYPE_NAME_SCALAR: &str = "scalar";
+
 pub static BOOL_FALSE: &str = "no";
 pub static BOOL_TRUE: &str = "yes";
 
@@ -76,3 +84,6 @@ pub static VTYPES: [&str; 10] = [
     VTYPE_NAME_STR,
     VTYPE_NAME_TABLE,
 ];
+
+pub static PSEUDO_VTYPES: [&str; 3] =
+    [VTYPE_NAME_KEY, VTYPE_NAME_NUMBER, VTYPE_NAME_SCALAR];
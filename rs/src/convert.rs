@@ -0,0 +1,357 @@
+// Copyright © 2022 Mark Summerfield. All rights reserved.
+// License: GPLv3
+
+/*! Conversions from `Value`s to common Rust types.
+
+The `FromValue` trait is implemented for the scalar types (`bool`, the
+integer types, `f64`, `String`, `NaiveDate`, `NaiveDateTime`), for `Key`,
+`Value`, `List`, `Map`, and `Table` (by cloning), and generically for
+`Option<T>` (where `null` is `None`), `Vec<T>` (from a `List`, from a
+`Table` whose records convert to `T`, or for `Vec<u8>` from `bytes`),
+`HashMap<K, V>` and `BTreeMap<K, V>` (from a `Map`, converting each `Key`
+to `K`), and tuples of up to six items (from a `List` of that many values,
+or from a table record using `FromValue::from_record()`).
+
+`TryFrom<&Value>` is implemented for all of these except `Option<T>` (which
+the standard library's blanket implementations don't allow), and
+`Value::get_as::<T>()` provides the same conversions in method form.
+
+Conversion errors name the expected type and the actual value's
+`typename()`.
+
+```
+use std::collections::HashMap;
+use uxf::value::Value;
+
+let uxo = uxf::parse("uxf 1\n=Point x:int y:int\n\
+                     [{<a> 1 <b> 2} (Point 1 2 3 4) [? 5]]\n").unwrap();
+let lst = uxo.value().as_list().unwrap();
+let m: HashMap<String, i64> = lst[0].get_as().unwrap();
+assert_eq!(m["b"], 2);
+let points: Vec<(i64, i64)> = lst[1].get_as().unwrap();
+assert_eq!(points, [(1, 2), (3, 4)]);
+let maybes: Vec<Option<i64>> = lst[2].get_as().unwrap();
+assert_eq!(maybes, [None, Some(5)]);
+let ints = Vec::<i64>::try_from(&lst[2]);
+assert_eq!(ints.unwrap_err().to_string(),
+           "E770:-:0:expected int, got null");
+```
+*/
+use crate::consts::*;
+use crate::key::Key;
+use crate::list::List;
+use crate::map::Map;
+use crate::table::Table;
+use crate::value::Value;
+use anyhow::{bail, Result};
+use chrono::{NaiveDate, NaiveDateTime};
+use std::collections::{BTreeMap, HashMap};
+use std::hash::Hash;
+
+/// Conversion from a `&Value` (see the module documentation).
+pub trait FromValue: Sized {
+    /// Returns the value converted to `Self` _or_ an Err if the value is
+    /// of the wrong type (or is out of range).
+    fn from_value(value: &Value) -> Result<Self>;
+
+    /// Returns the table record's values converted to `Self` _or_ an Err.
+    /// By default only tuples can be created from records.
+    fn from_record(record: &[Value]) -> Result<Self> {
+        bail!(
+            "E772:-:0:can't convert a table record of {} values to {}",
+            record.len(),
+            std::any::type_name::<Self>()
+        )
+    }
+}
+
+fn type_error(expected: &str, value: &Value) -> anyhow::Error {
+    anyhow::anyhow!(
+        "E770:-:0:expected {expected}, got {}",
+        value.typename()
+    )
+}
+
+impl FromValue for Value {
+    fn from_value(value: &Value) -> Result<Self> {
+        Ok(value.clone())
+    }
+}
+
+impl FromValue for bool {
+    fn from_value(value: &Value) -> Result<Self> {
+        value.as_bool().ok_or_else(|| type_error(VTYPE_NAME_BOOL, value))
+    }
+}
+
+impl FromValue for i64 {
+    fn from_value(value: &Value) -> Result<Self> {
+        value.as_int().ok_or_else(|| type_error(VTYPE_NAME_INT, value))
+    }
+}
+
+macro_rules! from_value_for_int {
+    ($($t:ty),*) => {
+        $(
+            impl FromValue for $t {
+                fn from_value(value: &Value) -> Result<Self> {
+                    let i = i64::from_value(value)?;
+                    match <$t>::try_from(i) {
+                        Ok(i) => Ok(i),
+                        Err(_) => bail!(
+                            "E771:-:0:int {i} is out of range for {}",
+                            stringify!($t)
+                        ),
+                    }
+                }
+            }
+        )*
+    };
+}
+
+from_value_for_int!(i8, i16, i32, u8, u16, u32, u64, usize);
+
+impl FromValue for f64 {
+    /// Accepts an `int` as well as a `real`.
+    fn from_value(value: &Value) -> Result<Self> {
+        match value {
+            Value::Real(r) => Ok(*r),
+            Value::Int(i) => Ok(*i as f64),
+            _ => Err(type_error(VTYPE_NAME_REAL, value)),
+        }
+    }
+}
+
+impl FromValue for String {
+    fn from_value(value: &Value) -> Result<Self> {
+        value
+            .as_str()
+            .map(|s| s.to_string())
+            .ok_or_else(|| type_error(VTYPE_NAME_STR, value))
+    }
+}
+
+impl FromValue for NaiveDate {
+    fn from_value(value: &Value) -> Result<Self> {
+        value.as_date().ok_or_else(|| type_error(VTYPE_NAME_DATE, value))
+    }
+}
+
+impl FromValue for NaiveDateTime {
+    fn from_value(value: &Value) -> Result<Self> {
+        value
+            .as_datetime()
+            .ok_or_else(|| type_error(VTYPE_NAME_DATETIME, value))
+    }
+}
+
+impl FromValue for Key {
+    fn from_value(value: &Value) -> Result<Self> {
+        Key::from_x(value.clone(), "-", 0).map_err(|_| {
+            type_error("key (bytes, date, int, or str)", value)
+        })
+    }
+}
+
+impl FromValue for List {
+    fn from_value(value: &Value) -> Result<Self> {
+        value
+            .as_list()
+            .cloned()
+            .ok_or_else(|| type_error(VTYPE_NAME_LIST, value))
+    }
+}
+
+impl FromValue for Map {
+    fn from_value(value: &Value) -> Result<Self> {
+        value
+            .as_map()
+            .cloned()
+            .ok_or_else(|| type_error(VTYPE_NAME_MAP, value))
+    }
+}
+
+impl FromValue for Table {
+    fn from_value(value: &Value) -> Result<Self> {
+        value
+            .as_table()
+            .cloned()
+            .ok_or_else(|| type_error(VTYPE_NAME_TABLE, value))
+    }
+}
+
+impl<T: FromValue> FromValue for Option<T> {
+    /// Returns `None` for `null`; otherwise `Some(T)`.
+    fn from_value(value: &Value) -> Result<Self> {
+        if value.is_null() {
+            Ok(None)
+        } else {
+            T::from_value(value).map(Some)
+        }
+    }
+}
+
+impl<T: FromValue> FromValue for Vec<T> {
+    /// Converts a `List`'s values or a `Table`'s records; or for
+    /// `Vec<u8>`, `bytes`.
+    fn from_value(value: &Value) -> Result<Self> {
+        match value {
+            Value::List(lst) => lst.iter().map(T::from_value).collect(),
+            Value::Table(t) => {
+                t.iter().map(|record| T::from_record(record)).collect()
+            }
+            Value::Bytes(b) => b
+                .iter()
+                .map(|b| T::from_value(&Value::Int(*b as i64)))
+                .collect(),
+            _ => Err(type_error(VTYPE_NAME_LIST, value)),
+        }
+    }
+}
+
+impl<K, V> FromValue for HashMap<K, V>
+where
+    K: FromValue + Eq + Hash,
+    V: FromValue,
+{
+    fn from_value(value: &Value) -> Result<Self> {
+        let m = value
+            .as_map()
+            .ok_or_else(|| type_error(VTYPE_NAME_MAP, value))?;
+        let mut items = HashMap::with_capacity(m.len());
+        for (key, value) in m.inner() {
+            items.insert(
+                K::from_value(&Value::from(key.clone()))?,
+                V::from_value(value)?,
+            );
+        }
+        Ok(items)
+    }
+}
+
+impl<K, V> FromValue for BTreeMap<K, V>
+where
+    K: FromValue + Ord,
+    V: FromValue,
+{
+    fn from_value(value: &Value) -> Result<Self> {
+        let m = value
+            .as_map()
+            .ok_or_else(|| type_error(VTYPE_NAME_MAP, value))?;
+        let mut items = BTreeMap::new();
+        for (key, value) in m.inner() {
+            items.insert(
+                K::from_value(&Value::from(key.clone()))?,
+                V::from_value(value)?,
+            );
+        }
+        Ok(items)
+    }
+}
+
+macro_rules! from_value_for_tuple {
+    ($len:literal => $($t:ident $i:tt),+) => {
+        impl<$($t: FromValue),+> FromValue for ($($t,)+) {
+            /// Converts a `List` of exactly the tuple's length.
+            fn from_value(value: &Value) -> Result<Self> {
+                match value {
+                    Value::List(lst) => Self::from_record(lst.inner()),
+                    _ => Err(type_error(VTYPE_NAME_LIST, value)),
+                }
+            }
+
+            fn from_record(record: &[Value]) -> Result<Self> {
+                if record.len() != $len {
+                    bail!(
+                        "E773:-:0:expected {} values for tuple, got {}",
+                        $len,
+                        record.len()
+                    )
+                }
+                Ok(($($t::from_value(&record[$i])?,)+))
+            }
+        }
+
+        impl<$($t: FromValue),+> TryFrom<&Value> for ($($t,)+) {
+            type Error = anyhow::Error;
+
+            fn try_from(value: &Value) -> Result<Self> {
+                Self::from_value(value)
+            }
+        }
+    };
+}
+
+from_value_for_tuple!(1 => A 0);
+from_value_for_tuple!(2 => A 0, B 1);
+from_value_for_tuple!(3 => A 0, B 1, C 2);
+from_value_for_tuple!(4 => A 0, B 1, C 2, D 3);
+from_value_for_tuple!(5 => A 0, B 1, C 2, D 3, E 4);
+from_value_for_tuple!(6 => A 0, B 1, C 2, D 3, E 4, F 5);
+
+macro_rules! try_from_value {
+    ($($t:ty),*) => {
+        $(
+            impl TryFrom<&Value> for $t {
+                type Error = anyhow::Error;
+
+                fn try_from(value: &Value) -> Result<Self> {
+                    <$t>::from_value(value)
+                }
+            }
+        )*
+    };
+}
+
+try_from_value!(
+    bool,
+    i8,
+    i16,
+    i32,
+    i64,
+    u8,
+    u16,
+    u32,
+    u64,
+    usize,
+    f64,
+    String,
+    NaiveDate,
+    NaiveDateTime,
+    Key,
+    List,
+    Map,
+    Table
+);
+
+impl<T: FromValue> TryFrom<&Value> for Vec<T> {
+    type Error = anyhow::Error;
+
+    fn try_from(value: &Value) -> Result<Self> {
+        Self::from_value(value)
+    }
+}
+
+impl<K, V> TryFrom<&Value> for HashMap<K, V>
+where
+    K: FromValue + Eq + Hash,
+    V: FromValue,
+{
+    type Error = anyhow::Error;
+
+    fn try_from(value: &Value) -> Result<Self> {
+        Self::from_value(value)
+    }
+}
+
+impl<K, V> TryFrom<&Value> for BTreeMap<K, V>
+where
+    K: FromValue + Ord,
+    V: FromValue,
+{
+    type Error = anyhow::Error;
+
+    fn try_from(value: &Value) -> Result<Self> {
+        Self::from_value(value)
+    }
+}
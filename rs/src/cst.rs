@@ -0,0 +1,365 @@
+// Copyright © 2022 Mark Summerfield. All rights reserved.
+// License: GPLv3
+
+/*! A Document provides a lossless (concrete syntax tree) view of a UXF
+file, for when a file (e.g., a hand-written config file) must be updated
+programmatically without reformatting it.
+
+A Document holds the file's original text and the `Uxf` parsed from it,
+plus the span (i.e., the char offsets) of every value in the original
+text. Values can be read using `get()` (or via `uxf()`) and changed using
+`set()`. Then `to_text()` (or `write()`) returns the original text with
+only the spans of the changed values replaced. So the original layout,
+whitespace, comments, ttype definition order, map item order, and table
+column alignment are all preserved.
+
+Values are identified by their path from the outermost collection (whose
+path is empty): use `Step::Index` for a list value, `Step::Key` for a map
+value, and `Step::Index` (the record) followed by `Step::Index` (the
+column) or `Step::Field` (the field name) for a table value, e.g.:
+
+```rust
+use uxf::cst::{Document, Step};
+let uxt = "uxf 1\n{<port>   8080\n<host> <localhost>}\n";
+let mut doc = Document::parse(uxt).unwrap();
+doc.set(&["port".into()], 8081.into()).unwrap();
+assert_eq!(doc.to_text(), "uxf 1\n{<port>   8081\n<host> <localhost>}\n");
+```
+
+The replacement text for a changed value is its compact UXF form (i.e.,
+from `to_string()`). To add or remove items, `set()` the enclosing
+collection to a new (e.g., modified clone) collection: this replaces only
+that collection's span.
+*/
+use crate::event::{self, OnEventFn};
+use crate::key::Key;
+use crate::parser::{self, TokenKind};
+use crate::repair::RepairPolicy;
+use crate::util::{read_file, write_file};
+use crate::uxf::{ParserOptions, Uxf};
+pub use crate::value::Step;
+use crate::value::Value;
+use anyhow::{bail, Result};
+use std::{collections::HashMap, rc::Rc};
+
+type Path = Vec<Step>;
+type Span = (usize, usize); // char offsets: start..end
+
+#[derive(Clone, Debug)]
+pub struct Document {
+    text: Vec<char>,
+    uxo: Uxf,
+    spans: HashMap<Path, Span>, // Only canonical paths (no Step::Field)
+    changed: Vec<Path>,
+}
+
+impl Document {
+    /// If `uxt_or_filename`' contains '\n` it is taken to be a UXF file
+    /// in a string; otherwise it is taken to be the name of file (which
+    /// may be compressed, e.g., gzipped; see the `codec` module). In the
+    /// latter case, the file's text is read.
+    /// Then in either case the UXF text is parsed into a `Document` if
+    /// possible, using the default `on_event` event handler.
+    pub fn parse(uxt_or_filename: &str) -> Result<Self> {
+        Document::parse_options(uxt_or_filename, None)
+    }
+
+    /// This is the same as `parse()` except that it uses the given
+    /// `on_event` event handler (or the default handler if `None`).
+    /// (There are no parser options since any that change the data, e.g.,
+    /// replacing imports, would defeat the purpose of a `Document`.)
+    pub fn parse_options(
+        uxt_or_filename: &str,
+        on_event: Option<OnEventFn>,
+    ) -> Result<Self> {
+        let (text, filename) = if uxt_or_filename.contains('\n') {
+            (uxt_or_filename.to_string(), "-")
+        } else {
+            (read_file(uxt_or_filename)?, uxt_or_filename)
+        };
+        // Parse using the real filename so that any relative imports are
+        // resolved against the file's folder
+        let uxo = parser::parse(
+            &text,
+            filename,
+            ParserOptions::default(),
+            &RepairPolicy::default(),
+            on_event.unwrap_or_else(|| Rc::new(event::on_event)),
+        )?;
+        let text: Vec<char> = text.chars().collect();
+        let spans = get_spans(&text, filename, &uxo)?;
+        Ok(Document { text, uxo, spans, changed: vec![] })
+    }
+
+    /// Returns the `Uxf` (which reflects any changes made using `set()`).
+    pub fn uxf(&self) -> &Uxf {
+        &self.uxo
+    }
+
+    /// Returns the value at the given `path` or None.
+    pub fn get(&self, path: &[Step]) -> Option<&Value> {
+        if let Ok(path) = self.canonical(path) {
+            value_at(self.uxo.value(), &path)
+        } else {
+            None
+        }
+    }
+
+    /// Replaces the value at the given `path` with the given `value`,
+    /// _or_ returns an Err if there is no value at that path.
+    pub fn set(&mut self, path: &[Step], value: Value) -> Result<()> {
+        let path = self.canonical(path)?;
+        if path.is_empty() {
+            if !value.is_collection() {
+                bail!(
+                    "E821:-:0:the outermost value must be a List, Map, or \
+                    Table, got {}",
+                    value.typename()
+                )
+            }
+            *self.uxo.value_mut() = value;
+        } else {
+            // safe to unwrap since canonical() succeeded
+            *value_at_mut(self.uxo.value_mut(), &path).unwrap() = value;
+        }
+        if !self.changed.contains(&path) {
+            self.changed.push(path);
+        }
+        Ok(())
+    }
+
+    /// Returns `true` if any value has been changed.
+    pub fn is_changed(&self) -> bool {
+        !self.changed.is_empty()
+    }
+
+    /// Returns the original text with the text of every changed value
+    /// replaced by the changed value's UXF text.
+    pub fn to_text(&self) -> String {
+        // Only replace outermost changes since they include any inner ones
+        let mut changes: Vec<(Span, &Path)> = self
+            .changed
+            .iter()
+            .filter(|path| {
+                !self.changed.iter().any(|other| {
+                    other.len() < path.len() && path.starts_with(other)
+                })
+            })
+            .map(|path| (self.spans[path], path))
+            .collect();
+        changes.sort_unstable_by_key(|((start, _), _)| *start);
+        let mut text = String::new();
+        let mut pos = 0;
+        for ((start, end), path) in changes {
+            text.extend(&self.text[pos..start]);
+            // safe to unwrap since every changed path is valid
+            text.push_str(
+                &value_at(self.uxo.value(), path).unwrap().to_string(),
+            );
+            pos = end;
+        }
+        text.extend(&self.text[pos..]);
+        text
+    }
+
+    /// Writes the document's text (see `to_text()`) to the specified
+    /// filename (gzip-compressing if the filename ends with `.gz`).
+    pub fn write(&self, filename: &str) -> Result<()> {
+        write_file(filename, &self.to_text())
+    }
+
+    // Returns the given path with any table column fieldnames replaced
+    // by column indexes, _or_ an Err if there is no value at the path.
+    fn canonical(&self, path: &[Step]) -> Result<Path> {
+        let mut canonical = vec![];
+        let mut value = self.uxo.value();
+        let mut steps = path.iter();
+        while let Some(step) = steps.next() {
+            let next = match (value, step) {
+                (Value::List(lst), Step::Index(index)) => {
+                    canonical.push(step.clone());
+                    lst.get(*index)
+                }
+                (Value::Map(m), Step::Key(key)) => {
+                    canonical.push(step.clone());
+                    m.get(key)
+                }
+                (Value::Table(t), Step::Index(row)) => {
+                    let column = match steps.next() {
+                        Some(Step::Index(column)) => Some(*column),
+                        Some(Step::Field(name)) => t
+                            .tclass()
+                            .fieldnames()
+                            .iter()
+                            .position(|fieldname| fieldname == name),
+                        _ => None,
+                    };
+                    if let Some(column) = column {
+                        canonical.push(step.clone());
+                        canonical.push(Step::Index(column));
+                        t.get(*row).and_then(|record| record.get(column))
+                    } else {
+                        None
+                    }
+                }
+                _ => None,
+            };
+            if let Some(next) = next {
+                value = next;
+            } else {
+                bail!("E820:-:0:no value at path {path:?}")
+            }
+        }
+        // A value that wasn't in the original text (e.g., a new value in a
+        // changed collection) can only be changed if an enclosing
+        // collection has been changed (since that will be output in full)
+        if !self.spans.contains_key(&canonical)
+            && !self.changed.iter().any(|path| canonical.starts_with(path))
+        {
+            bail!("E822:-:0:no original text for value at path {path:?}")
+        }
+        Ok(canonical)
+    }
+}
+
+// Paths must be canonical (i.e., tables use two Step::Index's)
+fn value_at<'a>(value: &'a Value, path: &[Step]) -> Option<&'a Value> {
+    match (value, path) {
+        (_, []) => Some(value),
+        (Value::List(lst), [Step::Index(index), rest @ ..]) => {
+            value_at(lst.get(*index)?, rest)
+        }
+        (Value::Map(m), [Step::Key(key), rest @ ..]) => {
+            value_at(m.get(key)?, rest)
+        }
+        (
+            Value::Table(t),
+            [Step::Index(row), Step::Index(column), rest @ ..],
+        ) => value_at(t.get(*row)?.get(*column)?, rest),
+        _ => None,
+    }
+}
+
+// Paths must be canonical (i.e., tables use two Step::Index's)
+fn value_at_mut<'a>(
+    value: &'a mut Value,
+    path: &[Step],
+) -> Option<&'a mut Value> {
+    match (value, path) {
+        (value, []) => Some(value),
+        (Value::List(lst), [Step::Index(index), rest @ ..]) => {
+            value_at_mut(lst.get_mut(*index)?, rest)
+        }
+        (Value::Map(m), [Step::Key(key), rest @ ..]) => {
+            value_at_mut(m.get_mut(key)?, rest)
+        }
+        (
+            Value::Table(t),
+            [Step::Index(row), Step::Index(column), rest @ ..],
+        ) => value_at_mut(t.get_mut(*row)?.get_mut(*column)?, rest),
+        _ => None,
+    }
+}
+
+// Where the next value goes in the enclosing collection
+enum Slot {
+    List { path: Path, index: usize },
+    Map { path: Path, ktype: String, key: Option<Key> },
+    Table { path: Path, columns: usize, index: usize },
+}
+
+impl Slot {
+    // Returns the path of the next value, or None if the next value is a
+    // map key.
+    fn next_path(&mut self) -> Option<Path> {
+        match self {
+            Slot::List { path, index } => {
+                let mut path = path.clone();
+                path.push(Step::Index(*index));
+                *index += 1;
+                Some(path)
+            }
+            Slot::Map { path, key, .. } => key.take().map(|key| {
+                let mut path = path.clone();
+                path.push(Step::Key(key));
+                path
+            }),
+            Slot::Table { path, columns, index } => {
+                let mut path = path.clone();
+                path.push(Step::Index(*index / *columns));
+                path.push(Step::Index(*index % *columns));
+                *index += 1;
+                Some(path)
+            }
+        }
+    }
+}
+
+fn get_spans(
+    text: &Vec<char>,
+    filename: &str,
+    uxo: &Uxf,
+) -> Result<HashMap<Path, Span>> {
+    let mut spans = HashMap::new();
+    let mut stack: Vec<Slot> = vec![];
+    let mut starts: Vec<usize> = vec![];
+    for token in parser::tokenize(text, filename, false)? {
+        if token.kind.is_collection_start() {
+            let path = match stack.last_mut() {
+                Some(slot) => slot.next_path().unwrap_or_default(),
+                None => vec![],
+            };
+            starts.push(token.start);
+            stack.push(match token.kind {
+                TokenKind::ListBegin => Slot::List { path, index: 0 },
+                TokenKind::MapBegin => {
+                    Slot::Map { path, ktype: token.ktype, key: None }
+                }
+                _ => Slot::Table {
+                    path,
+                    columns: uxo
+                        .tclass(&token.vtype)
+                        .map(|tclass| tclass.len())
+                        .unwrap_or_default()
+                        .max(1),
+                    index: 0,
+                },
+            });
+        } else if token.kind.is_collection_end() {
+            let path = match stack.pop() {
+                Some(Slot::List { path, .. })
+                | Some(Slot::Map { path, .. })
+                | Some(Slot::Table { path, .. }) => path,
+                None => vec![],
+            };
+            if let Some(start) = starts.pop() {
+                spans.insert(path, (start, token.end));
+            }
+        } else if token.kind.is_scalar() {
+            if let Some(slot) = stack.last_mut() {
+                if let Some(path) = slot.next_path() {
+                    spans.insert(path, (token.start, token.end));
+                } else if let Slot::Map { ktype, key, .. } = slot {
+                    *key = Some(map_key(token.value, ktype)?);
+                }
+            }
+        }
+    }
+    Ok(spans)
+}
+
+// Returns the key as the parser would (i.e., repairing a str key if the
+// ktype is a different ktype)
+fn map_key(value: Value, ktype: &str) -> Result<Key> {
+    let value = if value.is_str() && !ktype.is_empty() {
+        let new_value = value.naturalize();
+        if new_value.typename() == ktype {
+            new_value
+        } else {
+            value
+        }
+    } else {
+        value
+    };
+    Key::from(value)
+}
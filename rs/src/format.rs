@@ -5,16 +5,21 @@
 pretty printing method (Uxf::write(), Uxf::write_format(),
 Uxf::to_text(), or Uxf::to_text_format()).
 
-A Format holds an indent, a wrapwidth, and realdp which controls how
-real numbers are formatted.
+A Format holds an indent, a wrapwidth, realdp which controls how
+real numbers are formatted, map_order which controls whether map
+items are output in sorted key order (the default) or in insertion order,
+and compression_level which controls how compressed files are written.
 */
 use crate::consts::*;
+use crate::map::MapOrder;
 
 #[derive(Clone, Debug)]
 pub struct Format {
     pub indent: String,
     pub wrapwidth: u8,
     pub realdp: u8,
+    pub map_order: MapOrder,
+    pub compression_level: Option<u32>,
 }
 
 impl Format {
@@ -26,6 +31,9 @@ impl Format {
     /// realdp should be 0-15. The default is 0 which means use at least one
     /// decimal digit (even if .0) and as many as needed; 1-15 mean use that
     /// fixed number of decimal digits.
+    /// map_order is MapOrder::Sorted; use with_map_order() to change it.
+    /// compression_level is None (i.e., use the codec's default); use
+    /// with_compression_level() to change it.
     pub fn new(indent: u8, wrapwidth: u8, realdp: u8) -> Self {
         Format {
             indent: match indent {
@@ -42,12 +50,34 @@ impl Format {
                 96
             },
             realdp: if realdp <= 15 { realdp } else { 0 },
+            map_order: MapOrder::Sorted,
+            compression_level: None,
         }
     }
+
+    /// Returns this Format with its map_order set to the given `order`.
+    pub fn with_map_order(mut self, order: MapOrder) -> Self {
+        self.map_order = order;
+        self
+    }
+
+    /// Returns this Format with its compression_level set to the given
+    /// `level`, which is used when writing to a file whose suffix is for
+    /// a compressed file (e.g., `.gz`); see `codec::Codec::compress()`.
+    pub fn with_compression_level(mut self, level: u32) -> Self {
+        self.compression_level = Some(level);
+        self
+    }
 }
 
 impl Default for Format {
     fn default() -> Self {
-        Format { indent: "  ".to_string(), wrapwidth: 96, realdp: 0 }
+        Format {
+            indent: "  ".to_string(),
+            wrapwidth: 96,
+            realdp: 0,
+            map_order: MapOrder::Sorted,
+            compression_level: None,
+        }
     }
 }
@@ -0,0 +1,472 @@
+// Copyright © 2022 Mark Summerfield. All rights reserved.
+// License: GPLv3
+
+/*! An Index is a sidecar index for a large UXF file whose value is a table,
+which allows individual records (or the records whose key field values are
+in a given range) to be read without parsing the whole file.
+
+An index maps each record's number (i.e., row) to the byte offsets of the
+record's text in the file, and optionally maps the values of one of the
+table's fields (its key field) to record numbers. An index is built (and
+the file fully parsed) once, using `Index::build()` or the `uxf index`
+subcommand, and saved alongside the file with `.uxx` appended to the
+file's name (e.g., `events.uxf.uxx`) in binary UXF format (see the `binary`
+module). Thereafter `Index::open()` loads the index, and each record read
+is parsed using the file's TClasses so that it has the same values (i.e.,
+with any vtype repairs applied) as it would if the whole file were parsed.
+
+Only uncompressed files can be indexed. If the file is changed (including
+appended to) after it is indexed, reads fail until the index is rebuilt.
+
+```no_run
+use uxf::index::Index;
+
+// events.uxf contains, e.g., uxf 1\n=Event id:int name:str\n(Event ...)
+Index::build("events.uxf", Some("id")).unwrap().save().unwrap();
+// Later
+let index = Index::open("events.uxf").unwrap();
+let first = index.get(0).unwrap();
+let event = index.find(&uxf::key::Key::Int(42)).unwrap();
+let events = index.range(uxf::key::Key::Int(100)..uxf::key::Key::Int(200))
+    .unwrap();
+```
+*/
+use crate::binary;
+use crate::codec::Codec;
+use crate::event::ignore_event;
+use crate::key::Key;
+use crate::list::List;
+use crate::map::Map;
+use crate::parser::{self, TokenKind};
+use crate::repair::RepairPolicy;
+use crate::uxf::{ParserOptions, Uxf, WriteOptions};
+use crate::value::{Record, Value};
+use anyhow::{anyhow, bail, Context, Result};
+use std::{
+    fs::{self, File},
+    io::{Read, Seek, SeekFrom},
+    ops::{Bound, RangeBounds},
+    rc::Rc,
+    time::UNIX_EPOCH,
+};
+
+/// The suffix appended to a file's name to give its index's filename.
+pub const INDEX_SUFFIX: &str = ".uxx";
+
+const INDEX_CUSTOM: &str = "UXF Index";
+const INDEX_VERSION: i64 = 1;
+
+/// Returns the name of the index file for the given `filename`.
+pub fn index_filename(filename: &str) -> String {
+    format!("{filename}{INDEX_SUFFIX}")
+}
+
+#[derive(Clone, Debug)]
+pub struct Index {
+    filename: String,
+    header: String, // the file's header and TClasses used to parse records
+    ttype: String,
+    key: Option<String>,
+    size: u64,
+    modified: i64, // nanoseconds since the Unix epoch
+    offsets: Vec<(u64, u64)>, // each record's start and end byte offsets
+    rows_for_key: Vec<(Key, usize)>, // sorted by key then row
+}
+
+impl Index {
+    /// Returns a new index for the given uncompressed UXF file (which
+    /// must be a table with fields), mapping the values of the given `key`
+    /// field (if any) to record numbers, _or_ returns an Err if the file
+    /// can't be parsed, or the key field doesn't exist or has values that
+    /// aren't valid keys (e.g., `null`s). The index isn't saved; use
+    /// `save()` for that.
+    pub fn build(filename: &str, key: Option<&str>) -> Result<Self> {
+        let bytes = fs::read(filename)
+            .with_context(|| format!("E950:{filename}:0:failed to open"))?;
+        if Codec::for_bytes(&bytes) != Codec::Plain {
+            bail!("E880:{filename}:0:can only index uncompressed files")
+        }
+        let text = String::from_utf8(bytes)
+            .with_context(|| format!("E952:{filename}:0:failed to read"))?;
+        let uxo = parse(&text, filename)?;
+        let table = match uxo.value().as_table() {
+            Some(table) if !table.is_fieldless() => table,
+            Some(_) => {
+                bail!("E881:{filename}:0:can't index a fieldless table")
+            }
+            None => bail!(
+                "E881:{filename}:0:can only index a file whose value is a \
+                table, got {}",
+                uxo.value().typename()
+            ),
+        };
+        let offsets = record_offsets(&text, filename, table.ttype_len())?;
+        if offsets.len() != table.len() {
+            bail!(
+                "E885:{filename}:0:found {} records but expected {}",
+                offsets.len(),
+                table.len()
+            )
+        }
+        let mut rows_for_key = vec![];
+        if let Some(name) = key {
+            let column = match table
+                .tclass()
+                .fields()
+                .iter()
+                .position(|field| field.name() == name)
+            {
+                Some(column) => column,
+                None => bail!(
+                    "E882:{filename}:0:table of ttype {} has no field \
+                    {name}",
+                    table.ttype()
+                ),
+            };
+            for (row, record) in table.iter().enumerate() {
+                match Key::from(record[column].clone()) {
+                    Ok(key) => rows_for_key.push((key, row)),
+                    Err(_) => bail!(
+                        "E886:{filename}:0:record {row}'s {name} value \
+                        isn't a valid key, got {}",
+                        record[column].typename()
+                    ),
+                }
+            }
+            rows_for_key.sort();
+        }
+        let (size, modified) = stamp(filename)?;
+        Ok(Index {
+            filename: filename.to_string(),
+            header: header(&uxo),
+            ttype: table.ttype().to_string(),
+            key: key.map(|name| name.to_string()),
+            size,
+            modified,
+            offsets,
+            rows_for_key,
+        })
+    }
+
+    /// Loads the index for the given UXF file (i.e., from the file's name
+    /// with `.uxx` appended) _or_ returns an Err if the index can't be
+    /// read or is out of date.
+    pub fn open(filename: &str) -> Result<Self> {
+        let index_filename = index_filename(filename);
+        let uxo = binary::read_binary(&index_filename)?;
+        let invalid =
+            || anyhow!("E884:{index_filename}:0:invalid index file");
+        let map = match uxo.value().as_map() {
+            Some(map) if uxo.custom() == INDEX_CUSTOM => map,
+            _ => return Err(invalid()),
+        };
+        let get = |name: &str| map.get(&Key::Str(name.to_string()));
+        let get_int = |name| get(name).and_then(|value| value.as_int());
+        let get_str = |name| get(name).and_then(|value| value.as_str());
+        if get_int("version") != Some(INDEX_VERSION) {
+            return Err(invalid());
+        }
+        let offsets: Vec<i64> = get("offsets")
+            .and_then(|value| value.as_list())
+            .map(|lst| lst.iter().filter_map(|v| v.as_int()).collect())
+            .ok_or_else(invalid)?;
+        let keys = get("keys")
+            .and_then(|value| value.as_list())
+            .ok_or_else(invalid)?;
+        let key = get_str("key");
+        if !offsets.len().is_multiple_of(2)
+            || (key.is_some() && keys.len() * 2 != offsets.len())
+        {
+            return Err(invalid());
+        }
+        let mut rows_for_key = vec![];
+        for (row, value) in keys.iter().enumerate() {
+            let key = Key::from(value.clone()).map_err(|_| invalid())?;
+            rows_for_key.push((key, row));
+        }
+        rows_for_key.sort();
+        let index = Index {
+            filename: filename.to_string(),
+            header: get_str("header").ok_or_else(invalid)?.to_string(),
+            ttype: get_str("ttype").ok_or_else(invalid)?.to_string(),
+            key: key.map(|name| name.to_string()),
+            size: get_int("size").ok_or_else(invalid)? as u64,
+            modified: get_int("modified").ok_or_else(invalid)?,
+            offsets: offsets
+                .chunks(2)
+                .map(|pair| (pair[0] as u64, pair[1] as u64))
+                .collect(),
+            rows_for_key,
+        };
+        index.check_up_to_date()?;
+        Ok(index)
+    }
+
+    /// Saves the index alongside its file (i.e., to the file's name with
+    /// `.uxx` appended).
+    pub fn save(&self) -> Result<()> {
+        let mut offsets = List::new("int", "")?;
+        for (start, end) in &self.offsets {
+            offsets.push(Value::Int(*start as i64));
+            offsets.push(Value::Int(*end as i64));
+        }
+        let mut keys = vec![Value::Null; self.rows_for_key.len()];
+        for (key, row) in &self.rows_for_key {
+            keys[*row] = Value::from(key.clone());
+        }
+        let mut keys_list = List::new("", "")?;
+        keys_list.push_many(&keys);
+        let mut map = Map::new("str", "", "")?;
+        for (name, value) in [
+            ("version", Value::Int(INDEX_VERSION)),
+            ("header", Value::Str(self.header.clone())),
+            ("ttype", Value::Str(self.ttype.clone())),
+            ("key", self.key.clone().map_or(Value::Null, Value::Str)),
+            ("size", Value::Int(self.size as i64)),
+            ("modified", Value::Int(self.modified)),
+            ("offsets", Value::List(offsets)),
+            ("keys", Value::List(keys_list)),
+        ] {
+            map.insert(Key::Str(name.to_string()), value);
+        }
+        let mut uxo = Uxf::new(INDEX_CUSTOM, "");
+        uxo.set_value(Value::Map(map))?;
+        binary::write_binary(
+            &index_filename(&self.filename),
+            &uxo,
+            WriteOptions::DEFAULT,
+            None,
+        )
+    }
+
+    /// Returns the name of the indexed file.
+    pub fn filename(&self) -> &str {
+        &self.filename
+    }
+
+    /// Returns the ttype of the indexed file's table.
+    pub fn ttype(&self) -> &str {
+        &self.ttype
+    }
+
+    /// Returns the name of the key field or `None` if the index has no
+    /// key field.
+    pub fn key(&self) -> Option<&str> {
+        self.key.as_deref()
+    }
+
+    /// Returns the number of records in the indexed file's table.
+    pub fn len(&self) -> usize {
+        self.offsets.len()
+    }
+
+    /// Returns `true` if the indexed file's table has no records;
+    /// otherwise returns `false`.
+    pub fn is_empty(&self) -> bool {
+        self.offsets.is_empty()
+    }
+
+    /// Returns the record at the given `row` (or `None` if `row` is out of
+    /// range) _or_ returns an Err if the file can't be read or has changed
+    /// since it was indexed.
+    pub fn get(&self, row: usize) -> Result<Option<Record>> {
+        if row >= self.len() {
+            return Ok(None);
+        }
+        Ok(self.read(&[row])?.pop())
+    }
+
+    /// Returns the first record whose key field value is the given `key`
+    /// (or `None` if there isn't one) _or_ returns an Err if the index has
+    /// no key field, or if the file can't be read or has changed since it
+    /// was indexed.
+    pub fn find(&self, key: &Key) -> Result<Option<Record>> {
+        let rows =
+            self.rows_in((Bound::Included(key), Bound::Included(key)))?;
+        Ok(match rows.first() {
+            Some(row) => self.read(&[*row])?.pop(),
+            None => None,
+        })
+    }
+
+    /// Returns the records whose key field values are in the given `range`
+    /// in key order (and in file order for equal keys) _or_ returns an Err
+    /// if the index has no key field, or if the file can't be read or has
+    /// changed since it was indexed.
+    pub fn range<R: RangeBounds<Key>>(
+        &self,
+        range: R,
+    ) -> Result<Vec<Record>> {
+        let rows = self.rows_in(range)?;
+        self.read(&rows)
+    }
+
+    fn rows_in<R: RangeBounds<Key>>(&self, range: R) -> Result<Vec<usize>> {
+        if self.key.is_none() {
+            bail!("E887:{}:0:the index has no key field", self.filename)
+        }
+        let start =
+            self.rows_for_key.partition_point(|(key, _)| {
+                match range.start_bound() {
+                    Bound::Included(start) => key < start,
+                    Bound::Excluded(start) => key <= start,
+                    Bound::Unbounded => false,
+                }
+            });
+        Ok(self.rows_for_key[start..]
+            .iter()
+            .take_while(|(key, _)| match range.end_bound() {
+                Bound::Included(end) => key <= end,
+                Bound::Excluded(end) => key < end,
+                Bound::Unbounded => true,
+            })
+            .map(|(_, row)| *row)
+            .collect())
+    }
+
+    // Reads the given rows' texts and parses them as a table using the
+    // file's TClasses.
+    fn read(&self, rows: &[usize]) -> Result<Vec<Record>> {
+        if rows.is_empty() {
+            return Ok(vec![]);
+        }
+        self.check_up_to_date()?;
+        let filename = &self.filename;
+        let mut file = File::open(filename)
+            .with_context(|| format!("E950:{filename}:0:failed to open"))?;
+        let mut text = format!("{}({}\n", self.header, self.ttype);
+        for row in rows {
+            let (start, end) = self.offsets[*row];
+            let mut buffer = vec![0; (end - start) as usize];
+            file.seek(SeekFrom::Start(start))
+                .and_then(|_| file.read_exact(&mut buffer))
+                .with_context(|| {
+                    format!("E952:{filename}:0:failed to read")
+                })?;
+            text.push_str(&String::from_utf8(buffer).with_context(
+                || format!("E952:{filename}:0:failed to read"),
+            )?);
+            text.push('\n');
+        }
+        text.push_str(")\n");
+        let mut uxo = parse(&text, filename)?;
+        Ok(match uxo.value_mut() {
+            Value::Table(table) => std::mem::take(table.inner_mut()),
+            _ => vec![], // can't happen since the text is a table
+        })
+    }
+
+    fn check_up_to_date(&self) -> Result<()> {
+        if stamp(&self.filename)? != (self.size, self.modified) {
+            bail!(
+                "E883:{}:0:the index is out of date since the file has \
+                changed: rebuild the index",
+                self.filename
+            )
+        }
+        Ok(())
+    }
+}
+
+fn parse(text: &str, filename: &str) -> Result<Uxf> {
+    parser::parse(
+        text,
+        filename,
+        ParserOptions::ENUMS | ParserOptions::PSEUDO_VTYPES,
+        &RepairPolicy::default(),
+        Rc::new(ignore_event),
+    )
+}
+
+// Returns the text of the header and TClass definitions (i.e., everything
+// before the value) that can be prepended to records' text to parse them.
+fn header(uxo: &Uxf) -> String {
+    let mut defs = Uxf::new("", "");
+    defs.tclass_for_ttype = uxo.tclass_for_ttype.clone();
+    let text = defs.to_string();
+    text.strip_suffix("[]\n").unwrap_or(&text).to_string()
+}
+
+// Returns the start and end byte offsets of each record in the text's
+// table (whose ttype has the given number of fields).
+fn record_offsets(
+    text: &str,
+    filename: &str,
+    columns: usize,
+) -> Result<Vec<(u64, u64)>> {
+    let chars: Vec<char> = text.chars().collect();
+    let tokens = parser::tokenize(&chars, filename, true)?;
+    let mut byte_offsets = ByteOffsets::new(text);
+    let mut offsets = vec![];
+    let mut depth = 0;
+    let mut column = 0;
+    let mut start = 0;
+    // Skip to the first token after the table's opening (
+    for token in tokens
+        .iter()
+        .skip_while(|token| !token.kind.is_collection_start())
+        .skip(1)
+    {
+        if depth == 0 {
+            if matches!(token.kind, TokenKind::TableEnd | TokenKind::Eof) {
+                break;
+            }
+            if column == 0 {
+                start = token.start;
+            }
+        }
+        if token.kind.is_collection_start() {
+            depth += 1;
+        } else if token.kind.is_collection_end() {
+            depth -= 1;
+        }
+        if depth == 0 {
+            column += 1;
+            if column == columns {
+                offsets.push((
+                    byte_offsets.offset(start),
+                    byte_offsets.offset(token.end),
+                ));
+                column = 0;
+            }
+        }
+    }
+    Ok(offsets)
+}
+
+// Returns the file's size and modification time (as nanoseconds since
+// the Unix epoch).
+fn stamp(filename: &str) -> Result<(u64, i64)> {
+    let metadata = fs::metadata(filename)
+        .with_context(|| format!("E950:{filename}:0:failed to open"))?;
+    let modified = metadata
+        .modified()
+        .ok()
+        .and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+        .map_or(0, |duration| duration.as_nanos() as i64);
+    Ok((metadata.len(), modified))
+}
+
+// Converts increasing char offsets into byte offsets.
+struct ByteOffsets<'a> {
+    chars: std::str::Chars<'a>,
+    char_offset: usize,
+    byte_offset: usize,
+}
+
+impl<'a> ByteOffsets<'a> {
+    fn new(text: &'a str) -> Self {
+        ByteOffsets { chars: text.chars(), char_offset: 0, byte_offset: 0 }
+    }
+
+    fn offset(&mut self, char_offset: usize) -> u64 {
+        while self.char_offset < char_offset {
+            match self.chars.next() {
+                Some(c) => self.byte_offset += c.len_utf8(),
+                None => break,
+            }
+            self.char_offset += 1;
+        }
+        self.byte_offset as u64
+    }
+}
@@ -17,7 +17,7 @@ see the [UXF Overview](https://github.com/mark-summerfield/uxf/blob/main/README.
 
 # Reading and Writing UXF Files
 
-To read a UXF file into a `Uxf` object use `parse()` (or `parse_options()` for finer control), e.g.:
+To read a UXF file into a `Uxf` object use `parse()` (or `parse_options()` for finer control, or `parse_policy()` to control type repairs), e.g.:
 
 ```rust
 let uxt = "uxf 1\n#<File comment>\n{<alpha> 1\n<bravo> 2}\n";
@@ -25,12 +25,16 @@ let uxo = uxf::parse(uxt).unwrap(); // -or- pass a filename
 assert!(uxt == uxo.to_string());
 ```
 
-These functions can accept a filename (which may be gzip-compressed if it ends with `.gz`) or the _text_ of a UXF file.
+These functions can accept a filename (which may be compressed, e.g., gzipped; see the `codec` module) or the _text_ of a UXF file.
 
 It is also possible to create `Uxf` objects programmatically by creating and
 populating a `List`, `Map`, or `Table`; see the corresponding test files for
 some basic examples.
 
+To update a hand-written UXF file programmatically without reformatting it
+(i.e., preserving its layout, comments, and map item order), use a
+`cst::Document`: only the text of changed values is replaced.
+
 To write a `Uxf` object to a string (e.g., to write to a file) using
 canonical human-readable output, use `to_text()` (or `to_text_format()` for
 more control)). Or use `to_string() for bare bones not very human friendly
@@ -54,7 +58,28 @@ section: `uxf = "1"`.
 # Command Line Interface
 
 The library comes with an application, `uxf` which provides UXF comparisons,
-linting, and formatting functionality. Run `uxf -h` for details.
+linting, and formatting functionality, and which can show UXF files with
+colors or as HTML (using the `render` module). Run `uxf -h` for details.
+
+If the `lsp` cargo feature is enabled, a `uxf-lsp` language server is also
+built; it provides diagnostics, formatting, hover, go-to-definition, and
+completion for editors that support the Language Server Protocol (see the
+`lsp` module).
+
+# Linting
+
+In addition to the warnings and repairs reported when parsing, a `Linter`
+can check a `Uxf` against a set of named, individually enabled rules
+(e.g., for house style), optionally configured using a project config file
+(itself a UXF file); see the `lint` module for details. The `uxf lint
+--rules` command uses a `Linter`.
+
+# Binary Encoding
+
+A `Uxf` (or a `Value`) can be converted to and from a compact lossless
+binary encoding using `Uxf::to_binary()` and `Uxf::from_binary()`; see
+the `binary` module for details. The `uxf convert` command converts
+files between the text and binary forms.
 
 # API Notes
 
@@ -65,11 +90,11 @@ For TClasses the ttype is also a string, and this may not be empty.
 # Application Examples
 
 - The [amp](https://github.com/mark-summerfield/amp-rs) application uses UXF
-to store its configuration (see its `src/config.rs` file).
+  to store its configuration (see its `src/config.rs` file).
 - The [tlm](https://github.com/mark-summerfield/tlm) application uses UXF
-for two purposes: to store its configuration (see its `src/config.rs` file),
-and for its application data files (see its `src/model.rs` file—in
-particular the functions whose names end with `_uxf`).
+  for two purposes: to store its configuration (see its `src/config.rs`
+  file), and for its application data files (see its `src/model.rs`
+  file—in particular the functions whose names end with `_uxf`).
 
 # Tests
 
@@ -85,30 +110,53 @@ imports.
 
 */
 
+pub mod append;
+pub mod binary;
 pub mod check;
+pub mod codec;
+pub mod config;
 pub mod consts;
+pub mod convert;
+pub mod cst;
 pub mod event;
 pub mod field;
 pub mod format;
+pub mod index;
 pub mod key;
+pub mod lint;
 pub mod list;
+pub mod lsp;
+mod macros;
 pub mod map;
+pub mod merge;
 pub mod parser;
 pub mod pprint;
+pub mod render;
+pub mod repair;
 pub mod table;
 pub mod tclass;
 pub mod util;
 pub mod uxf;
 pub mod value;
+pub mod view;
+#[cfg(feature = "watch")]
+pub mod watch;
 
 // Public API
 pub use crate::consts::{UXF_VERSION, VERSION};
+pub use crate::convert::FromValue;
 pub use crate::event::{ignore_event, on_event, Event};
 pub use crate::field::{make_field, make_fields, Field};
 pub use crate::format::Format;
+pub use crate::lint::{Linter, Rule};
 pub use crate::list::List;
-pub use crate::map::Map;
+pub use crate::map::{Map, MapOrder};
+pub use crate::repair::{RepairAction, RepairPolicy};
 pub use crate::table::{NamedRecord, Table};
 pub use crate::tclass::{make_tclass, TClass};
-pub use crate::uxf::{parse, parse_options, Compare, ParserOptions, Uxf};
-pub use crate::value::{Value, Visit, naturalize};
+pub use crate::uxf::{
+    parse, parse_options, parse_policy, Compare, ParserOptions, Uxf,
+    WriteOptions,
+};
+pub use crate::value::{naturalize, Step, Value, Visit};
+pub use crate::view::{RecordView, RecordViewMut};
@@ -0,0 +1,490 @@
+// Copyright © 2022 Mark Summerfield. All rights reserved.
+// License: GPLv3
+
+/*! A Linter checks a `Uxf` against a set of named rules (e.g., for house
+style) and returns a warning `Event` for every problem it finds.
+
+The built-in rules are:
+
+- `no_untyped_lists` (W800): every list must have a vtype.
+- `tclass_comments` (W801): every TClass defined in the file (i.e., not
+  imported) must have a comment.
+- `snake_case_fields` (W802): every field name of every TClass defined in
+  the file must be snake_case (lowercase letters, digits, and underscores).
+- `no_date_like_str_keys` (W803): maps must not have str keys that look
+  like dates or datetimes (use a `date` or `datetime` ktype instead).
+- `no_unused_imports` (W804): every import must provide at least one
+  ttype that is actually used (by a table, or as a list, map, or field
+  vtype).
+- `max_depth` (W805): collections must not be nested more deeply than
+  the rule's limit (default 8; the outermost collection has depth 1).
+
+Every rule is enabled by default. Rules can be enabled or disabled (and
+`max_depth`'s limit set) programmatically, or by loading a config file.
+A config file is itself a UXF file whose value is a map of rule names to
+`yes` or `no` (or to an int limit, e.g., for `max_depth`), e.g.:
+
+```text
+uxf 1 uxflint
+{str
+  <tclass_comments> no
+  <max_depth> 5
+}
+```
+
+Custom rules can be added using `Linter::add_rule()`.
+
+Since a `Uxf` doesn't record line numbers, rule events have a lino of 0
+and their messages identify the offending value by its path, e.g.,
+`{<servers>}[2]` is the third value in the list that is the value of the
+`servers` key of the outermost map.
+*/
+use crate::event::Event;
+use crate::map::Map;
+use crate::uxf::{parse_options, ParserOptions, Uxf};
+use crate::value::{naturalize, Value};
+use anyhow::{bail, Result};
+use std::{
+    collections::HashSet,
+    path::{Path, PathBuf},
+    rc::Rc,
+};
+
+/// The name of the project lint config file that `find_config()` searches
+/// for.
+pub static LINT_CONFIG_FILENAME: &str = "uxflint.uxf";
+
+/// The default `max_depth` limit.
+pub const MAX_DEPTH: usize = 8;
+
+/// A rule's check function: given the rule itself (e.g., to access its
+/// `limit`) and the `Uxf` to check, it returns a (possibly empty) vec of
+/// messages, one per problem found.
+pub type CheckFn = Rc<dyn Fn(&Rule, &Uxf) -> Vec<String>>;
+
+#[derive(Clone)]
+pub struct Rule {
+    pub name: String,
+    pub description: String,
+    pub code: u16,
+    pub enabled: bool,
+    pub limit: Option<usize>,
+    pub check: CheckFn,
+}
+
+impl Rule {
+    /// Returns a new enabled `Rule` with the given `name`, `description`,
+    /// event `code`, and `check` function.
+    pub fn new(
+        name: &str,
+        description: &str,
+        code: u16,
+        check: CheckFn,
+    ) -> Self {
+        Rule {
+            name: name.to_string(),
+            description: description.to_string(),
+            code,
+            enabled: true,
+            limit: None,
+            check,
+        }
+    }
+}
+
+impl std::fmt::Debug for Rule {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Rule")
+            .field("name", &self.name)
+            .field("code", &self.code)
+            .field("enabled", &self.enabled)
+            .field("limit", &self.limit)
+            .finish()
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Linter {
+    rules: Vec<Rule>,
+}
+
+impl Linter {
+    /// Returns a new `Linter` with all the built-in rules enabled.
+    pub fn new() -> Self {
+        let mut max_depth = Rule::new(
+            "max_depth",
+            "collections must not be nested too deeply",
+            805,
+            Rc::new(check_max_depth),
+        );
+        max_depth.limit = Some(MAX_DEPTH);
+        Linter {
+            rules: vec![
+                Rule::new(
+                    "no_untyped_lists",
+                    "every list must have a vtype",
+                    800,
+                    Rc::new(check_no_untyped_lists),
+                ),
+                Rule::new(
+                    "tclass_comments",
+                    "every TClass must have a comment",
+                    801,
+                    Rc::new(check_tclass_comments),
+                ),
+                Rule::new(
+                    "snake_case_fields",
+                    "every field name must be snake_case",
+                    802,
+                    Rc::new(check_snake_case_fields),
+                ),
+                Rule::new(
+                    "no_date_like_str_keys",
+                    "maps must not have str keys that look like dates",
+                    803,
+                    Rc::new(check_no_date_like_str_keys),
+                ),
+                Rule::new(
+                    "no_unused_imports",
+                    "every import must provide at least one used ttype",
+                    804,
+                    Rc::new(check_no_unused_imports),
+                ),
+                max_depth,
+            ],
+        }
+    }
+
+    /// Returns a new `Linter` with all the built-in rules enabled except
+    /// as overridden by the given config file.
+    pub fn from_config(filename: &str) -> Result<Self> {
+        let mut linter = Linter::new();
+        linter.load_config(filename)?;
+        Ok(linter)
+    }
+
+    /// Applies the settings in the given config file (see the module
+    /// documentation for the format).
+    pub fn load_config(&mut self, filename: &str) -> Result<()> {
+        let uxo = parse_options(
+            filename,
+            ParserOptions::default(),
+            Some(Rc::new(crate::event::ignore_event)),
+        )?;
+        let map = if let Some(map) = uxo.value().as_map() {
+            map
+        } else {
+            bail!(
+                "E810:{filename}:0:lint config must be a map, got {}",
+                uxo.value().typename()
+            )
+        };
+        self.apply_config(map, filename)
+    }
+
+    fn apply_config(&mut self, map: &Map, filename: &str) -> Result<()> {
+        for key in map.sorted_keys() {
+            let name = if let Some(name) = key.as_str() {
+                name
+            } else {
+                bail!("E811:{filename}:0:expected a rule name, got {key}")
+            };
+            let rule = if let Some(rule) = self.rule_mut(name) {
+                rule
+            } else {
+                bail!("E812:{filename}:0:unknown lint rule {name:?}")
+            };
+            // safe to unwrap since key is from map
+            match map.get(key).unwrap() {
+                Value::Bool(enabled) => rule.enabled = *enabled,
+                Value::Int(limit)
+                    if rule.limit.is_some() && *limit >= 0 =>
+                {
+                    rule.enabled = true;
+                    rule.limit = Some(*limit as usize);
+                }
+                value => bail!(
+                    "E813:{filename}:0:invalid setting for lint rule \
+                    {name:?}: {value}"
+                ),
+            }
+        }
+        Ok(())
+    }
+
+    /// Adds a custom rule (replacing any existing rule of the same name).
+    pub fn add_rule(&mut self, rule: Rule) {
+        if let Some(old) = self.rule_mut(&rule.name) {
+            *old = rule;
+        } else {
+            self.rules.push(rule);
+        }
+    }
+
+    /// Returns the rules in the order they are checked.
+    pub fn rules(&self) -> &Vec<Rule> {
+        &self.rules
+    }
+
+    /// Returns the named rule or None.
+    pub fn rule(&self, name: &str) -> Option<&Rule> {
+        self.rules.iter().find(|rule| rule.name == name)
+    }
+
+    /// Returns the named rule (e.g., to change its `limit`) or None.
+    pub fn rule_mut(&mut self, name: &str) -> Option<&mut Rule> {
+        self.rules.iter_mut().find(|rule| rule.name == name)
+    }
+
+    /// Enables or disables the named rule, _or_ returns an Err if there is
+    /// no such rule.
+    pub fn set_enabled(&mut self, name: &str, enabled: bool) -> Result<()> {
+        if let Some(rule) = self.rule_mut(name) {
+            rule.enabled = enabled;
+            Ok(())
+        } else {
+            bail!("E812:-:0:unknown lint rule {name:?}")
+        }
+    }
+
+    /// Checks the given `uxo` against every enabled rule and returns a
+    /// (possibly empty) vec of warning events, one per problem found. The
+    /// `filename` is used for the events.
+    pub fn lint(&self, uxo: &Uxf, filename: &str) -> Vec<Event> {
+        let mut events = vec![];
+        for rule in self.rules.iter().filter(|rule| rule.enabled) {
+            for message in (rule.check)(rule, uxo) {
+                events.push(Event::new_warning(
+                    rule.code, &message, filename, 0,
+                ));
+            }
+        }
+        events
+    }
+}
+
+impl Default for Linter {
+    /// Returns a new `Linter` with all the built-in rules enabled.
+    fn default() -> Self {
+        Linter::new()
+    }
+}
+
+/// Returns the first `LINT_CONFIG_FILENAME` found in `dir` or in any of
+/// its ancestors, or None.
+pub fn find_config(dir: &Path) -> Option<PathBuf> {
+    for dir in dir.ancestors() {
+        let filename = dir.join(LINT_CONFIG_FILENAME);
+        if filename.is_file() {
+            return Some(filename);
+        }
+    }
+    None
+}
+
+fn check_no_untyped_lists(_rule: &Rule, uxo: &Uxf) -> Vec<String> {
+    let mut messages = vec![];
+    walk(uxo.value(), "", 1, &mut |value, path, _| {
+        if let Some(lst) = value.as_list() {
+            if lst.vtype().is_empty() {
+                messages.push(format!("untyped list at {}", at(path)));
+            }
+        }
+        true
+    });
+    messages
+}
+
+fn check_tclass_comments(_rule: &Rule, uxo: &Uxf) -> Vec<String> {
+    let mut messages = vec![];
+    for ttype in defined_ttypes(uxo) {
+        if uxo.tclass_for_ttype[ttype].comment().is_empty() {
+            messages.push(format!("ttype {ttype} has no comment"));
+        }
+    }
+    messages
+}
+
+fn check_snake_case_fields(_rule: &Rule, uxo: &Uxf) -> Vec<String> {
+    let mut messages = vec![];
+    for ttype in defined_ttypes(uxo) {
+        for field in uxo.tclass_for_ttype[ttype].fields() {
+            let name = field.name();
+            if !name
+                .chars()
+                .all(|c| c.is_lowercase() || c.is_ascii_digit() || c == '_')
+            {
+                messages.push(format!(
+                    "ttype {ttype}'s field {name} isn't snake_case"
+                ));
+            }
+        }
+    }
+    messages
+}
+
+fn check_no_date_like_str_keys(_rule: &Rule, uxo: &Uxf) -> Vec<String> {
+    let mut messages = vec![];
+    walk(uxo.value(), "", 1, &mut |value, path, _| {
+        if let Some(m) = value.as_map() {
+            for key in m.sorted_keys() {
+                if let Some(s) = key.as_str() {
+                    let value = naturalize(s);
+                    if value.is_date() || value.is_datetime() {
+                        messages.push(format!(
+                            "map at {} has str key {key} that looks like \
+                            a {}",
+                            at(path),
+                            value.typename()
+                        ));
+                    }
+                }
+            }
+        }
+        true
+    });
+    messages
+}
+
+fn check_no_unused_imports(_rule: &Rule, uxo: &Uxf) -> Vec<String> {
+    let used = used_ttypes(uxo);
+    let mut imports: Vec<&str> = vec![];
+    for import in uxo.import_for_ttype.values() {
+        if !imports.contains(&import.as_str()) {
+            imports.push(import);
+        }
+    }
+    let mut messages = vec![];
+    for import in imports {
+        if !uxo
+            .import_for_ttype
+            .iter()
+            .any(|(ttype, i)| i == import && used.contains(ttype))
+        {
+            messages.push(format!("unused import {import}"));
+        }
+    }
+    messages
+}
+
+fn check_max_depth(rule: &Rule, uxo: &Uxf) -> Vec<String> {
+    let limit = rule.limit.unwrap_or(MAX_DEPTH);
+    let mut messages = vec![];
+    walk(uxo.value(), "", 1, &mut |value, path, depth| {
+        if depth > limit {
+            messages.push(format!(
+                "{} at {} is nested {depth} deep (limit {limit})",
+                value.typename(),
+                at(path)
+            ));
+            return false; // Don't report its descendents too
+        }
+        true
+    });
+    messages
+}
+
+// Returns the ttypes that are used in the file: those of its tables, those
+// that are list or map vtypes, and those that are field vtypes of its
+// defined or used TClasses (recursively).
+fn used_ttypes(uxo: &Uxf) -> HashSet<String> {
+    let mut used: HashSet<String> = HashSet::new();
+    walk(uxo.value(), "", 1, &mut |value, _, _| {
+        match value {
+            Value::List(lst) => {
+                used.insert(lst.vtype().to_string());
+            }
+            Value::Map(m) => {
+                used.insert(m.vtype().to_string());
+            }
+            Value::Table(t) => {
+                used.insert(t.ttype().to_string());
+            }
+            _ => (),
+        }
+        true
+    });
+    let mut pending: Vec<&String> = defined_ttypes(uxo);
+    pending.extend(used.iter().filter_map(|ttype| {
+        uxo.tclass_for_ttype.get_key_value(ttype).map(|(ttype, _)| ttype)
+    }));
+    let mut done: HashSet<&String> = HashSet::new();
+    while let Some(ttype) = pending.pop() {
+        if !done.insert(ttype) {
+            continue;
+        }
+        for field in uxo.tclass_for_ttype[ttype].fields() {
+            if let Some(vtype) = field.vtype() {
+                if let Some((vtype, _)) =
+                    uxo.tclass_for_ttype.get_key_value(vtype)
+                {
+                    pending.push(vtype);
+                }
+            }
+        }
+    }
+    used.extend(done.into_iter().cloned());
+    used
+}
+
+// Returns the sorted ttypes that are defined in the file (i.e., not
+// imported).
+fn defined_ttypes(uxo: &Uxf) -> Vec<&String> {
+    let mut ttypes: Vec<&String> = uxo
+        .tclass_for_ttype
+        .keys()
+        .filter(|ttype| !uxo.import_for_ttype.contains_key(*ttype))
+        .collect();
+    ttypes.sort_unstable();
+    ttypes
+}
+
+// Calls visit(value, path, depth) for the given collection value and for
+// every collection it contains, recursively, unless visit returns false in
+// which case the value's contents are skipped.
+fn walk(
+    value: &Value,
+    path: &str,
+    depth: usize,
+    visit: &mut dyn FnMut(&Value, &str, usize) -> bool,
+) {
+    if !value.is_collection() || !visit(value, path, depth) {
+        return;
+    }
+    match value {
+        Value::List(lst) => {
+            for (i, value) in lst.iter().enumerate() {
+                walk(value, &format!("{path}[{i}]"), depth + 1, visit);
+            }
+        }
+        Value::Map(m) => {
+            for key in m.sorted_keys() {
+                // safe to unwrap since key is from m
+                let value = m.get(key).unwrap();
+                walk(value, &format!("{path}{{{key}}}"), depth + 1, visit);
+            }
+        }
+        Value::Table(t) => {
+            let names = t.tclass().fieldnames();
+            for (row, record) in t.iter().enumerate() {
+                for (value, name) in record.iter().zip(names.iter()) {
+                    walk(
+                        value,
+                        &format!("{path}[{row}].{name}"),
+                        depth + 1,
+                        visit,
+                    );
+                }
+            }
+        }
+        _ => (),
+    }
+}
+
+fn at(path: &str) -> &str {
+    if path.is_empty() {
+        "the top level"
+    } else {
+        path
+    }
+}
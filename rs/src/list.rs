@@ -5,11 +5,14 @@
 A List holds a vtype (a possibly empty string), a comment (also possibly
 empty), and a (possibly empty) vector of Values.
 */
-use crate::check::check_vtype;
-use crate::util::escape;
-use crate::uxf::Compare;
+use crate::check::{
+    check_vtype, first_problem, typecheck, typecheck_vtype,
+};
+use crate::util::{check_indexes, escape};
+use crate::uxf::{Compare, Uxf};
 use crate::value::{Value, Values};
-use anyhow::Result;
+use anyhow::{bail, Result};
+use std::cmp::Ordering;
 use std::fmt;
 use std::ops::{Index, IndexMut};
 
@@ -27,7 +30,8 @@ impl List {
     /// the `vtype` should be a built-in UXF type (e.g., `int`, `str`,
     /// `date`, etc), or a `ttype`.
     /// The `vtype` and `comment` are immutable after construction.
-    /// The List does _not_ enforce the `vtype` if it is specified.
+    /// The List does _not_ enforce the `vtype` if it is specified (except
+    /// when using `try_push()`).
     pub fn new(vtype: &str, comment: &str) -> Result<Self> {
         if !vtype.is_empty() {
             check_vtype(vtype)?;
@@ -44,6 +48,11 @@ impl List {
         &self.vtype
     }
 
+    /// Sets the `vtype` (e.g., when renaming a ttype while merging).
+    pub(crate) fn set_vtype(&mut self, vtype: &str) {
+        self.vtype = vtype.to_string();
+    }
+
     /// To support type checking during parsing
     pub(crate) fn expected_type(&self) -> String {
         self.vtype.to_string()
@@ -67,7 +76,7 @@ impl List {
     /// Returns `Some(&Value)` if there's at least one value;
     /// otherwise `None`.
     pub fn first(&self) -> Option<&Value> {
-        self.values.get(0)
+        self.values.first()
     }
 
     /// Returns `Some(&Value)` if `index` is in bounds; otherwise `None`.
@@ -96,6 +105,32 @@ impl List {
         self.values.push(value);
     }
 
+    /// Appends the given `value` to the end of the list _or_ returns an
+    /// Err if the `value` doesn't match the list's `vtype` (or if it is a
+    /// collection whose contents don't match its own types).
+    /// If `uxo` is given, any ttypes (e.g., the list's `vtype`) must be
+    /// defined in it, and tables must match their definitions.
+    /// Use this rather than `push()` to ensure that the list will parse
+    /// after being written.
+    pub fn try_push(
+        &mut self,
+        value: Value,
+        uxo: Option<&Uxf>,
+    ) -> Result<()> {
+        let mut problems = vec![];
+        typecheck_vtype(&self.vtype, "", uxo, &mut problems);
+        typecheck(
+            &value,
+            &self.vtype,
+            &format!("[{}]", self.values.len()),
+            uxo,
+            &mut problems,
+        );
+        first_problem(&problems)?;
+        self.values.push(value);
+        Ok(())
+    }
+
     /// Appends the given `values` to the end of the list.
     pub fn push_many(&mut self, values: &[Value]) {
         self.values.extend_from_slice(values);
@@ -106,6 +141,67 @@ impl List {
         self.values.push(value.into());
     }
 
+    /// Inserts the given `value` at position `index` (shifting any later
+    /// values up) _or_ returns an Err if `index` is greater than the
+    /// list's length.
+    pub fn insert(&mut self, index: usize, value: Value) -> Result<()> {
+        if index > self.values.len() {
+            bail!(
+                "E760:-:0:can't insert at index {index} in a list of \
+                length {}",
+                self.values.len()
+            )
+        }
+        self.values.insert(index, value);
+        Ok(())
+    }
+
+    /// Removes and returns the value at position `index` (shifting any
+    /// later values down) if `index` is in bounds; otherwise returns
+    /// `None`.
+    pub fn remove(&mut self, index: usize) -> Option<Value> {
+        if index < self.values.len() {
+            Some(self.values.remove(index))
+        } else {
+            None
+        }
+    }
+
+    /// Swaps the values at positions `a` and `b` _or_ returns an Err if
+    /// either is out of bounds.
+    pub fn swap(&mut self, a: usize, b: usize) -> Result<()> {
+        check_indexes(a, b, self.values.len())?;
+        self.values.swap(a, b);
+        Ok(())
+    }
+
+    /// Keeps only those values for which `predicate` returns `true`.
+    pub fn retain<F>(&mut self, predicate: F)
+    where
+        F: FnMut(&Value) -> bool,
+    {
+        self.values.retain(predicate);
+    }
+
+    /// Sorts the list's values using the `compare` function (e.g.,
+    /// `Value::cmp_key_order`). The sort is stable.
+    pub fn sort_by<F>(&mut self, compare: F)
+    where
+        F: FnMut(&Value, &Value) -> Ordering,
+    {
+        self.values.sort_by(compare);
+    }
+
+    /// Removes consecutive duplicate values.
+    pub fn dedup(&mut self) {
+        self.values.dedup();
+    }
+
+    /// Appends the given `values` to the end of the list.
+    pub fn extend<I: IntoIterator<Item = Value>>(&mut self, values: I) {
+        self.values.extend(values);
+    }
+
     /// Truncates the list to contain at most `size` values.
     pub fn truncate(&mut self, size: usize) {
         self.values.truncate(size);
@@ -117,12 +213,12 @@ impl List {
     }
 
     /// Returns an iterator of the list's values as immutables.
-    pub fn iter(&self) -> std::slice::Iter<Value> {
+    pub fn iter(&self) -> std::slice::Iter<'_, Value> {
         self.values.iter()
     }
 
     /// Returns an iterator of the list's values as mutables.
-    pub fn iter_mut(&mut self) -> std::slice::IterMut<Value> {
+    pub fn iter_mut(&mut self) -> std::slice::IterMut<'_, Value> {
         self.values.iter_mut()
     }
 
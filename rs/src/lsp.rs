@@ -0,0 +1,626 @@
+// Copyright © 2022 Mark Summerfield. All rights reserved.
+// License: GPLv3
+
+/*! This module provides the editor support offered by the `uxf-lsp`
+language server (which is built if the `lsp` cargo feature is enabled).
+The module itself doesn't depend on the Language Server Protocol, so it
+could be used to support other editors or tools.
+
+An `Analysis` is made from a UXF file's filename and (possibly unsaved)
+text. It provides the file's diagnostics (i.e., its parse error if any,
+plus any warning and repair events), its reformatted text, and for a given
+position: hover text (a ttype's TClass definition, and for a table value,
+its field and vtype), the location of a ttype's definition (its `=` line
+in this file or in an imported `.uxi` file), and completions (of ttypes,
+vtypes, and the field names of the enclosing table).
+
+Positions are zero-based lines and columns, with columns counted in UTF-16
+code units (as the Language Server Protocol requires).
+
+```
+use uxf::lsp::{Analysis, Position};
+
+let uxt = "uxf 1\n=Point x:int y:int\n(Point 1 2)\n";
+let analysis = Analysis::new("-", uxt);
+assert!(analysis.diagnostics().is_empty());
+let hover = analysis.hover(Position::new(2, 9)).unwrap(); // on the 2
+assert!(hover.contains("=Point x:int y:int"));
+assert!(hover.contains("`y:int`"));
+```
+*/
+use crate::consts::*;
+use crate::event::{Event, EventKind, OnEventFn};
+use crate::field::Field;
+use crate::format::Format;
+use crate::parser::{
+    self, find_import, import_kind, ImportKind, TokenKind, Tokens,
+};
+use crate::repair::RepairPolicy;
+use crate::tclass::TClass;
+use crate::util::{read_file, split_error};
+use crate::uxf::{ParserOptions, Uxf};
+use std::{
+    cell::RefCell,
+    collections::{HashMap, HashSet},
+    rc::Rc,
+};
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Position {
+    pub line: u32,   // zero-based
+    pub column: u32, // zero-based in UTF-16 code units
+}
+
+impl Position {
+    pub fn new(line: u32, column: u32) -> Self {
+        Position { line, column }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Range {
+    pub start: Position,
+    pub end: Position,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Severity {
+    Error,
+    Warning,
+    Information, // used for repairs
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Diagnostic {
+    pub range: Range,
+    pub severity: Severity,
+    pub code: String, // e.g., "E123", "W456", or "R789"
+    pub message: String,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Location {
+    pub filename: String,
+    pub range: Range,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CompletionKind {
+    TType,
+    VType,
+    Field,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Completion {
+    pub label: String,
+    pub kind: CompletionKind,
+    pub detail: String, // e.g., a ttype's definition or a field's vtype
+}
+
+#[derive(Clone)]
+pub struct Analysis {
+    filename: String,
+    text: Vec<char>,
+    line_starts: Vec<usize>, // char offsets
+    tokens: Tokens,          // empty if the text couldn't be tokenized
+    uxo: Option<Uxf>,        // None if the text couldn't be parsed
+    tclass_for_ttype: HashMap<String, TClass>,
+    diagnostics: Vec<Diagnostic>,
+}
+
+impl Analysis {
+    /// Returns an `Analysis` of the given UXF `text` whose `filename`
+    /// (which may be `"-"`) is used to find imports and in messages.
+    /// The text is parsed accepting enums; a parse error (or a warning
+    /// or repair) becomes one of the `diagnostics()`.
+    pub fn new(filename: &str, text: &str) -> Self {
+        let chars: Vec<char> = text.chars().collect();
+        let events = Rc::new(RefCell::new(vec![]));
+        let on_event: OnEventFn = {
+            let events = Rc::clone(&events);
+            Rc::new(move |event: &Event| {
+                events.borrow_mut().push(event.clone())
+            })
+        };
+        let result = parser::parse(
+            text,
+            filename,
+            ParserOptions::ENUMS | ParserOptions::PSEUDO_VTYPES,
+            &RepairPolicy::default(),
+            on_event,
+        );
+        let mut analysis = Analysis {
+            filename: filename.to_string(),
+            line_starts: line_starts(&chars),
+            tokens: parser::tokenize(&chars, filename, true)
+                .unwrap_or_default(),
+            text: chars,
+            uxo: None,
+            tclass_for_ttype: HashMap::new(),
+            diagnostics: vec![],
+        };
+        for event in events.borrow().iter() {
+            analysis.add_event(event);
+        }
+        match result {
+            Ok(uxo) => {
+                analysis.tclass_for_ttype = uxo.tclass_for_ttype.clone();
+                analysis.uxo = Some(uxo);
+            }
+            Err(err) => {
+                analysis.add_error(&format!("{err:#}"));
+                analysis.tclass_for_ttype =
+                    local_tclasses(&analysis.tokens);
+            }
+        }
+        analysis
+    }
+
+    /// Returns the parsed `Uxf` or `None` if the text has an error.
+    pub fn uxf(&self) -> Option<&Uxf> {
+        self.uxo.as_ref()
+    }
+
+    /// Returns the diagnostics in the order they were found.
+    pub fn diagnostics(&self) -> &[Diagnostic] {
+        &self.diagnostics
+    }
+
+    /// Returns the position just past the end of the text (e.g., for the
+    /// range of an edit that replaces the whole text).
+    pub fn end(&self) -> Position {
+        self.position(self.text.len())
+    }
+
+    /// Returns the text reformatted using the given `format` _or_ `None`
+    /// if the text has an error.
+    pub fn format(&self, format: &Format) -> Option<String> {
+        self.uxo.as_ref().map(|uxo| uxo.to_text_format(format))
+    }
+
+    /// Returns Markdown hover text for the given `position` _or_ `None`.
+    /// On a ttype this is the ttype's TClass definition; on a table value
+    /// this is the table's TClass definition and the value's field.
+    pub fn hover(&self, position: Position) -> Option<String> {
+        let offset = self.offset(position);
+        if let Some(ttype) = self.ttype_at(offset) {
+            return Some(self.describe(&ttype));
+        }
+        let (ttype, column) = self.table_at(offset)?;
+        let field =
+            self.tclass_for_ttype.get(&ttype)?.fields().get(column?)?;
+        Some(format!("{}\n\nField: `{field}`", self.describe(&ttype)))
+    }
+
+    /// Returns the location of the definition of the ttype at the given
+    /// `position` (its `=` line in this file or in an imported file), or
+    /// of the imported file if the position is on an import, _or_ `None`.
+    pub fn definition(&self, position: Position) -> Option<Location> {
+        let line = self.line_text(position.line as usize);
+        if let Some(import) = line.strip_prefix('!') {
+            let filename = import_filename(import.trim(), &self.filename)?;
+            return Some(Location { filename, range: Range::default() });
+        }
+        let ttype = self.ttype_at(self.offset(position))?;
+        if let Some(offset) = definition_offset(&self.tokens, &ttype) {
+            return Some(Location {
+                filename: self.filename.clone(),
+                range: self.line_range(self.position(offset).line),
+            });
+        }
+        for token in &self.tokens {
+            if token.kind != TokenKind::Import {
+                continue;
+            }
+            let filename = match import_filename(
+                token.value.as_str().unwrap_or_default(),
+                &self.filename,
+            ) {
+                Some(filename) => filename,
+                None => continue,
+            };
+            if let Ok(text) = read_file(&filename) {
+                let analysis = Analysis::new(&filename, &text);
+                if let Some(offset) =
+                    definition_offset(&analysis.tokens, &ttype)
+                {
+                    return Some(Location {
+                        range: analysis
+                            .line_range(analysis.position(offset).line),
+                        filename,
+                    });
+                }
+            }
+        }
+        None
+    }
+
+    /// Returns the completions for the given `position`: the field names
+    /// of the enclosing table (if any), the built-in vtypes if the
+    /// position follows a `:`, `[`, or `{`, and every ttype.
+    pub fn completions(&self, position: Position) -> Vec<Completion> {
+        let offset = self.offset(position);
+        let mut start = offset;
+        while start > 0 && is_identifier_char(self.text[start - 1]) {
+            start -= 1;
+        }
+        let mut completions = vec![];
+        if let Some((ttype, _)) = self.table_at(offset) {
+            if let Some(tclass) = self.tclass_for_ttype.get(&ttype) {
+                for field in tclass.fields() {
+                    completions.push(Completion {
+                        label: field.name().to_string(),
+                        kind: CompletionKind::Field,
+                        detail: field
+                            .vtype()
+                            .unwrap_or_default()
+                            .to_string(),
+                    });
+                }
+            }
+        }
+        if start > 0 && matches!(self.text[start - 1], ':' | '[' | '{') {
+            for vtype in VTYPES.iter().chain(PSEUDO_VTYPES.iter()) {
+                completions.push(Completion {
+                    label: vtype.to_string(),
+                    kind: CompletionKind::VType,
+                    detail: "".to_string(),
+                });
+            }
+        }
+        let mut tclasses: Vec<&TClass> =
+            self.tclass_for_ttype.values().collect();
+        tclasses.sort_by(|a, b| a.ttype().cmp(b.ttype()));
+        for tclass in tclasses {
+            completions.push(Completion {
+                label: tclass.ttype().to_string(),
+                kind: CompletionKind::TType,
+                detail: tclass.to_string(),
+            });
+        }
+        completions
+    }
+
+    fn add_event(&mut self, event: &Event) {
+        let (severity, letter) = match event.kind {
+            EventKind::Warning => (Severity::Warning, 'W'),
+            EventKind::Repair => (Severity::Information, 'R'),
+        };
+        self.add_diagnostic(
+            severity,
+            format!("{letter}{}", event.code),
+            &event.filename,
+            event.lino,
+            &event.message,
+        );
+    }
+
+    fn add_error(&mut self, text: &str) {
+        let (code, filename, lino, message) = split_error(text);
+        let code =
+            if code == 0 { "".to_string() } else { format!("E{code}") };
+        let filename = if filename.is_empty() {
+            self.filename.clone()
+        } else {
+            filename.to_string()
+        };
+        self.add_diagnostic(
+            Severity::Error,
+            code,
+            &filename,
+            lino,
+            message,
+        );
+    }
+
+    // An event or error in an imported file is reported on the first line
+    fn add_diagnostic(
+        &mut self,
+        severity: Severity,
+        code: String,
+        filename: &str,
+        lino: usize,
+        message: &str,
+    ) {
+        let (line, message) = if filename == self.filename {
+            (self.line_for_lino(lino), message.to_string())
+        } else {
+            (0, format!("{filename}:{lino}:{message}"))
+        };
+        self.diagnostics.push(Diagnostic {
+            range: self.line_range(line as u32),
+            severity,
+            code,
+            message,
+        });
+    }
+
+    // Returns the zero-based line for the given parser line number. The
+    // lexer doesn't count the newline that ends each import, so line
+    // numbers after the imports are one less per import (ambiguous ones,
+    // i.e., that of the first import, are left as-is).
+    fn line_for_lino(&self, lino: usize) -> usize {
+        let mut imports = self
+            .tokens
+            .iter()
+            .filter(|token| token.kind == TokenKind::Import);
+        let lino = match imports.next() {
+            Some(first) if lino > first.lino => lino + 1 + imports.count(),
+            _ => lino,
+        };
+        lino.saturating_sub(1)
+    }
+
+    fn describe(&self, ttype: &str) -> String {
+        let mut text = match self.tclass_for_ttype.get(ttype) {
+            Some(tclass) => format!("```uxf\n{tclass}\n```"),
+            None => format!("`{ttype}`"),
+        };
+        if let Some(uxo) = &self.uxo {
+            if let Some(import) = uxo.import_for_ttype.get(ttype) {
+                text.push_str(&format!("\n\nImported from `{import}`"));
+            }
+        }
+        text
+    }
+
+    // Returns the known ttype whose name is at the given offset (but not
+    // if it is in the header or in a str, bytes, or comment)
+    fn ttype_at(&self, offset: usize) -> Option<String> {
+        let mut start = offset.min(self.text.len());
+        while start > 0 && is_identifier_char(self.text[start - 1]) {
+            start -= 1;
+        }
+        let mut end = start;
+        while end < self.text.len() && is_identifier_char(self.text[end]) {
+            end += 1;
+        }
+        if start == end || start < self.line_starts.get(1).copied()? {
+            return None;
+        }
+        let in_text = self.tokens.iter().any(|token| {
+            matches!(
+                token.kind,
+                TokenKind::Str | TokenKind::Bytes | TokenKind::FileComment
+            ) && token.start <= start
+                && start < token.end
+        });
+        let ttype: String = self.text[start..end].iter().collect();
+        if !in_text && self.tclass_for_ttype.contains_key(&ttype) {
+            Some(ttype)
+        } else {
+            None
+        }
+    }
+
+    // Returns the ttype of the innermost table that encloses the given
+    // offset, and if the offset is on one of the table's values, the
+    // value's column; or None if the innermost collection isn't a table
+    fn table_at(&self, offset: usize) -> Option<(String, Option<usize>)> {
+        let mut stack: Vec<(String, usize)> = vec![]; // ttype & #values
+        for token in &self.tokens {
+            if token.start > offset {
+                break;
+            }
+            let is_value = match token.kind {
+                TokenKind::TableEnd
+                | TokenKind::ListEnd
+                | TokenKind::MapEnd => {
+                    if token.start == offset {
+                        break;
+                    }
+                    stack.pop();
+                    continue;
+                }
+                TokenKind::TableBegin
+                | TokenKind::ListBegin
+                | TokenKind::MapBegin
+                | TokenKind::Null
+                | TokenKind::Bool
+                | TokenKind::Int
+                | TokenKind::Real
+                | TokenKind::Date
+                | TokenKind::DateTime
+                | TokenKind::Str
+                | TokenKind::Bytes
+                | TokenKind::Identifier => true,
+                _ => false,
+            };
+            if !is_value {
+                continue;
+            }
+            let column = match stack.last_mut() {
+                Some((ttype, count)) if !ttype.is_empty() => {
+                    let column = self
+                        .tclass_for_ttype
+                        .get(ttype)
+                        .map(|tclass| tclass.len())
+                        .filter(|&size| size > 0)
+                        .map(|size| *count % size);
+                    *count += 1;
+                    column
+                }
+                _ => None,
+            };
+            if offset < token.end {
+                return match stack.last() {
+                    Some((ttype, _)) if !ttype.is_empty() => {
+                        Some((ttype.clone(), column))
+                    }
+                    _ => None,
+                };
+            }
+            match token.kind {
+                TokenKind::TableBegin => {
+                    stack.push((token.vtype.clone(), 0))
+                }
+                TokenKind::ListBegin | TokenKind::MapBegin => {
+                    stack.push(("".to_string(), 0))
+                }
+                _ => {}
+            }
+        }
+        match stack.last() {
+            Some((ttype, _)) if !ttype.is_empty() => {
+                Some((ttype.clone(), None))
+            }
+            _ => None,
+        }
+    }
+
+    // Returns the range of the whole of the given line
+    fn line_range(&self, line: u32) -> Range {
+        let line = (line as usize).min(self.line_starts.len() - 1);
+        let start = self.line_starts[line];
+        let mut end = start;
+        while end < self.text.len() && self.text[end] != NL {
+            end += 1;
+        }
+        Range { start: self.position(start), end: self.position(end) }
+    }
+
+    fn line_text(&self, line: usize) -> String {
+        let line = line.min(self.line_starts.len() - 1);
+        self.text[self.line_starts[line]..]
+            .iter()
+            .take_while(|&&c| c != NL)
+            .collect()
+    }
+
+    fn position(&self, offset: usize) -> Position {
+        let line =
+            self.line_starts.partition_point(|&start| start <= offset) - 1;
+        let column: usize = self.text[self.line_starts[line]..offset]
+            .iter()
+            .map(|c| c.len_utf16())
+            .sum();
+        Position::new(line as u32, column as u32)
+    }
+
+    fn offset(&self, position: Position) -> usize {
+        let line = (position.line as usize).min(self.line_starts.len() - 1);
+        let mut offset = self.line_starts[line];
+        let mut column = 0;
+        while offset < self.text.len()
+            && self.text[offset] != NL
+            && column < position.column as usize
+        {
+            column += self.text[offset].len_utf16();
+            offset += 1;
+        }
+        offset
+    }
+}
+
+fn line_starts(text: &[char]) -> Vec<usize> {
+    let mut starts = vec![0];
+    starts.extend(
+        text.iter()
+            .enumerate()
+            .filter(|(_, &c)| c == NL)
+            .map(|(i, _)| i + 1),
+    );
+    starts
+}
+
+fn is_identifier_char(c: char) -> bool {
+    c == '_' || c.is_alphanumeric()
+}
+
+// Returns the TClasses defined in the given tokens (ignoring any that are
+// invalid); used when the text has a parse error
+fn local_tclasses(tokens: &Tokens) -> HashMap<String, TClass> {
+    let mut tclass_for_ttype = HashMap::new();
+    let mut i = 0;
+    while i < tokens.len() {
+        let token = &tokens[i];
+        i += 1;
+        if !matches!(
+            token.kind,
+            TokenKind::TClassBegin | TokenKind::EnumBegin
+        ) {
+            continue;
+        }
+        let ttype = token.value.as_str().unwrap_or_default();
+        let mut fields = vec![];
+        while i < tokens.len() && tokens[i].kind == TokenKind::Field {
+            fields.push(&tokens[i]);
+            i += 1;
+        }
+        let tclass = if token.kind == TokenKind::EnumBegin {
+            let members: Vec<&str> = fields
+                .iter()
+                .map(|field| field.value.as_str().unwrap_or_default())
+                .collect();
+            for member in &members {
+                if let Ok(tclass) = TClass::new_fieldless(member, "") {
+                    tclass_for_ttype.insert(member.to_string(), tclass);
+                }
+            }
+            TClass::new_enum(ttype, &members, &token.comment)
+        } else if fields.is_empty() {
+            TClass::new_fieldless(ttype, &token.comment)
+        } else {
+            fields
+                .iter()
+                .map(|field| {
+                    Field::new(
+                        field.value.as_str().unwrap_or_default(),
+                        &field.vtype,
+                    )
+                })
+                .collect::<anyhow::Result<Vec<Field>>>()
+                .and_then(|fields| {
+                    TClass::new(ttype, fields, &token.comment)
+                })
+        };
+        if let Ok(tclass) = tclass {
+            tclass_for_ttype.insert(ttype.to_string(), tclass);
+        }
+    }
+    tclass_for_ttype
+}
+
+// Returns the char offset of the definition of the given ttype (or of
+// the enum it is a member of) in the given tokens
+fn definition_offset(tokens: &Tokens, ttype: &str) -> Option<usize> {
+    let mut enum_start = None;
+    for token in tokens {
+        match token.kind {
+            TokenKind::TClassBegin | TokenKind::EnumBegin => {
+                if token.value.as_str() == Some(ttype) {
+                    return Some(token.start);
+                }
+                enum_start = if token.kind == TokenKind::EnumBegin {
+                    Some(token.start)
+                } else {
+                    None
+                };
+            }
+            TokenKind::Field
+                if enum_start.is_some()
+                    && token.value.as_str() == Some(ttype) =>
+            {
+                return enum_start;
+            }
+            TokenKind::TClassEnd => enum_start = None,
+            _ => {}
+        }
+    }
+    None
+}
+
+// Returns the filename of the given import if it is an existing file
+// (i.e., not a URL or system import)
+fn import_filename(import: &str, importer: &str) -> Option<String> {
+    if import_kind(import) != ImportKind::File {
+        return None;
+    }
+    let (filename, _) = find_import(import, importer, &HashSet::new());
+    if std::path::Path::new(&filename).is_file() {
+        Some(filename)
+    } else {
+        None
+    }
+}
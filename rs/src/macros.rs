@@ -0,0 +1,197 @@
+// Copyright © 2022 Mark Summerfield. All rights reserved.
+// License: GPLv3
+
+/*! The `uxf!` macro for building `Value`s and `Uxf`s using UXF-like
+syntax with interpolated Rust expressions. */
+
+/// Returns a `Value` (or a `Uxf`) built from UXF-like syntax in which
+/// every scalar is a Rust expression that converts into a `Value` (e.g.,
+/// `1`, `2.5`, `"text"`, `true`, a `NaiveDate`, a variable), or `null`.
+///
+/// - `[a, b, c]` is an untyped list and `[int; 1, 2, 3]` a typed list.
+/// - `{k1 => v1, k2 => v2}` is an untyped map, `{str; ...}` a map with a
+///   ktype, and `{str int; "a" => 1}` a map with a ktype and vtype. Keys
+///   must convert into a `Key`.
+/// - `(=Point x:int y:int; (1, 2), (3, 4))` is a table whose TClass is
+///   defined inline (field vtypes are optional, and `(=Flag)` is a
+///   fieldless table); `(tclass; (1, 2))` is a table using the given
+///   `TClass` expression.
+/// - `uxf "custom"; value` (or `uxf; value`) is a `Uxf` containing the
+///   given list, map, or table value, with every TClass it uses defined.
+///
+/// Collections may be nested, and trailing commas are allowed.
+/// The syntax is checked at compile time; but vtypes and ttypes are only
+/// checked when the macro's code is run, which panics if any is invalid
+/// or if a table record has the wrong number of values. Values aren't
+/// checked against their collection's types (use `Uxf::typecheck()` for
+/// that).
+///
+/// Since `use uxf::uxf;` would also import the `uxf` module (and so
+/// shadow the crate's name), it is usually best to write `uxf::uxf!`.
+///
+/// ```
+/// let name = "Ann";
+/// let value = uxf::uxf!([
+///     [int; 1, 2, 3],
+///     {str int; "x" => 1, "y" => 2},
+///     (=Person name:str age:int; (name, 30), ("Bob", null)),
+/// ]);
+/// assert_eq!(value.as_list().unwrap().len(), 3);
+/// let uxo =
+///     uxf::uxf!(uxf "people"; (=Person name:str age:int; ("Cy", 7)));
+/// assert_eq!(uxo.to_text(),
+///            "uxf 1 people\n=Person name:str age:int\n(Person <Cy> 7)\n");
+/// ```
+#[macro_export]
+macro_rules! uxf {
+    // Internal: push each item onto $target (a List or Vec<Value>)
+    (@items $target:ident ; ) => {};
+    (@items $target:ident ; null $(, $($rest:tt)*)?) => {
+        $target.push($crate::value::Value::Null);
+        $crate::uxf!(@items $target ; $($($rest)*)?);
+    };
+    (@items $target:ident ; [$($inner:tt)*] $(, $($rest:tt)*)?) => {
+        $target.push($crate::uxf!([$($inner)*]));
+        $crate::uxf!(@items $target ; $($($rest)*)?);
+    };
+    (@items $target:ident ; {$($inner:tt)*} $(, $($rest:tt)*)?) => {
+        $target.push($crate::uxf!({$($inner)*}));
+        $crate::uxf!(@items $target ; $($($rest)*)?);
+    };
+    (@items $target:ident ; ($($inner:tt)*) $(, $($rest:tt)*)?) => {
+        $target.push($crate::uxf!(($($inner)*)));
+        $crate::uxf!(@items $target ; $($($rest)*)?);
+    };
+    (@items $target:ident ; $value:expr $(, $($rest:tt)*)?) => {
+        $target.push($crate::value::Value::from($value));
+        $crate::uxf!(@items $target ; $($($rest)*)?);
+    };
+
+    // Internal: insert each key => value item into map $m
+    (@items_map $m:ident ; ) => {};
+    (@items_map $m:ident ; $key:expr => $($rest:tt)*) => {
+        $crate::uxf!(@item_map $m ($key) ; $($rest)*);
+    };
+    (@item_map $m:ident ($key:expr) ; null $(, $($rest:tt)*)?) => {
+        $m.insert($crate::key::Key::from($key), $crate::value::Value::Null);
+        $crate::uxf!(@items_map $m ; $($($rest)*)?);
+    };
+    (@item_map $m:ident ($key:expr) ; [$($inner:tt)*]
+     $(, $($rest:tt)*)?) => {
+        $m.insert($crate::key::Key::from($key), $crate::uxf!([$($inner)*]));
+        $crate::uxf!(@items_map $m ; $($($rest)*)?);
+    };
+    (@item_map $m:ident ($key:expr) ; {$($inner:tt)*}
+     $(, $($rest:tt)*)?) => {
+        $m.insert($crate::key::Key::from($key), $crate::uxf!({$($inner)*}));
+        $crate::uxf!(@items_map $m ; $($($rest)*)?);
+    };
+    (@item_map $m:ident ($key:expr) ; ($($inner:tt)*)
+     $(, $($rest:tt)*)?) => {
+        $m.insert($crate::key::Key::from($key), $crate::uxf!(($($inner)*)));
+        $crate::uxf!(@items_map $m ; $($($rest)*)?);
+    };
+    (@item_map $m:ident ($key:expr) ; $value:expr $(, $($rest:tt)*)?) => {
+        $m.insert(
+            $crate::key::Key::from($key),
+            $crate::value::Value::from($value),
+        );
+        $crate::uxf!(@items_map $m ; $($($rest)*)?);
+    };
+
+    // Internal: append each (value, ...) record to table $t
+    (@records $t:ident ; ) => {};
+    (@records $t:ident ; ($($values:tt)*) $(, $($rest:tt)*)?) => {
+        let mut record = $crate::value::Values::new();
+        $crate::uxf!(@items record ; $($values)*);
+        $t.append(record).expect("uxf!: invalid table record");
+        $crate::uxf!(@records $t ; $($($rest)*)?);
+    };
+
+    // Uxf
+    (uxf $($custom:literal)? ; $($value:tt)+) => {{
+        let value = $crate::uxf!($($value)+);
+        let custom: &str = [$($custom,)? ""][0];
+        let mut uxo = $crate::uxf::Uxf::new(custom, "");
+        for tclass in value.tclasses() {
+            uxo.add_tclass(tclass);
+        }
+        uxo.set_value(value).expect("uxf!: invalid Uxf value");
+        uxo
+    }};
+
+    (null) => {
+        $crate::value::Value::Null
+    };
+
+    // Lists
+    ([$vtype:ident ; $($items:tt)*]) => {{
+        let mut list = $crate::list::List::new(stringify!($vtype), "")
+            .expect("uxf!: invalid list vtype");
+        $crate::uxf!(@items list ; $($items)*);
+        $crate::value::Value::List(list)
+    }};
+    ([$($items:tt)*]) => {{
+        #[allow(unused_mut)]
+        let mut list = $crate::list::List::default();
+        $crate::uxf!(@items list ; $($items)*);
+        $crate::value::Value::List(list)
+    }};
+
+    // Maps
+    ({$ktype:ident $vtype:ident ; $($items:tt)*}) => {{
+        let mut map = $crate::map::Map::new(
+            stringify!($ktype),
+            stringify!($vtype),
+            "",
+        )
+        .expect("uxf!: invalid map ktype or vtype");
+        $crate::uxf!(@items_map map ; $($items)*);
+        $crate::value::Value::Map(map)
+    }};
+    ({$ktype:ident ; $($items:tt)*}) => {{
+        let mut map = $crate::map::Map::new(stringify!($ktype), "", "")
+            .expect("uxf!: invalid map ktype");
+        $crate::uxf!(@items_map map ; $($items)*);
+        $crate::value::Value::Map(map)
+    }};
+    ({$($items:tt)*}) => {{
+        #[allow(unused_mut)]
+        let mut map = $crate::map::Map::default();
+        $crate::uxf!(@items_map map ; $($items)*);
+        $crate::value::Value::Map(map)
+    }};
+
+    // Tables
+    ((= $ttype:ident $($field:ident $(: $fvtype:ident)?)*
+      $(; $($records:tt)*)?)) => {{
+        let fields: Vec<$crate::field::Field> = vec![$(
+            $crate::field::Field::new(
+                stringify!($field),
+                [$(stringify!($fvtype),)? ""][0],
+            )
+            .expect("uxf!: invalid field"),
+        )*];
+        let tclass = if fields.is_empty() {
+            $crate::tclass::TClass::new_fieldless(stringify!($ttype), "")
+        } else {
+            $crate::tclass::TClass::new(stringify!($ttype), fields, "")
+        }
+        .expect("uxf!: invalid ttype");
+        #[allow(unused_mut)]
+        let mut table = $crate::table::Table::new(tclass, "");
+        $crate::uxf!(@records table ; $($($records)*)?);
+        $crate::value::Value::Table(table)
+    }};
+    (($tclass:expr ; $($records:tt)*)) => {{
+        let tclass: $crate::tclass::TClass = $tclass.clone();
+        let mut table = $crate::table::Table::new(tclass, "");
+        $crate::uxf!(@records table ; $($records)*);
+        $crate::value::Value::Table(table)
+    }};
+
+    // Scalars
+    ($value:expr) => {
+        $crate::value::Value::from($value)
+    };
+}
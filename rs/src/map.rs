@@ -4,23 +4,42 @@
 /*! A Map holds a ktype (a possibly empty string), vtype (also possibly
 empty), a comment (also possibly empty), and a (possibly empty) map
 of Key-Value pairs.
+
+A Map remembers the order in which its items were inserted (for parsed
+maps, this is their order in the source), but by default its items are
+iterated, visited, and output in sorted key order. Use `MapOrder` with
+`keys_in()`, `Value::visit_order()`, or `Format` to choose which order
+is used.
 */
-use crate::check::{check_ktype_x, check_vtype_x};
+use crate::check::{
+    check_ktype_x, check_vtype_x, first_problem, typecheck, typecheck_vtype,
+};
 use crate::key::Key;
 use crate::util::escape;
-use crate::uxf::Compare;
+use crate::uxf::{Compare, Uxf};
 use crate::value::Value;
 use anyhow::{bail, Result};
-use std::collections::HashMap;
+use indexmap::map::IndexMap;
 use std::fmt;
 
+/// The order in which a Map's items are iterated, visited, or output.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum MapOrder {
+    /// Sorted key order (see `Map::sorted_keys()`); this is the default.
+    #[default]
+    Sorted,
+    /// The order in which the items were inserted (for parsed maps, the
+    /// order they appeared in the source).
+    Insertion,
+}
+
 #[derive(Clone, Debug)]
 pub struct Map {
     ktype: String,
     vtype: String,
     comment: String,
     pending_key: Option<Key>,
-    items: HashMap<Key, Value>,
+    items: IndexMap<Key, Value>,
 }
 
 impl Map {
@@ -34,7 +53,8 @@ impl Map {
     /// `date`, etc), or a `ttype`.
     /// The `vtype` and `comment` are immutable after construction.
     /// The Map does _not_ enforce the `ktype` or `vtype` if either or both
-    /// is specified (although keys must be _a_ valid `ktype`).
+    /// is specified (although keys must be _a_ valid `ktype`) except when
+    /// using `try_insert()`.
     pub fn new(ktype: &str, vtype: &str, comment: &str) -> Result<Self> {
         Map::new_x(ktype, vtype, comment, "-", 0)
     }
@@ -76,7 +96,7 @@ impl Map {
             vtype: vtype.to_string(),
             comment: comment.to_string(),
             pending_key: None,
-            items: HashMap::new(),
+            items: IndexMap::new(),
         })
     }
 
@@ -90,6 +110,11 @@ impl Map {
         &self.vtype
     }
 
+    /// Sets the `vtype` (e.g., when renaming a ttype while merging).
+    pub(crate) fn set_vtype(&mut self, vtype: &str) {
+        self.vtype = vtype.to_string();
+    }
+
     /// Returns the `comment` which may be `""`.
     pub fn comment(&self) -> &str {
         &self.comment
@@ -153,18 +178,58 @@ impl Map {
     }
 
     /// Inserts the given `key` and `value` into the map.
-    /// If the `key` was already present, returns the previous value;
-    /// otherwise returns `None`.
+    /// If the `key` was already present, its value is replaced in place
+    /// (so its insertion position is unchanged) and the previous value is
+    /// returned; otherwise the item is added at the end and `None` is
+    /// returned.
     /// This ignores any pending key.
     pub fn insert(&mut self, key: Key, value: Value) -> Option<Value> {
         self.items.insert(key, value)
     }
 
+    /// Inserts the given `key` and `value` into the map _or_ returns an
+    /// Err if the `key` doesn't match the map's `ktype` or the `value`
+    /// doesn't match the map's `vtype` (or if it is a collection whose
+    /// contents don't match its own types).
+    /// If the `key` was already present, returns the previous value;
+    /// otherwise returns `None`.
+    /// If `uxo` is given, any ttypes (e.g., the map's `vtype`) must be
+    /// defined in it, and tables must match their definitions.
+    /// Use this rather than `insert()` to ensure that the map will parse
+    /// after being written.
+    pub fn try_insert(
+        &mut self,
+        key: Key,
+        value: Value,
+        uxo: Option<&Uxf>,
+    ) -> Result<Option<Value>> {
+        let key_value = Value::from(key.clone());
+        if !self.ktype.is_empty() && key_value.typename() != self.ktype {
+            bail!(
+                "E500:-:0:expected {} key, got {} {key}",
+                self.ktype,
+                key_value.typename()
+            )
+        }
+        let mut problems = vec![];
+        typecheck_vtype(&self.vtype, "", uxo, &mut problems);
+        typecheck(
+            &value,
+            &self.vtype,
+            &format!("{{{key}}}"),
+            uxo,
+            &mut problems,
+        );
+        first_problem(&problems)?;
+        Ok(self.items.insert(key, value))
+    }
+
     /// If there's an item with the given `key`, removes the item and
     /// returns its corresponding value; otherwise leaves the map unchanged
-    /// and returns `None`.
+    /// and returns `None`. The insertion order of the remaining items is
+    /// preserved.
     pub fn remove(&mut self, key: &Key) -> Option<Value> {
-        self.items.remove(key)
+        self.items.shift_remove(key)
     }
 
     /// Deletes every item in the map so that it is empty.
@@ -184,18 +249,40 @@ impl Map {
         keys
     }
 
-    /// Returns `&items` to make the entire immutable HashMap API available.
-    pub fn inner(&self) -> &HashMap<Key, Value> {
-        &self.items
+    /// Returns the map's keys in insertion order.
+    pub fn keys(&self) -> Vec<&Key> {
+        self.items.keys().collect()
+    }
+
+    /// Returns the map's keys in the given `order`.
+    pub fn keys_in(&self, order: MapOrder) -> Vec<&Key> {
+        match order {
+            MapOrder::Sorted => self.sorted_keys(),
+            MapOrder::Insertion => self.keys(),
+        }
+    }
+
+    /// Reorders the map's items into sorted key order so that insertion
+    /// order and sorted order are the same.
+    pub fn sort_keys(&mut self) {
+        self.items.sort_keys();
     }
 
-    /// Returns `&mut items` to make the entire mutable HashMap API
+    /// Returns `&items` to make the entire immutable IndexMap API
     /// available.
-    pub fn inner_mut(&mut self) -> &mut HashMap<Key, Value> {
+    pub fn inner(&self) -> &IndexMap<Key, Value> {
+        &self.items
+    }
+
+    /// Returns `&mut items` to make the entire mutable IndexMap API
+    /// available. (Note that IndexMap's `remove()` does _not_ preserve
+    /// order; use `shift_remove()` for that.)
+    pub fn inner_mut(&mut self) -> &mut IndexMap<Key, Value> {
         &mut self.items
     }
 
     /// Returns `true` if this `Map` and the `other` `Map` are the same.
+    /// Insertion order is ignored.
     /// Set `compare` to `EQUIVALENT` or `IGNORE_COMMENTS` if comment
     /// differences don't matter.
     /// See also `==` and `Uxf::is_equivalent()`.
@@ -219,7 +306,7 @@ impl Default for Map {
             vtype: "".to_string(),
             comment: "".to_string(),
             pending_key: None,
-            items: HashMap::new(),
+            items: IndexMap::new(),
         }
     }
 }
@@ -244,8 +331,8 @@ impl PartialEq for Map {
             if akey != bkey {
                 return false;
             }
-            let avalue = self.items.get(akey);
-            let bvalue = other.items.get(akey);
+            let avalue = self.items.get(*akey);
+            let bvalue = other.items.get(*akey);
             if avalue != bvalue {
                 return false;
             }
@@ -0,0 +1,388 @@
+// Copyright © 2022 Mark Summerfield. All rights reserved.
+// License: GPLv3
+
+/*! Combines any number of `Uxf`s into one, whose value is either a map
+(keyed by each `Uxf`'s name, e.g., its filename) or a list, with the
+`Uxf`s' values in the order given.
+
+The merged `Uxf` has the union of the `Uxf`s' TClasses and imports.
+System imports (e.g., `!complex`), URL imports, and imports of absolute
+filenames are kept since they mean the same wherever the merged `Uxf` is
+written. But an import of a relative filename is relative to its own
+file's folder, so the TClasses it provides are written inline instead.
+Definitions of the same ttype that are identical (ignoring comments) are
+merged into one. If a later `Uxf` defines a ttype of the same name as an
+earlier one but with different fields (or imports it from a different
+place with different fields), the later ttype is renamed (e.g., `Point`
+becomes `Point2`) in its TClass, in its tables, in any list or map vtypes
+and field vtypes that refer to it, and a repair `Event` (R830) is
+reported. A `Uxf` name that duplicates an earlier one when merging as a
+map produces a warning `Event` (W831) and replaces the earlier value.
+
+The comment of the merged `Uxf` consists of the unique `Uxf` comments,
+one per line.
+
+`include()` merges the files listed in an _include_ file as a list. An
+include file's value is a table of ttype `include` with a single `str`
+field, e.g.:
+
+```text
+uxf 1 UXF Include
+=include filename:str
+(include <file1.uxf> <file2.uxf> <file3.uxf>)
+```
+
+Relative filenames are relative to the include file's folder.
+
+```
+use uxf::merge::{merge, MergeAs};
+
+let a = uxf::parse("uxf 1\n=Point x y\n[(Point 1 2)]\n").unwrap();
+let b = uxf::parse("uxf 1\n=Point x y z\n[(Point 3 4 5)]\n").unwrap();
+let uxo = merge(vec![("a", a), ("b", b)], MergeAs::List,
+                Some(std::rc::Rc::new(uxf::ignore_event))).unwrap();
+assert_eq!(uxo.to_string(),
+           "uxf 1\n=Point x y\n=Point2 x y z\n\
+           [[(Point 1 2)]\n[(Point2 3 4 5)]]\n");
+```
+*/
+use crate::event::{self, Event, OnEventFn};
+use crate::field::Field;
+use crate::key::Key;
+use crate::list::List;
+use crate::map::Map;
+use crate::parser::{import_kind, ImportKind};
+use crate::tclass::TClass;
+use crate::util::{dirname, full_filename};
+use crate::uxf::{parse_options, ParserOptions, Uxf};
+use crate::value::Value;
+use anyhow::{bail, Result};
+use indexmap::map::IndexMap;
+use std::{
+    collections::{HashMap, HashSet},
+    path::Path,
+    rc::Rc,
+};
+
+/// The ttype of an include file's table.
+pub const INCLUDE_TTYPE: &str = "include";
+
+/// Whether the merged `Uxf`s' values are gathered into a map or a list.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub enum MergeAs {
+    /// A map whose keys are the `Uxf`s' names
+    #[default]
+    Map,
+    /// A list in the order the `Uxf`s are given
+    List,
+}
+
+/// Returns a new `Uxf` that combines the given (name, `Uxf`) pairs
+/// (see the module documentation) _or_ returns an Err if there are no
+/// `Uxf`s. Conflicting ttypes are renamed and reported to `on_event` (or
+/// to the default event handler if `None`).
+pub fn merge(
+    uxos: Vec<(&str, Uxf)>,
+    merge_as: MergeAs,
+    on_event: Option<OnEventFn>,
+) -> Result<Uxf> {
+    if uxos.is_empty() {
+        bail!("E834:-:0:nothing to merge")
+    }
+    let on_event = on_event.unwrap_or_else(|| Rc::new(event::on_event));
+    let mut merger = Merger::new(on_event);
+    let mut map = Map::default();
+    let mut list = List::default();
+    for (name, uxo) in uxos {
+        let value = merger.add(name, uxo)?;
+        match merge_as {
+            MergeAs::Map => {
+                if map.get(&Key::Str(name.to_string())).is_some() {
+                    (merger.on_event)(&Event::new_warning(
+                        831,
+                        "duplicate name replaces earlier value",
+                        name,
+                        0,
+                    ));
+                }
+                map.insert(Key::Str(name.to_string()), value);
+            }
+            MergeAs::List => list.push(value),
+        }
+    }
+    let mut uxo = Uxf::new("", &merger.comments.join("\n"));
+    uxo.set_value(match merge_as {
+        MergeAs::Map => Value::from(map),
+        MergeAs::List => Value::from(list),
+    })?;
+    uxo.tclass_for_ttype = merger.tclass_for_ttype;
+    uxo.import_for_ttype = merger.import_for_ttype;
+    uxo.set_pseudo_vtypes(merger.pseudo_vtypes);
+    Ok(uxo)
+}
+
+/// Parses each of the given files (using the given `options`) and returns
+/// them merged (see `merge()`) using their filenames as their names.
+pub fn merge_files(
+    filenames: &[&str],
+    merge_as: MergeAs,
+    options: ParserOptions,
+    on_event: Option<OnEventFn>,
+) -> Result<Uxf> {
+    let mut uxos = vec![];
+    for filename in filenames {
+        uxos.push((
+            *filename,
+            parse_options(filename, options, on_event.clone())?,
+        ));
+    }
+    merge(uxos, merge_as, on_event)
+}
+
+/// Parses the given include file (see the module documentation) and
+/// returns the files it lists merged as a list (see `merge()`).
+pub fn include(
+    filename: &str,
+    options: ParserOptions,
+    on_event: Option<OnEventFn>,
+) -> Result<Uxf> {
+    let uxo = parse_options(filename, options, on_event.clone())?;
+    let table = match uxo.value().as_table() {
+        Some(table)
+            if table.ttype() == INCLUDE_TTYPE && table.ttype_len() == 1 =>
+        {
+            table
+        }
+        _ => bail!(
+            "E832:{filename}:0:an include file's value must be a table \
+            of ttype {INCLUDE_TTYPE} with one field"
+        ),
+    };
+    let path = dirname(&full_filename(filename, "."));
+    let mut filenames = vec![];
+    for record in table.iter() {
+        match record[0].as_str() {
+            Some(name) => filenames.push(full_filename(name, &path)),
+            None => bail!(
+                "E833:{filename}:0:expected an include filename str, \
+                got {}",
+                record[0]
+            ),
+        }
+    }
+    let filenames: Vec<&str> =
+        filenames.iter().map(|f| f.as_str()).collect();
+    merge_files(&filenames, MergeAs::List, options, on_event)
+}
+
+struct Merger {
+    on_event: OnEventFn,
+    comments: Vec<String>,
+    tclass_for_ttype: HashMap<String, TClass>,
+    import_for_ttype: IndexMap<String, String>,
+    pseudo_vtypes: bool,
+}
+
+impl Merger {
+    fn new(on_event: OnEventFn) -> Self {
+        Merger {
+            on_event,
+            comments: vec![],
+            tclass_for_ttype: HashMap::new(),
+            import_for_ttype: IndexMap::new(),
+            pseudo_vtypes: false,
+        }
+    }
+
+    // Adds the uxo's comment, TClasses, and imports, renaming any of its
+    // ttypes that conflict with those already added, and returns its
+    // (possibly retyped) value.
+    fn add(&mut self, name: &str, uxo: Uxf) -> Result<Value> {
+        if !uxo.comment().is_empty()
+            && !self.comments.iter().any(|c| c == uxo.comment())
+        {
+            self.comments.push(uxo.comment().to_string());
+        }
+        self.pseudo_vtypes |= uxo.pseudo_vtypes();
+        let renames = self.renames(name, &uxo)?;
+        let mut retyped = HashMap::new();
+        for tclass in uxo.tclass_for_ttype.values() {
+            let new_tclass = retype_tclass(tclass, &renames)?;
+            if &new_tclass != tclass || new_tclass.ttype() != tclass.ttype()
+            {
+                retyped.insert(tclass.ttype().to_string(), new_tclass);
+            }
+        }
+        let mut ttypes: Vec<&String> =
+            uxo.tclass_for_ttype.keys().collect();
+        ttypes.sort_unstable();
+        for ttype in ttypes {
+            let tclass = retyped
+                .get(ttype)
+                .unwrap_or(&uxo.tclass_for_ttype[ttype])
+                .clone();
+            let new_ttype = tclass.ttype().to_string();
+            if self.tclass_for_ttype.contains_key(&new_ttype) {
+                continue; // identical to an existing definition
+            }
+            if !renames.contains_key(ttype) {
+                if let Some(import) = uxo.import_for_ttype.get(ttype) {
+                    if is_portable_import(import) {
+                        self.import_for_ttype
+                            .insert(new_ttype.clone(), import.to_string());
+                    }
+                }
+            }
+            self.tclass_for_ttype.insert(new_ttype, tclass);
+        }
+        let mut value = uxo.value().clone();
+        if !renames.is_empty() {
+            retype_value(&mut value, &retyped, &renames);
+        }
+        Ok(value)
+    }
+
+    // Returns the renames (old ttype to new ttype) needed for the uxo's
+    // ttypes that conflict with existing ones. A ttype whose fields refer
+    // to a renamed ttype may itself conflict, so repeats until stable.
+    fn renames(
+        &self,
+        name: &str,
+        uxo: &Uxf,
+    ) -> Result<HashMap<String, String>> {
+        let mut ttypes: Vec<&String> =
+            uxo.tclass_for_ttype.keys().collect();
+        ttypes.sort_unstable();
+        let mut renames = HashMap::new();
+        let mut used: HashSet<String> = self
+            .tclass_for_ttype
+            .keys()
+            .chain(uxo.tclass_for_ttype.keys())
+            .cloned()
+            .collect();
+        loop {
+            let mut changed = false;
+            for ttype in &ttypes {
+                if renames.contains_key(*ttype) {
+                    continue;
+                }
+                if let Some(existing) = self.tclass_for_ttype.get(*ttype) {
+                    let tclass = retype_tclass(
+                        &uxo.tclass_for_ttype[*ttype],
+                        &renames,
+                    )?;
+                    if existing != &tclass {
+                        let new_ttype = unique_ttype(ttype, &used);
+                        (self.on_event)(&Event::new_repair(
+                            830,
+                            &format!(
+                                "renamed ttype {ttype} to {new_ttype} \
+                                since it conflicts with an earlier \
+                                definition"
+                            ),
+                            name,
+                            0,
+                        ));
+                        used.insert(new_ttype.clone());
+                        renames.insert(ttype.to_string(), new_ttype);
+                        changed = true;
+                    }
+                }
+            }
+            if !changed {
+                break;
+            }
+        }
+        Ok(renames)
+    }
+}
+
+// Returns true if the import doesn't depend on the importing file's
+// folder, i.e., it is a system import, a URL, or an absolute filename.
+fn is_portable_import(import: &str) -> bool {
+    import_kind(import) != ImportKind::File
+        || Path::new(import).is_absolute()
+}
+
+fn unique_ttype(ttype: &str, used: &HashSet<String>) -> String {
+    let mut n = 2;
+    loop {
+        let new_ttype = format!("{ttype}{n}");
+        if !used.contains(&new_ttype) {
+            return new_ttype;
+        }
+        n += 1;
+    }
+}
+
+// Returns the tclass with its ttype, field vtypes, and enum members
+// renamed as per renames.
+fn retype_tclass(
+    tclass: &TClass,
+    renames: &HashMap<String, String>,
+) -> Result<TClass> {
+    let rename = |ttype: &str| -> String {
+        renames
+            .get(ttype)
+            .map_or_else(|| ttype.to_string(), |new| new.to_string())
+    };
+    let ttype = rename(tclass.ttype());
+    let mut new_tclass = if tclass.is_enum() {
+        let members: Vec<String> =
+            tclass.members().iter().map(|m| rename(m)).collect();
+        let members: Vec<&str> =
+            members.iter().map(|m| m.as_str()).collect();
+        TClass::new_enum(&ttype, &members, tclass.comment())?
+    } else if tclass.is_fieldless() {
+        TClass::new_fieldless(&ttype, tclass.comment())?
+    } else {
+        let mut fields = vec![];
+        for field in tclass.fields() {
+            fields.push(Field::new(
+                field.name(),
+                &rename(field.vtype().unwrap_or_default()),
+            )?);
+        }
+        TClass::new(&ttype, fields, tclass.comment())?
+    };
+    new_tclass.set_key(&tclass.key())?;
+    Ok(new_tclass)
+}
+
+// Replaces the TClasses of tables whose TClass has been retyped and
+// renames the vtypes of lists and maps, recursively.
+fn retype_value(
+    value: &mut Value,
+    retyped: &HashMap<String, TClass>,
+    renames: &HashMap<String, String>,
+) {
+    match value {
+        Value::List(lst) => {
+            if let Some(vtype) = renames.get(lst.vtype()) {
+                lst.set_vtype(vtype);
+            }
+            for value in lst.iter_mut() {
+                retype_value(value, retyped, renames);
+            }
+        }
+        Value::Map(m) => {
+            if let Some(vtype) = renames.get(m.vtype()) {
+                m.set_vtype(vtype);
+            }
+            for value in m.inner_mut().values_mut() {
+                retype_value(value, retyped, renames);
+            }
+        }
+        Value::Table(t) => {
+            if let Some(tclass) = retyped.get(t.ttype()) {
+                t.set_tclass(tclass.clone());
+            }
+            for record in t.iter_mut() {
+                for value in record.iter_mut() {
+                    retype_value(value, retyped, renames);
+                }
+            }
+        }
+        _ => (),
+    }
+}
@@ -8,6 +8,7 @@ use crate::event::{Event, OnEventFn};
 use crate::token::debug_tokens;
 */
 use crate::parser::token::{Token, TokenKind, Tokens};
+use crate::tclass::ENUM_INTRODUCER;
 use crate::util::{
     dirname, full_filename, hex_as_bytes, str_for_chars, unescape,
 };
@@ -20,8 +21,10 @@ pub struct Lexer<'a> {
     pub text: &'a Vec<char>,
     pub filename: &'a str,
     pub custom: String,
+    pub enums: bool, // accept enum definitions, e.g., |State A B
     on_event: OnEventFn,
     pos: usize,
+    start: usize, // pos of the start of the current token
     lino: usize,
     in_tclass: bool,
     concatenate: bool,
@@ -39,8 +42,10 @@ impl<'a> Lexer<'a> {
             text,
             filename,
             custom: String::new(),
+            enums: false,
             on_event: Rc::clone(&on_event),
             pos: 0,
+            start: 0,
             lino: 0,
             in_tclass: false,
             concatenate: false,
@@ -131,6 +136,7 @@ impl<'a> Lexer<'a> {
     }
 
     fn scan_next(&mut self) -> Result<()> {
+        self.start = self.pos;
         let c = self.getch();
         if c.is_ascii_whitespace() {
             // ignore insignificant whitespace
@@ -144,6 +150,7 @@ impl<'a> Lexer<'a> {
                 ')' => self.add_token(TokenKind::TableEnd, Value::Null),
                 '[' => self.handle_list_begin(),
                 '=' => self.handle_tclass_begin(),
+                ENUM_INTRODUCER if self.enums => self.handle_enum_begin(),
                 ']' => self.add_token(TokenKind::ListEnd, Value::Null),
                 '{' => self.handle_map_begin(),
                 '}' => self.handle_map_end(),
@@ -197,6 +204,13 @@ impl<'a> Lexer<'a> {
         Ok(())
     }
 
+    fn handle_enum_begin(&mut self) -> Result<()> {
+        self.check_in_tclass()?;
+        self.add_token(TokenKind::EnumBegin, Value::Null)?;
+        self.in_tclass = true;
+        Ok(())
+    }
+
     fn handle_map_begin(&mut self) -> Result<()> {
         self.check_in_tclass()?;
         self.has_collection = true;
@@ -251,6 +265,7 @@ impl<'a> Lexer<'a> {
                     | TokenKind::MapBegin
                     | TokenKind::TableBegin
                     | TokenKind::TClassBegin
+                    | TokenKind::EnumBegin
             ) && c == '<'
             {
                 self.pos += 1; // skip the leading <
@@ -284,12 +299,14 @@ impl<'a> Lexer<'a> {
             if matches!(top.kind, TokenKind::Str | TokenKind::FileComment) {
                 let old = top.value.as_str().unwrap(); // should be safe
                 top.value = Value::Str(old.to_owned() + &text);
+                top.end = self.pos;
             } else if matches!(
                 top.kind,
                 TokenKind::ListBegin
                     | TokenKind::MapBegin
                     | TokenKind::TableBegin
                     | TokenKind::TClassBegin
+                    | TokenKind::EnumBegin
             ) {
                 top.comment += &text;
             } else {
@@ -462,8 +479,10 @@ impl<'a> Lexer<'a> {
         if self.in_tclass {
             // safe because if in TClass there must have been a prev token
             let top = self.tokens.back_mut().unwrap();
-            if top.kind == TokenKind::TClassBegin
-                && top.value == Value::Null
+            if matches!(
+                top.kind,
+                TokenKind::TClassBegin | TokenKind::EnumBegin
+            ) && top.value == Value::Null
             {
                 top.value = identifier;
                 Ok(())
@@ -555,7 +574,11 @@ impl<'a> Lexer<'a> {
         for target in targets {
             let end = start + target.len();
             let chars: Vec<char> = target.chars().collect();
-            if end < self.text.len() && self.text[start..end] == chars {
+            if end < self.text.len()
+                && self.text[start..end] == chars
+                && !(self.text[end] == '_'
+                    || self.text[end].is_alphanumeric())
+            {
                 self.pos = end; // skip past target
                 return Some(target);
             }
@@ -585,7 +608,10 @@ impl<'a> Lexer<'a> {
         {
             return Ok(());
         }
-        self.tokens.push_back(Token::new(kind, value, self.lino));
+        let mut token = Token::new(kind, value, self.lino);
+        token.start = self.start;
+        token.end = self.pos;
+        self.tokens.push_back(token);
         Ok(())
     }
 
@@ -6,4 +6,65 @@ mod lexer;
 mod parse;
 mod token;
 
+pub(crate) use crate::parser::parse::find_import;
 pub(crate) use crate::parser::parse::parse;
+pub(crate) use crate::parser::token::{TokenKind, Tokens};
+
+use crate::event::ignore_event;
+use anyhow::Result;
+use std::rc::Rc;
+
+/// The kinds of import: a URL (`http://` or `https://`), a system import
+/// (which has no '.', e.g., `complex`), or a filename.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum ImportKind {
+    Url,
+    System,
+    File,
+}
+
+/// Returns the kind of the given import text. (Used by the parser,
+/// merge, watch, and lsp.)
+pub(crate) fn import_kind(import: &str) -> ImportKind {
+    if import.starts_with("http://") || import.starts_with("https://") {
+        ImportKind::Url
+    } else if !import.contains('.') {
+        ImportKind::System
+    } else {
+        ImportKind::File
+    }
+}
+
+/// Returns the tokens for the given UXF text (ignoring any events), each
+/// with the char offsets of its text. (Used by cst and index.)
+pub(crate) fn tokenize(
+    text: &Vec<char>,
+    filename: &str,
+    enums: bool,
+) -> Result<Tokens> {
+    let mut lexer =
+        lexer::Lexer::new(text, filename, Rc::new(ignore_event));
+    lexer.enums = enums;
+    let (_, tokens) = lexer.tokenize()?;
+    Ok(tokens)
+}
+
+/// Returns the import texts (e.g., filenames, URLs, or system imports) of
+/// the given UXF text (ignoring any events) in the order they appear.
+/// (Used by watch.)
+#[cfg(feature = "watch")]
+pub(crate) fn imports(
+    text: &Vec<char>,
+    filename: &str,
+    enums: bool,
+) -> Result<Vec<String>> {
+    let mut lexer =
+        lexer::Lexer::new(text, filename, Rc::new(ignore_event));
+    lexer.enums = enums;
+    let (_, tokens) = lexer.tokenize()?;
+    Ok(tokens
+        .iter()
+        .take_while(|token| token.kind == TokenKind::Import)
+        .filter_map(|token| token.value.as_str().map(|s| s.to_string()))
+        .collect())
+}
@@ -1,15 +1,19 @@
 // Copyright © 2022 Mark Summerfield. All rights reserved.
 // License: GPLv3
 
+use crate::check::is_of_vtype;
 use crate::consts::*;
 use crate::event::{Event, OnEventFn};
 use crate::field::make_fields_x;
 use crate::list::List;
 use crate::map::Map;
 use crate::parser::{
+    import_kind,
     lexer::Lexer,
     token::{Token, TokenKind, Tokens},
+    ImportKind,
 };
+use crate::repair::{apply_repair, RepairAction, RepairPolicy};
 use crate::table::Table;
 use crate::tclass::{TClass, TClassBuilder};
 use crate::util::{dirname, full_filename, read_file};
@@ -28,21 +32,25 @@ pub(crate) fn parse(
     text: &str,
     filename: &str,
     options: ParserOptions,
+    repair_policy: &RepairPolicy,
     on_event: OnEventFn,
 ) -> Result<Uxf> {
     let data: Vec<char> = text.chars().collect();
     let mut lexer = Lexer::new(&data, filename, Rc::clone(&on_event));
+    lexer.enums = options.contains(ParserOptions::ENUMS);
     let (custom, mut tokens) = lexer.tokenize()?;
     let mut uxo = Uxf::default();
     if !custom.is_empty() {
         uxo.set_custom(&custom);
     }
+    uxo.set_pseudo_vtypes(options.contains(ParserOptions::PSEUDO_VTYPES));
     if !tokens.is_empty() {
         let mut parser = Parser::new(
             filename,
             Rc::clone(&on_event),
             &mut uxo,
             options,
+            repair_policy.clone(),
             &mut tokens,
             None, // not an import and no imports carried over
         )?;
@@ -56,6 +64,7 @@ fn parse_import(
     filename: &str,
     on_event: OnEventFn,
     imported: HashSet<String>,
+    options: ParserOptions, // only language extensions are passed
 ) -> Result<Uxf> {
     let data: Vec<char> = if text.is_empty() {
         read_file(filename)?.chars().collect()
@@ -63,6 +72,7 @@ fn parse_import(
         text.chars().collect()
     };
     let mut lexer = Lexer::new(&data, filename, Rc::clone(&on_event));
+    lexer.enums = options.contains(ParserOptions::ENUMS);
     let (_, mut tokens) = lexer.tokenize()?; // ignore comment
     let mut uxo = Uxf::default();
     if !tokens.is_empty() {
@@ -70,7 +80,8 @@ fn parse_import(
             filename,
             Rc::clone(&on_event),
             &mut uxo,
-            ParserOptions::DEFAULT, // ignore options
+            options,
+            RepairPolicy::default(), // imports only provide ttypes
             &mut tokens,
             Some(imported),
         )?;
@@ -82,6 +93,7 @@ fn parse_import(
 pub struct Parser<'a> {
     filename: &'a str,
     options: ParserOptions,
+    repair_policy: RepairPolicy,
     on_event: OnEventFn,
     uxo: &'a mut Uxf,
     tokens: &'a mut Tokens,
@@ -100,6 +112,7 @@ impl<'a> Parser<'a> {
         on_event: OnEventFn,
         uxo: &'a mut Uxf,
         options: ParserOptions,
+        repair_policy: RepairPolicy,
         tokens: &'a mut Tokens,
         // None for not an import; empty for an import that has no ttypes
         imported: Option<HashSet<String>>,
@@ -121,6 +134,7 @@ impl<'a> Parser<'a> {
             on_event: Rc::clone(&on_event),
             uxo,
             options,
+            repair_policy,
             tokens,
             is_import,
             imported,
@@ -206,18 +220,16 @@ impl<'a> Parser<'a> {
     }
 
     fn handle_import(&mut self, value: &str) -> Result<()> {
-        let (filename, text) = if value.starts_with("http://")
-            || value.starts_with("https://")
-        {
-            let (text, already_imported) = self.url_import(value)?;
-            if already_imported {
-                return Ok(()); // don't reimport & errors already handled
+        let (filename, text) = match import_kind(value) {
+            ImportKind::Url => {
+                let (text, already_imported) = self.url_import(value)?;
+                if already_imported {
+                    return Ok(()); // don't reimport; errors handled
+                }
+                ("".to_string(), text)
             }
-            ("".to_string(), text)
-        } else if !value.contains('.') {
-            return self.system_import(value);
-        } else {
-            (value.to_string(), "".to_string())
+            ImportKind::System => return self.system_import(value),
+            ImportKind::File => (value.to_string(), "".to_string()),
         };
         let (uxo, already_imported) = if !filename.is_empty() {
             self.load_import(&filename)?
@@ -282,6 +294,8 @@ impl<'a> Parser<'a> {
             filename,
             self.on_event.clone(),
             self.imported.clone(),
+            self.options
+                & (ParserOptions::ENUMS | ParserOptions::PSEUDO_VTYPES),
         ) {
             Ok(uxo) => Ok((Some(uxo), false)),
             Err(err) => bail!(self.error(
@@ -376,19 +390,31 @@ impl<'a> Parser<'a> {
         for (index, token) in self.tokens.iter().enumerate() {
             self.lino = token.lino;
             match token.kind {
-                TokenKind::TClassBegin => {
+                TokenKind::TClassBegin | TokenKind::EnumBegin => {
                     self.handle_tclass_begin(&mut tclass_builder, token)?;
+                    tclass_builder.is_enum =
+                        token.kind == TokenKind::EnumBegin;
                     lino = self.lino;
                 }
                 TokenKind::Field => {
                     self.handle_tclass_field(&mut tclass_builder, token)?;
                 }
                 TokenKind::TClassEnd => {
-                    let tclass = if tclass_builder.is_valid() {
+                    let mut tclass = if tclass_builder.is_valid() {
                         tclass_builder.build()?
                     } else {
                         bail!(self.error(526, "TClass without ttype"));
                     };
+                    self.handle_key_declaration(&mut tclass)?;
+                    for member in tclass.member_tclasses() {
+                        add_to_tclasses(
+                            &mut self.tclass_for_ttype,
+                            member,
+                            self.filename,
+                            self.lino,
+                            528,
+                        )?;
+                    }
                     add_to_tclasses(
                         &mut self.tclass_for_ttype,
                         tclass,
@@ -445,7 +471,22 @@ impl<'a> Parser<'a> {
     ) -> Result<()> {
         if tclass_builder.is_valid() {
             if let Some(s) = token.value.as_str() {
-                tclass_builder.append_field(s, &token.vtype)
+                if tclass_builder.is_enum {
+                    if !token.vtype.is_empty() {
+                        bail!(self.error(
+                            521,
+                            &format!(
+                                "enum members can't have vtypes, got \
+                                {s}:{}",
+                                token.vtype
+                            )
+                        ))
+                    }
+                    tclass_builder.append_member(s);
+                    Ok(())
+                } else {
+                    tclass_builder.append_field(s, &token.vtype)
+                }
             } else {
                 bail!(self.error(522, "invalid or missing field name"))
             }
@@ -509,6 +550,7 @@ impl<'a> Parser<'a> {
             || expected_type == typename
             || expected_type == token.vtype /* can't be ktype */
             || expected_type == token.value.as_str().unwrap_or("")
+            || self.is_enum_member(expected_type, &token.vtype)
         {
             Ok(())
         } else {
@@ -575,9 +617,31 @@ impl<'a> Parser<'a> {
     ) -> Result<Value> {
         if let Some(tclass) = self.tclass_for_ttype.get(&token.vtype) {
             let ttype = tclass.ttype();
+            if tclass.is_enum() {
+                bail!(self.error(
+                    452,
+                    &format!(
+                        "can't create a table of enum ttype {ttype}; \
+                        use one of its members: {}",
+                        tclass.members().join(" ")
+                    )
+                ))
+            }
             self.used_tclasses.insert(ttype.to_string());
             self.verify_ttype_identifier(ttype, expected_type)?;
-            Ok(Value::from(Table::new(tclass.clone(), &token.comment)))
+            let mut table = Table::new(tclass.clone(), &token.comment);
+            if self.options.contains(ParserOptions::TABLE_KEYS) {
+                let key = tclass.key();
+                if !key.is_empty() {
+                    table.set_key_x(
+                        &key,
+                        false,
+                        self.filename,
+                        self.lino,
+                    )?;
+                }
+            }
+            Ok(Value::from(table))
         } else {
             let next_value = if let Some(next_value) = next_value {
                 next_value.to_string()
@@ -615,23 +679,26 @@ impl<'a> Parser<'a> {
         }
         let mut value = token.value.clone();
         let message = self.verify_type(&value, expected_type);
+        if !message.is_empty() && self.is_enum(expected_type) {
+            bail!(self.error(500, &message));
+        }
         if value != Value::Null
             && !message.is_empty()
-            && ["bool", "int", "real", "date", "datetime"]
+            && (["bool", "int", "real", "date", "datetime"]
                 .contains(&expected_type)
+                || (expected_type == VTYPE_NAME_NUMBER
+                    && self.is_builtin_vtype(expected_type)))
         {
             let new_value = value.naturalize();
-            if new_value != value {
-                (self.on_event)(&Event::new_repair(
-                    486,
-                    &format!(
-                        "converted {} {value} to {} {new_value}",
-                        value.typename(),
-                        new_value.typename(),
-                    ),
-                    self.filename,
-                    self.lino,
-                ));
+            if new_value != value
+                && (expected_type != VTYPE_NAME_NUMBER
+                    || is_of_vtype(&new_value, expected_type))
+            {
+                self.repair(
+                    self.repair_policy.str_to_scalar,
+                    &value,
+                    &new_value,
+                )?;
                 value = new_value;
             } else {
                 bail!(self.error(488, &message));
@@ -652,28 +719,39 @@ impl<'a> Parser<'a> {
         let mut value = token.value.clone();
         let message = self.verify_type(&value, expected_type);
         if value != Value::Null && !message.is_empty() {
-            let new_value = if expected_type == "real" && value.is_int() {
-                Value::Real(value.as_int().unwrap() as f64) // safe
-            } else if expected_type == "int" && value.is_real() {
-                Value::Int(value.as_real().unwrap().round() as i64) // safe
-            } else {
-                bail!(self.error(500, &message));
-            };
-            (self.on_event)(&Event::new_repair(
-                486,
-                &format!(
-                    "converted {} {value} to {} {new_value}",
-                    value.typename(),
-                    new_value.typename(),
-                ),
-                self.filename,
-                self.lino,
-            ));
+            let policy = &self.repair_policy;
+            let (new_value, action) =
+                if expected_type == "real" && value.is_int() {
+                    let i = value.as_int().unwrap(); // safe
+                    (Value::Real(i as f64), policy.int_to_real)
+                } else if expected_type == "int" && value.is_real() {
+                    let r = value.as_real().unwrap(); // safe
+                    (Value::Int(r.round() as i64), policy.real_to_int)
+                } else {
+                    bail!(self.error(500, &message));
+                };
+            self.repair(action, &value, &new_value)?;
             value = new_value;
         }
         Ok(Some(value))
     }
 
+    fn repair(
+        &self,
+        action: RepairAction,
+        value: &Value,
+        new_value: &Value,
+    ) -> Result<()> {
+        apply_repair(
+            action,
+            value,
+            new_value,
+            self.filename,
+            self.lino,
+            &self.on_event,
+        )
+    }
+
     fn handle_invalid_identifier(&self, token: &Token) -> String {
         // All valid identifiers have already been handled
         if let Some(s) = token.value.as_str() {
@@ -698,7 +776,7 @@ impl<'a> Parser<'a> {
         what: &str,
     ) -> Result<()> {
         if !vtype.is_empty() {
-            if VTYPES.contains(&vtype) {
+            if self.is_builtin_vtype(vtype) {
                 return Ok(()); // built-in type
             }
             if let Some(tclass) = self.tclass_for_ttype.get(vtype) {
@@ -721,6 +799,7 @@ impl<'a> Parser<'a> {
         if !expected_type.is_empty()
             && expected_type != "table"
             && expected_type != ttype
+            && !self.is_enum_member(expected_type, ttype)
         {
             bail!(self.error(
                 456,
@@ -733,22 +812,71 @@ impl<'a> Parser<'a> {
         Ok(())
     }
 
+    // Pseudo-vtypes are only built-in if enabled and not shadowed by a
+    // ttype of the same name (e.g., in a file that predates them).
+    fn is_builtin_vtype(&self, vtype: &str) -> bool {
+        VTYPES.contains(&vtype)
+            || (self.options.contains(ParserOptions::PSEUDO_VTYPES)
+                && PSEUDO_VTYPES.contains(&vtype)
+                && !self.tclass_for_ttype.contains_key(vtype))
+    }
+
     // uxf.py: typecheck()
     fn verify_type(&self, value: &Value, expected_type: &str) -> String {
         if value != &Value::Null && !expected_type.is_empty() {
-            if VTYPES.contains(&expected_type) {
-                if value.typename() != expected_type {
+            if self.is_builtin_vtype(expected_type) {
+                if !is_of_vtype(value, expected_type) {
                     return format!(
                         "expected {expected_type}, got {value}"
                     );
                 }
-            } else if !self.tclass_for_ttype.contains_key(expected_type) {
+            } else if let Some(tclass) =
+                self.tclass_for_ttype.get(expected_type)
+            {
+                if tclass.is_enum() {
+                    return format!(
+                        "expected {expected_type} (one of: {}), got {value}",
+                        tclass.members().join(" ")
+                    );
+                }
+            } else {
                 return format!("expected {expected_type}, got {value}",);
             }
         }
         "".to_string()
     }
 
+    fn is_enum(&self, ttype: &str) -> bool {
+        self.tclass_for_ttype
+            .get(ttype)
+            .is_some_and(|tclass| tclass.is_enum())
+    }
+
+    fn is_enum_member(&self, ttype: &str, member: &str) -> bool {
+        self.tclass_for_ttype
+            .get(ttype)
+            .is_some_and(|tclass| tclass.has_member(member))
+    }
+
+    // An enum is used if any of its members is used, and if an enum is
+    // used then so are all its members.
+    fn update_used_enums(&mut self) {
+        for tclass in self.tclass_for_ttype.values() {
+            if tclass.is_enum()
+                && (self.used_tclasses.contains(tclass.ttype())
+                    || tclass
+                        .members()
+                        .iter()
+                        .any(|m| self.used_tclasses.contains(*m)))
+            {
+                self.used_tclasses.insert(tclass.ttype().to_string());
+                for member in tclass.members() {
+                    self.used_tclasses.insert(member.to_string());
+                }
+            }
+        }
+    }
+
     fn update_uxo(&mut self) {
         std::mem::swap(
             &mut self.uxo.tclass_for_ttype,
@@ -760,7 +888,21 @@ impl<'a> Parser<'a> {
         );
     }
 
+    // A key declaration that names a field the TClass doesn't have is
+    // just part of the comment unless tables are being keyed.
+    fn handle_key_declaration(&self, tclass: &mut TClass) -> Result<()> {
+        match tclass.take_key_declaration() {
+            Err(err)
+                if self.options.contains(ParserOptions::TABLE_KEYS) =>
+            {
+                Err(err)
+            }
+            _ => Ok(()),
+        }
+    }
+
     fn cleanup_tclasses(&mut self) -> Result<()> {
+        self.update_used_enums();
         let mut imported: HashSet<String> =
             self.import_for_ttype.keys().cloned().collect();
         // replace imports
@@ -910,30 +1052,8 @@ impl<'a> Parser<'a> {
         ttypes_for_filename
     }
 
-    // Searches in order: filename's path, cwd, UXF_PATH
     fn find_import(&self, filename: &str) -> (String, bool) {
-        let mut paths = vec![];
-        if !self.filename.is_empty() && self.filename != "-" {
-            paths.push(dirname(self.filename));
-        }
-        if !paths.is_empty() && paths[0] != "." {
-            paths.push(".".to_string());
-        }
-        if let Ok(uxf_paths) = env::var("UXF_PATH") {
-            for path in env::split_paths(&uxf_paths) {
-                paths.push(path.to_string_lossy().to_string());
-            }
-        }
-        for path in &paths {
-            let fullname = full_filename(filename, path);
-            if self.imported.contains(&fullname) {
-                return (fullname, true); // already imported
-            }
-            if Path::new(&fullname).is_file() {
-                return (fullname, false); // stop as soon as we find one
-            }
-        }
-        (full_filename(filename, "."), false)
+        find_import(filename, self.filename, &self.imported)
     }
 
     fn error(&self, code: u16, message: &str) -> String {
@@ -983,6 +1103,9 @@ fn add_to_tclasses(
         {
             first_tclass.set_comment(tclass.comment()); // last one wins
         }
+        if !tclass.key().is_empty() {
+            first_tclass.set_key(&tclass.key())?; // last one wins
+        }
         return Ok(true); // harmless duplicate
     }
     bail!(
@@ -993,3 +1116,34 @@ fn add_to_tclasses(
         tclass.ttype()
     )
 }
+
+// Searches in order: importer's path, cwd, UXF_PATH; returns the full
+// filename and whether it has already been imported.
+pub(crate) fn find_import(
+    filename: &str,
+    importer: &str,
+    imported: &HashSet<String>,
+) -> (String, bool) {
+    let mut paths = vec![];
+    if !importer.is_empty() && importer != "-" {
+        paths.push(dirname(importer));
+    }
+    if !paths.is_empty() && paths[0] != "." {
+        paths.push(".".to_string());
+    }
+    if let Ok(uxf_paths) = env::var("UXF_PATH") {
+        for path in env::split_paths(&uxf_paths) {
+            paths.push(path.to_string_lossy().to_string());
+        }
+    }
+    for path in &paths {
+        let fullname = full_filename(filename, path);
+        if imported.contains(&fullname) {
+            return (fullname, true); // already imported
+        }
+        if Path::new(&fullname).is_file() {
+            return (fullname, false); // stop as soon as we find one
+        }
+    }
+    (full_filename(filename, "."), false)
+}
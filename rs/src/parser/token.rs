@@ -12,6 +12,8 @@ pub struct Token {
     pub kind: TokenKind,
     pub value: Value,
     pub lino: usize,
+    pub start: usize, // char offsets of the token's text (used by cst)
+    pub end: usize,
     pub comment: String,
     pub ktype: String,
     pub vtype: String,
@@ -23,6 +25,8 @@ impl Token {
             kind,
             value, // may store vtype or ttype
             lino,
+            start: 0,
+            end: 0,
             comment: "".to_string(),
             ktype: "".to_string(),
             vtype: "".to_string(), // stores vtype _or_ ttype
@@ -62,6 +66,7 @@ pub enum TokenKind {
     FileComment,
     Import,
     TClassBegin,
+    EnumBegin, // ends with TClassEnd
     TClassEnd,
     Field,
     TableBegin,
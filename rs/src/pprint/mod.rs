@@ -3,11 +3,15 @@
 
 /*! The UXF pretty printer; all this is private. (See the Uxf::to_text()
 and Uxf::to_text_options() methods both of which use this module
-internally.)
+internally, and the render module which uses its spans.)
 */
 mod to_text;
 mod token;
 mod tokenizer;
 mod writer;
 
-pub(crate) use crate::pprint::to_text::to_text;
+pub(crate) use crate::pprint::to_text::{
+    preamble_to_spans, to_spans, to_text,
+};
+pub(crate) use crate::pprint::token::{Span, Style};
+pub(crate) use crate::pprint::tokenizer::real_text;
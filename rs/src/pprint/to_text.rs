@@ -3,6 +3,7 @@
 
 use crate::consts::UXF_VERSION;
 use crate::format::Format;
+use crate::pprint::token::Spans;
 use crate::pprint::{tokenizer::tokenize, writer};
 use crate::uxf::Uxf;
 
@@ -17,6 +18,40 @@ pub(crate) fn to_text(uxo: &Uxf, format: &Format) -> String {
     writer::to_text(&header, tokens, format)
 }
 
+// Returns the same text as to_text() and the spans of its styled parts
+pub(crate) fn to_spans(uxo: &Uxf, format: &Format) -> (String, Spans) {
+    let header = header(uxo.custom());
+    let tokens = tokenize(
+        uxo,
+        format,
+        uxo.tclass_for_ttype.clone(),
+        uxo.import_for_ttype.clone(),
+    );
+    writer::to_spans(&header, tokens, format)
+}
+
+// Returns the text and spans of everything that precedes the value, i.e.,
+// the header, comment, imports, and ttype definitions
+pub(crate) fn preamble_to_spans(
+    uxo: &Uxf,
+    format: &Format,
+) -> (String, Spans) {
+    let empty = Uxf::new(uxo.custom(), uxo.comment()); // value is []
+    let header = header(uxo.custom());
+    let tokens = tokenize(
+        &empty,
+        format,
+        uxo.tclass_for_ttype.clone(),
+        uxo.import_for_ttype.clone(),
+    );
+    let (mut text, mut spans) = writer::to_spans(&header, tokens, format);
+    if let Some(i) = text.rfind("[]") {
+        text.truncate(i);
+    }
+    spans.retain(|span| span.end <= text.len());
+    (text, spans)
+}
+
 fn header(custom: &str) -> String {
     let mut text = format!("uxf {UXF_VERSION}");
     if !custom.is_empty() {
@@ -4,6 +4,7 @@
 use std::fmt;
 
 pub type Tokens = Vec<Token>;
+pub type Spans = Vec<Span>;
 
 #[derive(Clone)]
 pub struct Token {
@@ -11,6 +12,7 @@ pub struct Token {
     pub text: String,
     pub depth: usize,
     pub num_records: Option<usize>,
+    pub spans: Spans, // byte ranges of the text
 }
 
 impl Token {
@@ -20,7 +22,22 @@ impl Token {
         depth: usize,
         num_records: Option<usize>,
     ) -> Self {
-        Token { kind, text: text.to_string(), depth, num_records }
+        Token {
+            kind,
+            text: text.to_string(),
+            depth,
+            num_records,
+            spans: vec![],
+        }
+    }
+
+    // Appends the given text, and if it has a style, its span
+    pub fn push_str(&mut self, text: &str, style: Option<Style>) {
+        if let (Some(style), false) = (style, text.is_empty()) {
+            let start = self.text.len();
+            self.spans.push(Span::new(start, start + text.len(), style));
+        }
+        self.text.push_str(text);
     }
 
     pub fn is_multiline(&self) -> bool {
@@ -29,7 +46,7 @@ impl Token {
 }
 
 impl fmt::Debug for Token {
-    fn fmt<'a>(&'a self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         let n = if let Some(num_records) = self.num_records {
             format!(" #{num_records} ")
         } else {
@@ -55,7 +72,7 @@ pub enum TokenKind {
 }
 
 impl fmt::Debug for TokenKind {
-    fn fmt<'a>(&'a self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         write!(
             f,
             "{}",
@@ -70,3 +87,34 @@ impl fmt::Debug for TokenKind {
         )
     }
 }
+
+/// The kind of UXF text that a `Span` covers.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Style {
+    Header,   // uxf 1 and any custom text
+    Comment,  // including its #
+    Import,   // including its !
+    TType,    // in a TClass definition or a table
+    Field,    // a field name in a TClass definition
+    VType,    // a field's vtype or a list's vtype or a map's ktype & vtype
+    Key,      // a map key of any ktype
+    Constant, // ? yes no
+    Number,   // int or real
+    Date,     // date or datetime
+    Str,      // including its < and >
+    Bytes,    // including its (: and :)
+}
+
+/// A byte range of UXF text and the kind of text it covers.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Span {
+    pub start: usize,
+    pub end: usize,
+    pub style: Style,
+}
+
+impl Span {
+    pub fn new(start: usize, end: usize, style: Style) -> Self {
+        Span { start, end, style }
+    }
+}
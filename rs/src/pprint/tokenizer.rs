@@ -3,8 +3,8 @@
 
 use crate::consts::*;
 use crate::format::Format;
-use crate::pprint::token::{Token, TokenKind, Tokens};
-use crate::tclass::TClass;
+use crate::pprint::token::{Style, Token, TokenKind, Tokens};
+use crate::tclass::{is_implicit_enum_member, TClass};
 use crate::util::{escape, rindex_of_char, str_for_chars, VecExt};
 use crate::uxf::Uxf;
 use crate::value::{Value, Visit};
@@ -27,13 +27,16 @@ pub(crate) fn tokenize(
         tclass_for_ttype,
         import_for_ttype,
     )));
-    uxo.visit(Rc::new({
-        let tokenizer = Rc::clone(&tokenizer);
-        move |visit: Visit, value: &Value| {
-            let mut tokenizer = tokenizer.borrow_mut();
-            tokenizer.visit(visit, value)
-        }
-    }))
+    uxo.visit_order(
+        Rc::new({
+            let tokenizer = Rc::clone(&tokenizer);
+            move |visit: Visit, value: &Value| {
+                let mut tokenizer = tokenizer.borrow_mut();
+                tokenizer.visit(visit, value)
+            }
+        }),
+        format.map_order,
+    )
     .unwrap(); // Safe since Tokenizer::visit() always returns Ok(())
     let tokens = tokenizer.borrow_mut().get_tokens();
     // debug_tokens(&tokens); // DEBUG
@@ -59,6 +62,7 @@ struct Tokenizer {
     pub list_value_counts: Vec<usize>,
     pub map_item_counts: Vec<usize>,
     pub table_record_counts: Vec<usize>,
+    pub in_key: bool, // the next scalar is a map key
 }
 
 impl Tokenizer {
@@ -78,6 +82,7 @@ impl Tokenizer {
             list_value_counts: vec![],
             map_item_counts: vec![],
             table_record_counts: vec![],
+            in_key: false,
         }
     }
 
@@ -97,7 +102,7 @@ impl Tokenizer {
             Visit::ListValueEnd => self.handle_list_value_end(),
             Visit::MapBegin => self.handle_map_begin(value),
             Visit::MapEnd => self.handle_map_end(),
-            Visit::MapItemBegin => self.begin(),
+            Visit::MapItemBegin => self.handle_item_begin(),
             Visit::MapItemEnd => self.handle_item_end(),
             Visit::TableBegin => self.handle_table_begin(value),
             Visit::TableEnd => self.handle_table_end(),
@@ -111,7 +116,7 @@ impl Tokenizer {
     fn handle_uxf_begin(&mut self, value: &Value) {
         if let Some(comment) = value.as_str() {
             if !comment.is_empty() {
-                self.handle_str(comment, "#", "\n");
+                self.handle_str(comment, "#", "\n", Style::Comment);
             }
         }
         self.handle_imports();
@@ -124,7 +129,8 @@ impl Tokenizer {
             self.import_for_ttype.values().map(|i| i.to_string()).collect();
         for import in imports {
             if !seen.contains(&import) {
-                self.puts(&format!("!{import}\n"));
+                self.puts_style(&format!("!{import}"), Style::Import);
+                self.push_suffix("\n");
                 seen.insert(import);
             }
         }
@@ -134,26 +140,38 @@ impl Tokenizer {
         let mut ttype_tclass_pairs: Vec<(String, TClass)> = self
             .tclass_for_ttype
             .iter()
-            .filter(|pair| !self.import_for_ttype.contains_key(pair.0))
+            .filter(|pair| {
+                !self.import_for_ttype.contains_key(pair.0)
+                    && !is_implicit_enum_member(
+                        pair.1,
+                        &self.tclass_for_ttype,
+                    )
+            })
             .map(|pair| (pair.0.to_lowercase(), pair.1.clone()))
             .collect();
         ttype_tclass_pairs.sort();
         for (_, tclass) in ttype_tclass_pairs {
             self.depth = 0;
-            self.puts("=");
-            if !tclass.comment().is_empty() {
-                self.handle_comment(tclass.comment());
+            self.puts(if tclass.is_enum() { "|" } else { "=" });
+            let comment = tclass.text_comment();
+            if !comment.is_empty() {
+                self.handle_comment(&comment);
                 self.rws();
             }
-            self.puts(tclass.ttype());
+            self.puts_style(tclass.ttype(), Style::TType);
             self.depth = 1; // to indent any wrapped fields
             for field in tclass.fields() {
                 self.rws();
-                self.puts(field.name());
+                self.puts_style(field.name(), Style::Field);
                 if let Some(vtype) = field.vtype() {
-                    self.puts(&format!(":{vtype}"));
+                    self.puts(":");
+                    self.puts_style(vtype, Style::VType);
                 }
             }
+            for member in tclass.members() {
+                self.rws();
+                self.puts_style(member, Style::TType);
+            }
             self.rnl();
         }
         self.depth = 0;
@@ -173,7 +191,7 @@ impl Tokenizer {
             if has_comment {
                 self.rws();
             }
-            self.puts(lst.vtype());
+            self.puts_style(lst.vtype(), Style::VType);
             if lst.len() == 1 {
                 self.rws();
             }
@@ -216,12 +234,11 @@ impl Tokenizer {
             if has_comment {
                 self.rws();
             }
-            let mut text = String::from(m.ktype());
+            self.puts_style(m.ktype(), Style::VType);
             if !m.vtype().is_empty() {
-                text.push(' ');
-                text.push_str(m.vtype());
+                self.puts(" ");
+                self.puts_style(m.vtype(), Style::VType);
             }
-            self.puts(&text);
             if m.len() == 1 {
                 self.rws();
             }
@@ -242,6 +259,11 @@ impl Tokenizer {
         self.map_item_counts.chop();
     }
 
+    fn handle_item_begin(&mut self) {
+        self.begin();
+        self.in_key = true;
+    }
+
     fn handle_item_end(&mut self) {
         self.end();
         // Safe index because we can only get here within a list
@@ -260,7 +282,7 @@ impl Tokenizer {
             self.handle_comment(t.comment());
             self.rws();
         }
-        self.puts_num(t.ttype(), Some(t.len()));
+        self.puts_num(t.ttype(), Some(t.len()), Some(Style::TType));
         match t.len() {
             0 => (),
             1 => self.rws(),
@@ -299,37 +321,51 @@ impl Tokenizer {
     }
 
     fn handle_scalar(&mut self, value: &Value) {
+        let key = if self.in_key {
+            self.in_key = false;
+            Some(Style::Key)
+        } else {
+            None
+        };
         match value {
-            Value::Null => self.puts("?"),
-            Value::Bool(b) => self.puts(if *b { "yes" } else { "no" }),
-            Value::Bytes(b) => self.handle_bytes(b),
-            Value::Date(d) => {
-                self.puts(&d.format(ISO8601_DATE).to_string())
-            }
-            Value::DateTime(dt) => {
-                self.puts(&dt.format(ISO8601_DATETIME).to_string())
+            Value::Null => self.puts_style("?", Style::Constant),
+            Value::Bool(b) => self
+                .puts_style(if *b { "yes" } else { "no" }, Style::Constant),
+            Value::Bytes(b) => {
+                self.handle_bytes(b, key.unwrap_or(Style::Bytes))
             }
-            Value::Int(i) => self.puts(&format!("{i}")),
+            Value::Date(d) => self.puts_style(
+                &d.format(ISO8601_DATE).to_string(),
+                key.unwrap_or(Style::Date),
+            ),
+            Value::DateTime(dt) => self.puts_style(
+                &dt.format(ISO8601_DATETIME).to_string(),
+                key.unwrap_or(Style::Date),
+            ),
+            Value::Int(i) => self
+                .puts_style(&format!("{i}"), key.unwrap_or(Style::Number)),
             Value::Real(r) => self.handle_real(*r),
-            Value::Str(s) => self.handle_str(s, "", ""),
+            Value::Str(s) => {
+                self.handle_str(s, "", "", key.unwrap_or(Style::Str))
+            }
             _ => panic!("expected scalar, got {value:?}"), // impossible
         };
         self.rws();
     }
 
-    fn handle_bytes(&mut self, b: &[u8]) {
+    fn handle_bytes(&mut self, b: &[u8], style: Style) {
         // We can safely slice chars because they're all ASCII
         let mut text =
             b.iter().map(|x| format!("{x:02X}")).collect::<String>();
         if text.len() + 4 > self.wrapwidth {
             let span = self.wrapwidth - self.indent.len();
-            self.puts("(:");
+            self.puts_style("(:", style);
             self.rnl();
             while !text.is_empty() {
                 let chunk =
                     if text.len() < span { &text } else { &text[..span] };
                 if !chunk.is_empty() {
-                    self.put_line(chunk, 1);
+                    self.put_line(chunk, "", 1, style);
                     self.rnl();
                 }
                 if text.len() < span {
@@ -337,26 +373,24 @@ impl Tokenizer {
                 }
                 text.drain(..span);
             }
-            self.puts(":)");
+            self.puts_style(":)", style);
             self.rnl() // newline always follows multiline bytes or str
         } else {
-            self.puts(&format!("(:{text}:)"))
+            self.puts_style(&format!("(:{text}:)"), style)
         };
     }
 
     fn handle_real(&mut self, r: f64) {
-        let mut text = if self.realdp > 0 {
-            format!("{r:.*}", self.realdp as usize)
-        } else {
-            format!("{r}")
-        };
-        if !text.contains(&['.', 'e', 'E']) {
-            text.push_str(".0");
-        }
-        self.puts(&text);
+        self.puts_style(&real_text(r, self.realdp), Style::Number);
     }
 
-    fn handle_str(&mut self, s: &str, prefix: &str, suffix: &str) {
+    fn handle_str(
+        &mut self,
+        s: &str,
+        prefix: &str,
+        suffix: &str,
+        style: Style,
+    ) {
         let text = escape(s);
         let prefix_len = prefix.chars().count();
         let span = self.wrapwidth - prefix_len;
@@ -368,10 +402,11 @@ impl Tokenizer {
             }
         }
         if !too_wide {
-            self.puts(&format!("{prefix}<{text}>{suffix}"));
+            self.puts_style(&format!("{prefix}<{text}>"), style);
+            self.push_suffix(suffix);
         } else {
             // Assumes there is no suffix
-            self.handle_long_str(&text, prefix, prefix_len);
+            self.handle_long_str(&text, prefix, prefix_len, style);
         }
     }
 
@@ -381,6 +416,7 @@ impl Tokenizer {
         text: &str,
         prefix: &str,
         prefix_len: usize,
+        style: Style,
     ) {
         let span = self.wrapwidth - (4 + prefix_len);
         let mut chars: Vec<char> = text.chars().collect();
@@ -395,8 +431,10 @@ impl Tokenizer {
             if !chunk.is_empty() {
                 let end = if chars.is_empty() { "" } else { " &" };
                 self.put_line(
-                    &format!("{prefix}<{chunk}>{end}"),
+                    &format!("{prefix}<{chunk}>"),
+                    end,
                     self.depth,
+                    style,
                 );
                 prefix.clear();
                 self.rnl();
@@ -405,7 +443,7 @@ impl Tokenizer {
     }
 
     fn handle_comment(&mut self, comment: &str) {
-        self.handle_str(comment, "#", "")
+        self.handle_str(comment, "#", "", Style::Comment)
     }
 
     fn begin(&mut self) {
@@ -467,22 +505,42 @@ impl Tokenizer {
         self.append_bare(TokenKind::Eof, self.depth);
     }
 
-    fn put_line(&mut self, s: &str, depth: usize) {
-        self.append(TokenKind::Str, s, depth, None);
+    // The suffix (if any) is unstyled
+    fn put_line(
+        &mut self,
+        s: &str,
+        suffix: &str,
+        depth: usize,
+        style: Style,
+    ) {
+        self.append(TokenKind::Str, "", depth, None);
+        if let Some(token) = self.tokens.last_mut() {
+            token.push_str(s, Some(style));
+        }
+        self.push_suffix(suffix);
     }
 
     fn puts(&mut self, s: &str) {
-        self.puts_num(s, None);
+        self.puts_num(s, None, None);
+    }
+
+    fn puts_style(&mut self, s: &str, style: Style) {
+        self.puts_num(s, None, Some(style));
     }
 
-    fn puts_num(&mut self, s: &str, num_records: Option<usize>) {
+    fn puts_num(
+        &mut self,
+        s: &str,
+        num_records: Option<usize>,
+        style: Option<Style>,
+    ) {
         if !self.tokens.is_empty() {
             if let Some(token) = self.tokens.last_mut() {
                 if token.kind == TokenKind::Str
                     && !token.is_multiline()
                     && !token.text.ends_with('\n')
                 {
-                    token.text.push_str(s); // absorb s into the prev one
+                    token.push_str(s, style); // absorb s into the prev one
                     if let Some(num_records) = num_records {
                         if token.num_records.is_none() {
                             token.num_records = Some(num_records);
@@ -492,7 +550,17 @@ impl Tokenizer {
                 }
             }
         }
-        self.append(TokenKind::Str, s, self.depth, num_records);
+        self.append(TokenKind::Str, "", self.depth, num_records);
+        if let Some(token) = self.tokens.last_mut() {
+            token.push_str(s, style);
+        }
+    }
+
+    // Appends unstyled text to the last token which must be a Str
+    fn push_suffix(&mut self, suffix: &str) {
+        if let Some(token) = self.tokens.last_mut() {
+            token.push_str(suffix, None);
+        }
     }
 
     fn append_bare(&mut self, kind: TokenKind, depth: usize) {
@@ -518,3 +586,17 @@ impl Tokenizer {
         }
     }
 }
+
+// Returns the real as UXF text using the given number of decimal places
+// (or as many as needed if realdp is 0)
+pub(crate) fn real_text(r: f64, realdp: u8) -> String {
+    let mut text = if realdp > 0 {
+        format!("{r:.*}", realdp as usize)
+    } else {
+        format!("{r}")
+    };
+    if !text.contains(['.', 'e', 'E']) {
+        text.push_str(".0");
+    }
+    text
+}
@@ -2,23 +2,33 @@
 // License: GPLv3
 
 use crate::format::Format;
-use crate::pprint::token::{Token, TokenKind, Tokens};
+use crate::pprint::token::{Span, Spans, Style, Token, TokenKind, Tokens};
 
 pub(crate) fn to_text(
     header: &str,
     tokens: Tokens,
     format: &Format,
 ) -> String {
+    to_spans(header, tokens, format).0
+}
+
+// Returns the text and the spans of its styled parts in text order
+pub(crate) fn to_spans(
+    header: &str,
+    tokens: Tokens,
+    format: &Format,
+) -> (String, Spans) {
     let mut writer = Writer::new(header, tokens, format);
     writer.pprint();
     let mut uxt = String::new();
     std::mem::swap(&mut uxt, &mut writer.uxt);
-    uxt
+    (uxt, writer.spans)
 }
 
 struct Writer {
     pub tokens: Tokens,
     pub uxt: String,
+    pub spans: Spans,
     pub wrapwidth: usize,
     pub indent: String,
     pub pos: usize, // line position
@@ -32,6 +42,11 @@ impl Writer {
         Self {
             tokens,
             uxt: String::from(header),
+            spans: vec![Span::new(
+                0,
+                header.trim_end().len(),
+                Style::Header,
+            )],
             wrapwidth: format.wrapwidth as usize,
             indent: format.indent.clone(),
             pos: 0,
@@ -127,6 +142,7 @@ impl Writer {
             match token.kind {
                 TokenKind::Str => {
                     self.write(&token.text);
+                    self.add_spans(&token);
                     if token.is_multiline() {
                         break;
                     }
@@ -149,6 +165,7 @@ impl Writer {
                 let n = if self.pending_rws { 1 } else { 0 };
                 if self.pos + width + n <= self.wrapwidth {
                     self.write(&token.text);
+                    self.add_spans(&token);
                     return;
                 } else {
                     self.write("\n");
@@ -159,10 +176,25 @@ impl Writer {
                 self.write(&tab); // fits after indent
             }
             self.write(&token.text);
+            self.add_spans(&token);
         }
     }
 
+    // Must be called immediately after the token's text has been written
+    // (which it always is in full, so that it is the end of the uxt)
+    fn add_spans(&mut self, token: &Token) {
+        let offset = self.uxt.len() - token.text.len();
+        self.spans.extend(token.spans.iter().map(|span| {
+            Span::new(offset + span.start, offset + span.end, span.style)
+        }));
+    }
+
     fn multiline(&mut self, token: Token) {
+        self.multiline_text(&token);
+        self.add_spans(&token);
+    }
+
+    fn multiline_text(&mut self, token: &Token) {
         // This method writes direct to the uxt
         if self.pos > 0 {
             // in a line
@@ -0,0 +1,308 @@
+// Copyright © 2022 Mark Summerfield. All rights reserved.
+// License: GPLv3
+
+/*! This module provides renderings of a `Uxf` for reviewing rather than
+for reading back in.
+
+`to_ansi()` returns the same text as `Uxf::to_text_format()` but with ANSI
+colour escapes that distinguish ttypes, field names, vtypes, map keys,
+strs, numbers, dates, bytes, comments, and imports. (Each line is coloured
+separately so that the text can be paged or grepped.)
+
+`to_html()` returns a standalone HTML page: the header, comment, imports,
+and ttype definitions are shown as coloured UXF text, and the value is
+shown as nested HTML: a table as an HTML table whose caption is its TClass
+definition and whose header row is its field names (and vtypes), a map as
+a two column HTML table of keys and values, and a list as an ordered list.
+
+Both use the pretty printer's tokenizer (and `to_ansi()` its layout), so
+scalars are shown exactly as they would be in a UXF file.
+
+```
+use uxf::render::{to_ansi, to_html};
+
+let uxo = uxf::parse("uxf 1\n=Point x:int y:int\n(Point 1 2)\n").unwrap();
+let format = uxf::Format::default();
+let ansi = to_ansi(&uxo, &format);
+assert!(ansi.contains("\x1b[1;34mPoint\x1b[0m"));
+let html = to_html(&uxo, &format);
+assert!(html.contains("<th><span class=\"field\">x</span>"));
+```
+*/
+use crate::format::Format;
+use crate::list::List;
+use crate::map::Map;
+use crate::pprint::{preamble_to_spans, real_text, to_spans, Span, Style};
+use crate::table::Table;
+use crate::tclass::TClass;
+use crate::util::escape;
+use crate::uxf::Uxf;
+use crate::value::Value;
+use std::fmt::Write as _;
+
+static ANSI_RESET: &str = "\x1b[0m";
+
+static CSS: &str = "body { font-family: sans-serif; }
+pre.uxf { font-family: monospace; }
+table { border-collapse: collapse; margin: 0.25em 0; }
+th, td { border: 1px solid #BBB; padding: 0.1em 0.4em;
+    vertical-align: top; text-align: left; font-family: monospace; }
+caption { text-align: left; font-family: monospace; }
+ol.list { margin: 0.25em 0; font-family: monospace; }
+.header { font-weight: bold; }
+.comment { color: #777; font-style: italic; }
+.import { color: #909; }
+.ttype { color: #00C; font-weight: bold; }
+.field { color: #088; }
+.vtype { color: #008; }
+.key { color: #960; font-weight: bold; }
+.constant { color: #909; }
+.number { color: #C00; }
+.date { color: #C0C; }
+.str { color: #080; }
+.bytes { color: #A60; }
+";
+
+/// Returns the UXF text of the given `uxo` formatted using `format` with
+/// ANSI colour escapes.
+pub fn to_ansi(uxo: &Uxf, format: &Format) -> String {
+    let (text, spans) = to_spans(uxo, format);
+    let mut ansi = String::with_capacity(text.len() * 2);
+    let mut pos = 0;
+    for span in spans {
+        ansi.push_str(&text[pos..span.start]);
+        let code = ansi_code(span.style);
+        for (i, line) in text[span.start..span.end].split('\n').enumerate()
+        {
+            if i > 0 {
+                ansi.push('\n');
+            }
+            if !line.is_empty() {
+                let _ = write!(ansi, "\x1b[{code}m{line}{ANSI_RESET}");
+            }
+        }
+        pos = span.end;
+    }
+    ansi.push_str(&text[pos..]);
+    ansi
+}
+
+/// Returns a standalone HTML page showing the given `uxo`; the `format` is
+/// used for the ttype definitions and for reals.
+pub fn to_html(uxo: &Uxf, format: &Format) -> String {
+    let (text, spans) = preamble_to_spans(uxo, format);
+    let mut html = format!(
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n\
+        <title>UXF</title>\n<style>\n{CSS}</style>\n</head>\n<body>\n\
+        <pre class=\"uxf\">"
+    );
+    html.push_str(&spans_to_html(text.trim_end(), &spans));
+    html.push_str("</pre>\n");
+    html_value(&mut html, uxo.value(), format);
+    html.push_str("\n</body>\n</html>\n");
+    html
+}
+
+fn ansi_code(style: Style) -> &'static str {
+    match style {
+        Style::Header => "1",
+        Style::Comment => "3;90",
+        Style::Import => "35",
+        Style::TType => "1;34",
+        Style::Field => "36",
+        Style::VType => "34",
+        Style::Key => "1;33",
+        Style::Constant => "95",
+        Style::Number => "31",
+        Style::Date => "35",
+        Style::Str => "32",
+        Style::Bytes => "33",
+    }
+}
+
+fn css_class(style: Style) -> &'static str {
+    match style {
+        Style::Header => "header",
+        Style::Comment => "comment",
+        Style::Import => "import",
+        Style::TType => "ttype",
+        Style::Field => "field",
+        Style::VType => "vtype",
+        Style::Key => "key",
+        Style::Constant => "constant",
+        Style::Number => "number",
+        Style::Date => "date",
+        Style::Str => "str",
+        Style::Bytes => "bytes",
+    }
+}
+
+// Spans must be in text order and within the text
+fn spans_to_html(text: &str, spans: &[Span]) -> String {
+    let mut html = String::with_capacity(text.len() * 2);
+    let mut pos = 0;
+    for span in spans {
+        html.push_str(&escape(&text[pos..span.start]));
+        html.push_str(&styled(&text[span.start..span.end], span.style));
+        pos = span.end;
+    }
+    html.push_str(&escape(&text[pos..]));
+    html
+}
+
+// Returns the given UXF text HTML-escaped in a span of the style's class
+fn styled(text: &str, style: Style) -> String {
+    format!("<span class=\"{}\">{}</span>", css_class(style), escape(text))
+}
+
+fn html_value(html: &mut String, value: &Value, format: &Format) {
+    match value {
+        Value::List(lst) => html_list(html, lst, format),
+        Value::Map(m) => html_map(html, m, format),
+        Value::Table(t) => html_table(html, t, format),
+        Value::Null | Value::Bool(_) => {
+            html.push_str(&styled(&value.to_string(), Style::Constant))
+        }
+        Value::Int(_) => {
+            html.push_str(&styled(&value.to_string(), Style::Number))
+        }
+        Value::Real(r) => html.push_str(&styled(
+            &real_text(*r, format.realdp),
+            Style::Number,
+        )),
+        Value::Date(_) | Value::DateTime(_) => {
+            html.push_str(&styled(&value.to_string(), Style::Date))
+        }
+        Value::Str(_) => {
+            html.push_str(&styled(&value.to_string(), Style::Str))
+        }
+        Value::Bytes(_) => {
+            html.push_str(&styled(&value.to_string(), Style::Bytes))
+        }
+    }
+}
+
+fn html_list(html: &mut String, lst: &List, format: &Format) {
+    if lst.is_empty() {
+        html.push('[');
+        html_comment_and_types(html, lst.comment(), lst.vtype(), "");
+        html.push(']');
+        return;
+    }
+    if !lst.comment().is_empty() || !lst.vtype().is_empty() {
+        html.push_str("<div>[");
+        html_comment_and_types(html, lst.comment(), lst.vtype(), "");
+        html.push_str("]</div>");
+    }
+    html.push_str("<ol class=\"list\" start=\"0\">\n");
+    for value in lst.iter() {
+        html.push_str("<li>");
+        html_value(html, value, format);
+        html.push_str("</li>\n");
+    }
+    html.push_str("</ol>");
+}
+
+fn html_map(html: &mut String, m: &Map, format: &Format) {
+    if m.is_empty() {
+        html.push('{');
+        html_comment_and_types(html, m.comment(), m.ktype(), m.vtype());
+        html.push('}');
+        return;
+    }
+    html.push_str("<table class=\"map\">\n");
+    if !m.comment().is_empty() || !m.ktype().is_empty() {
+        html.push_str("<caption>{");
+        html_comment_and_types(html, m.comment(), m.ktype(), m.vtype());
+        html.push_str("}</caption>\n");
+    }
+    for key in m.keys_in(format.map_order) {
+        let _ = write!(
+            html,
+            "<tr><th>{}</th><td>",
+            styled(&key.to_string(), Style::Key)
+        );
+        // Safe because the key is one of the map's
+        html_value(html, m.get(key).unwrap(), format);
+        html.push_str("</td></tr>\n");
+    }
+    html.push_str("</table>");
+}
+
+// A fieldless table (e.g., an enum member) is shown inline
+fn html_table(html: &mut String, t: &Table, format: &Format) {
+    if t.is_fieldless() {
+        let _ = write!(html, "({})", styled(t.ttype(), Style::TType));
+        return;
+    }
+    html.push_str("<table class=\"table\">\n<caption>");
+    html_tclass(html, t.tclass());
+    if !t.comment().is_empty() {
+        html.push(' ');
+        html.push_str(&styled(
+            &format!("#<{}>", escape(t.comment())),
+            Style::Comment,
+        ));
+    }
+    html.push_str("</caption>\n<thead><tr>");
+    for field in t.tclass().fields() {
+        let _ = write!(html, "<th>{}", styled(field.name(), Style::Field));
+        if let Some(vtype) = field.vtype() {
+            let _ = write!(html, ":{}", styled(vtype, Style::VType));
+        }
+        html.push_str("</th>");
+    }
+    html.push_str("</tr></thead>\n<tbody>\n");
+    for record in t.iter() {
+        html.push_str("<tr>");
+        for value in record.iter() {
+            html.push_str("<td>");
+            html_value(html, value, format);
+            html.push_str("</td>");
+        }
+        html.push_str("</tr>\n");
+    }
+    html.push_str("</tbody>\n</table>");
+}
+
+fn html_tclass(html: &mut String, tclass: &TClass) {
+    html.push('=');
+    let comment = tclass.text_comment();
+    if !comment.is_empty() {
+        html.push_str(&styled(
+            &format!("#<{}>", escape(&comment)),
+            Style::Comment,
+        ));
+        html.push(' ');
+    }
+    html.push_str(&styled(tclass.ttype(), Style::TType));
+    for field in tclass.fields() {
+        let _ = write!(html, " {}", styled(field.name(), Style::Field));
+        if let Some(vtype) = field.vtype() {
+            let _ = write!(html, ":{}", styled(vtype, Style::VType));
+        }
+    }
+}
+
+fn html_comment_and_types(
+    html: &mut String,
+    comment: &str,
+    ktype_or_vtype: &str,
+    vtype: &str,
+) {
+    let mut sep = "";
+    if !comment.is_empty() {
+        html.push_str(&styled(
+            &format!("#<{}>", escape(comment)),
+            Style::Comment,
+        ));
+        sep = " ";
+    }
+    for xtype in [ktype_or_vtype, vtype] {
+        if !xtype.is_empty() {
+            html.push_str(sep);
+            html.push_str(&styled(xtype, Style::VType));
+            sep = " ";
+        }
+    }
+}
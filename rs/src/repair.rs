@@ -0,0 +1,143 @@
+// Copyright © 2022 Mark Summerfield. All rights reserved.
+// License: GPLv3
+
+/*! A RepairPolicy is used to specify what the parser should do when it
+encounters a value whose type doesn't match the expected vtype but which
+could be repaired (i.e., converted to the expected vtype).
+
+There are three kinds of repair: converting a str to a bool, int, real,
+date, or datetime (using `naturalize()`); converting an int to a real;
+and converting a real to an int (with rounding). For each kind the parser
+may silently repair, repair and report an `Event` (the default), or fail
+with an error (E487).
+
+Use `RepairPolicy::strict()` when data must be exactly as written (e.g.,
+financial data where rounding a real to an int is unacceptable).
+*/
+use crate::check::is_of_vtype;
+use crate::consts::*;
+use crate::event::{Event, OnEventFn};
+use crate::value::Value;
+use anyhow::{bail, Result};
+
+/// What to do for a particular kind of repair.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum RepairAction {
+    /// Repair without reporting an `Event`.
+    Repair,
+    /// Repair and report an `Event` (code 486) via the on_event handler.
+    Warn,
+    /// Don't repair: fail with an error (code 487).
+    Fail,
+}
+
+/// The action to take for each kind of repair.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct RepairPolicy {
+    /// str → bool, int, real, date, or datetime (via `naturalize()`),
+    /// e.g., `<17>` where an int is expected
+    pub str_to_scalar: RepairAction,
+    /// int → real, e.g., `3` where a real is expected
+    pub int_to_real: RepairAction,
+    /// real → int (rounded), e.g., `2.5` where an int is expected
+    pub real_to_int: RepairAction,
+}
+
+impl RepairPolicy {
+    /// Returns a `RepairPolicy` that uses the given `action` for every
+    /// kind of repair.
+    pub fn new(action: RepairAction) -> Self {
+        RepairPolicy {
+            str_to_scalar: action,
+            int_to_real: action,
+            real_to_int: action,
+        }
+    }
+
+    /// Returns a `RepairPolicy` that fails rather than doing any repair.
+    pub fn strict() -> Self {
+        RepairPolicy::new(RepairAction::Fail)
+    }
+}
+
+impl Default for RepairPolicy {
+    /// Returns a `RepairPolicy` that repairs and reports every repair
+    /// (which is how the parser has always behaved).
+    fn default() -> Self {
+        RepairPolicy::new(RepairAction::Warn)
+    }
+}
+
+// Returns the repaired value and the action to take for the repair if the
+// (non-null) value isn't of the given scalar vtype but can be repaired;
+// otherwise returns None.
+pub(crate) fn repaired(
+    value: &Value,
+    vtype: &str,
+    policy: &RepairPolicy,
+) -> Option<(Value, RepairAction)> {
+    if value.is_null() || vtype.is_empty() || is_of_vtype(value, vtype) {
+        return None;
+    }
+    match value {
+        Value::Str(_)
+            if [
+                VTYPE_NAME_BOOL,
+                VTYPE_NAME_INT,
+                VTYPE_NAME_REAL,
+                VTYPE_NAME_DATE,
+                VTYPE_NAME_DATETIME,
+                VTYPE_NAME_NUMBER,
+            ]
+            .contains(&vtype) =>
+        {
+            let new_value = value.naturalize();
+            if new_value != *value
+                && (vtype != VTYPE_NAME_NUMBER
+                    || is_of_vtype(&new_value, vtype))
+            {
+                Some((new_value, policy.str_to_scalar))
+            } else {
+                None
+            }
+        }
+        Value::Int(i) if vtype == VTYPE_NAME_REAL => {
+            Some((Value::Real(*i as f64), policy.int_to_real))
+        }
+        Value::Real(r) if vtype == VTYPE_NAME_INT => {
+            Some((Value::Int(r.round() as i64), policy.real_to_int))
+        }
+        _ => None,
+    }
+}
+
+// Reports the repair of value to new_value (or fails) as per the action.
+pub(crate) fn apply_repair(
+    action: RepairAction,
+    value: &Value,
+    new_value: &Value,
+    filename: &str,
+    lino: usize,
+    on_event: &OnEventFn,
+) -> Result<()> {
+    match action {
+        RepairAction::Repair => (),
+        RepairAction::Warn => on_event(&Event::new_repair(
+            486,
+            &format!(
+                "converted {} {value} to {} {new_value}",
+                value.typename(),
+                new_value.typename(),
+            ),
+            filename,
+            lino,
+        )),
+        RepairAction::Fail => bail!(
+            "E487:{filename}:{lino}:won't convert {} {value} to {} \
+            {new_value} since repairs are not permitted",
+            value.typename(),
+            new_value.typename(),
+        ),
+    }
+    Ok(())
+}
@@ -7,7 +7,9 @@ the case of a fieldless table).
 A Table also has a TClass a ttype (the TClass's name), and a (possibly
 empty) comment.
 
-The safest way to access a record is using one of the `*_named()`
+The safest way to access a record is using `view(row)` or `views()`
+which return a `RecordView` that provides access by field name and typed
+getters (see the `view` module), or using one of the `*_named()`
 methods, e.g., `first_named(), `last_named()`, or `get_named(row)`.
 These return a `NamedRecord` (a `HashMap<&str, &Value>`) whose keys are
 field names and whose values are the corresponding field values. Using
@@ -17,28 +19,47 @@ are.
 
 The easiest way to create a Table is to use Table::new() with the TClass
 provided by the make_tclass() function.
+
+A Table may be keyed on one or more of its fields using `set_key()`, in
+which case the key's values must be unique (and be valid `ktype` values),
+and records can be looked up by key using `find_by()` or `find_by_keys()`
+using a hash index that's maintained by the table.
 */
+use crate::check::{first_problem, typecheck, typecheck_vtype};
+use crate::consts::KTYPES;
+use crate::field::Field;
+use crate::key::Key;
+use crate::map::Map;
 use crate::tclass::TClass;
-use crate::util::escape;
-use crate::uxf::Compare;
+use crate::util::{check_indexes, escape};
+use crate::uxf::{Compare, Uxf};
 use crate::value::{Record, Value, Values};
-use anyhow::{bail, Result};
+use crate::view::{RecordView, RecordViewMut};
+use anyhow::{bail, Context, Result};
 use std::{
+    cmp::Ordering,
     collections::HashMap,
     fmt,
     ops::{Index, IndexMut},
+    sync::OnceLock,
 };
 
 /// The keys are field names and the values are the corresponding field
 /// values.
 pub type NamedRecord<'a> = HashMap<&'a str, &'a Value>;
 
+/// The keys are the values of a keyed table's key fields and the values
+/// are the rows of the corresponding records.
+type RowForKey = HashMap<Vec<Key>, usize>;
+
 #[derive(Clone, Debug)]
 pub struct Table {
     tclass: TClass,
     comment: String,
     pending_record: Record,
     records: Vec<Record>,
+    key_columns: Vec<usize>,
+    row_for_key: OnceLock<RowForKey>, // Uninitialized means needs rebuild
 }
 
 impl Table {
@@ -51,6 +72,8 @@ impl Table {
             comment: comment.to_string(),
             pending_record: Values::new(),
             records: vec![],
+            key_columns: vec![],
+            row_for_key: OnceLock::new(),
         }
     }
 
@@ -64,6 +87,8 @@ impl Table {
             comment: comment.to_string(),
             pending_record: Values::new(),
             records: vec![],
+            key_columns: vec![],
+            row_for_key: OnceLock::new(),
         })
     }
 
@@ -72,6 +97,13 @@ impl Table {
         &self.tclass
     }
 
+    /// Replaces the `TClass` with one that has the same number of fields
+    /// (e.g., when renaming a ttype while merging).
+    pub(crate) fn set_tclass(&mut self, tclass: TClass) {
+        debug_assert_eq!(tclass.len(), self.tclass.len());
+        self.tclass = tclass;
+    }
+
     /// Returns the `ttype`.
     pub fn ttype(&self) -> &str {
         self.tclass.ttype()
@@ -105,19 +137,19 @@ impl Table {
     /// Returns the record as a `Some(NamedRecord)` whose keys are
     /// field names and whose values are field values at `row` 0 if any;
     /// otherwise `None`.
-    pub fn first_named(&self) -> Option<NamedRecord> {
+    pub fn first_named(&self) -> Option<NamedRecord<'_>> {
         self.get_named(0)
     }
 
     /// Returns `Some(&Record)` at row 0 if any; otherwise `None`.
     pub fn first(&self) -> Option<&Record> {
-        self.records.get(0)
+        self.records.first()
     }
 
     /// Returns the record as a `Some(NamedRecord)` whose keys are
     /// field names and whose values are field values at `row` 1 if any;
     /// otherwise `None`.
-    pub fn second_named(&self) -> Option<NamedRecord> {
+    pub fn second_named(&self) -> Option<NamedRecord<'_>> {
         self.get_named(1)
     }
 
@@ -129,7 +161,7 @@ impl Table {
     /// Returns the record as a `Some(NamedRecord)` whose keys are
     /// field names and whose values are field values at `row` 2 if any;
     /// otherwise `None`.
-    pub fn third_named(&self) -> Option<NamedRecord> {
+    pub fn third_named(&self) -> Option<NamedRecord<'_>> {
         self.get_named(2)
     }
 
@@ -141,7 +173,7 @@ impl Table {
     /// Returns the record as a `Some(NamedRecord)` whose keys are
     /// field names and whose values are field values at `row` 3 if any;
     /// otherwise `None`.
-    pub fn fourth_named(&self) -> Option<NamedRecord> {
+    pub fn fourth_named(&self) -> Option<NamedRecord<'_>> {
         self.get_named(3)
     }
 
@@ -153,7 +185,7 @@ impl Table {
     /// Returns the last record as a `Some(NamedRecord)` whose
     /// keys are field names and whose values are field values if the table
     /// isn't empty; otherwise `None`.
-    pub fn last_named(&self) -> Option<NamedRecord> {
+    pub fn last_named(&self) -> Option<NamedRecord<'_>> {
         self.get_named(self.len() - 1)
     }
 
@@ -166,7 +198,7 @@ impl Table {
     /// Returns a record as a `Some(NamedRecord)` whose keys are
     /// field names and whose values are field values if `row` is in
     /// bounds; otherwise `None`.
-    pub fn get_named(&self, row: usize) -> Option<NamedRecord> {
+    pub fn get_named(&self, row: usize) -> Option<NamedRecord<'_>> {
         if let Some(record) = self.records.get(row) {
             let mut named = HashMap::new();
             for (i, name) in self.tclass.fieldnames().iter().enumerate() {
@@ -178,6 +210,31 @@ impl Table {
         }
     }
 
+    /// Returns a `Some(RecordView)` of the record at `row` if `row` is in
+    /// bounds; otherwise `None`. A `RecordView` provides access to the
+    /// record's values by field name without allocating.
+    pub fn view(&self, row: usize) -> Option<RecordView<'_>> {
+        self.records
+            .get(row)
+            .map(|record| RecordView::new(&self.tclass, record))
+    }
+
+    /// Returns an iterator of `RecordView`s of the table's records.
+    pub fn views(&self) -> impl Iterator<Item = RecordView<'_>> {
+        self.records
+            .iter()
+            .map(|record| RecordView::new(&self.tclass, record))
+    }
+
+    /// Returns a `Some(RecordViewMut)` of the record at `row` if `row` is
+    /// in bounds; otherwise `None`.
+    pub fn view_mut(&mut self, row: usize) -> Option<RecordViewMut<'_>> {
+        self.invalidate_key_index();
+        self.records
+            .get_mut(row)
+            .map(|record| RecordViewMut::new(&self.tclass, record))
+    }
+
     /// Returns `Some(&Record)` if `row` is in bounds; otherwise `None`.
     pub fn get(&self, row: usize) -> Option<&Record> {
         self.records.get(row)
@@ -186,16 +243,13 @@ impl Table {
     /// Returns `Some(&mut Record)` if `row` is in bounds; otherwise
     /// `None`.
     pub fn get_mut(&mut self, row: usize) -> Option<&mut Record> {
+        self.invalidate_key_index();
         self.records.get_mut(row)
     }
 
     /// Returns `Some(&Value)` if `row` is in bounds and `fieldname`
     /// is valid; otherwise `None`.
-    pub fn get_field(
-        &mut self,
-        row: usize,
-        fieldname: &str,
-    ) -> Option<&Value> {
+    pub fn get_field(&self, row: usize, fieldname: &str) -> Option<&Value> {
         if let Some(column) = self.tclass.column_for_fieldname(fieldname) {
             if let Some(record) = self.records.get(row) {
                 record.get(column)
@@ -241,6 +295,7 @@ impl Table {
         row: usize,
         fieldname: &str,
     ) -> Option<&mut Value> {
+        self.invalidate_key_index();
         if let Some(column) = self.tclass.column_for_fieldname(fieldname) {
             if let Some(record) = self.records.get_mut(row) {
                 record.get_mut(column)
@@ -254,7 +309,8 @@ impl Table {
 
     /// Appends the given `record` of `Value`s to the end of the table or
     /// returns `Err` if `record` doesn't have `Table::ttype_len()` values
-    /// or if this is a fieldless table.
+    /// or if this is a fieldless table, or if the table is keyed and the
+    /// record's key is invalid or duplicates an existing record's key.
     pub fn append(&mut self, record: Record) -> Result<()> {
         self.append_x(record, "-", 0)
     }
@@ -268,23 +324,152 @@ impl Table {
         filename: &str,
         lino: usize,
     ) -> Result<()> {
-        if record.len() != self.tclass.len() {
+        self.check_record_len(&record, filename, lino)?;
+        self.add_to_key_index(&record, filename, lino)?;
+        self.records.push(record);
+        Ok(())
+    }
+
+    /// Appends the given `record` of `Value`s to the end of the table or
+    /// returns `Err` if `record` doesn't have `Table::ttype_len()` values
+    /// or if any value doesn't match its field's vtype (or if it is a
+    /// collection whose contents don't match its own types).
+    /// If `uxo` is given, the table's ttype (and any ttypes used as field
+    /// vtypes) must be defined in it, and tables must match their
+    /// definitions.
+    /// Use this rather than `append()` to ensure that the table will parse
+    /// after being written.
+    pub fn try_append(
+        &mut self,
+        record: Record,
+        uxo: Option<&Uxf>,
+    ) -> Result<()> {
+        let mut problems = vec![];
+        if let Some(uxo) = uxo {
+            match uxo.tclass_for_ttype.get(self.ttype()) {
+                None => bail!("E450:-:0:undefined ttype {}", self.ttype()),
+                Some(tclass) if tclass != &self.tclass => bail!(
+                    "E457:-:0:table of ttype {} doesn't match its TClass \
+                    definition",
+                    self.ttype()
+                ),
+                _ => (),
+            }
+        }
+        self.check_record_len(&record, "-", 0)?;
+        let row = self.records.len();
+        for (field, value) in self.tclass.fields().iter().zip(&record) {
+            let vtype = field.vtype().unwrap_or_default();
+            typecheck_vtype(vtype, "", uxo, &mut problems);
+            typecheck(
+                value,
+                vtype,
+                &format!("[{row}].{}", field.name()),
+                uxo,
+                &mut problems,
+            );
+        }
+        first_problem(&problems)?;
+        self.add_to_key_index(&record, "-", 0)?;
+        self.records.push(record);
+        Ok(())
+    }
+
+    /// Inserts the given `record` at position `row` (shifting any later
+    /// records up) _or_ returns an Err if `row` is greater than the
+    /// table's length, or if `record` doesn't have `Table::ttype_len()`
+    /// values, or if the table is keyed and the record's key is invalid or
+    /// duplicates an existing record's key.
+    pub fn insert(&mut self, row: usize, record: Record) -> Result<()> {
+        if row > self.records.len() {
             bail!(
-                "E736:{}:{}:rows for table of ttype {} must have exactly \
-                {} values, got {}",
-                self.ttype(),
-                self.tclass.len(),
-                record.len(),
-                filename,
-                lino
+                "E760:-:0:can't insert at row {row} in a table of length {}",
+                self.records.len()
             )
         }
-        self.records.push(record);
+        self.check_record_len(&record, "-", 0)?;
+        if self.check_new_key(&record, "-", 0)?.is_some() {
+            self.invalidate_key_index();
+        }
+        self.records.insert(row, record);
+        Ok(())
+    }
+
+    /// Removes and returns the record at position `row` (shifting any
+    /// later records down) if `row` is in bounds; otherwise returns `None`.
+    pub fn remove(&mut self, row: usize) -> Option<Record> {
+        if row < self.records.len() {
+            self.invalidate_key_index();
+            Some(self.records.remove(row))
+        } else {
+            None
+        }
+    }
+
+    /// Swaps the records at rows `a` and `b` _or_ returns an Err if
+    /// either is out of bounds.
+    pub fn swap(&mut self, a: usize, b: usize) -> Result<()> {
+        check_indexes(a, b, self.records.len())?;
+        self.invalidate_key_index();
+        self.records.swap(a, b);
+        Ok(())
+    }
+
+    /// Keeps only those records for which `predicate` returns `true`.
+    /// See also `filter()`.
+    ///
+    /// ```
+    /// let mut uxo = uxf::parse("uxf 1\n=Point x:int y:int\n\
+    ///                           (Point 1 2 3 4 5 6)\n").unwrap();
+    /// let table = uxo.value_mut().as_table_mut().unwrap();
+    /// table.retain(|record| record.get_int("y").unwrap() != 4);
+    /// assert_eq!(table.to_string(), "(Point 1 2\n5 6)");
+    /// ```
+    pub fn retain<F>(&mut self, mut predicate: F)
+    where
+        F: FnMut(RecordView) -> bool,
+    {
+        self.invalidate_key_index();
+        let tclass = &self.tclass;
+        self.records
+            .retain(|record| predicate(RecordView::new(tclass, record)));
+    }
+
+    /// Sorts the table's records using the `compare` function. The sort is
+    /// stable. See also `sort_by_fields()`.
+    pub fn sort_by<F>(&mut self, mut compare: F)
+    where
+        F: FnMut(RecordView, RecordView) -> Ordering,
+    {
+        self.invalidate_key_index();
+        let tclass = &self.tclass;
+        self.records.sort_by(|a, b| {
+            compare(RecordView::new(tclass, a), RecordView::new(tclass, b))
+        });
+    }
+
+    /// Removes consecutive duplicate records.
+    pub fn dedup(&mut self) {
+        self.invalidate_key_index();
+        self.records.dedup();
+    }
+
+    /// Appends the given `records` to the end of the table _or_ returns an
+    /// Err at the first record that can't be appended (see `append()`);
+    /// any records before it will have been appended.
+    pub fn extend<I: IntoIterator<Item = Record>>(
+        &mut self,
+        records: I,
+    ) -> Result<()> {
+        for record in records {
+            self.append(record)?;
+        }
         Ok(())
     }
 
     /// Appends a `record` of `Value::Null`s to the end of the table or
-    /// returns `Err` if this is a fieldless table.
+    /// returns `Err` if this is a fieldless table (or a keyed table, since
+    /// `null` isn't a valid key).
     pub fn append_empty(&mut self) -> Result<()> {
         self.append_empty_x("-", 0)
     }
@@ -297,6 +482,7 @@ impl Table {
         lino: usize,
     ) -> Result<()> {
         let record = self.tclass.record_of_nulls_x(filename, lino)?;
+        self.add_to_key_index(&record, filename, lino)?;
         self.records.push(record);
         Ok(())
     }
@@ -361,21 +547,24 @@ impl Table {
 
     /// Truncates the table to contain at most `size` records.
     pub fn truncate(&mut self, size: usize) {
+        self.invalidate_key_index();
         self.records.truncate(size);
     }
 
     /// Deletes every value in the table so that it is empty.
     pub fn clear(&mut self) {
+        self.invalidate_key_index();
         self.records.clear();
     }
 
     /// Returns an iterator of the table's records as immutables.
-    pub fn iter(&self) -> std::slice::Iter<Record> {
+    pub fn iter(&self) -> std::slice::Iter<'_, Record> {
         self.records.iter()
     }
 
     /// Returns an iterator of the table's records as mutables.
-    pub fn iter_mut(&mut self) -> std::slice::IterMut<Record> {
+    pub fn iter_mut(&mut self) -> std::slice::IterMut<'_, Record> {
+        self.invalidate_key_index();
         self.records.iter_mut()
     }
 
@@ -386,9 +575,483 @@ impl Table {
 
     /// Returns `&mut records` to make the entire mutable Vec API available.
     pub fn inner_mut(&mut self) -> &mut Vec<Record> {
+        self.invalidate_key_index();
         &mut self.records
     }
 
+    /// Makes the given `fieldnames` the table's key (or makes the table
+    /// unkeyed if `fieldnames` is empty) and builds its key index _or_
+    /// returns an Err (leaving the table unkeyed) if any of the
+    /// `fieldnames` isn't one of the table's fields, or if any record's key
+    /// values aren't valid `ktype` values, or if any two records have the
+    /// same key.
+    /// If `declare` is `true` the key is also declared by the table's
+    /// TClass (see `TClass::set_key()`) so that it is output in the text
+    /// form. (For this to take effect the owning `Uxf`'s TClass
+    /// must be updated too, e.g., using `Uxf::add_tclass()`.)
+    pub fn set_key(
+        &mut self,
+        fieldnames: &[&str],
+        declare: bool,
+    ) -> Result<()> {
+        self.set_key_x(fieldnames, declare, "-", 0)
+    }
+
+    /// Makes the given `fieldnames` the table's key; see `set_key()`.
+    pub(crate) fn set_key_x(
+        &mut self,
+        fieldnames: &[&str],
+        declare: bool,
+        filename: &str,
+        lino: usize,
+    ) -> Result<()> {
+        self.key_columns = self.columns_for(fieldnames)?;
+        self.invalidate_key_index();
+        if let Err(err) = self.reindex_x(filename, lino) {
+            self.key_columns.clear();
+            return Err(err);
+        }
+        if declare {
+            self.tclass.set_key(fieldnames)?;
+        }
+        Ok(())
+    }
+
+    /// Returns the names of the table's key fields (which will be empty if
+    /// the table isn't keyed).
+    pub fn key(&self) -> Vec<&str> {
+        self.key_columns
+            .iter()
+            .map(|column| self.tclass.fields()[*column].name())
+            .collect()
+    }
+
+    /// Returns `true` if the table is keyed; otherwise returns `false`.
+    pub fn is_keyed(&self) -> bool {
+        !self.key_columns.is_empty()
+    }
+
+    /// Returns `Some(&Record)` for the record with the given `key` if
+    /// the table is keyed on a single field and there is such a record;
+    /// otherwise `None`.
+    ///
+    /// ```
+    /// let uxo = uxf::parse_options(
+    ///     "uxf 1\n=#<key: id> Person id:int name:str\n\
+    ///     (Person 7 <Ann> 3 <Bob>)\n",
+    ///     uxf::ParserOptions::TABLE_KEYS,
+    ///     None,
+    /// ).unwrap();
+    /// let people = uxo.value().as_table().unwrap();
+    /// let bob = people.find_by(&3.into()).unwrap();
+    /// assert_eq!(bob[1].as_str(), Some("Bob"));
+    /// assert!(people.find_by(&4.into()).is_none());
+    /// ```
+    pub fn find_by(&self, key: &Key) -> Option<&Record> {
+        if self.key_columns.len() == 1 {
+            self.find_by_keys(std::slice::from_ref(key))
+        } else {
+            None
+        }
+    }
+
+    /// Returns `Some(&Record)` for the record whose key field values are
+    /// `keys` (in key field order) if the table is keyed and there is such
+    /// a record; otherwise `None`.
+    pub fn find_by_keys(&self, keys: &[Key]) -> Option<&Record> {
+        if !self.is_keyed() || keys.len() != self.key_columns.len() {
+            return None;
+        }
+        let row_for_key = self.row_for_key.get_or_init(|| {
+            // The records have been mutated so rebuild leniently: records
+            // with invalid keys are skipped and the first of any
+            // duplicates is used
+            let mut row_for_key = RowForKey::new();
+            for (row, record) in self.records.iter().enumerate() {
+                if let Ok(key) = self.key_for(record, "-", 0) {
+                    row_for_key.entry(key).or_insert(row);
+                }
+            }
+            row_for_key
+        });
+        row_for_key.get(keys).and_then(|row| self.records.get(*row))
+    }
+
+    /// Rebuilds the key index _or_ returns an Err if any record's key
+    /// values aren't valid `ktype` values, or if any two records have the
+    /// same key. This is done automatically by `set_key()`, but may be
+    /// useful to check a keyed table's records after they have been
+    /// mutated.
+    pub fn reindex(&self) -> Result<()> {
+        self.reindex_x("-", 0)
+    }
+
+    fn reindex_x(&self, filename: &str, lino: usize) -> Result<()> {
+        let mut row_for_key = RowForKey::new();
+        if self.is_keyed() {
+            for (row, record) in self.records.iter().enumerate() {
+                let key = self.key_for(record, filename, lino)?;
+                if row_for_key.contains_key(&key) {
+                    bail!(self.duplicate_key_error(&key, filename, lino))
+                }
+                row_for_key.insert(key, row);
+            }
+        }
+        // If the index is already built it is the same as this one since
+        // the records have no invalid or duplicate keys
+        let _ = self.row_for_key.set(row_for_key);
+        Ok(())
+    }
+
+    /// Adds the given `record` (which must not yet have been added) to
+    /// the key index as the last row if the table is keyed _or_ returns an
+    /// Err if the record's key is invalid or already present.
+    fn add_to_key_index(
+        &mut self,
+        record: &Record,
+        filename: &str,
+        lino: usize,
+    ) -> Result<()> {
+        if let Some(key) = self.check_new_key(record, filename, lino)? {
+            let row = self.records.len();
+            if let Some(row_for_key) = self.row_for_key.get_mut() {
+                row_for_key.insert(key, row);
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns the given `record`'s key if the table is keyed (and
+    /// ensures the index is built), or `None` if the table isn't keyed _or_
+    /// returns an Err if the record's key is invalid or already present.
+    fn check_new_key(
+        &mut self,
+        record: &Record,
+        filename: &str,
+        lino: usize,
+    ) -> Result<Option<Vec<Key>>> {
+        if !self.is_keyed() {
+            return Ok(None);
+        }
+        if self.row_for_key.get().is_none() {
+            self.reindex_x(filename, lino)?;
+        }
+        let key = self.key_for(record, filename, lino)?;
+        if let Some(row_for_key) = self.row_for_key.get() {
+            if row_for_key.contains_key(&key) {
+                bail!(self.duplicate_key_error(&key, filename, lino))
+            }
+        }
+        Ok(Some(key))
+    }
+
+    fn invalidate_key_index(&mut self) {
+        self.row_for_key.take();
+    }
+
+    fn key_for(
+        &self,
+        record: &Record,
+        filename: &str,
+        lino: usize,
+    ) -> Result<Vec<Key>> {
+        let mut key = vec![];
+        for column in &self.key_columns {
+            let value = &record[*column];
+            if !value.is_ktype() {
+                bail!(
+                    "E743:{}:{}:table of ttype {} has invalid {} value for \
+                    key field {}",
+                    filename,
+                    lino,
+                    self.ttype(),
+                    value.typename(),
+                    self.tclass.fields()[*column].name()
+                )
+            }
+            key.push(Key::from_x(value.clone(), filename, lino)?);
+        }
+        Ok(key)
+    }
+
+    fn duplicate_key_error(
+        &self,
+        key: &[Key],
+        filename: &str,
+        lino: usize,
+    ) -> String {
+        let key: Vec<String> = key.iter().map(|k| k.to_string()).collect();
+        format!(
+            "E744:{}:{}:table of ttype {} already has a record with key {}",
+            filename,
+            lino,
+            self.ttype(),
+            key.join(" ")
+        )
+    }
+
+    /// Sorts the table's records by the values of the given fields (the
+    /// first field's values, then the second's to break ties, and so on)
+    /// using `Value::cmp_key_order()` _or_ returns an Err if any of the
+    /// `fieldnames` isn't one of the table's fields.
+    /// The sort is stable.
+    pub fn sort_by_fields(&mut self, fieldnames: &[&str]) -> Result<()> {
+        let columns = self.columns_for(fieldnames)?;
+        self.invalidate_key_index();
+        self.records.sort_by(|a, b| {
+            for column in &columns {
+                let ordering = a[*column].cmp_key_order(&b[*column]);
+                if ordering != Ordering::Equal {
+                    return ordering;
+                }
+            }
+            Ordering::Equal
+        });
+        Ok(())
+    }
+
+    /// Returns a new table with the same `TClass` and comment as this one
+    /// that contains (clones of) those records for which `predicate`
+    /// returns `true`.
+    ///
+    /// ```
+    /// let uxo = uxf::parse("uxf 1\n=Point x:int y:int\n\
+    ///                       (Point 1 2 3 4 5 6)\n").unwrap();
+    /// let table = uxo.value().as_table().unwrap();
+    /// let right = table.filter(|record| {
+    ///     record.get("x").unwrap().as_int().unwrap() > 2
+    /// });
+    /// assert_eq!(right.to_string(), "(Point 3 4\n5 6)");
+    /// ```
+    pub fn filter<F>(&self, predicate: F) -> Table
+    where
+        F: Fn(&NamedRecord) -> bool,
+    {
+        let mut table = Table::new(self.tclass.clone(), &self.comment);
+        table.key_columns = self.key_columns.clone();
+        let fieldnames = self.tclass.fieldnames();
+        for record in self.records.iter() {
+            let named: NamedRecord =
+                fieldnames.iter().copied().zip(record.iter()).collect();
+            if predicate(&named) {
+                table.records.push(record.clone());
+            }
+        }
+        table
+    }
+
+    /// Returns a new table with a new `TClass` of the given `ttype` whose
+    /// fields are the given `fieldnames` (with their vtypes) in the given
+    /// order, and whose records hold (clones of) the corresponding values
+    /// _or_ returns an Err if the `ttype` is invalid or any of the
+    /// `fieldnames` isn't one of the table's fields (or is repeated).
+    /// The new `TClass` has this table's `TClass`'s comment, and its key
+    /// if every key field is one of the `fieldnames`.
+    pub fn project(
+        &self,
+        ttype: &str,
+        fieldnames: &[&str],
+    ) -> Result<Table> {
+        let columns = self.columns_for(fieldnames)?;
+        let fields = columns
+            .iter()
+            .map(|column| self.tclass.fields()[*column].clone())
+            .collect();
+        let mut tclass = TClass::new(ttype, fields, self.tclass.comment())?;
+        let key = self.tclass.key();
+        if key.iter().all(|name| fieldnames.contains(name)) {
+            tclass.set_key(&key)?; // only if every key field is projected
+        }
+        let mut table = Table::new(tclass, &self.comment);
+        for record in self.records.iter() {
+            table.records.push(
+                columns
+                    .iter()
+                    .map(|column| record[*column].clone())
+                    .collect(),
+            );
+        }
+        Ok(table)
+    }
+
+    /// Returns a Map whose keys are the distinct values of the given field
+    /// and whose values are tables (with the same `TClass` and comment as
+    /// this one) holding (clones of) the records with that key, in their
+    /// original order _or_ returns an Err if `fieldname` isn't one of the
+    /// table's fields or if any of its values isn't a valid `ktype` (e.g.,
+    /// is `null` or a `real`).
+    /// If the field's vtype is a `ktype` the Map's `ktype` is set to it
+    /// and its `vtype` to this table's `ttype`.
+    pub fn group_by(&self, fieldname: &str) -> Result<Map> {
+        let column = self.columns_for(&[fieldname])?[0];
+        let (ktype, vtype) = match self.tclass.fields()[column].vtype() {
+            Some(vtype) if KTYPES.contains(&vtype) => (vtype, self.ttype()),
+            _ => ("", ""),
+        };
+        let mut map = Map::new(ktype, vtype, "")?;
+        for record in self.records.iter() {
+            let key = Key::from_x(record[column].clone(), "-", 0)
+                .with_context(|| {
+                    format!(
+                        "E741:-:0:can't group table of ttype {} by field \
+                        {fieldname}",
+                        self.ttype()
+                    )
+                })?;
+            if map.get(&key).is_none() {
+                let mut table =
+                    Table::new(self.tclass.clone(), &self.comment);
+                table.key_columns = self.key_columns.clone();
+                map.insert(key.clone(), table.into());
+            }
+            if let Some(Value::Table(table)) = map.get_mut(&key) {
+                table.records.push(record.clone());
+            }
+        }
+        Ok(map)
+    }
+
+    /// Returns a new table with a new `TClass` of the given `ttype`
+    /// holding every combination of this table's records and the `other`
+    /// table's records whose values match for every `(field, other_field)`
+    /// pair in `on`, _or_ returns an Err if `on` is empty, any field
+    /// isn't in its table, or `ttype` is invalid.
+    /// As in SQL, a `null` never matches anything (not even another
+    /// `null`), so records with a `null` in any of the `on` fields are
+    /// never joined.
+    /// The new `TClass`'s fields are all this table's fields followed by
+    /// those of the `other` table's fields that aren't in `on`, with their
+    /// vtypes. If an `other` field has the same name as one of this
+    /// table's fields, it is prefixed with the `other` table's `ttype` in
+    /// lowercase and an underscore, e.g., `name` becomes `customer_name`.
+    /// See also `left_join()`.
+    pub fn inner_join(
+        &self,
+        other: &Table,
+        ttype: &str,
+        on: &[(&str, &str)],
+    ) -> Result<Table> {
+        self.join(other, ttype, on, false)
+    }
+
+    /// Returns a new table like `inner_join()` except that every one of
+    /// this table's records is included at least once: those that don't
+    /// match any of the `other` table's records have `null`s for the
+    /// `other` table's fields.
+    pub fn left_join(
+        &self,
+        other: &Table,
+        ttype: &str,
+        on: &[(&str, &str)],
+    ) -> Result<Table> {
+        self.join(other, ttype, on, true)
+    }
+
+    fn join(
+        &self,
+        other: &Table,
+        ttype: &str,
+        on: &[(&str, &str)],
+        left: bool,
+    ) -> Result<Table> {
+        if on.is_empty() {
+            bail!(
+                "E742:-:0:can't join tables of ttypes {} and {} without \
+                any fields to join on",
+                self.ttype(),
+                other.ttype()
+            )
+        }
+        let names: Vec<&str> = on.iter().map(|(name, _)| *name).collect();
+        let columns = self.columns_for(&names)?;
+        let names: Vec<&str> = on.iter().map(|(_, name)| *name).collect();
+        let other_columns = other.columns_for(&names)?;
+        let mut fields = self.tclass.fields().clone();
+        let mut extra_columns = vec![];
+        for (column, field) in other.tclass.fields().iter().enumerate() {
+            if other_columns.contains(&column) {
+                continue;
+            }
+            extra_columns.push(column);
+            if self.tclass.fieldnames().contains(&field.name()) {
+                let name = format!(
+                    "{}_{}",
+                    other.ttype().to_lowercase(),
+                    field.name()
+                );
+                fields.push(Field::new(
+                    &name,
+                    field.vtype().unwrap_or_default(),
+                )?);
+            } else {
+                fields.push(field.clone());
+            }
+        }
+        let tclass = TClass::new(ttype, fields, "")?;
+        let mut rows_for_key: HashMap<Vec<String>, Vec<usize>> =
+            HashMap::new();
+        for (row, record) in other.records.iter().enumerate() {
+            if let Some(key) = join_key(record, &other_columns) {
+                rows_for_key.entry(key).or_default().push(row);
+            }
+        }
+        let mut table = Table::new(tclass, "");
+        for record in self.records.iter() {
+            let rows = join_key(record, &columns)
+                .and_then(|key| rows_for_key.get(&key));
+            match rows {
+                Some(rows) => {
+                    for row in rows {
+                        let other_record = &other.records[*row];
+                        let mut joined = record.clone();
+                        for column in &extra_columns {
+                            joined.push(other_record[*column].clone());
+                        }
+                        table.records.push(joined);
+                    }
+                }
+                None if left => {
+                    let mut joined = record.clone();
+                    joined.resize(table.ttype_len(), Value::Null);
+                    table.records.push(joined);
+                }
+                None => (),
+            }
+        }
+        Ok(table)
+    }
+
+    fn check_record_len(
+        &self,
+        record: &Record,
+        filename: &str,
+        lino: usize,
+    ) -> Result<()> {
+        if record.len() != self.tclass.len() {
+            bail!(
+                "E736:{}:{}:rows for table of ttype {} must have exactly \
+                {} values, got {}",
+                filename,
+                lino,
+                self.ttype(),
+                self.tclass.len(),
+                record.len(),
+            )
+        }
+        Ok(())
+    }
+
+    /// Returns the columns for the given fieldnames _or_ returns an Err
+    /// if any of the fieldnames isn't one of the table's fields.
+    fn columns_for(&self, fieldnames: &[&str]) -> Result<Vec<usize>> {
+        fieldnames
+            .iter()
+            .map(|fieldname| {
+                self.tclass.try_column_for_fieldname(fieldname)
+            })
+            .collect()
+    }
+
     /// Returns `true` if this `Table` and the `other` `Table` are the same.
     /// Set `compare` to `EQUIVALENT` or `IGNORE_COMMENTS` if comment
     /// differences don't matter.
@@ -403,6 +1066,23 @@ impl Table {
     }
 }
 
+/// Returns a hashable key for matching the values in the given columns
+/// (values only match if they have the same type and value) _or_ `None`
+/// if any of the values is `null` (since `null`s never match).
+fn join_key(record: &Record, columns: &[usize]) -> Option<Vec<String>> {
+    columns
+        .iter()
+        .map(|column| {
+            let value = &record[*column];
+            if value.is_null() {
+                None
+            } else {
+                Some(format!("{}:{value}", value.typename()))
+            }
+        })
+        .collect()
+}
+
 impl Index<usize> for Table {
     type Output = Record;
 
@@ -415,6 +1095,7 @@ impl Index<usize> for Table {
 impl IndexMut<usize> for Table {
     /// Returns `&mut Record` if `row` is in bounds; otherwise panics.
     fn index_mut(&mut self, row: usize) -> &mut Self::Output {
+        self.invalidate_key_index();
         &mut self.records[row]
     }
 }
@@ -9,19 +9,53 @@ comment.
 
 The easiest way to programmatically create a TClass is to use the
 make_tclass() function.
+
+A TClass may declare which of its fields form its tables' unique key (see
+`TClass::set_key()`). In the text form the key is written as the last line
+of the TClass's comment, of the form `key: fieldname1 fieldname2 ...`,
+e.g., `=#<key: id> Person id:int name:str`, so the declaration is valid
+UXF for any UXF processor. When parsed, such a line (if it only names the
+TClass's fields) becomes the TClass's key rather than part of its comment;
+and when parsed with `ParserOptions::TABLE_KEYS` the tables of such a
+TClass are indexed by their key fields (see `Table::set_key()`).
+
+A TClass may instead be an _enum_, i.e., a ttype whose values are one of
+a fixed set of fieldless tables (its _members_). For example, given
+`|State Pending Active Finished`, a field (or list or map vtype) of type
+`State` may only hold `(Pending)`, `(Active)`, `(Finished)`, or `?`.
+Enums are a UXF language extension, so they are only accepted when
+parsing with `ParserOptions::ENUMS`. Use `TClass::new_enum()` to create
+an enum and `TClass::member()` to create one of its values.
+
+```
+let state = uxf::TClass::new_enum("State", &["Pending", "Active"], "")
+    .unwrap();
+assert!(state.is_enum() && state.has_member("Active"));
+let active = state.member("Active").unwrap();
+assert_eq!(active.to_string(), "(Active)");
+assert_eq!(state.to_string(), "|State Pending Active");
+```
 */
 use crate::check::check_ttype;
 use crate::field::{check_fields, make_field, Field};
+use crate::table::Table;
 use crate::util::escape;
 use crate::value::{Record, Value};
 use anyhow::{bail, Result};
 use std::{
     cmp::Ordering,
-    collections::{HashMap, VecDeque},
+    collections::{HashMap, HashSet, VecDeque},
     fmt,
     fmt::Write as _,
 };
 
+/// The prefix of the TClass comment line that declares a table key in
+/// the text form.
+pub const KEY_DECLARATION_PREFIX: &str = "key:";
+
+/// The character that introduces an enum definition (e.g., `|State A B`).
+pub const ENUM_INTRODUCER: char = '|';
+
 /// Convenience method for making a TClass from a UXF ttype definition,
 /// e.g., `let tclass = make_tclass("=Point x:real y:real").unwrap();`.
 /// The leading `=` is optional, but the rest must be a valid UXF ttype
@@ -65,7 +99,9 @@ pub struct TClass {
     ttype: String,
     fields: Vec<Field>,
     comment: String,
-    columns_for_names_cache: Option<HashMap<String, usize>>,
+    column_for_name: HashMap<String, usize>,
+    members: Vec<String>, // nonempty iff this is an enum
+    key: Vec<String>,     // the key's fieldnames (empty if none)
 }
 
 impl TClass {
@@ -82,11 +118,18 @@ impl TClass {
     ) -> Result<Self> {
         check_ttype(ttype)?;
         check_fields(&fields)?;
+        let column_for_name = fields
+            .iter()
+            .enumerate()
+            .map(|(column, field)| (field.name().to_string(), column))
+            .collect();
         Ok(TClass {
             ttype: ttype.to_string(),
             fields,
             comment: comment.to_string(),
-            columns_for_names_cache: None,
+            column_for_name,
+            members: vec![],
+            key: vec![],
         })
     }
 
@@ -99,10 +142,84 @@ impl TClass {
             ttype: ttype.to_string(),
             fields: vec![],
             comment: comment.to_string(),
-            columns_for_names_cache: None,
+            column_for_name: HashMap::new(),
+            members: vec![],
+            key: vec![],
+        })
+    }
+
+    /// Creates a new enum `TClass` with the given `ttype`, `members` (each
+    /// of which is the ttype of a fieldless table), and `comment` _or_
+    /// returns an Err if the `ttype` or any member is invalid, if there
+    /// are no members, or if there are duplicate members.
+    /// `TClass` instances are immutable.
+    pub fn new_enum(
+        ttype: &str,
+        members: &[&str],
+        comment: &str,
+    ) -> Result<Self> {
+        check_ttype(ttype)?;
+        if members.is_empty() {
+            bail!("E525:-:0:enum {ttype} must have at least one member")
+        }
+        let mut seen = HashSet::new();
+        for member in members {
+            check_ttype(member)?;
+            if *member == ttype || !seen.insert(*member) {
+                bail!("E527:-:0:enum {ttype} has duplicate member {member}")
+            }
+        }
+        Ok(TClass {
+            ttype: ttype.to_string(),
+            fields: vec![],
+            comment: comment.to_string(),
+            column_for_name: HashMap::new(),
+            members: members.iter().map(|m| m.to_string()).collect(),
+            key: vec![],
         })
     }
 
+    /// Returns `true` if this is an enum; otherwise returns `false`.
+    /// (An enum has no fields so `is_fieldless()` is also `true`.)
+    pub fn is_enum(&self) -> bool {
+        !self.members.is_empty()
+    }
+
+    /// Returns the enum's members' ttypes (which will be empty if this
+    /// isn't an enum).
+    pub fn members(&self) -> Vec<&str> {
+        self.members.iter().map(|m| m.as_str()).collect()
+    }
+
+    /// Returns `true` if this is an enum with the given `member`;
+    /// otherwise returns `false`.
+    pub fn has_member(&self, member: &str) -> bool {
+        self.members.iter().any(|m| m == member)
+    }
+
+    /// Returns the fieldless `TClass`es of the enum's members (which will
+    /// be empty if this isn't an enum).
+    pub fn member_tclasses(&self) -> Vec<TClass> {
+        self.members
+            .iter()
+            .map(|m| TClass::new_fieldless(m, "").unwrap()) // checked
+            .collect()
+    }
+
+    /// Returns the enum value for the given `member`, i.e., an empty
+    /// fieldless table of the member's ttype, _or_ returns an Err if
+    /// this isn't an enum with the given `member`.
+    pub fn member(&self, member: &str) -> Result<Value> {
+        if !self.has_member(member) {
+            bail!(
+                "E529:-:0:{} is not a member of enum {}",
+                member,
+                self.ttype
+            )
+        }
+        Ok(Value::from(Table::new(TClass::new_fieldless(member, "")?, "")))
+    }
+
     /// Returns `true` fieldless; otherwise returns `false`.
     pub fn is_fieldless(&self) -> bool {
         self.fields.is_empty()
@@ -123,6 +240,60 @@ impl TClass {
         self.comment = comment.to_string()
     }
 
+    /// Returns the names of the fields declared as the TClass's key
+    /// (which will be empty if there's no declaration).
+    pub fn key(&self) -> Vec<&str> {
+        self.key.iter().map(|name| name.as_str()).collect()
+    }
+
+    /// Declares the given `fieldnames` as the TClass's key (or removes the
+    /// declaration if `fieldnames` is empty) _or_ returns an Err if any of
+    /// the `fieldnames` isn't one of the TClass's fields.
+    pub fn set_key(&mut self, fieldnames: &[&str]) -> Result<()> {
+        for fieldname in fieldnames {
+            self.try_column_for_fieldname(fieldname)?;
+        }
+        self.key = fieldnames.iter().map(|name| name.to_string()).collect();
+        Ok(())
+    }
+
+    /// Returns the comment as it is written in the text form, i.e., with
+    /// a key declaration line appended if the TClass has a key.
+    pub(crate) fn text_comment(&self) -> String {
+        if self.key.is_empty() {
+            self.comment.clone()
+        } else if self.comment.is_empty() {
+            format!("{KEY_DECLARATION_PREFIX} {}", self.key.join(" "))
+        } else {
+            format!(
+                "{}\n{KEY_DECLARATION_PREFIX} {}",
+                self.comment,
+                self.key.join(" ")
+            )
+        }
+    }
+
+    /// If the comment has a key declaration line (see `text_comment()`),
+    /// makes it the TClass's key and removes it from the comment _or_
+    /// returns an Err (leaving the TClass unchanged) if it names a field
+    /// the TClass doesn't have.
+    pub(crate) fn take_key_declaration(&mut self) -> Result<()> {
+        let comment = self.comment.clone();
+        let mut lines: Vec<&str> = comment.lines().collect();
+        for (i, line) in lines.iter().enumerate() {
+            if let Some(names) = line.strip_prefix(KEY_DECLARATION_PREFIX) {
+                let key: Vec<&str> = names.split_whitespace().collect();
+                if !key.is_empty() {
+                    self.set_key(&key)?;
+                    lines.remove(i);
+                    self.comment = lines.join("\n");
+                    break;
+                }
+            }
+        }
+        Ok(())
+    }
+
     /// Returns the `fields` (which will be empty if `is_fieldless()`).
     pub fn fields(&self) -> &Vec<Field> {
         &self.fields
@@ -174,21 +345,22 @@ impl TClass {
     /// Returns the column for the given fieldname.
     /// More robust in the face of change than using column indexes
     /// directly.
-    pub fn column_for_fieldname(
-        &mut self,
+    pub fn column_for_fieldname(&self, fieldname: &str) -> Option<usize> {
+        self.column_for_name.get(fieldname).copied()
+    }
+
+    /// Returns the column for the given fieldname _or_ returns an Err if
+    /// the fieldname isn't one of the TClass's fields.
+    pub(crate) fn try_column_for_fieldname(
+        &self,
         fieldname: &str,
-    ) -> Option<usize> {
-        if self.columns_for_names_cache.is_none() {
-            let mut named = HashMap::new();
-            for (i, field) in self.fields.iter().enumerate() {
-                named.insert(field.name().to_string(), i);
-            }
-            self.columns_for_names_cache = Some(named);
-        }
-        if let Some(named) = &self.columns_for_names_cache {
-            named.get(fieldname).copied()
-        } else {
-            None
+    ) -> Result<usize> {
+        match self.column_for_fieldname(fieldname) {
+            Some(column) => Ok(column),
+            None => bail!(
+                "E740:-:0:table of ttype {} has no field {fieldname}",
+                self.ttype
+            ),
         }
     }
 }
@@ -204,7 +376,9 @@ impl Ord for TClass {
             self.ttype.cmp(&other.ttype)
         } else {
             // identical names names so use fields to tie-break
-            self.fields.cmp(&other.fields)
+            self.fields
+                .cmp(&other.fields)
+                .then_with(|| self.members.cmp(&other.members))
         }
     }
 }
@@ -217,16 +391,23 @@ impl PartialOrd for TClass {
 
 impl PartialEq for TClass {
     fn eq(&self, other: &Self) -> bool {
-        self.ttype == other.ttype && self.fields == other.fields
+        self.ttype == other.ttype
+            && self.fields == other.fields
+            && self.members == other.members
     }
 }
 
 impl fmt::Display for TClass {
     /// Provides a .to_string() that returns a valid UXF fragment
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        let mut s = String::from("=");
-        if !self.comment().is_empty() {
-            let _ = write!(s, "#<{}> ", escape(self.comment()));
+        let mut s = String::from(if self.is_enum() {
+            ENUM_INTRODUCER
+        } else {
+            '='
+        });
+        let comment = self.text_comment();
+        if !comment.is_empty() {
+            let _ = write!(s, "#<{}> ", escape(&comment));
         }
         s.push_str(&self.ttype);
         if !self.is_fieldless() {
@@ -234,16 +415,36 @@ impl fmt::Display for TClass {
                 let _ = write!(s, " {}", &field);
             }
         }
+        for member in &self.members {
+            let _ = write!(s, " {member}");
+        }
         write!(f, "{}", s)
     }
 }
 
+/// Returns `true` if the given `tclass` is just the implicit definition of
+/// a member of one of the enums in `tclass_for_ttype` (and so needn't be
+/// output); otherwise returns `false`.
+pub(crate) fn is_implicit_enum_member(
+    tclass: &TClass,
+    tclass_for_ttype: &HashMap<String, TClass>,
+) -> bool {
+    tclass.is_fieldless()
+        && !tclass.is_enum()
+        && tclass.comment().is_empty()
+        && tclass_for_ttype
+            .values()
+            .any(|other| other.has_member(tclass.ttype()))
+}
+
 /// This allows us to build up a TClass incrementally since a real
 /// TClass is immutable.
 pub struct TClassBuilder {
     pub ttype: String,
     fields: Vec<Field>,
     comment: String,
+    pub is_enum: bool,
+    members: Vec<String>,
 }
 
 impl TClassBuilder {
@@ -252,6 +453,8 @@ impl TClassBuilder {
             ttype: ttype.to_string(),
             fields: vec![],
             comment: comment.to_string(),
+            is_enum: false,
+            members: vec![],
         }
     }
 
@@ -264,6 +467,8 @@ impl TClassBuilder {
         self.ttype = "".to_string();
         self.comment = "".to_string();
         self.fields.clear();
+        self.is_enum = false;
+        self.members.clear();
     }
 
     pub fn is_valid(&self) -> bool {
@@ -283,7 +488,16 @@ impl TClassBuilder {
         Ok(())
     }
 
+    pub fn append_member(&mut self, member: &str) {
+        self.members.push(member.to_string());
+    }
+
     pub fn build(&self) -> Result<TClass> {
+        if self.is_enum {
+            let members: Vec<&str> =
+                self.members.iter().map(|m| m.as_str()).collect();
+            return TClass::new_enum(&self.ttype, &members, &self.comment);
+        }
         check_fields(&self.fields)?;
         TClass::new(&self.ttype, self.fields.clone(), &self.comment)
     }
@@ -295,6 +509,8 @@ impl Default for TClassBuilder {
             ttype: "".to_string(),
             fields: vec![],
             comment: "".to_string(),
+            is_enum: false,
+            members: vec![],
         }
     }
 }
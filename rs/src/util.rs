@@ -2,15 +2,21 @@
 // License: GPLv3
 
 /*! This module provides some useful UXF utility functions. */
+use crate::codec::Codec;
 use crate::consts::*;
+use crate::uxf::WriteOptions;
 use anyhow::{bail, Context, Result};
-use flate2::read::GzDecoder;
 use std::{
-    fs::File,
-    io::{prelude::*, BufReader},
-    path::{self, PathBuf},
+    fs::{self, File, OpenOptions},
+    io::{self, prelude::*, BufReader},
+    path::{self, Path, PathBuf},
+    process,
+    sync::atomic::{AtomicUsize, Ordering},
+    time::{SystemTime, UNIX_EPOCH},
 };
 
+const MAX_TEMP_TRIES: usize = 100;
+
 /// Returns a clone of `s` with replacements & → &amp; < → &lt; > → &gt;
 pub fn escape(s: &str) -> String {
     s.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;")
@@ -41,41 +47,229 @@ pub fn isclose64(a: f64, b: f64) -> bool {
 /// or to end with ".0".
 pub fn realstr64(x: f64) -> String {
     let mut s = x.to_string();
-    if !s.contains(&['.', 'e', 'E']) {
+    if !s.contains(['.', 'e', 'E']) {
         s.push_str(".0");
     }
     s
 }
 
+/// Splits an `"Ecode:filename:lino:message"` error text into its parts,
+/// e.g., `(500, "file.uxf", 2, "message")`; returns `(0, "", 0, text)` if
+/// the text isn't in this form.
+pub fn split_error(text: &str) -> (u16, &str, usize, &str) {
+    let parts: Vec<&str> = text.splitn(4, ':').collect();
+    if parts.len() == 4 && parts[0].starts_with('E') {
+        if let (Ok(code), Ok(lino)) =
+            (parts[0][1..].parse::<u16>(), parts[2].parse::<usize>())
+        {
+            return (code, parts[1], lino, parts[3]);
+        }
+    }
+    (0, "", 0, text)
+}
+
 /// Returns the entire text of the given file which is either plain text
-/// or gzipped plain text (UTF-8 encoded).
-pub(crate) fn read_file(filename: &str) -> Result<String> {
-    let compressed = is_compressed(filename)?;
-    let mut text = String::new();
-    let file = File::open(&filename)
-        .with_context(|| format!("E950:{filename}:0:failed to open"))?;
-    if compressed {
-        let mut gz = GzDecoder::new(file);
-        gz.read_to_string(&mut text).with_context(|| {
-            format!("E951:{filename}:0:failed to read gzipped")
+/// or compressed plain text (UTF-8 encoded, possibly as several
+/// concatenated compressed streams, e.g., from appending). The compression
+/// codec (if any) is detected from the file's magic bytes (see the `codec`
+/// module).
+pub fn read_file(filename: &str) -> Result<String> {
+    String::from_utf8(read_bytes(filename)?)
+        .with_context(|| format!("E952:{filename}:0:failed to read"))
+}
+
+/// Returns the entire contents of the given file, decompressed if it is
+/// compressed (see `read_file()`).
+pub fn read_bytes(filename: &str) -> Result<Vec<u8>> {
+    let mut bytes = vec![];
+    let file = File::open(filename)?;
+    BufReader::new(file)
+        .read_to_end(&mut bytes)
+        .with_context(|| format!("E952:{filename}:0:failed to read"))?;
+    Codec::for_bytes(&bytes).decompress(&bytes, filename)
+}
+
+/// Writes the given text to the given file (compressing if the filename
+/// ends with `.gz`, etc.; see the `codec` module) atomically, using the
+/// default `WriteOptions` and compression level (see
+/// `write_file_options()`).
+pub(crate) fn write_file(filename: &str, text: &str) -> Result<()> {
+    write_file_options(filename, text, WriteOptions::DEFAULT, None)
+}
+
+/// Writes the given text to the given file (compressing if the filename
+/// ends with `.gz`, `.zst`, `.xz`, or `.bz2`, using the given compression
+/// `level` or the codec's default level if `None`; see the `codec`
+/// module) atomically: the text is written to a temporary file in the
+/// same folder which is flushed to disk and then renamed to the given
+/// filename, so the file is either completely replaced or (e.g., if the
+/// disk is full) left unchanged.
+/// If the file exists and `options` includes `BACKUP`, the file is first
+/// copied to `filename.bak`. The new file is given the existing file's
+/// permissions (if any) unless `options` includes `DEFAULT_PERMISSIONS`.
+/// (On Unix the temporary file is private (0600) until it has been
+/// written, and if the default permissions for a new file can't be
+/// determined, it stays private.)
+/// (If the filename is a symlink, the file it links to is replaced.)
+pub fn write_file_options(
+    filename: &str,
+    text: &str,
+    options: WriteOptions,
+    level: Option<u32>,
+) -> Result<()> {
+    write_bytes(
+        filename,
+        &compressed(filename, text.as_bytes(), level)?,
+        options,
+    )
+}
+
+/// Returns the bytes compressed as a single stream using the codec for
+/// the given filename's suffix (or as-is if the suffix isn't for a
+/// compressed file).
+pub(crate) fn compressed(
+    filename: &str,
+    bytes: &[u8],
+    level: Option<u32>,
+) -> Result<Vec<u8>> {
+    Codec::for_filename(filename).compress(bytes, level, filename)
+}
+
+/// Writes the given bytes to the given file atomically (see
+/// `write_file_options()`).
+pub(crate) fn write_bytes(
+    filename: &str,
+    bytes: &[u8],
+    options: WriteOptions,
+) -> Result<()> {
+    let mut path = PathBuf::from(filename);
+    if path.is_symlink() {
+        path = path.canonicalize().with_context(|| {
+            format!("E903:{filename}:0:failed to resolve symlink")
         })?;
-    } else {
-        let mut buffer = BufReader::new(file);
-        buffer
-            .read_to_string(&mut text)
-            .with_context(|| format!("E952:{filename}:0:failed to read"))?;
     }
-    Ok(text)
+    let name = match path.file_name() {
+        Some(name) => name.to_string_lossy().to_string(),
+        None => bail!("E903:{filename}:0:invalid filename"),
+    };
+    let folder = match path.parent() {
+        Some(folder) if !folder.as_os_str().is_empty() => folder,
+        _ => Path::new("."),
+    };
+    let (file, temp) = create_temp(folder, &name).with_context(|| {
+        format!("E904:{filename}:0:failed to create temporary file")
+    })?;
+    let reply = write_temp(file, &temp, filename, bytes, options, &path);
+    if reply.is_err() {
+        let _ = fs::remove_file(&temp);
+    }
+    reply?;
+    // Make the rename durable (not supported on every platform)
+    if let Ok(folder) = File::open(folder) {
+        let _ = folder.sync_all();
+    }
+    Ok(())
 }
 
-/// Returns true if the given file is gzip compressed; otherwise false.
-pub(crate) fn is_compressed(filename: &str) -> Result<bool> {
-    let mut file = File::open(&filename)?;
-    let mut buffer = [0; 2]; // 0x1F 0x8B gzip magic
-    file.read_exact(&mut buffer).with_context(|| {
-        format!("E953:{filename}:0:failed to read start")
-    })?;
-    Ok(buffer[0] == 0x1F && buffer[1] == 0x8B)
+// Creates a new temp file in the folder with a unique name (so it is never
+// shared by concurrent writes, nor is an existing file or symlink opened),
+// retrying if the name is taken. On Unix it is created private (0600) so
+// that its data is never more widely readable than the file it replaces.
+fn create_temp(folder: &Path, name: &str) -> io::Result<(File, PathBuf)> {
+    static COUNTER: AtomicUsize = AtomicUsize::new(0);
+    for _ in 0..MAX_TEMP_TRIES {
+        let nanos = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |duration| duration.subsec_nanos());
+        let temp = folder.join(format!(
+            ".{name}.{}.{}{nanos:08x}.tmp",
+            process::id(),
+            COUNTER.fetch_add(1, Ordering::Relaxed)
+        ));
+        let mut options = OpenOptions::new();
+        options.write(true).create_new(true);
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::OpenOptionsExt;
+            options.mode(0o600);
+        }
+        match options.open(&temp) {
+            Ok(file) => return Ok((file, temp)),
+            Err(err) if err.kind() == io::ErrorKind::AlreadyExists => (),
+            Err(err) => return Err(err),
+        }
+    }
+    Err(io::Error::new(
+        io::ErrorKind::AlreadyExists,
+        "failed to find an unused temporary filename",
+    ))
+}
+
+// Writes and syncs the temp file then renames it to path.
+fn write_temp(
+    mut file: File,
+    temp: &Path,
+    filename: &str,
+    bytes: &[u8],
+    options: WriteOptions,
+    path: &Path,
+) -> Result<()> {
+    let metadata = fs::metadata(path).ok();
+    let exists = metadata.is_some();
+    let permissions = metadata
+        .filter(|_| !options.contains(WriteOptions::DEFAULT_PERMISSIONS))
+        .map(|metadata| metadata.permissions());
+    // On Unix set the permissions before writing any data (which works
+    // even if they are read-only since the file is already open)
+    #[cfg(unix)]
+    if let Some(permissions) = &permissions {
+        file.set_permissions(permissions.clone()).with_context(|| {
+            format!("E906:{filename}:0:failed to set permissions")
+        })?;
+    }
+    file.write_all(bytes)
+        .with_context(|| format!("E902:{filename}:0:failed to write"))?;
+    file.sync_all()
+        .with_context(|| format!("E905:{filename}:0:failed to sync"))?;
+    #[cfg(unix)]
+    if permissions.is_none() {
+        if let Some(mode) = default_mode() {
+            use std::os::unix::fs::PermissionsExt;
+            file.set_permissions(fs::Permissions::from_mode(mode))
+                .with_context(|| {
+                    format!("E906:{filename}:0:failed to set permissions")
+                })?;
+        }
+    }
+    drop(file);
+    #[cfg(not(unix))]
+    if let Some(permissions) = permissions {
+        fs::set_permissions(temp, permissions).with_context(|| {
+            format!("E906:{filename}:0:failed to set permissions")
+        })?;
+    }
+    if exists && options.contains(WriteOptions::BACKUP) {
+        let mut bak = path.as_os_str().to_owned();
+        bak.push(".bak");
+        fs::copy(path, &bak).with_context(|| {
+            format!("E907:{filename}:0:failed to write backup")
+        })?;
+    }
+    fs::rename(temp, path)
+        .with_context(|| format!("E908:{filename}:0:failed to replace"))?;
+    Ok(())
+}
+
+// Returns the permissions a new file gets by default (0666 less the
+// umask) if the umask is known (Linux reports it in /proc/self/status);
+// otherwise returns None, in which case a new file stays private (0600).
+#[cfg(unix)]
+fn default_mode() -> Option<u32> {
+    let status = fs::read_to_string("/proc/self/status").ok()?;
+    let umask =
+        status.lines().find_map(|line| line.strip_prefix("Umask:"))?;
+    let umask = u32::from_str_radix(umask.trim(), 8).ok()?;
+    Some(0o666 & !umask)
 }
 
 /// If filename is absolute, returns it as-is, otherwise returns the
@@ -139,6 +333,20 @@ pub(crate) fn hex_as_bytes(h: &str) -> Result<Vec<u8>> {
     Ok(raw)
 }
 
+/// Returns Ok if both `a` and `b` are valid indexes for a collection of
+/// length `len`; otherwise returns an Err.
+pub(crate) fn check_indexes(a: usize, b: usize, len: usize) -> Result<()> {
+    for index in [a, b] {
+        if index >= len {
+            bail!(
+                "E761:-:0:index {index} out of range for collection of \
+                length {len}"
+            )
+        }
+    }
+    Ok(())
+}
+
 pub trait PathBufExt {
     fn is_empty(&self) -> bool;
 }
@@ -22,24 +22,25 @@ formatting, or write() or write_format() to wite to a file.
 Uxf objects can be created entirely programmatically. And all the data
 in a Uxf object can be visited using the visit() method.
 */
+use crate::binary;
+use crate::check::typecheck;
 use crate::consts::*;
-use crate::event::{self, OnEventFn};
+use crate::event::{self, Event, OnEventFn};
 use crate::format::Format;
 use crate::list::List;
+use crate::map::MapOrder;
 use crate::parser;
 use crate::pprint;
-use crate::tclass::TClass;
-use crate::util::{escape, read_file};
+use crate::repair::RepairPolicy;
+use crate::tclass::{is_implicit_enum_member, TClass};
+use crate::util::{escape, read_file, write_file_options};
 use crate::value::{Value, Visit, Visitor};
-use anyhow::{bail, Context, Result};
+use anyhow::{bail, Result};
 use bitflags::bitflags;
-use flate2::{write::GzEncoder, Compression};
 use indexmap::map::IndexMap;
 use std::{
     collections::{HashMap, HashSet},
     fmt,
-    fs::File,
-    io::Write,
     rc::Rc,
 };
 
@@ -50,6 +51,7 @@ pub struct Uxf {
     value: Value, // NOTE must be Value::List | Value::Map | Value::Table
     pub(crate) tclass_for_ttype: HashMap<String, TClass>, // ttype x TClass
     pub(crate) import_for_ttype: IndexMap<String, String>, // ttype x import
+    pseudo_vtypes: bool, // accept key, number, and scalar as vtypes
 }
 
 impl Uxf {
@@ -62,6 +64,7 @@ impl Uxf {
             value: Value::List(List::default()),
             tclass_for_ttype: HashMap::new(),
             import_for_ttype: IndexMap::new(),
+            pseudo_vtypes: false,
         }
     }
 
@@ -85,6 +88,21 @@ impl Uxf {
         self.comment = comment.to_string();
     }
 
+    /// Returns `true` if the pseudo-vtypes `key`, `number`, and `scalar`
+    /// are accepted as vtypes when typechecking (e.g., if this `Uxf` was
+    /// parsed with `ParserOptions::PSEUDO_VTYPES`); otherwise they are
+    /// treated as _ttypes_.
+    pub fn pseudo_vtypes(&self) -> bool {
+        self.pseudo_vtypes
+    }
+
+    /// Use to accept (or not) the pseudo-vtypes when typechecking, e.g.,
+    /// for a programmatically created `Uxf` that uses them. (A `Uxf` that
+    /// uses them can only be parsed with `ParserOptions::PSEUDO_VTYPES`.)
+    pub fn set_pseudo_vtypes(&mut self, pseudo_vtypes: bool) {
+        self.pseudo_vtypes = pseudo_vtypes;
+    }
+
     /// The collection value. This defaults to an empty List.
     pub fn value(&self) -> &Value {
         &self.value
@@ -120,8 +138,14 @@ impl Uxf {
         self.tclass_for_ttype.get(ttype)
     }
 
-    /// Adds a new TClass
+    /// Adds a new TClass (and if it is an enum, a fieldless TClass for
+    /// each of its members that isn't already present)
     pub fn add_tclass(&mut self, tclass: TClass) {
+        for member in tclass.member_tclasses() {
+            self.tclass_for_ttype
+                .entry(member.ttype().to_string())
+                .or_insert(member);
+        }
         self.tclass_for_ttype.insert(tclass.ttype().to_string(), tclass);
     }
 
@@ -131,11 +155,38 @@ impl Uxf {
     /// method. For a full example, see the `pprint::tokenizer::tokenize()`
     /// function.
     pub fn visit(&self, visitor: Visitor) -> Result<()> {
+        self.visit_order(visitor, MapOrder::Sorted)
+    }
+
+    /// Iterates like `visit()` except that Map items are visited in the
+    /// given `order`; see Value::visit_order().
+    pub fn visit_order(
+        &self,
+        visitor: Visitor,
+        order: MapOrder,
+    ) -> Result<()> {
         (Rc::clone(&visitor))(Visit::UxfBegin, &self.comment().into())?;
-        self.value.visit(Rc::clone(&visitor))?;
+        self.value.visit_order(Rc::clone(&visitor), order)?;
         (Rc::clone(&visitor))(Visit::UxfEnd, &Value::Null)
     }
 
+    /// Checks every value against its collection's ktype, vtype, or
+    /// TClass, and checks that every ttype used is defined (and that every
+    /// table's TClass matches its definition), and returns a (possibly
+    /// empty) vec of warning events, one per problem found.
+    /// Use this before writing a programmatically created or modified
+    /// `Uxf` to ensure that it will parse.
+    pub fn typecheck(&self) -> Vec<Event> {
+        let mut problems = vec![];
+        typecheck(&self.value, "", "", Some(self), &mut problems);
+        problems
+            .iter()
+            .map(|(code, message)| {
+                Event::new_warning(*code, message, "-", 0)
+            })
+            .collect()
+    }
+
     /// Returns the text of a valid UXF file using the default human
     /// readable `Format` options.
     /// Use `to_string()` for compact output if human readability isn't
@@ -155,18 +206,32 @@ impl Uxf {
         pprint::to_text(self, format)
     }
 
-    /// Writes the Uxf's data to the specified filename (gzip-compressing if
-    /// the filename ends with `.gz`) using the default human readable
-    /// `Format` options and ignoring repair and warning events.
+    /// Returns the lossless binary encoding of this Uxf; see the `binary`
+    /// module.
+    pub fn to_binary(&self) -> Vec<u8> {
+        binary::to_binary(self)
+    }
+
+    /// Returns the Uxf encoded in the given bytes (as returned by
+    /// `to_binary()`) _or_ returns an Err if they aren't binary UXF.
+    pub fn from_binary(bytes: &[u8]) -> Result<Uxf> {
+        binary::from_binary(bytes)
+    }
+
+    /// Writes the Uxf's data to the specified filename (compressing if the
+    /// filename ends with `.gz`, etc.; see the `codec` module) using the
+    /// default human readable `Format` options and ignoring repair and
+    /// warning events.
     /// This is a convenience wrapper for
     /// `write_format(&Format::default())`
     pub fn write(&self, filename: &str) -> Result<()> {
         self.write_format(filename, &Format::default())
     }
 
-    /// Writes the Uxf's data to the specified filename (gzip-compressing if
-    /// the filename ends with `.gz`) using the given `Format`
-    /// options and ignoring repair and warning events.
+    /// Writes the Uxf's data to the specified filename (compressing if the
+    /// filename ends with `.gz`, etc., at the `Format`'s compression level;
+    /// see the `codec` module) using the given `Format` options and
+    /// ignoring repair and warning events.
     ///
     /// (For the most compact output without human friendly formatting, use
     /// `to_string()` and write the text returned to a file ending `.gz`
@@ -176,22 +241,25 @@ impl Uxf {
         filename: &str,
         format: &Format,
     ) -> Result<()> {
-        let mut file = File::create(filename)?;
-        let text = self.to_text_format(format);
-        if filename.ends_with(".gz") {
-            let mut out = GzEncoder::new(file, Compression::best());
-            out.write_all(text.as_bytes()).with_context(|| {
-                format!("E900:{filename}:0:failed to write gzipped")
-            })?;
-            out.finish().with_context(|| {
-                format!("E901:{filename}:0:failed to gzip")
-            })?;
-        } else {
-            file.write_all(text.as_bytes()).with_context(|| {
-                format!("E902:{filename}:0:failed to write")
-            })?
-        }
-        Ok(())
+        self.write_options(filename, format, WriteOptions::DEFAULT)
+    }
+
+    /// This is the same as `write_format()` except that it uses the given
+    /// write `options`, e.g., `WriteOptions::BACKUP` to keep a copy of the
+    /// existing file as `filename.bak`. (All writes are atomic: see
+    /// `util::write_file_options()`.)
+    pub fn write_options(
+        &self,
+        filename: &str,
+        format: &Format,
+        options: WriteOptions,
+    ) -> Result<()> {
+        write_file_options(
+            filename,
+            &self.to_text_format(format),
+            options,
+            format.compression_level,
+        )
     }
 
     /// Returns `true` if this `Uxf` and the `other` `Uxf` have the same
@@ -248,6 +316,7 @@ impl Default for Uxf {
             value: Value::List(List::default()),
             tclass_for_ttype: HashMap::new(),
             import_for_ttype: IndexMap::new(),
+            pseudo_vtypes: false,
         }
     }
 }
@@ -277,7 +346,7 @@ impl PartialEq for Uxf {
 impl Eq for Uxf {}
 
 impl fmt::Debug for Uxf {
-    fn fmt<'a>(&'a self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         f.debug_struct("Uxf")
             .field("custom", &self.custom)
             .field("comment", &self.comment)
@@ -314,7 +383,9 @@ impl fmt::Display for Uxf {
             self.tclass_for_ttype.values().cloned().collect();
         tclasses.sort_unstable(); // Use alphabetical order
         for tclass in tclasses.iter() {
-            if !self.import_for_ttype.contains_key(tclass.ttype()) {
+            if !self.import_for_ttype.contains_key(tclass.ttype())
+                && !is_implicit_enum_member(tclass, &self.tclass_for_ttype)
+            {
                 parts.push(tclass.to_string());
                 parts.push(NL.to_string());
             }
@@ -327,8 +398,8 @@ impl fmt::Display for Uxf {
 
 /// If `uxt_or_filename`' contains '\n` it is taken to be a UXF file
 /// in a string; otherwise it is taken to be the name of file (which
-/// may be gzipped if the filename ends `.gz`). In the latter case,
-/// the file's text is read.
+/// may be compressed, e.g., gzipped; see the `codec` module). In the
+/// latter case, the file's text is read.
 /// Then in either case the UXF text is parsed into a `Uxf` object if
 /// possible, using the default `on_event` event handler.
 /// This is just a convenience wrapper for
@@ -339,17 +410,41 @@ pub fn parse(uxt_or_filename: &str) -> Result<Uxf> {
 
 /// If `uxt_or_filename`' contains '\n` it is taken to be a UXF file
 /// in a string; otherwise it is taken to be the name of file (which
-/// may be gzipped if the filename ends `.gz`). In the latter case,
-/// the file's text is read.
+/// may be compressed, e.g., gzipped; see the `codec` module). In the
+/// latter case, the file's text is read.
 /// Then in either case the UXF text is parsed into a `Uxf` object if
 /// possible, dropping unused _ttypes_ if `options` is `DROP_UNUSED_TTYPES`
 /// or `AS_STANDALONE` and replacing imports with the _ttypes_ they import
-/// if `options` is `REPLACE_IMPORTS` or `AS_STANDALONE` and using the given
-/// `on_event` event handler (or the default handler if `None`).
+/// if `options` is `REPLACE_IMPORTS` or `AS_STANDALONE`, keying the tables
+/// of any _ttypes_ that declare a key (see `TClass::key()`) if `options`
+/// includes `TABLE_KEYS` (in which case duplicate keys are errors),
+/// accepting enum definitions (e.g., `|State Pending Active Finished`; see
+/// `TClass::new_enum()`) if `options` includes `ENUMS`, accepting the
+/// pseudo-vtypes `key`, `number`, and `scalar` as vtypes if `options`
+/// includes `PSEUDO_VTYPES`, and using the given `on_event` event handler
+/// (or the default handler if `None`).
 pub fn parse_options(
     uxt_or_filename: &str,
     options: ParserOptions,
     on_event: Option<OnEventFn>,
+) -> Result<Uxf> {
+    parse_policy(
+        uxt_or_filename,
+        options,
+        &RepairPolicy::default(),
+        on_event,
+    )
+}
+
+/// This is the same as `parse_options()` except that it also accepts a
+/// `RepairPolicy` which determines, for each kind of repair, whether the
+/// parser silently repairs, repairs and reports an `Event` (the default),
+/// or fails with an error.
+pub fn parse_policy(
+    uxt_or_filename: &str,
+    options: ParserOptions,
+    repair_policy: &RepairPolicy,
+    on_event: Option<OnEventFn>,
 ) -> Result<Uxf> {
     let on_event = on_event.unwrap_or_else(|| Rc::new(event::on_event));
     let filename: &str;
@@ -361,7 +456,13 @@ pub fn parse_options(
         text = uxt_or_filename.to_string();
         filename = "-";
     }
-    parser::parse(&text, filename, options, Rc::clone(&on_event))
+    parser::parse(
+        &text,
+        filename,
+        options,
+        repair_policy,
+        Rc::clone(&on_event),
+    )
 }
 
 bitflags! {
@@ -372,5 +473,21 @@ bitflags! {
         const REPLACE_IMPORTS = 0b10;
         const AS_STANDALONE = Self::DROP_UNUSED_TTYPES.bits |
             Self::REPLACE_IMPORTS.bits;
+        const TABLE_KEYS = 0b100;
+        const ENUMS = 0b1000;
+        const PSEUDO_VTYPES = 0b1_0000;
+    }
+}
+
+bitflags! {
+    /// Options for writing files (see `util::write_file_options()`).
+    #[derive(Default)]
+    pub struct WriteOptions: u8 {
+        const DEFAULT = 0b00;
+        /// Copy the existing file (if any) to `filename.bak` first
+        const BACKUP = 0b01;
+        /// Give the new file the default permissions for a new file
+        /// rather than the existing file's permissions (if any)
+        const DEFAULT_PERMISSIONS = 0b10;
     }
 }
@@ -8,25 +8,58 @@ A Value may be a scalar or a collection (which in turn may hold scalars
 or collections, recursively). So a single Value may hold an arbitrarily
 complex data structure.
 */
+use crate::binary;
 use crate::consts::*;
+use crate::convert::FromValue;
 use crate::key::Key;
 use crate::list::List;
-use crate::map::Map;
+use crate::map::{Map, MapOrder};
 use crate::table::Table;
 use crate::tclass::TClass;
 use crate::util::{escape, isclose64, realstr64};
 use crate::uxf::Compare;
-use anyhow::Result;
+use anyhow::{bail, Result};
 use chrono::{NaiveDate, NaiveDateTime};
 use std::fmt::Write as _;
-use std::{cell::RefCell, fmt, rc::Rc};
+use std::{cell::RefCell, cmp::Ordering, fmt, rc::Rc};
 
 pub type Values = Vec<Value>; // For Lists
 pub type Record = Values; // For Tables
 pub type Visitor = Rc<dyn Fn(Visit, &Value) -> Result<()>>;
 
-#[derive(Clone, Debug)]
+/// One step in the path to a value within a collection: an index into a
+/// list or table, a map key, or a table field name. Used by
+/// `Value::insert_at()` etc., and by `cst::Document`.
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub enum Step {
+    Index(usize),
+    Key(Key),
+    Field(String),
+}
+
+impl From<usize> for Step {
+    fn from(index: usize) -> Self {
+        Step::Index(index)
+    }
+}
+
+impl From<Key> for Step {
+    fn from(key: Key) -> Self {
+        Step::Key(key)
+    }
+}
+
+impl From<&str> for Step {
+    /// Returns a `Step::Key` for the given str key. (For a table field
+    /// name use `Step::Field`.)
+    fn from(key: &str) -> Self {
+        Step::Key(Key::Str(key.to_string()))
+    }
+}
+
+#[derive(Clone, Debug, Default)]
 pub enum Value {
+    #[default]
     Null,
     Bool(bool),
     Bytes(Vec<u8>),
@@ -386,6 +419,242 @@ impl Value {
         }
     }
 
+    /// Returns this value converted to `T` _or_ an Err naming the expected
+    /// type and this value's `typename()` if it can't be converted.
+    /// See the `convert` module for the supported types.
+    ///
+    /// ```
+    /// let value = uxf::Value::from(7);
+    /// assert_eq!(value.get_as::<i64>().unwrap(), 7);
+    /// assert_eq!(value.get_as::<Option<u8>>().unwrap(), Some(7));
+    /// assert!(value.get_as::<String>().is_err());
+    /// ```
+    pub fn get_as<T: FromValue>(&self) -> Result<T> {
+        T::from_value(self)
+    }
+
+    /// Inserts the given `value` into this collection at the given step
+    /// and returns `None` (or for a map, the key's previous value if any)
+    /// _or_ returns an Err if the step is invalid for this value.
+    /// For a List use `Step::Index` (which may equal the list's length
+    /// to append), for a Table use `Step::Index` with a list of values for
+    /// the record, and for a Map use `Step::Key`.
+    pub fn insert_at(
+        &mut self,
+        at: &Step,
+        value: Value,
+    ) -> Result<Option<Value>> {
+        match (self, at) {
+            (Value::List(lst), Step::Index(index)) => {
+                lst.insert(*index, value)?;
+                Ok(None)
+            }
+            (Value::Table(t), Step::Index(row)) => match value {
+                Value::List(record) => {
+                    t.insert(*row, record.inner().clone())?;
+                    Ok(None)
+                }
+                _ => bail!(
+                    "E762:-:0:can only insert a list of values as a \
+                    table record, got {}",
+                    value.typename()
+                ),
+            },
+            (Value::Map(m), Step::Key(key)) => {
+                Ok(m.insert(key.clone(), value))
+            }
+            (this, at) => bail!(
+                "E762:-:0:can't insert at {at:?} in a {}",
+                this.typename()
+            ),
+        }
+    }
+
+    /// Removes and returns the value at the given step if this is a
+    /// collection and the step is valid for it; otherwise returns `None`.
+    /// For a List use `Step::Index`, for a Table use `Step::Index` (in
+    /// which case the record is returned as a list of values), and for a
+    /// Map use `Step::Key`.
+    pub fn remove_at(&mut self, at: &Step) -> Option<Value> {
+        match (self, at) {
+            (Value::List(lst), Step::Index(index)) => lst.remove(*index),
+            (Value::Table(t), Step::Index(row)) => {
+                t.remove(*row).map(|r| {
+                    let mut lst = List::default();
+                    lst.extend(r);
+                    Value::List(lst)
+                })
+            }
+            (Value::Map(m), Step::Key(key)) => m.remove(key),
+            _ => None,
+        }
+    }
+
+    /// Swaps the values at the given steps (list values, table records,
+    /// or the values of two map keys) _or_ returns an Err if either step
+    /// is invalid for this value.
+    pub fn swap_at(&mut self, a: &Step, b: &Step) -> Result<()> {
+        match (self, a, b) {
+            (Value::List(lst), Step::Index(a), Step::Index(b)) => {
+                lst.swap(*a, *b)
+            }
+            (Value::Table(t), Step::Index(a), Step::Index(b)) => {
+                t.swap(*a, *b)
+            }
+            (Value::Map(m), Step::Key(a), Step::Key(b)) => {
+                for key in [a, b] {
+                    if m.get(key).is_none() {
+                        bail!("E761:-:0:no item with key {key} in map")
+                    }
+                }
+                if a != b {
+                    let avalue = m.insert(a.clone(), Value::Null).unwrap();
+                    let bvalue = m.insert(b.clone(), avalue).unwrap();
+                    m.insert(a.clone(), bvalue);
+                }
+                Ok(())
+            }
+            (this, a, b) => bail!(
+                "E762:-:0:can't swap {a:?} and {b:?} in a {}",
+                this.typename()
+            ),
+        }
+    }
+
+    /// Keeps only those list values, map items, or table records for
+    /// which `predicate` returns `true` _or_ returns an Err if this isn't
+    /// a collection. The `predicate` is given each one's step (its index,
+    /// or its key) and its values (a list value or map item's value as a
+    /// one value slice, or a record's values).
+    ///
+    /// ```
+    /// let mut value = uxf::parse("uxf 1\n{<a> 1 <b> 2 <c> 3}\n")
+    ///     .unwrap()
+    ///     .value()
+    ///     .clone();
+    /// value.retain(|_, values| values[0].as_int() != Some(2)).unwrap();
+    /// assert_eq!(value.to_string(), "{<a> 1\n<c> 3}");
+    /// ```
+    pub fn retain<F>(&mut self, mut predicate: F) -> Result<()>
+    where
+        F: FnMut(&Step, &[Value]) -> bool,
+    {
+        let mut index = 0;
+        let mut keep = |values: &[Value]| {
+            index += 1;
+            predicate(&Step::Index(index - 1), values)
+        };
+        match self {
+            Value::List(lst) => {
+                lst.retain(|value| keep(std::slice::from_ref(value)))
+            }
+            Value::Table(t) => t.retain(|view| keep(view.record())),
+            Value::Map(m) => m.inner_mut().retain(|key, value| {
+                predicate(
+                    &Step::Key(key.clone()),
+                    std::slice::from_ref(value),
+                )
+            }),
+            _ => bail!("E762:-:0:can't retain in a {}", self.typename()),
+        }
+        Ok(())
+    }
+
+    /// Sorts the list values, map items, or table records using the
+    /// `compare` function _or_ returns an Err if this isn't a collection.
+    /// The `compare` function is given the step and values of each of the
+    /// two being compared (see `retain()`). The sort is stable.
+    pub fn sort_by<F>(&mut self, mut compare: F) -> Result<()>
+    where
+        F: FnMut(&Step, &[Value], &Step, &[Value]) -> Ordering,
+    {
+        match self {
+            Value::List(lst) => {
+                let values = lst.inner_mut();
+                let order = sorted_order(values.len(), |a, b| {
+                    compare(
+                        &Step::Index(a),
+                        std::slice::from_ref(&values[a]),
+                        &Step::Index(b),
+                        std::slice::from_ref(&values[b]),
+                    )
+                });
+                reorder(values, &order);
+            }
+            Value::Table(t) => {
+                let records = t.inner_mut();
+                let order = sorted_order(records.len(), |a, b| {
+                    compare(
+                        &Step::Index(a),
+                        &records[a],
+                        &Step::Index(b),
+                        &records[b],
+                    )
+                });
+                reorder(records, &order);
+            }
+            Value::Map(m) => m.inner_mut().sort_by(|akey, a, bkey, b| {
+                compare(
+                    &Step::Key(akey.clone()),
+                    std::slice::from_ref(a),
+                    &Step::Key(bkey.clone()),
+                    std::slice::from_ref(b),
+                )
+            }),
+            _ => bail!("E762:-:0:can't sort a {}", self.typename()),
+        }
+        Ok(())
+    }
+
+    /// Removes consecutive duplicate list values or table records _or_
+    /// returns an Err if this isn't a collection. (A map's keys are
+    /// unique so a map is unchanged.)
+    pub fn dedup(&mut self) -> Result<()> {
+        match self {
+            Value::List(lst) => lst.dedup(),
+            Value::Table(t) => t.dedup(),
+            Value::Map(_) => (),
+            _ => bail!("E762:-:0:can't dedup a {}", self.typename()),
+        }
+        Ok(())
+    }
+
+    /// Appends the values of the given `other` list to this list, or the
+    /// records of the `other` table to this table (see
+    /// `Table::extend()`), or inserts the items of the `other` map into
+    /// this map (replacing the values of any existing keys) _or_ returns
+    /// an Err if this and `other` aren't collections of the same kind (or
+    /// tables of the same ttype).
+    pub fn extend(&mut self, other: Value) -> Result<()> {
+        match (self, other) {
+            (Value::List(lst), Value::List(other)) => {
+                lst.extend(other.inner().iter().cloned())
+            }
+            (Value::Table(t), Value::Table(other)) => {
+                if t.ttype() != other.ttype() {
+                    bail!(
+                        "E763:-:0:can't extend a table of ttype {} with a \
+                        table of ttype {}",
+                        t.ttype(),
+                        other.ttype()
+                    )
+                }
+                t.extend(other.inner().iter().cloned())?
+            }
+            (Value::Map(m), Value::Map(other)) => {
+                for (key, value) in other.inner() {
+                    m.insert(key.clone(), value.clone());
+                }
+            }
+            (this, other) => bail!(
+                "E762:-:0:can't extend a {} with a {}",
+                this.typename(),
+                other.typename()
+            ),
+        }
+        Ok(())
+    }
+
     /// Returns `Some(&Value)` if this is a `Value::List` and `index` is
     /// in bounds; otherwise `None`.
     pub fn get(&self, index: usize) -> Option<&Value> {
@@ -406,6 +675,19 @@ impl Value {
         }
     }
 
+    /// Returns the lossless binary encoding of this value; see the
+    /// `binary` module.
+    pub fn to_binary(&self) -> Vec<u8> {
+        binary::value_to_binary(self)
+    }
+
+    /// Returns the value encoded in the given bytes (as returned by
+    /// `to_binary()`) _or_ returns an Err if they aren't a binary UXF
+    /// value.
+    pub fn from_binary(bytes: &[u8]) -> Result<Value> {
+        binary::value_from_binary(bytes)
+    }
+
     // Can't be vtype() because VALUE_NAME_NULL "null" is not a valid vtype
     /// Returns "null" if the Value is `Value::Null`; otherwise returns the
     /// Value's `vtype` (`bool`, `bytes', ... `table`).
@@ -428,13 +710,24 @@ impl Value {
     /// Iterates over this value and if it is a collection over every
     /// contained value, recursively, calling visitor() once for every
     /// value. List values and Table rows (and values within rows) are
-    /// visited in order; Map items are visited in key order, key, then
-    /// value, key, then value, etc. See also `Uxf::visit()`.
+    /// visited in order; Map items are visited in sorted key order, key,
+    /// then value, key, then value, etc. See also `Uxf::visit()` and
+    /// `visit_order()`.
     ///
     /// For a very short and simple example see the `Value::tclasses()`
     /// method. For a full example, see the `pprint::tokenizer::tokenize()`
     /// function.
     pub fn visit(&self, visitor: Visitor) -> Result<()> {
+        self.visit_order(visitor, MapOrder::Sorted)
+    }
+
+    /// Iterates like `visit()` except that Map items are visited in the
+    /// given `order`.
+    pub fn visit_order(
+        &self,
+        visitor: Visitor,
+        order: MapOrder,
+    ) -> Result<()> {
         match self {
             Value::List(lst) => {
                 (Rc::clone(&visitor))(Visit::ListBegin, self)?;
@@ -443,7 +736,7 @@ impl Value {
                         Visit::ListValueBegin,
                         &Value::Null,
                     )?;
-                    value.visit(Rc::clone(&visitor))?;
+                    value.visit_order(Rc::clone(&visitor), order)?;
                     (Rc::clone(&visitor))(
                         Visit::ListValueEnd,
                         &Value::Null,
@@ -453,7 +746,7 @@ impl Value {
             }
             Value::Map(m) => {
                 (Rc::clone(&visitor))(Visit::MapBegin, self)?;
-                for key in m.sorted_keys() {
+                for key in m.keys_in(order) {
                     (Rc::clone(&visitor))(
                         Visit::MapItemBegin,
                         &Value::Null,
@@ -461,7 +754,9 @@ impl Value {
                     // A key is never a collection
                     let key_value = Value::from(key.clone());
                     (Rc::clone(&visitor))(Visit::Value, &key_value)?;
-                    m.get(key).unwrap().visit(Rc::clone(&visitor))?;
+                    m.get(key)
+                        .unwrap()
+                        .visit_order(Rc::clone(&visitor), order)?;
                     (Rc::clone(&visitor))(Visit::MapItemEnd, &Value::Null)?;
                 }
                 (Rc::clone(&visitor))(Visit::MapEnd, &Value::Null)?;
@@ -474,7 +769,7 @@ impl Value {
                         &Value::Null,
                     )?;
                     for value in record.iter() {
-                        value.visit(Rc::clone(&visitor))?;
+                        value.visit_order(Rc::clone(&visitor), order)?;
                     }
                     (Rc::clone(&visitor))(
                         Visit::TableRecordEnd,
@@ -506,6 +801,44 @@ impl Value {
         tclasses.take()
     }
 
+    /// Compares this value with the `other` value using an extension of
+    /// `Key` ordering that covers every value:
+    /// `null` `<` `bool` `<` `bytes` `<` `date` `<` `datetime` `<` `int`
+    /// and `real` (compared numerically) `<` `str` (case-insensitive) `<`
+    /// `list` `<` `map` `<` `table`. Collections of the same type compare
+    /// as equal. Used for sorting tables by field (see
+    /// `Table::sort_by_fields()`).
+    pub fn cmp_key_order(&self, other: &Value) -> Ordering {
+        fn rank(value: &Value) -> u8 {
+            match value {
+                Value::Null => 0,
+                Value::Bool(_) => 1,
+                Value::Bytes(_) => 2,
+                Value::Date(_) => 3,
+                Value::DateTime(_) => 4,
+                Value::Int(_) | Value::Real(_) => 5,
+                Value::Str(_) => 6,
+                Value::List(_) => 7,
+                Value::Map(_) => 8,
+                Value::Table(_) => 9,
+            }
+        }
+        match (self, other) {
+            (Value::Bool(a), Value::Bool(b)) => a.cmp(b),
+            (Value::Bytes(a), Value::Bytes(b)) => a.cmp(b),
+            (Value::Date(a), Value::Date(b)) => a.cmp(b),
+            (Value::DateTime(a), Value::DateTime(b)) => a.cmp(b),
+            (Value::Int(a), Value::Int(b)) => a.cmp(b),
+            (Value::Int(a), Value::Real(b)) => (*a as f64).total_cmp(b),
+            (Value::Real(a), Value::Int(b)) => a.total_cmp(&(*b as f64)),
+            (Value::Real(a), Value::Real(b)) => a.total_cmp(b),
+            (Value::Str(a), Value::Str(b)) => {
+                a.to_lowercase().cmp(&b.to_lowercase())
+            }
+            _ => rank(self).cmp(&rank(other)),
+        }
+    }
+
     /// Returns `true` if this `Value` and the `other` `Value` are the same
     /// (or contain the same maps, lists, or tables, in the same order),
     /// Set `compare` to `EQUIVALENT` or `IGNORE_COMMENTS` if comment
@@ -537,12 +870,6 @@ impl Value {
     }
 }
 
-impl Default for Value {
-    fn default() -> Self {
-        Value::Null
-    }
-}
-
 impl fmt::Display for Value {
     /// Provides a .to_string() that returns a valid UXF fragment
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
@@ -771,6 +1098,25 @@ pub enum Visit {
     Value,
 }
 
+// Returns the indexes 0..len in the order given by a stable sort using
+// the compare function.
+fn sorted_order<F>(len: usize, mut compare: F) -> Vec<usize>
+where
+    F: FnMut(usize, usize) -> Ordering,
+{
+    let mut order: Vec<usize> = (0..len).collect();
+    order.sort_by(|a, b| compare(*a, *b));
+    order
+}
+
+// Reorders the items so that the new i-th item is the old order[i]-th.
+fn reorder<T>(items: &mut Vec<T>, order: &[usize]) {
+    let mut old: Vec<Option<T>> =
+        std::mem::take(items).into_iter().map(Some).collect();
+    // Safe to unwrap since order is a permutation of the indexes
+    items.extend(order.iter().map(|i| old[*i].take().unwrap()));
+}
+
 pub(crate) fn bytes_to_uxf(b: &[u8]) -> String {
     let mut s = String::from("(:");
     for x in b {
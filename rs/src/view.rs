@@ -0,0 +1,339 @@
+// Copyright © 2022 Mark Summerfield. All rights reserved.
+// License: GPLv3
+
+/*! A `RecordView` borrows a Table's record together with the Table's
+TClass to provide access to the record's values by field name, and a
+`RecordViewMut` does the same for editing a record in place.
+
+Unlike a `NamedRecord`, a view doesn't allocate: field names are looked up
+using the TClass's column map. Views are normally obtained from a Table
+using `Table::view()`, `Table::views()`, or `Table::view_mut()`.
+
+The typed getters (e.g., `get_int("x")`, `get_date("when")`) return an
+Err if there is no such field (or the record has no value for it) or if
+the field's value isn't of the requested type (including if it is
+`null`). Use `get()` for untyped (and
+nullable) access.
+
+```
+let uxo = uxf::parse("uxf 1\n=Point x:int y:int\n(Point 1 2 3 4)\n")
+    .unwrap();
+let table = uxo.value().as_table().unwrap();
+let total: i64 = table
+    .views()
+    .map(|view| view.get_int("x").unwrap() + view.get_int("y").unwrap())
+    .sum();
+assert_eq!(total, 10);
+```
+*/
+use crate::check::{first_problem, typecheck};
+use crate::consts::*;
+use crate::list::List;
+use crate::map::Map;
+use crate::table::Table;
+use crate::tclass::TClass;
+use crate::value::{Record, Value};
+use anyhow::{bail, Result};
+use chrono::{NaiveDate, NaiveDateTime};
+
+/// A read-only view of a record that provides access by field name.
+#[derive(Clone, Copy, Debug)]
+pub struct RecordView<'a> {
+    tclass: &'a TClass,
+    record: &'a Record,
+}
+
+impl<'a> RecordView<'a> {
+    /// Returns a new `RecordView` of the given `record` whose fields are
+    /// described by the given `tclass`.
+    pub fn new(tclass: &'a TClass, record: &'a Record) -> Self {
+        RecordView { tclass, record }
+    }
+
+    /// Returns the `TClass`.
+    pub fn tclass(&self) -> &'a TClass {
+        self.tclass
+    }
+
+    /// Returns the underlying `Record`.
+    pub fn record(&self) -> &'a Record {
+        self.record
+    }
+
+    /// Returns `Some(&Value)` if `fieldname` is valid; otherwise `None`.
+    pub fn get(&self, fieldname: &str) -> Option<&'a Value> {
+        self.tclass
+            .column_for_fieldname(fieldname)
+            .and_then(|column| self.record.get(column))
+    }
+
+    /// Returns the `bool` value of the given field _or_ an Err.
+    pub fn get_bool(&self, fieldname: &str) -> Result<bool> {
+        self.typed(fieldname, VTYPE_NAME_BOOL, Value::as_bool)
+    }
+
+    /// Returns the `bytes` value of the given field _or_ an Err.
+    pub fn get_bytes(&self, fieldname: &str) -> Result<&'a Vec<u8>> {
+        self.typed(fieldname, VTYPE_NAME_BYTES, Value::as_bytes)
+    }
+
+    /// Returns the `date` value of the given field _or_ an Err.
+    pub fn get_date(&self, fieldname: &str) -> Result<NaiveDate> {
+        self.typed(fieldname, VTYPE_NAME_DATE, Value::as_date)
+    }
+
+    /// Returns the `datetime` value of the given field _or_ an Err.
+    pub fn get_datetime(&self, fieldname: &str) -> Result<NaiveDateTime> {
+        self.typed(fieldname, VTYPE_NAME_DATETIME, Value::as_datetime)
+    }
+
+    /// Returns the `int` value of the given field _or_ an Err.
+    pub fn get_int(&self, fieldname: &str) -> Result<i64> {
+        self.typed(fieldname, VTYPE_NAME_INT, Value::as_int)
+    }
+
+    /// Returns the `real` value of the given field _or_ an Err.
+    pub fn get_real(&self, fieldname: &str) -> Result<f64> {
+        self.typed(fieldname, VTYPE_NAME_REAL, Value::as_real)
+    }
+
+    /// Returns the `str` value of the given field _or_ an Err.
+    pub fn get_str(&self, fieldname: &str) -> Result<&'a str> {
+        self.typed(fieldname, VTYPE_NAME_STR, Value::as_str)
+    }
+
+    /// Returns the `list` value of the given field _or_ an Err.
+    pub fn get_list(&self, fieldname: &str) -> Result<&'a List> {
+        self.typed(fieldname, VTYPE_NAME_LIST, Value::as_list)
+    }
+
+    /// Returns the `map` value of the given field _or_ an Err.
+    pub fn get_map(&self, fieldname: &str) -> Result<&'a Map> {
+        self.typed(fieldname, VTYPE_NAME_MAP, Value::as_map)
+    }
+
+    /// Returns the `table` value of the given field _or_ an Err.
+    pub fn get_table(&self, fieldname: &str) -> Result<&'a Table> {
+        self.typed(fieldname, VTYPE_NAME_TABLE, Value::as_table)
+    }
+
+    fn typed<T>(
+        &self,
+        fieldname: &str,
+        expected: &str,
+        convert: fn(&'a Value) -> Option<T>,
+    ) -> Result<T> {
+        let value = match self
+            .record
+            .get(self.tclass.try_column_for_fieldname(fieldname)?)
+        {
+            Some(value) => value,
+            None => bail!(missing_error(self.tclass, fieldname)),
+        };
+        match convert(value) {
+            Some(value) => Ok(value),
+            None => {
+                bail!(type_error(self.tclass, fieldname, expected, value))
+            }
+        }
+    }
+}
+
+/// A mutable view of a record that provides access by field name.
+#[derive(Debug)]
+pub struct RecordViewMut<'a> {
+    tclass: &'a TClass,
+    record: &'a mut Record,
+}
+
+impl<'a> RecordViewMut<'a> {
+    /// Returns a new `RecordViewMut` of the given `record` whose fields
+    /// are described by the given `tclass`.
+    pub fn new(tclass: &'a TClass, record: &'a mut Record) -> Self {
+        RecordViewMut { tclass, record }
+    }
+
+    /// Returns a read-only view of the record (to provide the typed
+    /// getters, e.g., `view().get_int("x")`).
+    pub fn view(&self) -> RecordView<'_> {
+        RecordView::new(self.tclass, self.record)
+    }
+
+    /// Returns the `TClass`.
+    pub fn tclass(&self) -> &'a TClass {
+        self.tclass
+    }
+
+    /// Returns `Some(&Value)` if `fieldname` is valid; otherwise `None`.
+    pub fn get(&self, fieldname: &str) -> Option<&Value> {
+        self.tclass
+            .column_for_fieldname(fieldname)
+            .and_then(|column| self.record.get(column))
+    }
+
+    /// Returns `Some(&mut Value)` if `fieldname` is valid; otherwise
+    /// `None`.
+    /// Use `set()` to ensure that the new value matches the field's
+    /// vtype.
+    pub fn get_mut(&mut self, fieldname: &str) -> Option<&mut Value> {
+        self.tclass
+            .column_for_fieldname(fieldname)
+            .and_then(|column| self.record.get_mut(column))
+    }
+
+    /// Sets the given field's value to `value` and returns the old value
+    /// _or_ returns an Err if there is no such field or if the value
+    /// doesn't match the field's vtype (or if it is a collection whose
+    /// contents don't match its own types). `null` is always accepted.
+    pub fn set<T: Into<Value>>(
+        &mut self,
+        fieldname: &str,
+        value: T,
+    ) -> Result<Value> {
+        let column = self.tclass.try_column_for_fieldname(fieldname)?;
+        let old = match self.record.get_mut(column) {
+            Some(old) => old,
+            None => bail!(missing_error(self.tclass, fieldname)),
+        };
+        let value = value.into();
+        let vtype =
+            self.tclass.fields()[column].vtype().unwrap_or_default();
+        let mut problems = vec![];
+        typecheck(
+            &value,
+            vtype,
+            &format!(".{fieldname}"),
+            None,
+            &mut problems,
+        );
+        first_problem(&problems)?;
+        Ok(std::mem::replace(old, value))
+    }
+
+    /// Returns the given field's `bool` value as a mutable _or_ an Err.
+    pub fn get_bool_mut(&mut self, fieldname: &str) -> Result<&mut bool> {
+        self.typed_mut(fieldname, VTYPE_NAME_BOOL, |value| match value {
+            Value::Bool(b) => Some(b),
+            _ => None,
+        })
+    }
+
+    /// Returns the given field's `bytes` value as a mutable _or_ an Err.
+    pub fn get_bytes_mut(
+        &mut self,
+        fieldname: &str,
+    ) -> Result<&mut Vec<u8>> {
+        self.typed_mut(fieldname, VTYPE_NAME_BYTES, |value| match value {
+            Value::Bytes(b) => Some(b),
+            _ => None,
+        })
+    }
+
+    /// Returns the given field's `date` value as a mutable _or_ an Err.
+    pub fn get_date_mut(
+        &mut self,
+        fieldname: &str,
+    ) -> Result<&mut NaiveDate> {
+        self.typed_mut(fieldname, VTYPE_NAME_DATE, |value| match value {
+            Value::Date(d) => Some(d),
+            _ => None,
+        })
+    }
+
+    /// Returns the given field's `datetime` value as a mutable _or_ an
+    /// Err.
+    pub fn get_datetime_mut(
+        &mut self,
+        fieldname: &str,
+    ) -> Result<&mut NaiveDateTime> {
+        self.typed_mut(
+            fieldname,
+            VTYPE_NAME_DATETIME,
+            |value| match value {
+                Value::DateTime(d) => Some(d),
+                _ => None,
+            },
+        )
+    }
+
+    /// Returns the given field's `int` value as a mutable _or_ an Err.
+    pub fn get_int_mut(&mut self, fieldname: &str) -> Result<&mut i64> {
+        self.typed_mut(fieldname, VTYPE_NAME_INT, |value| match value {
+            Value::Int(i) => Some(i),
+            _ => None,
+        })
+    }
+
+    /// Returns the given field's `real` value as a mutable _or_ an Err.
+    pub fn get_real_mut(&mut self, fieldname: &str) -> Result<&mut f64> {
+        self.typed_mut(fieldname, VTYPE_NAME_REAL, |value| match value {
+            Value::Real(r) => Some(r),
+            _ => None,
+        })
+    }
+
+    /// Returns the given field's `str` value as a mutable _or_ an Err.
+    pub fn get_str_mut(&mut self, fieldname: &str) -> Result<&mut String> {
+        self.typed_mut(fieldname, VTYPE_NAME_STR, |value| match value {
+            Value::Str(s) => Some(s),
+            _ => None,
+        })
+    }
+
+    /// Returns the given field's `list` value as a mutable _or_ an Err.
+    pub fn get_list_mut(&mut self, fieldname: &str) -> Result<&mut List> {
+        self.typed_mut(fieldname, VTYPE_NAME_LIST, Value::as_list_mut)
+    }
+
+    /// Returns the given field's `map` value as a mutable _or_ an Err.
+    pub fn get_map_mut(&mut self, fieldname: &str) -> Result<&mut Map> {
+        self.typed_mut(fieldname, VTYPE_NAME_MAP, Value::as_map_mut)
+    }
+
+    /// Returns the given field's `table` value as a mutable _or_ an Err.
+    pub fn get_table_mut(&mut self, fieldname: &str) -> Result<&mut Table> {
+        self.typed_mut(fieldname, VTYPE_NAME_TABLE, Value::as_table_mut)
+    }
+
+    fn typed_mut<'b, T>(
+        &'b mut self,
+        fieldname: &str,
+        expected: &str,
+        convert: fn(&'b mut Value) -> Option<&'b mut T>,
+    ) -> Result<&'b mut T> {
+        let column = self.tclass.try_column_for_fieldname(fieldname)?;
+        let tclass = self.tclass;
+        let value = match self.record.get_mut(column) {
+            Some(value) => value,
+            None => bail!(missing_error(tclass, fieldname)),
+        };
+        if value.typename() == expected {
+            Ok(convert(value).unwrap()) // Safe since the type matches
+        } else {
+            bail!(type_error(tclass, fieldname, expected, value))
+        }
+    }
+}
+
+// A view's record may have fewer values than its TClass has fields
+fn missing_error(tclass: &TClass, fieldname: &str) -> String {
+    format!(
+        "E752:-:0:record of ttype {} has no value for field {fieldname}",
+        tclass.ttype()
+    )
+}
+
+fn type_error(
+    tclass: &TClass,
+    fieldname: &str,
+    expected: &str,
+    value: &Value,
+) -> String {
+    format!(
+        "E751:-:0:field {fieldname} of table of ttype {}: expected {}, \
+        got {} {value}",
+        tclass.ttype(),
+        expected,
+        value.typename()
+    )
+}
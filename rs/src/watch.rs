@@ -0,0 +1,450 @@
+// Copyright © 2022 Mark Summerfield. All rights reserved.
+// License: GPLv3
+
+/*! A Watcher (available with the `watch` feature) reloads a UXF file
+whenever it, or any file it imports (directly or indirectly), changes, so
+that long-running programs can pick up edits to their (e.g., config)
+files without restarting.
+
+On Linux the Watcher uses inotify (watching the files' folders, so that
+editors which save by replacing a file are handled); on other platforms
+it polls the files' modification times.
+
+After each successful reload the new `Uxf` and a `Summary` of what
+changed structurally (ttypes added, removed, or changed, and the paths of
+values added, removed, or changed) are passed to the callback. If the
+changed file fails to parse, the last good `Uxf` is kept and the error
+is passed to the callback instead. (Any warnings or repairs are reported
+to the Watcher's `on_event` handler as usual.)
+
+```no_run
+use uxf::watch::Watcher;
+
+let mut watcher =
+    Watcher::new("app.conf", uxf::ParserOptions::DEFAULT, None).unwrap();
+watcher
+    .watch(|reply| {
+        match reply {
+            Ok((uxo, summary)) => println!("reloaded:\n{summary}"),
+            Err(err) => eprintln!("kept last good config: {err}"),
+        }
+        true // keep watching
+    })
+    .unwrap();
+```
+*/
+use crate::event::{self, OnEventFn};
+use crate::parser::{find_import, import_kind, imports, ImportKind};
+use crate::util::read_file;
+use crate::uxf::{parse_options, ParserOptions, Uxf};
+use crate::value::{Step, Value};
+use anyhow::Result;
+use std::{
+    collections::HashSet,
+    fmt,
+    path::{Path, PathBuf},
+    rc::Rc,
+    thread,
+    time::Duration,
+};
+
+#[cfg(target_os = "linux")]
+use inotify::{Inotify, WatchDescriptor, WatchMask};
+#[cfg(target_os = "linux")]
+use std::collections::HashMap;
+
+// How long to wait for a burst of file changes (e.g., an editor's save)
+// to settle before reloading
+const SETTLE: Duration = Duration::from_millis(50);
+#[cfg(not(target_os = "linux"))]
+const POLL: Duration = Duration::from_millis(250);
+
+/// The structural differences between two `Uxf`s. Paths are from the
+/// outermost collection (whose path is empty), using `Step::Field` for
+/// table fields.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Summary {
+    /// `true` if the custom string or the file comment changed
+    pub header_changed: bool,
+    pub ttypes_added: Vec<String>,
+    pub ttypes_removed: Vec<String>,
+    pub ttypes_changed: Vec<String>,
+    pub added: Vec<Vec<Step>>,
+    pub removed: Vec<Vec<Step>>,
+    /// The paths of values that changed (including collections whose
+    /// types or comments changed)
+    pub changed: Vec<Vec<Step>>,
+}
+
+impl Summary {
+    /// Returns `true` if there are no differences.
+    pub fn is_empty(&self) -> bool {
+        *self == Summary::default()
+    }
+}
+
+impl fmt::Display for Summary {
+    /// Returns one line per difference, prefixed with `+` (added), `-`
+    /// (removed), or `~` (changed), e.g., `~ <colors><bg>`.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.header_changed {
+            writeln!(f, "~ header")?;
+        }
+        for (prefix, ttypes) in [
+            ('+', &self.ttypes_added),
+            ('-', &self.ttypes_removed),
+            ('~', &self.ttypes_changed),
+        ] {
+            for ttype in ttypes {
+                writeln!(f, "{prefix} ={ttype}")?;
+            }
+        }
+        for (prefix, paths) in
+            [('+', &self.added), ('-', &self.removed), ('~', &self.changed)]
+        {
+            for path in paths {
+                writeln!(f, "{prefix} {}", path_text(path))?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Returns a `Summary` of the differences between the `old` and `new`
+/// `Uxf`s.
+pub fn summarize(old: &Uxf, new: &Uxf) -> Summary {
+    let mut summary = Summary {
+        header_changed: old.custom() != new.custom()
+            || old.comment() != new.comment(),
+        ..Default::default()
+    };
+    for (ttype, tclass) in &old.tclass_for_ttype {
+        match new.tclass_for_ttype.get(ttype) {
+            Some(new_tclass) if new_tclass != tclass => {
+                summary.ttypes_changed.push(ttype.to_string())
+            }
+            Some(_) => (),
+            None => summary.ttypes_removed.push(ttype.to_string()),
+        }
+    }
+    for ttype in new.tclass_for_ttype.keys() {
+        if !old.tclass_for_ttype.contains_key(ttype) {
+            summary.ttypes_added.push(ttype.to_string());
+        }
+    }
+    summary.ttypes_added.sort_unstable();
+    summary.ttypes_removed.sort_unstable();
+    summary.ttypes_changed.sort_unstable();
+    diff(old.value(), new.value(), &mut vec![], &mut summary);
+    summary
+}
+
+/// A Watcher holds the last good `Uxf` parsed from a file and reloads it
+/// when the file or any of its file imports change (see the module
+/// documentation).
+pub struct Watcher {
+    filename: String,
+    options: ParserOptions,
+    on_event: OnEventFn,
+    uxo: Uxf,
+    files: Vec<String>,
+    notifier: Notifier,
+}
+
+impl Watcher {
+    /// Parses the given UXF file (using the given `options` and
+    /// `on_event` handler, or the default handler if `None`) and returns
+    /// a Watcher for it _or_ returns an Err if the file can't be parsed
+    /// or watched.
+    pub fn new(
+        filename: &str,
+        options: ParserOptions,
+        on_event: Option<OnEventFn>,
+    ) -> Result<Self> {
+        let on_event = on_event.unwrap_or_else(|| Rc::new(event::on_event));
+        let filename = Path::new(filename)
+            .canonicalize()?
+            .to_string_lossy()
+            .to_string();
+        let uxo =
+            parse_options(&filename, options, Some(on_event.clone()))?;
+        let files = watched_files(&filename, options);
+        let mut notifier = Notifier::new()?;
+        notifier.register(&files)?;
+        Ok(Watcher { filename, options, on_event, uxo, files, notifier })
+    }
+
+    /// Returns the last good `Uxf`.
+    pub fn uxf(&self) -> &Uxf {
+        &self.uxo
+    }
+
+    /// Returns the (absolute) filenames of the watched file and of the
+    /// files it imports.
+    pub fn files(&self) -> &[String] {
+        &self.files
+    }
+
+    /// Reparses the file now, and returns a `Summary` of the changes from
+    /// the last good `Uxf` (which the new `Uxf` replaces) _or_ returns an
+    /// Err (keeping the last good `Uxf`) if the file can't be parsed.
+    pub fn reload(&mut self) -> Result<Summary> {
+        let uxo = parse_options(
+            &self.filename,
+            self.options,
+            Some(self.on_event.clone()),
+        )?;
+        let summary = summarize(&self.uxo, &uxo);
+        self.uxo = uxo;
+        self.files = watched_files(&self.filename, self.options);
+        self.notifier.register(&self.files)?;
+        Ok(summary)
+    }
+
+    /// Blocks, waiting for the watched files to change. After each change
+    /// the file is reloaded and the callback is called with either the new
+    /// `Uxf` and a `Summary` of the changes (unless there are none), or
+    /// with the error that prevented the reload. Stops when the callback
+    /// returns `false`, _or_ returns an Err if the files can't be
+    /// watched.
+    pub fn watch<F>(&mut self, mut on_change: F) -> Result<()>
+    where
+        F: FnMut(Result<(&Uxf, &Summary), &anyhow::Error>) -> bool,
+    {
+        loop {
+            self.notifier.wait(&self.files)?;
+            let keep_watching = match self.reload() {
+                Ok(summary) => {
+                    summary.is_empty()
+                        || on_change(Ok((&self.uxo, &summary)))
+                }
+                Err(err) => on_change(Err(&err)),
+            };
+            if !keep_watching {
+                return Ok(());
+            }
+        }
+    }
+}
+
+// Returns the full filename and the full filenames of all the files it
+// imports (directly or indirectly) that exist. Files that can't be read
+// or tokenized are still watched but contribute no imports.
+fn watched_files(filename: &str, options: ParserOptions) -> Vec<String> {
+    let enums = options.contains(ParserOptions::ENUMS);
+    let mut files = vec![filename.to_string()];
+    let mut i = 0;
+    while i < files.len() {
+        if let Ok(text) = read_file(&files[i]) {
+            let text: Vec<char> = text.chars().collect();
+            for import in
+                imports(&text, &files[i], enums).unwrap_or_default()
+            {
+                if import_kind(&import) != ImportKind::File {
+                    continue;
+                }
+                let (import, _) =
+                    find_import(&import, &files[i], &HashSet::new());
+                if !files.contains(&import) && Path::new(&import).is_file()
+                {
+                    files.push(import);
+                }
+            }
+        }
+        i += 1;
+    }
+    files
+}
+
+fn diff(
+    old: &Value,
+    new: &Value,
+    path: &mut Vec<Step>,
+    summary: &mut Summary,
+) {
+    match (old, new) {
+        (Value::List(a), Value::List(b))
+            if a.vtype() == b.vtype() && a.comment() == b.comment() =>
+        {
+            for index in 0..a.len().max(b.len()) {
+                path.push(Step::Index(index));
+                match (a.get(index), b.get(index)) {
+                    (Some(x), Some(y)) => diff(x, y, path, summary),
+                    (Some(_), None) => summary.removed.push(path.clone()),
+                    _ => summary.added.push(path.clone()),
+                }
+                path.pop();
+            }
+        }
+        (Value::Map(a), Value::Map(b))
+            if a.ktype() == b.ktype()
+                && a.vtype() == b.vtype()
+                && a.comment() == b.comment() =>
+        {
+            for (key, x) in a.inner() {
+                path.push(Step::Key(key.clone()));
+                match b.get(key) {
+                    Some(y) => diff(x, y, path, summary),
+                    None => summary.removed.push(path.clone()),
+                }
+                path.pop();
+            }
+            for key in b.inner().keys() {
+                if a.get(key).is_none() {
+                    path.push(Step::Key(key.clone()));
+                    summary.added.push(path.clone());
+                    path.pop();
+                }
+            }
+        }
+        (Value::Table(a), Value::Table(b))
+            if a.tclass() == b.tclass() && a.comment() == b.comment() =>
+        {
+            for row in 0..a.len().max(b.len()) {
+                path.push(Step::Index(row));
+                match (a.get(row), b.get(row)) {
+                    (Some(x), Some(y)) => {
+                        for (column, field) in
+                            a.tclass().fields().iter().enumerate()
+                        {
+                            path.push(Step::Field(
+                                field.name().to_string(),
+                            ));
+                            diff(&x[column], &y[column], path, summary);
+                            path.pop();
+                        }
+                    }
+                    (Some(_), None) => summary.removed.push(path.clone()),
+                    _ => summary.added.push(path.clone()),
+                }
+                path.pop();
+            }
+        }
+        _ => {
+            if old != new {
+                summary.changed.push(path.clone());
+            }
+        }
+    }
+}
+
+// Returns the path as text, e.g., <general>[0].min
+fn path_text(path: &[Step]) -> String {
+    if path.is_empty() {
+        return "(value)".to_string();
+    }
+    let mut text = String::new();
+    for step in path {
+        match step {
+            Step::Index(index) => text.push_str(&format!("[{index}]")),
+            Step::Key(key) => text.push_str(&key.to_string()),
+            Step::Field(name) => {
+                text.push('.');
+                text.push_str(name);
+            }
+        }
+    }
+    text
+}
+
+#[cfg(target_os = "linux")]
+struct Notifier {
+    inotify: Inotify,
+    folder_for_wd: HashMap<WatchDescriptor, PathBuf>,
+}
+
+#[cfg(target_os = "linux")]
+impl Notifier {
+    fn new() -> Result<Self> {
+        Ok(Notifier {
+            inotify: Inotify::init()?,
+            folder_for_wd: HashMap::new(),
+        })
+    }
+
+    // Watches the files' folders (those not already watched) so that
+    // changes made before the next wait() are queued rather than missed.
+    fn register(&mut self, files: &[String]) -> Result<()> {
+        for folder in
+            files.iter().filter_map(|file| Path::new(file).parent())
+        {
+            if !self.folder_for_wd.values().any(|f| f == folder) {
+                let wd = self.inotify.watches().add(
+                    folder,
+                    WatchMask::CLOSE_WRITE
+                        | WatchMask::MOVED_TO
+                        | WatchMask::DELETE,
+                )?;
+                self.folder_for_wd.insert(wd, folder.to_path_buf());
+            }
+        }
+        Ok(())
+    }
+
+    // Blocks until one of the files is written, replaced, or deleted.
+    fn wait(&mut self, files: &[String]) -> Result<()> {
+        let files: HashSet<PathBuf> =
+            files.iter().map(PathBuf::from).collect();
+        let mut buffer = [0; 4096];
+        loop {
+            let events = self.inotify.read_events_blocking(&mut buffer)?;
+            let changed = events.into_iter().any(|event| {
+                match (self.folder_for_wd.get(&event.wd), event.name) {
+                    (Some(folder), Some(name)) => {
+                        files.contains(&folder.join(name))
+                    }
+                    _ => false,
+                }
+            });
+            if changed {
+                break;
+            }
+        }
+        thread::sleep(SETTLE);
+        // Discard the rest of the burst's events
+        while let Ok(mut events) = self.inotify.read_events(&mut buffer) {
+            if events.next().is_none() {
+                break;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+struct Notifier {
+    mtimes: Vec<Option<std::time::SystemTime>>,
+}
+
+#[cfg(not(target_os = "linux"))]
+impl Notifier {
+    fn new() -> Result<Self> {
+        Ok(Notifier { mtimes: vec![] })
+    }
+
+    // Records the files' modification times so that changes made before
+    // the next wait() are noticed rather than missed.
+    fn register(&mut self, files: &[String]) -> Result<()> {
+        self.mtimes = mtimes(files);
+        Ok(())
+    }
+
+    // Blocks until one of the files' modification times changes (or the
+    // file is deleted or created).
+    fn wait(&mut self, files: &[String]) -> Result<()> {
+        while mtimes(files) == self.mtimes {
+            thread::sleep(POLL);
+        }
+        thread::sleep(SETTLE);
+        self.mtimes = mtimes(files);
+        Ok(())
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+fn mtimes(files: &[String]) -> Vec<Option<std::time::SystemTime>> {
+    files
+        .iter()
+        .map(|file| std::fs::metadata(file).and_then(|m| m.modified()).ok())
+        .collect()
+}
@@ -0,0 +1,162 @@
+// Copyright © 2022 Mark Summerfield. All rights reserved.
+// License: GPLv3
+
+mod utils;
+
+use std::{cell::RefCell, fs, rc::Rc};
+use utils::{assert_event, temp_dir};
+use uxf::append::Appender;
+use uxf::event::{Event, EventKind};
+use uxf::repair::RepairPolicy;
+use uxf::value::Value;
+
+#[test]
+fn t_append_plain() {
+    let dir = temp_dir("uxf_t_append_plain");
+    let log = dir.join("log.uxf");
+    let log = log.to_string_lossy().to_string();
+    // Empty table on one line
+    fs::write(&log, "uxf 1 Log\n=Event name:str size:real\n(Event)\n")
+        .unwrap();
+    let events = Rc::new(RefCell::new(Vec::<Event>::new()));
+    let mut appender = Appender::open_policy(
+        &log,
+        &RepairPolicy::default(),
+        Some({
+            let events = Rc::clone(&events);
+            Rc::new(move |event: &Event| {
+                events.borrow_mut().push(event.clone())
+            })
+        }),
+    )
+    .unwrap();
+    assert_eq!(appender.tclass().ttype(), "Event");
+    appender.append(vec!["start".into(), Value::Real(1.5)]).unwrap();
+    // int repaired to real
+    appender.append(vec!["<a&b>".into(), Value::Int(2)]).unwrap();
+    assert_eq!(events.borrow().len(), 1);
+    assert_event(
+        &events.borrow()[0],
+        EventKind::Repair,
+        486,
+        &log,
+        0,
+        "converted int 2 to real 2.0",
+    );
+    assert_eq!(
+        fs::read_to_string(&log).unwrap(),
+        "uxf 1 Log\n=Event name:str size:real\n\
+        (Event <start> 1.5\n<&lt;a&amp;b&gt;> 2.0)\n"
+    );
+    // Invalid records append nothing
+    let err = appender.append(vec!["x".into()]).unwrap_err();
+    assert!(err.to_string().starts_with("E736:"));
+    let err = appender
+        .append_many(vec![
+            vec!["ok".into(), Value::Real(3.0)],
+            vec![Value::Int(1), Value::Real(3.0)],
+        ])
+        .unwrap_err();
+    assert!(err.to_string().starts_with("E"));
+    let uxo = uxf::parse(&log).unwrap();
+    assert_eq!(uxo.value().as_table().unwrap().len(), 2);
+    // One record per line
+    fs::write(
+        &log,
+        "uxf 1\n=Point x:int y:int\n(Point\n    1 2\n    3 4\n)\n",
+    )
+    .unwrap();
+    let mut appender = Appender::open(&log).unwrap();
+    appender
+        .append_many(vec![
+            vec![Value::Int(5), Value::Int(6)],
+            vec![Value::Int(7), Value::Null],
+        ])
+        .unwrap();
+    assert_eq!(
+        fs::read_to_string(&log).unwrap(),
+        "uxf 1\n=Point x:int y:int\n(Point\n    1 2\n    3 4\n    5 6\n\
+        \x20   7 ?\n)\n"
+    );
+    // Opening checks the file
+    fs::write(&log, "uxf 1\n[1 2]\n").unwrap();
+    assert!(Appender::open(&log)
+        .err()
+        .unwrap()
+        .to_string()
+        .starts_with("E860:"));
+    fs::write(&log, "uxf 1\n=Flag\n(Flag)\n").unwrap();
+    assert!(Appender::open(&log)
+        .err()
+        .unwrap()
+        .to_string()
+        .starts_with("E861:"));
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn t_append_changed() {
+    let dir = temp_dir("uxf_t_append_changed");
+    let log = dir.join("log.uxf");
+    let log = log.to_string_lossy().to_string();
+    fs::write(&log, "uxf 1\n=Pair a b\n(Pair 1 2)\n").unwrap();
+    let mut appender = Appender::open(&log).unwrap();
+    fs::write(&log, "uxf 1\n=Pair a b\n(Pair 1 2)").unwrap();
+    let err =
+        appender.append(vec![Value::Int(3), Value::Int(4)]).unwrap_err();
+    assert!(err.to_string().starts_with("E862:"));
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn t_append_gzip() {
+    let dir = temp_dir("uxf_t_append_gzip");
+    let log = dir.join("log.uxf.gz");
+    let log = log.to_string_lossy().to_string();
+    let uxo =
+        uxf::parse("uxf 1\n=Pair a:int b:str\n(Pair 1 <one>)\n").unwrap();
+    uxo.write(&log).unwrap();
+    let mut appender = Appender::open(&log).unwrap();
+    // The first append rewrites the file; later ones only append
+    appender.append(vec![Value::Int(2), "two".into()]).unwrap();
+    let size = fs::metadata(&log).unwrap().len();
+    appender.append(vec![Value::Int(3), "three".into()]).unwrap();
+    assert!(fs::metadata(&log).unwrap().len() > size);
+    let mut appender = Appender::open(&log).unwrap();
+    appender.append(vec![Value::Int(4), "four".into()]).unwrap();
+    assert_eq!(
+        uxf::parse(&log).unwrap().to_string(),
+        "uxf 1\n=Pair a:int b:str\n\
+        (Pair 1 <one>\n2 <two>\n3 <three>\n4 <four>)\n"
+    );
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn t_append_codec_mismatch() {
+    use uxf::codec::Codec;
+    let dir = temp_dir("uxf_t_append_codec_mismatch");
+    // A gzipped file whose name doesn't end .gz
+    let gz = dir.join("log.uxf.gz").to_string_lossy().to_string();
+    let log = dir.join("log.uxf").to_string_lossy().to_string();
+    let uxo =
+        uxf::parse("uxf 1\n=Pair a:int b:str\n(Pair 1 <one>)\n").unwrap();
+    uxo.write(&gz).unwrap();
+    fs::rename(&gz, &log).unwrap();
+    let mut appender = Appender::open(&log).unwrap();
+    appender.append(vec![Value::Int(2), "two".into()]).unwrap();
+    assert_eq!(Codec::for_bytes(&fs::read(&log).unwrap()), Codec::Gzip);
+    assert_eq!(
+        uxf::parse(&log).unwrap().to_string(),
+        "uxf 1\n=Pair a:int b:str\n(Pair 1 <one>\n2 <two>)\n"
+    );
+    // A plain text file whose name ends .gz stays plain text
+    fs::write(&gz, "uxf 1\n=Pair a:int b:str\n(Pair 1 <one>)\n").unwrap();
+    let mut appender = Appender::open(&gz).unwrap();
+    appender.append(vec![Value::Int(2), "two".into()]).unwrap();
+    assert_eq!(
+        fs::read_to_string(&gz).unwrap(),
+        "uxf 1\n=Pair a:int b:str\n(Pair 1 <one>\n2 <two>)\n"
+    );
+    fs::remove_dir_all(&dir).unwrap();
+}
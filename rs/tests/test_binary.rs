@@ -0,0 +1,143 @@
+// Copyright © 2022 Mark Summerfield. All rights reserved.
+// License: GPLv3
+
+mod utils;
+
+use std::fs;
+use utils::temp_dir;
+use uxf::binary::{
+    from_binary, is_binary, read_binary, to_binary, write_binary,
+};
+use uxf::value::Value;
+use uxf::{ParserOptions, Uxf, WriteOptions};
+
+fn parse(uxt: &str, options: ParserOptions) -> Uxf {
+    uxf::parse_options(
+        uxt,
+        options,
+        Some(std::rc::Rc::new(uxf::ignore_event)),
+    )
+    .unwrap()
+}
+
+#[test]
+fn t_binary_round_trip() {
+    let uxo = parse(
+        "uxf 1 Custom\n#<File comment>\n!complex\n\
+        =#<Shapes\nkey: name> Shape name:str at:Point\n\
+        =#<pt> Point x:int y:real\n=Unused a b:bytes\n=Empty\n\
+        |Color Red Green Blue\n\
+        {str #<Map comment> <a> (Shape <sq> (Point 1 2.5) <tri> \
+        (Point -3 4.0)) <b> [#<dates> date 2022-01-02 2022-03-04] \
+        <c> (:0A1B:) <d> 2022-01-02T03:04:05 <e> [(Red) (Blue) (Empty)] \
+        <f> {int real 1 1.5 -2 ?} <g> [yes no ? -9223372036854775807 \
+        <tab\tnew\nline &amp; &lt;&gt;> 1e-300] <h> (Complex 1.0 2.0)}\n",
+        ParserOptions::ENUMS | ParserOptions::TABLE_KEYS,
+    );
+    let bytes = to_binary(&uxo);
+    assert!(is_binary(&bytes));
+    let uxo2 = from_binary(&bytes).unwrap();
+    assert_eq!(uxo2, uxo);
+    assert_eq!(uxo2.to_text(), uxo.to_text());
+    assert_eq!(uxo2.tclass("Unused"), uxo.tclass("Unused"));
+    assert_eq!(uxo2.tclass("Shape").unwrap().key(), ["name"]);
+    assert_eq!(uxo2.tclass("Shape").unwrap().comment(), "Shapes");
+    assert_eq!(uxo2.tclass("Red").unwrap().ttype(), "Red");
+    assert_eq!(
+        uxo2.tclass("Color").unwrap().members(),
+        ["Red", "Green", "Blue"]
+    );
+    let shapes = uxo2.value().as_map().unwrap();
+    let shapes = shapes.get(&uxf::key::Key::Str("a".to_string())).unwrap();
+    assert_eq!(shapes.as_table().unwrap().key(), ["name"]);
+    // Same value, same bytes
+    assert_eq!(uxo2.to_binary(), bytes);
+    assert_eq!(Uxf::from_binary(&bytes).unwrap(), uxo);
+}
+
+#[test]
+fn t_binary_value() {
+    let uxo = parse(
+        "uxf 1\n=Point x y\n[#<list> (Point 1 2) [(Point 3 4)] 2022-07-01]\n",
+        ParserOptions::DEFAULT,
+    );
+    let bytes = uxo.value().to_binary();
+    assert_eq!(&Value::from_binary(&bytes).unwrap(), uxo.value());
+    for value in [
+        Value::Null,
+        Value::Int(-1),
+        Value::Real(f64::MAX),
+        Value::Str("".to_string()),
+        Value::Bytes(vec![0, 255]),
+    ] {
+        assert_eq!(Value::from_binary(&value.to_binary()).unwrap(), value);
+    }
+}
+
+#[test]
+fn t_binary_errors() {
+    let uxo = parse("uxf 1\n[1 <two> 3.0]\n", ParserOptions::DEFAULT);
+    let bytes = uxo.to_binary();
+    let err = from_binary(b"uxf 1\n[]\n").unwrap_err();
+    assert_eq!(err.to_string(), "E870:-:0:not binary UXF");
+    let mut newer = bytes.clone();
+    newer[4] = 99;
+    let err = from_binary(&newer).unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "E871:-:0:unsupported binary UXF version 99"
+    );
+    let err = from_binary(&bytes[..bytes.len() - 2]).unwrap_err();
+    assert!(err.to_string().starts_with("E872:-:0:"));
+    let err = Value::from_binary(&bytes).unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "E874:-:0:expected binary UXF Value got Uxf"
+    );
+    let mut extra = bytes.clone();
+    extra.push(0);
+    let err = from_binary(&extra).unwrap_err();
+    assert!(err.to_string().starts_with("E875:-:0:"));
+    let err = from_binary(&uxf::Value::Int(1).to_binary()).unwrap_err();
+    assert!(err.to_string().starts_with("E874:-:0:"));
+}
+
+#[test]
+fn t_binary_too_deep() {
+    // The header and (zero) TClass count then lists each holding a list
+    let mut bytes = Value::Null.to_binary();
+    bytes.pop(); // the null's tag
+    for _ in 0..1_000_000 {
+        bytes.extend_from_slice(&[9, 0, 0, 1]);
+    }
+    bytes.push(0);
+    let err = Value::from_binary(&bytes).unwrap_err();
+    assert!(err.to_string().starts_with("E873:-:0:"));
+    assert!(err.to_string().ends_with("too deeply nested"));
+    // Nesting up to the limit is fine
+    let mut value = Value::Null;
+    for _ in 0..uxf::binary::MAX_DEPTH {
+        let mut lst = uxf::list::List::default();
+        lst.push(value);
+        value = Value::from(lst);
+    }
+    assert_eq!(Value::from_binary(&value.to_binary()).unwrap(), value);
+}
+
+#[test]
+fn t_binary_file() {
+    let dir = temp_dir("uxf_t_binary_file");
+    let filename = dir.join("data.uxb");
+    let filename = filename.to_string_lossy().to_string();
+    let uxo = parse(
+        "uxf 1\n=Pair a b\n(Pair <x> 2022-01-01T12:00:00 <y> (:FF:))\n",
+        ParserOptions::DEFAULT,
+    );
+    write_binary(&filename, &uxo, WriteOptions::DEFAULT, None).unwrap();
+    assert_eq!(read_binary(&filename).unwrap(), uxo);
+    let gzname = format!("{filename}.gz");
+    write_binary(&gzname, &uxo, WriteOptions::DEFAULT, Some(1)).unwrap();
+    assert_eq!(&fs::read(&gzname).unwrap()[..2], [0x1F, 0x8B]);
+    assert_eq!(read_binary(&gzname).unwrap(), uxo);
+    fs::remove_dir_all(&dir).unwrap();
+}
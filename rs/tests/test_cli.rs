@@ -0,0 +1,99 @@
+// Copyright © 2022 Mark Summerfield. All rights reserved.
+// License: GPLv3
+
+mod utils;
+
+use std::{fs, path::PathBuf, process::Command};
+use utils::temp_dir;
+
+// Writes the given files into a new temporary folder and returns it
+fn temp_files(name: &str, files: &[(&str, &str)]) -> PathBuf {
+    let dir = temp_dir(name);
+    for (filename, text) in files {
+        fs::write(dir.join(filename), text).unwrap();
+    }
+    dir
+}
+
+// Runs uxf in the given folder and returns its exit code, stdout, and
+// stderr
+fn uxf(dir: &PathBuf, args: &[&str]) -> (i32, String, String) {
+    let output = Command::new(env!("CARGO_BIN_EXE_uxf"))
+        .args(args)
+        .current_dir(dir)
+        .output()
+        .unwrap();
+    (
+        output.status.code().unwrap(),
+        String::from_utf8(output.stdout).unwrap(),
+        String::from_utf8(output.stderr).unwrap(),
+    )
+}
+
+#[test]
+fn t_cli_exit_codes() {
+    let dir = temp_files(
+        "uxf_t_cli_exit_codes",
+        &[
+            ("ok.uxf", "uxf 1\n=P x\n(P 1)\n"),
+            ("warning.uxf", "uxf 1\n=P x\n[]\n"),
+            ("repair.uxf", "uxf 1\n=P x:real\n(P 1)\n"),
+            ("error.uxf", "uxf 1\n[<a]\n"),
+            ("other.uxf", "uxf 1\n=P x\n(P 2)\n"),
+            ("messy.uxf", "uxf 1\n=P   x\n(P\n1)\n"),
+        ],
+    );
+    assert_eq!(uxf(&dir, &["lint", "ok.uxf"]).0, 0);
+    assert_eq!(uxf(&dir, &["lint", "warning.uxf"]).0, 2);
+    assert_eq!(uxf(&dir, &["lint", "repair.uxf"]).0, 3);
+    assert_eq!(uxf(&dir, &["lint", "error.uxf"]).0, 4);
+    assert_eq!(uxf(&dir, &["lint", "--strict", "repair.uxf"]).0, 4);
+    // Errors are the worst outcome across all the files
+    assert_eq!(uxf(&dir, &["lint", "warning.uxf", "error.uxf"]).0, 4);
+    // Format only reports errors (and unformatted files) via its exit code
+    assert_eq!(uxf(&dir, &["format", "-l", "repair.uxf", "-"]).0, 0);
+    assert_eq!(uxf(&dir, &["format", "error.uxf", "-"]).0, 4);
+    assert_eq!(uxf(&dir, &["format", "--check", "ok.uxf"]).0, 0);
+    assert_eq!(uxf(&dir, &["format", "--check", "messy.uxf"]).0, 1);
+    assert_eq!(uxf(&dir, &["compare", "ok.uxf", "ok.uxf"]).0, 0);
+    assert_eq!(uxf(&dir, &["compare", "ok.uxf", "other.uxf"]).0, 1);
+    assert_eq!(uxf(&dir, &["compare", "ok.uxf", "missing.uxf"]).0, 4);
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn t_cli_json() {
+    let dir = temp_files(
+        "uxf_t_cli_json",
+        &[
+            ("ok.uxf", "uxf 1\n=P x\n(P 1)\n"),
+            ("repair.uxf", "uxf 1\n=P x:real\n(P 1)\n"),
+            ("error.uxf", "uxf 1\n[<a\"b]\n"),
+        ],
+    );
+    let (code, stdout, stderr) =
+        uxf(&dir, &["lint", "--json", "repair.uxf", "error.uxf"]);
+    assert_eq!(code, 4);
+    assert!(stderr.is_empty());
+    let lines: Vec<&str> = stdout.lines().collect();
+    assert_eq!(lines.len(), 2);
+    assert!(lines[0].starts_with("{\"kind\": \"repair\", \"code\": 486, "));
+    assert!(lines[0].contains("\"line\": 3, "));
+    assert!(lines[1].starts_with("{\"kind\": \"error\", \"code\": "));
+    assert!(lines[1].contains("error.uxf\", \"line\": 2, "));
+    let (code, stdout, _) =
+        uxf(&dir, &["compare", "--json", "ok.uxf", "ok.uxf"]);
+    assert_eq!(code, 0);
+    assert_eq!(
+        stdout,
+        "{\"kind\": \"compare\", \"result\": \"EQUAL\", \
+        \"file1\": \"ok.uxf\", \"file2\": \"ok.uxf\"}\n"
+    );
+    // With an outfile of - the JSON records go to stderr
+    let (code, stdout, stderr) =
+        uxf(&dir, &["format", "--json", "error.uxf", "-"]);
+    assert_eq!(code, 4);
+    assert!(stdout.is_empty());
+    assert!(stderr.starts_with("{\"kind\": \"error\", "));
+    fs::remove_dir_all(&dir).unwrap();
+}
@@ -0,0 +1,104 @@
+// Copyright © 2022 Mark Summerfield. All rights reserved.
+// License: GPLv3
+
+mod utils;
+
+use std::fs;
+use utils::temp_dir;
+use uxf::codec::Codec;
+
+// Writes a Uxf to a file with the given suffix, checks that its codec is
+// detected, and that it (and two concatenated streams) read back.
+fn check_round_trip(name: &str, suffix: &str, codec: Codec) {
+    let dir = temp_dir(name);
+    let filename = dir.join(format!("data.uxf{suffix}"));
+    let filename = filename.to_string_lossy().to_string();
+    let uxo = uxf::parse("uxf 1\n=Point x y\n(Point 1 2 3 4)\n").unwrap();
+    uxo.write(&filename).unwrap();
+    let bytes = fs::read(&filename).unwrap();
+    assert_eq!(Codec::for_filename(&filename), codec);
+    assert_eq!(Codec::for_bytes(&bytes), codec);
+    assert_eq!(uxf::parse(&filename).unwrap(), uxo);
+    let mut bytes = codec.compress(b"uxf 1\n[1 ", Some(1), "-").unwrap();
+    bytes.extend(codec.compress(b"2]\n", None, "-").unwrap());
+    fs::write(&filename, bytes).unwrap();
+    assert_eq!(uxf::util::read_file(&filename).unwrap(), "uxf 1\n[1 2]\n");
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn t_codec_detect() {
+    assert_eq!(Codec::for_filename("a.uxf"), Codec::Plain);
+    assert_eq!(Codec::for_filename("a.uxf.gz"), Codec::Gzip);
+    assert_eq!(Codec::for_filename("a.uxb.zst"), Codec::Zstd);
+    assert_eq!(Codec::for_filename("a.uxf.xz"), Codec::Xz);
+    assert_eq!(Codec::for_filename("a.uxf.bz2"), Codec::Bzip2);
+    assert_eq!(Codec::for_bytes(b"uxf 1\n[]\n"), Codec::Plain);
+    assert_eq!(Codec::for_bytes(b""), Codec::Plain);
+    assert_eq!(
+        Codec::for_bytes(&[0xFD, b'7', b'z', b'X', b'Z', 0]),
+        Codec::Xz
+    );
+    assert_eq!(Codec::Zstd.suffix(), ".zst");
+    assert_eq!(Codec::Zstd.to_string(), "zstd");
+    assert!(Codec::Plain.is_available());
+    assert!(Codec::Gzip.is_available());
+}
+
+#[test]
+fn t_codec_gzip() {
+    check_round_trip("uxf_t_codec_gzip", ".gz", Codec::Gzip);
+}
+
+#[test]
+fn t_codec_level() {
+    let dir = temp_dir("uxf_t_codec_level");
+    let uxo = uxf::parse(&format!(
+        "uxf 1\n[{}]\n",
+        "<repeated text> ".repeat(200)
+    ))
+    .unwrap();
+    let mut sizes = vec![];
+    for level in [0, 9, 99] {
+        let filename = dir.join(format!("data{level}.uxf.gz"));
+        let filename = filename.to_string_lossy();
+        let format = uxf::Format::default().with_compression_level(level);
+        uxo.write_format(&filename, &format).unwrap();
+        assert_eq!(uxf::parse(&filename).unwrap(), uxo);
+        sizes.push(fs::metadata(filename.as_ref()).unwrap().len());
+    }
+    assert!(sizes[0] > sizes[1]); // stored vs best
+    assert_eq!(sizes[1], sizes[2]); // 99 is clamped to 9
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[cfg(feature = "zstd")]
+#[test]
+fn t_codec_zstd() {
+    check_round_trip("uxf_t_codec_zstd", ".zst", Codec::Zstd);
+}
+
+#[cfg(feature = "xz")]
+#[test]
+fn t_codec_xz() {
+    check_round_trip("uxf_t_codec_xz", ".xz", Codec::Xz);
+}
+
+#[cfg(feature = "bzip2")]
+#[test]
+fn t_codec_bzip2() {
+    check_round_trip("uxf_t_codec_bzip2", ".bz2", Codec::Bzip2);
+}
+
+#[cfg(not(feature = "zstd"))]
+#[test]
+fn t_codec_unavailable() {
+    assert!(!Codec::Zstd.is_available());
+    let uxo = uxf::parse("uxf 1\n[]\n").unwrap();
+    let err = uxo.write("data.uxf.zst").unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "E960:data.uxf.zst:0:zstd compression isn't available (uxf must \
+        be built with the zstd feature)"
+    );
+}
@@ -0,0 +1,169 @@
+// Copyright © 2022 Mark Summerfield. All rights reserved.
+// License: GPLv3
+
+mod utils;
+
+use std::fs;
+use utils::temp_dir;
+use uxf::config::{Config, Layer};
+use uxf::value::Value;
+
+fn defaults() -> Config {
+    let uxo = uxf::parse(
+        "uxf 1\n{<fontsize> 18 <name> <Sudoku> <colors> {str str \
+        <bg> <white> <fg> <black>}}\n",
+    )
+    .unwrap();
+    Config::new(uxo.value().as_map().unwrap().clone())
+}
+
+#[test]
+fn t_config_layers() {
+    let dir = temp_dir("uxf_t_config_layers");
+    let system = dir.join("system.uxf");
+    fs::write(&system, "uxf 1\n{<colors> {<bg> <grey>} <fontsize> 20}\n")
+        .unwrap();
+    let project = dir.join("project.uxf");
+    fs::write(&project, "uxf 1\n{<colors> <none>}\n").unwrap();
+    let mut config = defaults();
+    assert!(config.load(Layer::System, &system.to_string_lossy()).unwrap());
+    assert!(!config
+        .load(Layer::User, &dir.join("user.uxf").to_string_lossy())
+        .unwrap());
+    assert_eq!(config.get_as::<i64>("fontsize").unwrap(), 20);
+    assert_eq!(config.get_as::<String>("colors.bg").unwrap(), "grey");
+    assert_eq!(config.get_as::<String>("colors.fg").unwrap(), "black");
+    assert_eq!(config.layer("colors.bg"), Some(Layer::System));
+    assert_eq!(config.layer("colors.fg"), Some(Layer::Default));
+    assert_eq!(config.layer("colors"), Some(Layer::System));
+    assert_eq!(config.layer("name"), Some(Layer::Default));
+    assert_eq!(config.layer("nosuch"), None);
+    // A non-map value replaces a whole map
+    assert!(config
+        .load(Layer::Project, &project.to_string_lossy())
+        .unwrap());
+    assert_eq!(config.get("colors"), Some(&Value::from("none")));
+    assert_eq!(config.get("colors.bg"), None);
+    assert_eq!(config.layer("colors.bg"), None);
+    assert_eq!(config.layer("colors"), Some(Layer::Project));
+    assert!(config
+        .get_as::<i64>("colors.bg")
+        .unwrap_err()
+        .to_string()
+        .starts_with("E841:"));
+    fs::write(&project, "uxf 1\n[1 2]\n").unwrap();
+    let err = config
+        .load(Layer::Project, &project.to_string_lossy())
+        .unwrap_err();
+    assert!(err.to_string().starts_with("E843:"));
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn t_config_overrides() {
+    let mut config = defaults();
+    config.apply_override("fontsize=24").unwrap();
+    config.apply_override("name=123").unwrap();
+    config.apply_override("window.maximized=yes").unwrap();
+    assert_eq!(config.get("fontsize"), Some(&Value::Int(24)));
+    assert_eq!(config.get("name"), Some(&Value::from("123")));
+    assert_eq!(config.get("window.maximized"), Some(&Value::Bool(true)));
+    assert_eq!(config.layer("window.maximized"), Some(Layer::Override));
+    assert_eq!(config.layer("fontsize"), Some(Layer::Override));
+    assert!(config
+        .apply_override("fontsize")
+        .unwrap_err()
+        .to_string()
+        .starts_with("E840:"));
+    assert!(config
+        .apply_override("fontsize.big=1")
+        .unwrap_err()
+        .to_string()
+        .starts_with("E845:"));
+    assert!(config
+        .apply_override("colors..bg=1")
+        .unwrap_err()
+        .to_string()
+        .starts_with("E844:"));
+    std::env::set_var("UXF_T_CONFIG_FONTSIZE", "30");
+    std::env::set_var("UXF_T_CONFIG_COLORS__FG", "navy");
+    config.apply_env("UXF_T_CONFIG").unwrap();
+    assert_eq!(config.get("colors.fg"), Some(&Value::from("navy")));
+    assert_eq!(config.layer("colors.fg"), Some(Layer::Env));
+    // Overrides take precedence over env vars
+    assert_eq!(config.get("fontsize"), Some(&Value::Int(24)));
+    assert_eq!(
+        config.layer_map(Layer::Env).unwrap().to_string(),
+        "{<colors> {<fg> <navy>}\n<fontsize> 30}"
+    );
+}
+
+#[test]
+fn t_config_save() {
+    let dir = temp_dir("uxf_t_config_save");
+    let user = dir.join("user.uxf");
+    let user = user.to_string_lossy();
+    fs::write(
+        user.as_ref(),
+        "uxf 1 config\n#<My settings>\n{#<keep this>\n  <fontsize> 16\n  \
+        <colors> {<bg> <ivory>}\n}\n",
+    )
+    .unwrap();
+    let mut config = defaults();
+    config.load(Layer::User, &user).unwrap();
+    config.apply_override("colors.fg=red").unwrap();
+    assert!(!config.is_changed());
+    assert_eq!(config.get_as::<String>("colors.bg").unwrap(), "ivory");
+    config.set("fontsize", Value::Int(14)).unwrap();
+    config.set("colors.fg", Value::from("green")).unwrap();
+    assert!(config.is_changed());
+    assert_eq!(config.get_as::<i64>("fontsize").unwrap(), 14);
+    assert_eq!(config.layer("fontsize"), Some(Layer::User));
+    // The override still takes precedence
+    assert_eq!(config.get_as::<String>("colors.fg").unwrap(), "red");
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        fs::set_permissions(
+            user.as_ref(),
+            fs::Permissions::from_mode(0o600),
+        )
+        .unwrap();
+    }
+    config.save().unwrap();
+    // A private config file stays private
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        let metadata = fs::metadata(user.as_ref()).unwrap();
+        assert_eq!(metadata.permissions().mode() & 0o777, 0o600);
+    }
+    // Only the user layer is saved, with its comments and layout intact
+    assert_eq!(
+        fs::read_to_string(user.as_ref()).unwrap(),
+        "uxf 1 config\n#<My settings>\n{#<keep this>\n  <fontsize> 14\n  \
+        <colors> {<bg> <ivory>\n<fg> <green>}\n}\n"
+    );
+    // A new top-level key means the whole outermost map is rewritten
+    config.set("window.size.width", Value::Int(800)).unwrap();
+    assert_eq!(config.get_as::<i64>("window.size.width").unwrap(), 800);
+    config.save().unwrap();
+    assert_eq!(
+        fs::read_to_string(user.as_ref()).unwrap(),
+        "uxf 1 config\n#<My settings>\n{#<keep this> <colors> {<bg> \
+        <ivory>\n<fg> <green>}\n<fontsize> 14\n\
+        <window> {<size> {<width> 800}}}\n"
+    );
+    let mut config = defaults();
+    assert!(config.save().unwrap_err().to_string().starts_with("E842:"));
+    // A missing user config file is created on save
+    let new = dir.join("new.uxf");
+    config.load(Layer::User, &new.to_string_lossy()).unwrap();
+    config.set("fontsize", Value::Int(12)).unwrap();
+    config.save().unwrap();
+    assert_eq!(
+        fs::read_to_string(&new).unwrap(),
+        "uxf 1\n{<fontsize> 12}\n"
+    );
+    fs::remove_dir_all(&dir).unwrap();
+}
@@ -0,0 +1,81 @@
+// Copyright © 2022 Mark Summerfield. All rights reserved.
+// License: GPLv3
+
+use chrono::NaiveDate;
+use std::collections::{BTreeMap, HashMap};
+use uxf::key::Key;
+use uxf::value::Value;
+use uxf::FromValue;
+
+#[test]
+fn t_convert_scalars() {
+    assert!(bool::try_from(&Value::Bool(true)).unwrap());
+    assert_eq!(i64::try_from(&Value::Int(-9)).unwrap(), -9);
+    assert_eq!(u8::try_from(&Value::Int(255)).unwrap(), 255);
+    assert_eq!(
+        u8::try_from(&Value::Int(256)).unwrap_err().to_string(),
+        "E771:-:0:int 256 is out of range for u8"
+    );
+    assert_eq!(f64::try_from(&Value::Real(1.5)).unwrap(), 1.5);
+    assert_eq!(f64::try_from(&Value::Int(2)).unwrap(), 2.0);
+    assert_eq!(String::try_from(&Value::from("x")).unwrap(), "x");
+    let d = NaiveDate::from_ymd(2022, 10, 7);
+    assert_eq!(NaiveDate::try_from(&Value::from(d)).unwrap(), d);
+    assert_eq!(Key::try_from(&Value::from(d)).unwrap(), Key::Date(d));
+    assert_eq!(
+        i64::try_from(&Value::from("7")).unwrap_err().to_string(),
+        "E770:-:0:expected int, got str"
+    );
+    assert_eq!(
+        Key::try_from(&Value::Real(1.0)).unwrap_err().to_string(),
+        "E770:-:0:expected key (bytes, date, int, or str), got real"
+    );
+    assert_eq!(Value::Null.get_as::<Option<i64>>().unwrap(), None);
+    assert_eq!(Value::Int(3).get_as::<Option<i64>>().unwrap(), Some(3));
+    assert_eq!(
+        Value::Null.get_as::<bool>().unwrap_err().to_string(),
+        "E770:-:0:expected bool, got null"
+    );
+}
+
+#[test]
+fn t_convert_collections() {
+    let uxo = uxf::parse(
+        "uxf 1\n=Pair first:str second:int\n\
+        [[int 1 2 3] (:0102FF:) {int 1 <a> 2 <b>} (Pair <x> 1 <y> 2) \
+        [<z> 3]]\n",
+    )
+    .unwrap();
+    let lst = uxo.value().as_list().unwrap();
+    assert_eq!(Vec::<i64>::try_from(&lst[0]).unwrap(), [1, 2, 3]);
+    assert_eq!(Vec::<u8>::try_from(&lst[1]).unwrap(), [1, 2, 255]);
+    let m: BTreeMap<i64, String> = lst[2].get_as().unwrap();
+    assert_eq!(m[&2], "b");
+    let m: HashMap<Key, String> = (&lst[2]).try_into().unwrap();
+    assert_eq!(m[&Key::Int(1)], "a");
+    let pairs: Vec<(String, i64)> = lst[3].get_as().unwrap();
+    assert_eq!(pairs, [("x".to_string(), 1), ("y".to_string(), 2)]);
+    let pair = <(String, i64)>::try_from(&lst[4]).unwrap();
+    assert_eq!(pair, ("z".to_string(), 3));
+    let table = lst[3].as_table().unwrap();
+    let pair: (String, i64) =
+        FromValue::from_record(table.last().unwrap()).unwrap();
+    assert_eq!(pair, ("y".to_string(), 2));
+    assert_eq!(
+        lst[3].get_as::<Vec<(String,)>>().unwrap_err().to_string(),
+        "E773:-:0:expected 1 values for tuple, got 2"
+    );
+    assert!(lst[3]
+        .get_as::<Vec<i64>>()
+        .unwrap_err()
+        .to_string()
+        .starts_with("E772:-:0:can't convert a table record of 2 values"));
+    assert_eq!(
+        lst[4].get_as::<Vec<i64>>().unwrap_err().to_string(),
+        "E770:-:0:expected int, got str"
+    );
+    assert_eq!(
+        lst[0].get_as::<HashMap<i64, i64>>().unwrap_err().to_string(),
+        "E770:-:0:expected map, got list"
+    );
+}
@@ -0,0 +1,142 @@
+// Copyright © 2022 Mark Summerfield. All rights reserved.
+// License: GPLv3
+
+mod utils;
+
+use std::rc::Rc;
+use utils::temp_dir;
+use uxf::cst::{Document, Step};
+use uxf::key::Key;
+use uxf::value::Value;
+
+static CONFIG: &str = "uxf 1 config
+#<Hand-written config>
+=#<Server details> Server  name:str      port:int  started:date
+=Alias from to
+{
+  <window>  {#<dims> <width> 1024   <height> 768}
+  <recent>  [str <a.txt> <b.txt>
+             <c.txt>]
+  <dates>   {date  2022-10-01 <first>  2022-10-05 <second>}
+  <servers> (Server
+    <alpha>       8080   2022-01-07
+    <bravo>       8443   2022-02-11
+  )
+  <motto>   <Fast &amp;> & < Good>
+  <zero>    0.5
+}
+";
+
+fn parse(uxt: &str) -> Document {
+    Document::parse_options(uxt, Some(Rc::new(uxf::ignore_event))).unwrap()
+}
+
+#[test]
+fn t_cst_unchanged() {
+    let doc = parse(CONFIG);
+    assert!(!doc.is_changed());
+    assert_eq!(doc.to_text(), CONFIG);
+    assert_eq!(doc.uxf().custom(), "config");
+}
+
+#[test]
+fn t_cst_get() {
+    let doc = parse(CONFIG);
+    assert_eq!(
+        doc.get(&["window".into(), "height".into()]),
+        Some(&Value::Int(768))
+    );
+    assert_eq!(
+        doc.get(&["recent".into(), 2.into()]),
+        Some(&Value::Str("c.txt".to_string()))
+    );
+    let date = Key::Date(chrono::NaiveDate::from_ymd(2022, 10, 5));
+    assert_eq!(
+        doc.get(&["dates".into(), date.into()]),
+        Some(&Value::Str("second".to_string()))
+    );
+    assert_eq!(
+        doc.get(&["servers".into(), 1.into(), 1.into()]),
+        Some(&Value::Int(8443))
+    );
+    assert_eq!(
+        doc.get(&[
+            "servers".into(),
+            1.into(),
+            Step::Field("name".to_string())
+        ]),
+        Some(&Value::Str("bravo".to_string()))
+    );
+    assert!(doc.get(&["servers".into(), 2.into(), 0.into()]).is_none());
+    assert!(doc.get(&["nosuchkey".into()]).is_none());
+}
+
+#[test]
+fn t_cst_set_scalars() {
+    let mut doc = parse(CONFIG);
+    doc.set(&["window".into(), "width".into()], 1280.into()).unwrap();
+    doc.set(&["recent".into(), 1.into()], "b b.txt".into()).unwrap();
+    doc.set(
+        &["servers".into(), 0.into(), Step::Field("port".to_string())],
+        80.into(),
+    )
+    .unwrap();
+    doc.set(&["motto".into()], "Fast & <Good>".into()).unwrap();
+    doc.set(&["zero".into()], Value::Null).unwrap();
+    assert!(doc.is_changed());
+    let expected = CONFIG
+        .replace("<width> 1024 ", "<width> 1280 ")
+        .replace("<b.txt>", "<b b.txt>")
+        .replace("<alpha>       8080", "<alpha>       80")
+        .replace("<Fast &amp;> & < Good>", "<Fast &amp; &lt;Good&gt;>")
+        .replace("0.5", "?");
+    assert_eq!(doc.to_text(), expected);
+    let uxo = uxf::parse(&doc.to_text()).unwrap();
+    assert_eq!(&uxo, doc.uxf());
+}
+
+#[test]
+fn t_cst_set_collections() {
+    let mut doc = parse(CONFIG);
+    let mut recent = doc.get(&["recent".into()]).unwrap().clone();
+    recent.push("d.txt".into()).unwrap();
+    doc.set(&["recent".into()], recent).unwrap();
+    // An inner change to a changed collection is also output
+    doc.set(&["recent".into(), 3.into()], "e.txt".into()).unwrap();
+    let expected = CONFIG.replace(
+        "[str <a.txt> <b.txt>\n             <c.txt>]",
+        "[str <a.txt>\n<b.txt>\n<c.txt>\n<e.txt>]",
+    );
+    assert_eq!(doc.to_text(), expected);
+    assert_eq!(
+        doc.set(&["recent".into(), 4.into()], "f.txt".into())
+            .unwrap_err()
+            .to_string(),
+        "E820:-:0:no value at path [Key(Str(\"recent\")), Index(4)]"
+    );
+    assert!(doc
+        .set(&[], 1.into())
+        .unwrap_err()
+        .to_string()
+        .starts_with("E821:"));
+}
+
+#[test]
+fn t_cst_relative_import() {
+    // Relative imports are resolved against the file's folder, not the
+    // current folder
+    let dir = temp_dir("uxf_t_cst_relative_import");
+    std::fs::write(
+        dir.join("point.uxi"),
+        "uxf 1\n=Point x:int y:int\n[]\n",
+    )
+    .unwrap();
+    let filename = dir.join("doc.uxf");
+    std::fs::write(&filename, "uxf 1\n!point.uxi\n[(Point 1 2)]\n")
+        .unwrap();
+    let mut doc = Document::parse(&filename.to_string_lossy()).unwrap();
+    assert_eq!(doc.uxf().tclass("Point").unwrap().len(), 2);
+    doc.set(&[0.into(), 0.into(), 1.into()], 3.into()).unwrap();
+    assert_eq!(doc.to_text(), "uxf 1\n!point.uxi\n[(Point 1 3)]\n");
+    std::fs::remove_dir_all(&dir).unwrap();
+}
@@ -0,0 +1,133 @@
+// Copyright © 2022 Mark Summerfield. All rights reserved.
+// License: GPLv3
+
+mod utils;
+
+use chrono::NaiveDate;
+use std::fs;
+use utils::temp_dir;
+use uxf::index::{index_filename, Index};
+use uxf::key::Key;
+use uxf::value::Value;
+
+const LOG: &str = "uxf 1 Log\n#<comment>\n\
+    =Event id:int when:date what:str extra\n=Point x y\n\
+    (Event\n  3 <2022-01-03> <héllo\nwörld> [1 (Point 1 2)]\n\
+    1 2022-01-01 <a &lt;b&gt;> ?\n  2 2022-01-02 <c> {<k> (:AB:)}\n)\n";
+
+fn temp_file(name: &str, text: &str) -> (std::path::PathBuf, String) {
+    let dir = temp_dir(name);
+    let filename = dir.join("log.uxf");
+    fs::write(&filename, text).unwrap();
+    (dir, filename.to_string_lossy().to_string())
+}
+
+#[test]
+fn t_index_get() {
+    let (dir, filename) = temp_file("uxf_t_index_get", LOG);
+    let uxo = uxf::parse(&filename).unwrap();
+    let table = uxo.value().as_table().unwrap();
+    Index::build(&filename, None).unwrap().save().unwrap();
+    assert!(fs::metadata(index_filename(&filename)).is_ok());
+    let index = Index::open(&filename).unwrap();
+    assert_eq!(index.len(), 3);
+    assert_eq!(index.ttype(), "Event");
+    assert_eq!(index.key(), None);
+    for row in 0..index.len() {
+        assert_eq!(
+            &index.get(row).unwrap().unwrap(),
+            table.get(row).unwrap()
+        );
+    }
+    assert!(index.get(3).unwrap().is_none());
+    // The str was repaired to the when field's date vtype
+    let record = index.get(0).unwrap().unwrap();
+    assert_eq!(
+        record[1],
+        Value::Date(NaiveDate::from_ymd_opt(2022, 1, 3).unwrap())
+    );
+    assert_eq!(record[2], Value::Str("héllo\nwörld".to_string()));
+    let err = index.find(&Key::Int(1)).unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        format!("E887:{filename}:0:the index has no key field")
+    );
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn t_index_key() {
+    let (dir, filename) = temp_file("uxf_t_index_key", LOG);
+    Index::build(&filename, Some("id")).unwrap().save().unwrap();
+    let index = Index::open(&filename).unwrap();
+    assert_eq!(index.key(), Some("id"));
+    let record = index.find(&Key::Int(2)).unwrap().unwrap();
+    assert_eq!(record[2], Value::Str("c".to_string()));
+    assert!(index.find(&Key::Int(4)).unwrap().is_none());
+    let records = index.range(Key::Int(2)..).unwrap();
+    let ids: Vec<i64> =
+        records.iter().map(|r| r[0].as_int().unwrap()).collect();
+    assert_eq!(ids, [2, 3]);
+    let records = index.range(..Key::Int(3)).unwrap();
+    let ids: Vec<i64> =
+        records.iter().map(|r| r[0].as_int().unwrap()).collect();
+    assert_eq!(ids, [1, 2]);
+    assert_eq!(index.range(..).unwrap().len(), 3);
+    // Key by date
+    let index = Index::build(&filename, Some("when")).unwrap();
+    let date =
+        |day| Key::Date(NaiveDate::from_ymd_opt(2022, 1, day).unwrap());
+    let records = index.range(date(2)..=date(3)).unwrap();
+    assert_eq!(records.len(), 2);
+    assert_eq!(records[0][0], Value::Int(2));
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn t_index_errors() {
+    let (dir, filename) = temp_file("uxf_t_index_errors", LOG);
+    let err = Index::build(&filename, Some("extra")).unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        format!(
+            "E886:{filename}:0:record 0's extra value isn't a valid key, \
+            got list"
+        )
+    );
+    let err = Index::build(&filename, Some("nosuch")).unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        format!(
+            "E882:{filename}:0:table of ttype Event has no field nosuch"
+        )
+    );
+    // Out of date
+    Index::build(&filename, None).unwrap().save().unwrap();
+    let index = Index::open(&filename).unwrap();
+    fs::write(&filename, LOG.replace("<c>", "<cc>")).unwrap();
+    let err = index.get(0).unwrap_err();
+    assert!(err.to_string().starts_with("E883:"));
+    assert!(Index::open(&filename)
+        .unwrap_err()
+        .to_string()
+        .starts_with("E883:"));
+    // Not a table
+    fs::write(&filename, "uxf 1\n[1 2]\n").unwrap();
+    let err = Index::build(&filename, None).unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        format!(
+            "E881:{filename}:0:can only index a file whose value is a \
+            table, got list"
+        )
+    );
+    // Compressed
+    let gz = format!("{filename}.gz");
+    uxf::parse(LOG).unwrap().write(&gz).unwrap();
+    let err = Index::build(&gz, None).unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        format!("E880:{gz}:0:can only index uncompressed files")
+    );
+    fs::remove_dir_all(&dir).unwrap();
+}
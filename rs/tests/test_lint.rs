@@ -0,0 +1,190 @@
+// Copyright © 2022 Mark Summerfield. All rights reserved.
+// License: GPLv3
+
+mod utils;
+
+use std::{fs, rc::Rc};
+use utils::{assert_event, temp_dir};
+use uxf::event::EventKind;
+use uxf::lint::Rule;
+use uxf::Linter;
+
+fn parse(uxt: &str) -> uxf::Uxf {
+    uxf::parse_options(
+        uxt,
+        uxf::ParserOptions::default(),
+        Some(Rc::new(uxf::ignore_event)),
+    )
+    .unwrap()
+}
+
+#[test]
+fn t_lint_clean() {
+    let uxo = parse(
+        "uxf 1\n=#<A pair> Pair first:int second_2:str\n\
+        [Pair (Pair 1 <a> 2 <b>)]\n",
+    );
+    let linter = Linter::new();
+    assert!(linter.lint(&uxo, "clean.uxf").is_empty());
+}
+
+#[test]
+fn t_lint_rules() {
+    let uxo = parse(
+        "uxf 1\n!complex\n=Pair First:int second\n\
+        {<servers> [1 2 [int 3]] <when> {<2022-01-05> 1 <x> 2} \
+        <p> (Pair 1 2)}\n",
+    );
+    let linter = Linter::new();
+    let events = linter.lint(&uxo, "rules.uxf");
+    assert_eq!(events.len(), 5);
+    assert_event(
+        &events[0],
+        EventKind::Warning,
+        800,
+        "rules.uxf",
+        0,
+        "untyped list at {<servers>}",
+    );
+    assert_event(
+        &events[1],
+        EventKind::Warning,
+        801,
+        "rules.uxf",
+        0,
+        "ttype Pair has no comment",
+    );
+    assert_event(
+        &events[2],
+        EventKind::Warning,
+        802,
+        "rules.uxf",
+        0,
+        "ttype Pair's field First isn't snake_case",
+    );
+    assert_event(
+        &events[3],
+        EventKind::Warning,
+        803,
+        "rules.uxf",
+        0,
+        "map at {<when>} has str key <2022-01-05> that looks like a date",
+    );
+    assert_event(
+        &events[4],
+        EventKind::Warning,
+        804,
+        "rules.uxf",
+        0,
+        "unused import complex",
+    );
+}
+
+#[test]
+fn t_lint_unused_imports_vtypes() {
+    let mut linter = Linter::new();
+    linter.set_enabled("no_untyped_lists", false).unwrap();
+    linter.set_enabled("tclass_comments", false).unwrap();
+    // Imports used only as list, map, or field vtypes are used
+    for uxt in [
+        "uxf 1\n!complex\n[Complex]\n",
+        "uxf 1\n!complex\n{int Complex}\n",
+        "uxf 1\n!complex\n=Pair first:Complex second\n[(Pair)]\n",
+        "uxf 1\n!complex\n=Pair first:Complex second\n[]\n",
+    ] {
+        let uxo = parse(uxt);
+        assert!(linter.lint(&uxo, "-").is_empty(), "{uxt:?}");
+    }
+    let uxo = parse("uxf 1\n!complex\n!fraction\n[Complex]\n");
+    let events = linter.lint(&uxo, "-");
+    assert_eq!(events.len(), 1);
+    assert_event(
+        &events[0],
+        EventKind::Warning,
+        804,
+        "-",
+        0,
+        "unused import fraction",
+    );
+}
+
+#[test]
+fn t_lint_max_depth() {
+    let uxo = parse("uxf 1\n[int 1 [int 2 [int 3 [int 4]]]]\n");
+    let mut linter = Linter::new();
+    assert!(linter.lint(&uxo, "-").is_empty());
+    linter.rule_mut("max_depth").unwrap().limit = Some(2);
+    let events = linter.lint(&uxo, "-");
+    assert_eq!(events.len(), 1);
+    assert_event(
+        &events[0],
+        EventKind::Warning,
+        805,
+        "-",
+        0,
+        "list at [1][1] is nested 3 deep (limit 2)",
+    );
+    linter.set_enabled("max_depth", false).unwrap();
+    assert!(linter.lint(&uxo, "-").is_empty());
+    assert_eq!(
+        linter.set_enabled("bogus", false).unwrap_err().to_string(),
+        "E812:-:0:unknown lint rule \"bogus\""
+    );
+}
+
+#[test]
+fn t_lint_config() {
+    let dir = temp_dir("uxf_t_lint_config");
+    let config = dir.join(uxf::lint::LINT_CONFIG_FILENAME);
+    fs::write(
+        &config,
+        "uxf 1 uxflint\n{<tclass_comments> no <max_depth> 1 \
+        <snake_case_fields> yes}\n",
+    )
+    .unwrap();
+    let sub = dir.join("sub");
+    fs::create_dir_all(&sub).unwrap();
+    assert_eq!(uxf::lint::find_config(&sub).unwrap(), config);
+    let linter = Linter::from_config(&config.to_string_lossy()).unwrap();
+    assert!(!linter.rule("tclass_comments").unwrap().enabled);
+    assert_eq!(linter.rule("max_depth").unwrap().limit, Some(1));
+    let uxo = parse("uxf 1\n=Pair a b\n[(Pair 1 2)]\n");
+    let events = linter.lint(&uxo, "-");
+    assert_eq!(events.len(), 2);
+    assert_eq!(events[0].code, 800);
+    assert_eq!(events[1].code, 805);
+    fs::write(&config, "uxf 1\n{<snake_case_fields> 3}\n").unwrap();
+    let err = Linter::from_config(&config.to_string_lossy()).unwrap_err();
+    assert!(err.to_string().starts_with("E813:"));
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn t_lint_custom_rule() {
+    let mut linter = Linter::new();
+    linter.add_rule(Rule::new(
+        "file_comment",
+        "every file must have a comment",
+        899,
+        Rc::new(|_, uxo| {
+            if uxo.comment().is_empty() {
+                vec!["missing file comment".to_string()]
+            } else {
+                vec![]
+            }
+        }),
+    ));
+    let uxo = parse("uxf 1\n[int 1]\n");
+    let events = linter.lint(&uxo, "-");
+    assert_eq!(events.len(), 1);
+    assert_event(
+        &events[0],
+        EventKind::Warning,
+        899,
+        "-",
+        0,
+        "missing file comment",
+    );
+    let uxo = parse("uxf 1\n#<Comment>\n[int 1]\n");
+    assert!(linter.lint(&uxo, "-").is_empty());
+}
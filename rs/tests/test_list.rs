@@ -36,12 +36,12 @@ fn t_list1() {
     );
     assert!(!lst.is_empty());
     assert_eq!(lst.len(), 11);
-    assert_eq!(lst[0].as_bool().unwrap(), true);
+    assert!(lst[0].as_bool().unwrap());
     assert_eq!(lst[1].as_int().unwrap(), -919);
     assert!(lst[2].is_null());
     assert_eq!(lst[3].as_str().unwrap(), "elephant <ears>");
     assert!(isclose64(lst[4].as_real().unwrap(), 1.73e-5));
-    assert_eq!(lst[0].as_bool().unwrap(), true);
+    assert!(lst[0].as_bool().unwrap());
     assert_eq!(lst[1].as_int().unwrap(), -919);
     assert!(lst[2].is_null());
     assert_eq!(lst[3].as_str().unwrap(), "elephant <ears>");
@@ -49,7 +49,7 @@ fn t_list1() {
     lst[0] = 7070.into();
     assert_eq!(lst[0].as_int().unwrap(), 7070);
     lst[1] = false.into();
-    assert_eq!(lst[1].as_bool().unwrap(), false);
+    assert!(!lst[1].as_bool().unwrap());
     lst[1] = Value::Null;
     assert!(lst[1].is_null());
     assert!(lst[2].is_null());
@@ -132,7 +132,7 @@ fn t_list3() {
         assert_eq!(values.len(), 4);
         assert!(!values.is_empty());
         assert!(values[0].is_null());
-        assert!(values.get(0).unwrap().is_null());
+        assert!(values.first().unwrap().is_null());
         assert_eq!(values[2].as_int().unwrap(), 17);
         assert_eq!(values.get(2).unwrap().as_int().unwrap(), 17);
         for (i, value) in values.iter().enumerate() {
@@ -262,13 +262,78 @@ fn t_list_str() {
     );
 }
 
+#[test]
+fn t_list_try_push() {
+    let mut lst = List::new("int", "").unwrap();
+    lst.try_push(1.into(), None).unwrap();
+    lst.try_push(Value::Null, None).unwrap();
+    let err = lst.try_push("2".into(), None).unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "E500:-:0:expected int, got str <2> at [2]"
+    );
+    let err = lst.try_push(Value::new_list(), None).unwrap_err();
+    assert_eq!(err.to_string(), "E506:-:0:expected int, got list at [2]");
+    assert_eq!(lst.len(), 2);
+    // Nested collections are checked too
+    let mut inner = List::new("real", "").unwrap();
+    inner.push(1.into());
+    let mut outer = List::new("list", "").unwrap();
+    let err = outer.try_push(inner.into(), None).unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "E500:-:0:expected real, got int 1 at [0][0]"
+    );
+    // ttypes are only checked against the owning Uxf if one is given
+    let mut points = List::new("Point", "").unwrap();
+    points.try_push(Value::Null, None).unwrap();
+    let uxo = uxf::Uxf::default();
+    let err = points.try_push(Value::Null, Some(&uxo)).unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "E446:-:0:undefined ttype Point used at the top level"
+    );
+}
+
 fn valid_row() -> Values {
-    let mut values = Values::new();
     // Normally we'd use .into() for all except Null, but just to show
-    values.push(Value::Bool(true));
-    values.push(Value::Int(-919));
-    values.push(Value::Null);
-    values.push(Value::from("elephant <ears>"));
-    values.push(Value::Real(1.73e-5));
+    let values: Values = vec![
+        Value::Bool(true),
+        Value::Int(-919),
+        Value::Null,
+        Value::from("elephant <ears>"),
+        Value::Real(1.73e-5),
+    ];
     values
 }
+
+fn ints(lst: &List) -> Vec<i64> {
+    lst.iter().map(|v| v.as_int().unwrap()).collect()
+}
+
+#[test]
+fn t_list_mutation() {
+    let mut lst = List::new("int", "").unwrap();
+    lst.extend([5.into(), 1.into(), 1.into(), 3.into()]);
+    lst.insert(0, 7.into()).unwrap();
+    lst.insert(5, 9.into()).unwrap();
+    assert_eq!(ints(&lst), [7, 5, 1, 1, 3, 9]);
+    assert_eq!(
+        lst.insert(7, 0.into()).unwrap_err().to_string(),
+        "E760:-:0:can't insert at index 7 in a list of length 6"
+    );
+    assert_eq!(lst.remove(1), Some(5.into()));
+    assert!(lst.remove(5).is_none());
+    lst.dedup();
+    assert_eq!(ints(&lst), [7, 1, 3, 9]);
+    lst.swap(0, 3).unwrap();
+    assert_eq!(ints(&lst), [9, 1, 3, 7]);
+    assert_eq!(
+        lst.swap(0, 4).unwrap_err().to_string(),
+        "E761:-:0:index 4 out of range for collection of length 4"
+    );
+    lst.sort_by(Value::cmp_key_order);
+    assert_eq!(ints(&lst), [1, 3, 7, 9]);
+    lst.retain(|v| v.as_int().unwrap() > 2);
+    assert_eq!(ints(&lst), [3, 7, 9]);
+}
@@ -0,0 +1,147 @@
+// Copyright © 2022 Mark Summerfield. All rights reserved.
+// License: GPLv3
+
+mod utils;
+
+use std::fs;
+use utils::temp_dir;
+use uxf::lsp::{Analysis, CompletionKind, Position, Range, Severity};
+
+const UXT: &str = "uxf 1\n=#<pt> Point x:int y:real\n\
+    =Shape name:str at:Point\n|Color Red Green\n\
+    [(Shape <sq> (Point 1 2.5) <tri> (Point -3 4.0)) (Red) <héllo> 7]\n";
+
+// Returns the position of the given text in the given line of UXT
+fn at(line: u32, text: &str) -> Position {
+    let column =
+        UXT.lines().nth(line as usize).unwrap().find(text).unwrap();
+    Position::new(line, column as u32)
+}
+
+#[test]
+fn t_lsp_diagnostics() {
+    let analysis = Analysis::new("-", UXT);
+    assert!(analysis.diagnostics().is_empty());
+    assert!(analysis.uxf().is_some());
+    // A repair (int to real) is reported on its line
+    let analysis = Analysis::new("-", "uxf 1\n=P x:real\n\n(P 1)\n");
+    let diagnostics = analysis.diagnostics();
+    assert_eq!(diagnostics.len(), 1);
+    assert_eq!(diagnostics[0].severity, Severity::Information);
+    assert!(diagnostics[0].code.starts_with('R'));
+    assert_eq!(diagnostics[0].range.start, Position::new(3, 0));
+    // An error spans its whole line
+    let analysis = Analysis::new("-", "uxf 1\n=P x\n(P 1 <a)\n");
+    assert!(analysis.uxf().is_none());
+    let diagnostics = analysis.diagnostics();
+    assert_eq!(diagnostics.len(), 1);
+    assert_eq!(diagnostics[0].severity, Severity::Error);
+    assert!(diagnostics[0].code.starts_with('E'));
+    assert!(!diagnostics[0].message.starts_with('E'));
+    assert_eq!(analysis.end(), Position::new(3, 0));
+}
+
+#[test]
+fn t_lsp_format() {
+    let uxt = "uxf 1\n=P x y\n[(P   1 2)   3]\n";
+    let format = uxf::Format::default();
+    let analysis = Analysis::new("-", uxt);
+    assert_eq!(
+        analysis.format(&format).unwrap(),
+        uxf::parse(uxt).unwrap().to_text_format(&format)
+    );
+    assert!(Analysis::new("-", "uxf 1\n[<a]\n")
+        .format(&uxf::Format::default())
+        .is_none());
+}
+
+#[test]
+fn t_lsp_hover() {
+    let analysis = Analysis::new("-", UXT);
+    // On a ttype use
+    let hover = analysis.hover(Position::new(4, 4)).unwrap();
+    assert_eq!(hover, "```uxf\n=Shape name:str at:Point\n```");
+    let hover = analysis.hover(Position::new(2, 20)).unwrap();
+    assert_eq!(hover, "```uxf\n=#<pt> Point x:int y:real\n```");
+    // On table values: the column follows the record
+    let hover = analysis.hover(at(4, "<sq>")).unwrap(); // <sq>
+    assert!(hover.ends_with("Field: `name:str`"));
+    let hover = analysis.hover(at(4, "2.5")).unwrap(); // 2.5
+    assert!(hover.ends_with("Field: `y:real`"));
+    let hover = analysis.hover(at(4, "-3")).unwrap(); // -3
+    assert!(hover.contains("=#<pt> Point"));
+    assert!(hover.ends_with("Field: `x:int`"));
+    // Not in a table, or in a str
+    assert!(analysis.hover(at(4, "7")).is_none()); // 7
+    assert!(analysis.hover(Position::new(0, 0)).is_none());
+    let analysis = Analysis::new("-", "uxf 1\n=P x\n(P <P>)\n");
+    assert!(analysis.hover(Position::new(2, 4)).unwrap().contains("`x`"));
+}
+
+#[test]
+fn t_lsp_definition() {
+    let dir = temp_dir("uxf_t_lsp_definition");
+    let uxi = dir.join("shapes.uxi");
+    fs::write(&uxi, "uxf 1\n#<shapes>\n=Circle x y r\n=Square x y s\n[]\n")
+        .unwrap();
+    let filename = dir.join("data.uxf").to_string_lossy().to_string();
+    let uxi = uxi.to_string_lossy().to_string();
+    let uxt = "uxf 1\n!shapes.uxi\n=Point x y\n|Color Red Green\n\
+        [(Square 1 2 3) (Point 4 5) (Green)]\n";
+    let analysis = Analysis::new(&filename, uxt);
+    assert!(analysis.diagnostics().is_empty());
+    let location = analysis.definition(Position::new(4, 20)).unwrap();
+    assert_eq!(location.filename, filename);
+    assert_eq!(
+        location.range,
+        Range { start: Position::new(2, 0), end: Position::new(2, 10) }
+    );
+    // An enum member goes to its enum
+    let location = analysis.definition(Position::new(4, 30)).unwrap();
+    assert_eq!(location.range.start, Position::new(3, 0));
+    let location = analysis.definition(Position::new(4, 3)).unwrap();
+    assert_eq!(location.filename, uxi);
+    assert_eq!(location.range.start, Position::new(3, 0));
+    let location = analysis.definition(Position::new(1, 3)).unwrap();
+    assert_eq!(location.filename, uxi);
+    assert_eq!(location.range, Range::default());
+    assert!(analysis
+        .hover(Position::new(4, 3))
+        .unwrap()
+        .ends_with("Imported from `shapes.uxi`"));
+    assert!(analysis.definition(Position::new(4, 0)).is_none());
+    // Diagnostics after the imports are on the right lines
+    let uxt = "uxf 1\n!shapes.uxi\n!shapes.uxi\n=P x:int\n\n(P <a>)\n";
+    let analysis = Analysis::new(&filename, uxt);
+    let diagnostics = analysis.diagnostics();
+    assert_eq!(diagnostics.len(), 1);
+    assert_eq!(diagnostics[0].range.start, Position::new(5, 0));
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn t_lsp_completions() {
+    let analysis = Analysis::new("-", UXT);
+    let completions = analysis.completions(at(4, "2.5"));
+    let labels: Vec<&str> =
+        completions.iter().map(|c| c.label.as_str()).collect();
+    assert_eq!(
+        labels,
+        ["x", "y", "Color", "Green", "Point", "Red", "Shape"]
+    );
+    assert_eq!(completions[1].kind, CompletionKind::Field);
+    assert_eq!(completions[1].detail, "real");
+    assert_eq!(completions[4].kind, CompletionKind::TType);
+    assert_eq!(completions[4].detail, "=#<pt> Point x:int y:real");
+    // After : the vtypes are offered too
+    let completions = analysis.completions(Position::new(2, 20));
+    assert_eq!(completions[0].label, "bool");
+    assert_eq!(completions[0].kind, CompletionKind::VType);
+    // Still works (using local definitions) if the text has an error
+    let analysis = Analysis::new("-", "uxf 1\n=Point x y\n[(Po\n");
+    assert!(analysis.uxf().is_none());
+    let completions = analysis.completions(Position::new(2, 4));
+    let labels: Vec<&str> =
+        completions.iter().map(|c| c.label.as_str()).collect();
+    assert_eq!(labels, ["Point"]);
+}
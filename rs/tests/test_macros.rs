@@ -0,0 +1,85 @@
+// Copyright © 2022 Mark Summerfield. All rights reserved.
+// License: GPLv3
+
+use chrono::NaiveDate;
+use uxf::value::Value;
+
+#[test]
+fn t_macros_value() {
+    assert_eq!(uxf::uxf!(null), Value::Null);
+    assert_eq!(uxf::uxf!(7), Value::Int(7));
+    assert_eq!(uxf::uxf!((3 + 4) * 2), Value::Int(14));
+    let x = 2.5;
+    let when = NaiveDate::from_ymd(2022, 10, 18);
+    let value = uxf::uxf!([
+        1,
+        x,
+        "one",
+        null,
+        true,
+        when,
+        [int; 1, 2, 3,],
+        [],
+        {str int; "a" => 1, "b" => 2},
+        {int; 1 => [real; x], 2 => null},
+        {},
+        {"k" => {"n" => (=Pair a b; (1, 2))}},
+    ]);
+    let lst = value.as_list().unwrap();
+    assert_eq!(lst.len(), 12);
+    assert_eq!(lst[0], Value::Int(1));
+    assert_eq!(lst[1], Value::Real(2.5));
+    assert_eq!(lst[3], Value::Null);
+    assert_eq!(lst[5], Value::Date(when));
+    let ints = lst[6].as_list().unwrap();
+    assert_eq!(ints.vtype(), "int");
+    assert_eq!(ints.len(), 3);
+    let m = lst[8].as_map().unwrap();
+    assert_eq!(m.ktype(), "str");
+    assert_eq!(m.vtype(), "int");
+    assert_eq!(m.get(&"b".into()), Some(&Value::Int(2)));
+    let m = lst[9].as_map().unwrap();
+    assert_eq!(m.ktype(), "int");
+    assert_eq!(m.vtype(), "");
+    assert!(lst[10].as_map().unwrap().is_empty());
+}
+
+#[test]
+fn t_macros_table() {
+    let name = String::from("Ann");
+    let value = uxf::uxf!((=Person name:str age:int;
+                      (name.clone(), 30), ("Bob", null),));
+    let table = value.as_table().unwrap();
+    assert_eq!(table.ttype(), "Person");
+    assert_eq!(table.len(), 2);
+    assert_eq!(table.view(0).unwrap().get_str("name").unwrap(), "Ann");
+    assert_eq!(table.view(1).unwrap().get("age"), Some(&Value::Null));
+    let tclass = table.tclass().clone();
+    let value = uxf::uxf!((tclass; ("Cy", 7)));
+    assert_eq!(value.as_table().unwrap().len(), 1);
+    let value = uxf::uxf!((=Flag));
+    let table = value.as_table().unwrap();
+    assert!(table.is_fieldless());
+    assert!(table.is_empty());
+}
+
+#[test]
+fn t_macros_uxf() {
+    let uxo = uxf::uxf!(uxf "test"; [
+        (=Point x:int y:int; (1, 2), (3, 4)),
+        {str; "origin" => (=Point x:int y:int; (0, 0))},
+    ]);
+    assert_eq!(uxo.custom(), "test");
+    let text = uxo.to_text();
+    assert!(text.starts_with("uxf 1 test\n=Point x:int y:int\n[\n"));
+    let parsed = uxf::parse(&text).unwrap();
+    assert_eq!(parsed, uxo);
+    let uxo = uxf::uxf!(uxf; [int; 1]);
+    assert_eq!(uxo.to_text(), "uxf 1\n[int 1]\n");
+}
+
+#[test]
+#[should_panic(expected = "uxf!: invalid table record")]
+fn t_macros_bad_record() {
+    uxf::uxf!((=Point x y; (1, 2, 3)));
+}
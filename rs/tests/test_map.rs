@@ -5,8 +5,9 @@ use chrono::prelude::*;
 use std::collections::HashMap;
 use uxf::key::Key;
 use uxf::list::List;
-use uxf::map::Map;
+use uxf::map::{Map, MapOrder};
 use uxf::value::Value;
+use uxf::Format;
 
 #[test]
 fn t_map_empty() {
@@ -276,7 +277,7 @@ fn t_map_ordering() {
             (50, "L"),
             (1, "I"),
         ] {
-            items.insert(n.into(), s.clone().into());
+            items.insert(n.into(), s.into());
             items.insert(s.into(), n.into());
         }
     }
@@ -322,6 +323,67 @@ fn t_map_ordering() {
     );
 }
 
+#[test]
+fn t_map_insertion_order() {
+    let mut m = Map::default();
+    for (k, v) in [("zed", 1), ("art", 2), ("Max", 3), ("bee", 4)] {
+        m.insert(k.into(), v.into());
+    }
+    let sorted: Vec<String> =
+        m.sorted_keys().iter().map(|k| k.to_string()).collect();
+    assert_eq!(sorted, ["<art>", "<bee>", "<Max>", "<zed>"]);
+    let keys: Vec<String> =
+        m.keys().iter().map(|k| k.to_string()).collect();
+    assert_eq!(keys, ["<zed>", "<art>", "<Max>", "<bee>"]);
+    // Replacing a value keeps its position; removing keeps the others'
+    m.insert("art".into(), 20.into());
+    assert_eq!(m.remove(&"Max".into()), Some(3.into()));
+    m.insert("cat".into(), 5.into());
+    let keys: Vec<String> = m
+        .keys_in(MapOrder::Insertion)
+        .iter()
+        .map(|k| k.to_string())
+        .collect();
+    assert_eq!(keys, ["<zed>", "<art>", "<bee>", "<cat>"]);
+    assert_eq!(m.to_string(), "{<art> 20\n<bee> 4\n<cat> 5\n<zed> 1}");
+    // Insertion order doesn't affect equality
+    let mut n = m.clone();
+    n.sort_keys();
+    assert_eq!(n.keys(), n.sorted_keys());
+    assert_eq!(m, n);
+}
+
+#[test]
+fn t_map_format_order() {
+    let uxt =
+        "uxf 1\n{\n  <zed> 1\n  <art> {\n    3 <c>\n    1 <a>\n  }\n  \
+               <Max> 3\n}\n";
+    let uxo = uxf::parse(uxt).unwrap();
+    assert_eq!(
+        uxo.to_text(),
+        "uxf 1\n{\n  <art> {\n    1 <a>\n    3 <c>\n  }\n  <Max> 3\n  \
+        <zed> 1\n}\n"
+    );
+    let format = Format::default().with_map_order(MapOrder::Insertion);
+    assert_eq!(uxo.to_text_format(&format), uxt);
+}
+
+#[test]
+fn t_map_try_insert() {
+    let mut m = Map::new("int", "date", "").unwrap();
+    let d = NaiveDate::from_ymd(2022, 10, 7);
+    assert!(m.try_insert(Key::Int(1), d.into(), None).unwrap().is_none());
+    assert!(m.try_insert(Key::Int(1), d.into(), None).unwrap().is_some());
+    let err = m.try_insert("x".into(), d.into(), None).unwrap_err();
+    assert_eq!(err.to_string(), "E500:-:0:expected int key, got str <x>");
+    let err = m.try_insert(Key::Int(2), 3.5.into(), None).unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "E500:-:0:expected date, got real 3.5 at {2}"
+    );
+    assert_eq!(m.len(), 1);
+}
+
 /*
 #[test]
 fn t_map_err() {
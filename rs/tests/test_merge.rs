@@ -0,0 +1,223 @@
+// Copyright © 2022 Mark Summerfield. All rights reserved.
+// License: GPLv3
+
+mod utils;
+
+use std::{cell::RefCell, fs, rc::Rc};
+use utils::{assert_event, assert_warning, temp_dir};
+use uxf::event::{Event, EventKind, OnEventFn};
+use uxf::merge::{include, merge, merge_files, MergeAs};
+
+fn parse(text: &str) -> uxf::Uxf {
+    uxf::parse(text).unwrap()
+}
+
+fn collector() -> (OnEventFn, Rc<RefCell<Vec<Event>>>) {
+    let events = Rc::new(RefCell::new(Vec::<Event>::new()));
+    let on_event: OnEventFn = {
+        let events = Rc::clone(&events);
+        Rc::new(move |event: &Event| {
+            events.borrow_mut().push(event.clone())
+        })
+    };
+    (on_event, events)
+}
+
+#[test]
+fn t_merge_map_and_list() {
+    let a = parse("uxf 1 A\n#<one>\n=Point x y\n[(Point 1 2)]\n");
+    let b = parse("uxf 1 B\n#<two>\n=Point x y\n{<k> (Point 3 4)}\n");
+    let (on_event, events) = collector();
+    let uxo = merge(
+        vec![("a", a.clone()), ("b", b.clone())],
+        MergeAs::Map,
+        Some(on_event),
+    )
+    .unwrap();
+    assert!(events.borrow().is_empty());
+    assert_eq!(uxo.comment(), "one\ntwo");
+    assert!(uxo.tclass("Point").is_some());
+    assert_eq!(
+        uxo.to_string(),
+        "uxf 1\n#<one\ntwo>\n=Point x y\n\
+        {<a> [(Point 1 2)]\n<b> {<k> (Point 3 4)}}\n"
+    );
+    let uxo = merge(vec![("a", a), ("b", b)], MergeAs::List, None).unwrap();
+    let list = uxo.value().as_list().unwrap();
+    assert_eq!(list.len(), 2);
+    assert!(list[1].is_map());
+    assert!(merge(vec![], MergeAs::List, None)
+        .unwrap_err()
+        .to_string()
+        .starts_with("E834:"));
+}
+
+#[test]
+fn t_merge_duplicate_name() {
+    let (on_event, events) = collector();
+    let uxo = merge(
+        vec![("a", parse("uxf 1\n[1]\n")), ("a", parse("uxf 1\n[2]\n"))],
+        MergeAs::Map,
+        Some(on_event),
+    )
+    .unwrap();
+    assert_eq!(uxo.to_string(), "uxf 1\n{<a> [2]}\n");
+    let events = events.borrow();
+    assert_eq!(events.len(), 1);
+    assert_event(
+        &events[0],
+        EventKind::Warning,
+        831,
+        "a",
+        0,
+        "duplicate name replaces earlier value",
+    );
+}
+
+#[test]
+fn t_merge_conflict() {
+    let a =
+        parse("uxf 1\n=Point x y\n=Point2 a\n[(Point 1 2) (Point2 0)]\n");
+    let b = parse(
+        "uxf 1\n=Point x y z\n=Line a:Point b:Point\n\
+        [[Point (Point 3 4 5)] (Line (Point 1 2 3) ?)]\n",
+    );
+    let (on_event, events) = collector();
+    let uxo =
+        merge(vec![("a", a), ("b", b)], MergeAs::List, Some(on_event))
+            .unwrap();
+    {
+        let events = events.borrow();
+        assert_eq!(events.len(), 1);
+        assert_event(
+            &events[0],
+            EventKind::Repair,
+            830,
+            "b",
+            0,
+            "renamed ttype Point to Point3 since it conflicts with an \
+            earlier definition",
+        );
+    }
+    assert_eq!(
+        uxo.to_string(),
+        "uxf 1\n=Line a:Point3 b:Point3\n=Point x y\n=Point2 a\n\
+        =Point3 x y z\n[[(Point 1 2)\n(Point2 0)]\n\
+        [[Point3 (Point3 3 4 5)]\n(Line (Point3 1 2 3) ?)]]\n"
+    );
+    // The merged output must itself be valid UXF
+    assert_eq!(parse(&uxo.to_string()), uxo);
+}
+
+#[test]
+fn t_merge_dependent_conflict() {
+    // Line's fields are identical but refer to a renamed ttype so Line
+    // must be renamed too
+    let a = parse("uxf 1\n=Point x y\n=Line a:Point\n[(Line ?)]\n");
+    let b = parse("uxf 1\n=Point x y z\n=Line a:Point\n[(Line ?)]\n");
+    let (on_event, events) = collector();
+    let uxo =
+        merge(vec![("a", a), ("b", b)], MergeAs::List, Some(on_event))
+            .unwrap();
+    assert_eq!(events.borrow().len(), 2);
+    assert_eq!(
+        uxo.to_string(),
+        "uxf 1\n=Line a:Point\n=Line2 a:Point2\n=Point x y\n\
+        =Point2 x y z\n[[(Line ?)]\n[(Line2 ?)]]\n"
+    );
+}
+
+#[test]
+fn t_merge_imports() {
+    let a = parse("uxf 1\n!complex\n[(Complex 1.0 2.0)]\n");
+    let b = parse("uxf 1\n!complex\n=Point x y\n[(Point 1 2)]\n");
+    let uxo = merge(vec![("a", a), ("b", b)], MergeAs::List, None).unwrap();
+    assert!(uxo.to_string().starts_with("uxf 1\n!complex\n=Point x y\n"));
+}
+
+#[test]
+fn t_merge_relative_imports() {
+    let dir = temp_dir("uxf_t_merge_relative_imports");
+    for (folder, import, def, value) in [
+        ("a", "point.uxi", "=Point x y", "[(Point 1 2)]"),
+        ("b", "defs/shape.uxi", "=Shape name", "[(Shape <box>)]"),
+    ] {
+        let path = dir.join(folder);
+        fs::create_dir_all(path.join("defs")).unwrap();
+        fs::write(path.join(import), format!("uxf 1\n{def}\n[]\n"))
+            .unwrap();
+        fs::write(
+            path.join(format!("{folder}.uxf")),
+            format!("uxf 1\n!{import}\n!complex\n{value}\n"),
+        )
+        .unwrap();
+    }
+    let a = dir.join("a").join("a.uxf").to_string_lossy().to_string();
+    let b = dir.join("b").join("b.uxf").to_string_lossy().to_string();
+    let uxo = merge_files(
+        &[&a, &b],
+        MergeAs::List,
+        uxf::ParserOptions::DEFAULT,
+        None,
+    )
+    .unwrap();
+    // The relative imports are inlined; the system import is kept
+    let text = "uxf 1\n!complex\n=Point x y\n=Shape name\n\
+        [[(Point 1 2)]\n[(Shape <box>)]]\n";
+    assert_eq!(uxo.to_string(), text);
+    let out = dir.join("out.uxf");
+    uxo.write(&out.to_string_lossy()).unwrap();
+    let uxo = uxf::parse(&out.to_string_lossy()).unwrap();
+    assert_eq!(uxo.to_string(), text);
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn t_merge_include() {
+    let dir = temp_dir("uxf_t_merge_include");
+    fs::write(dir.join("a.uxf"), "uxf 1\n=Point x y\n[(Point 1 2)]\n")
+        .unwrap();
+    fs::write(dir.join("b.uxf"), "uxf 1\n=Point x y z\n[(Point 3 4 5)]\n")
+        .unwrap();
+    let inc = dir.join("inc.uxf");
+    fs::write(
+        &inc,
+        "uxf 1 UXF Include\n=include filename:str\n\
+        (include <a.uxf> <b.uxf>)\n",
+    )
+    .unwrap();
+    let (on_event, events) = collector();
+    let uxo = include(
+        &inc.to_string_lossy(),
+        uxf::ParserOptions::DEFAULT,
+        Some(on_event),
+    )
+    .unwrap();
+    assert_eq!(events.borrow().len(), 1);
+    assert_eq!(
+        uxo.to_string(),
+        "uxf 1\n=Point x y\n=Point2 x y z\n\
+        [[(Point 1 2)]\n[(Point2 3 4 5)]]\n"
+    );
+    let a = dir.join("a.uxf").to_string_lossy().to_string();
+    let uxo = merge_files(
+        &[&a, &a],
+        MergeAs::Map,
+        uxf::ParserOptions::DEFAULT,
+        Some(Rc::new(|event: &Event| {
+            assert_warning(
+                &Event { filename: "-".to_string(), ..event.clone() },
+                831,
+                "duplicate name replaces earlier value",
+            )
+        })),
+    )
+    .unwrap();
+    assert_eq!(uxo.value().as_map().unwrap().len(), 1);
+    fs::write(&inc, "uxf 1\n[<a.uxf>]\n").unwrap();
+    let err =
+        include(&inc.to_string_lossy(), uxf::ParserOptions::DEFAULT, None)
+            .unwrap_err();
+    assert!(err.to_string().starts_with("E832:"));
+    fs::remove_dir_all(&dir).unwrap();
+}
@@ -0,0 +1,98 @@
+// Copyright © 2022 Mark Summerfield. All rights reserved.
+// License: GPLv3
+
+use uxf::render::{to_ansi, to_html};
+
+const UXT: &str = "uxf 1 Custom\n#<file comment>\n\
+    =#<a pt> Point x:int y:real\n=Red\n{str\n  <a> [(Point 1 2.5 -3 4.0)]\n\
+    <b> (:AB CD:)\n  <c> 2022-01-02\n  <d> (Red)\n  <e> ?\n\
+    <f> {int 1 <x&lt;y>}\n  <g> <a long str that will need to be wrapped \
+    over two lines because it is wider than the wrapwidth allows>\n}\n";
+
+// Returns the text with the ANSI escapes removed
+fn strip_ansi(text: &str) -> String {
+    let mut plain = String::new();
+    let mut in_escape = false;
+    for c in text.chars() {
+        if in_escape {
+            in_escape = c != 'm';
+        } else if c == '\x1b' {
+            in_escape = true;
+        } else {
+            plain.push(c);
+        }
+    }
+    plain
+}
+
+#[test]
+fn t_render_ansi() {
+    let uxo = uxf::parse(UXT).unwrap();
+    let format = uxf::Format::new(2, 60, 0);
+    let ansi = to_ansi(&uxo, &format);
+    // Same text and layout as the pretty printer's
+    assert_eq!(strip_ansi(&ansi), uxo.to_text_format(&format));
+    for part in [
+        "\x1b[1muxf 1 Custom\x1b[0m",
+        "\x1b[3;90m#<file comment>\x1b[0m",
+        "=\x1b[3;90m#<a pt>\x1b[0m \x1b[1;34mPoint\x1b[0m \
+        \x1b[36mx\x1b[0m:\x1b[34mint\x1b[0m",
+        "{\x1b[34mstr\x1b[0m",
+        "\x1b[1;33m<a>\x1b[0m [(\x1b[1;34mPoint\x1b[0m",
+        "\x1b[31m2.5\x1b[0m",
+        "\x1b[33m(:ABCD:)\x1b[0m",
+        "\x1b[35m2022-01-02\x1b[0m",
+        "(\x1b[1;34mRed\x1b[0m)",
+        "\x1b[95m?\x1b[0m",
+        "\x1b[1;33m1\x1b[0m \x1b[32m<x&lt;y>\x1b[0m}",
+        "\x1b[32m<a long str that will need to be wrapped over two lines \
+        >\x1b[0m &\n",
+    ] {
+        assert!(ansi.contains(part), "{part:?} not in {ansi:?}");
+    }
+    // A multiline str is coloured line by line
+    let uxo = uxf::parse("uxf 1\n[<one\ntwo>]\n").unwrap();
+    let ansi = to_ansi(&uxo, &uxf::Format::default());
+    assert!(ansi.contains("\x1b[32m<one\x1b[0m\n\x1b[32mtwo>\x1b[0m"));
+}
+
+#[test]
+fn t_render_html() {
+    let uxo = uxf::parse(UXT).unwrap();
+    let html = to_html(&uxo, &uxf::Format::default());
+    assert!(html.starts_with("<!DOCTYPE html>\n<html>\n"));
+    assert!(html.ends_with("</body>\n</html>\n"));
+    assert!(html.contains(
+        "<pre class=\"uxf\"><span class=\"header\">uxf 1 Custom</span>\n\
+        <span class=\"comment\">#&lt;file comment&gt;</span>\n"
+    ));
+    // The ttype definitions end the preamble
+    assert!(html.contains("=<span class=\"ttype\">Red</span></pre>\n"));
+    // A table has its TClass definition as its caption and a field header
+    assert!(html.contains(
+        "<table class=\"table\">\n<caption>=<span class=\"comment\">\
+        #&lt;a pt&gt;</span> <span class=\"ttype\">Point</span> \
+        <span class=\"field\">x</span>:<span class=\"vtype\">int</span> \
+        <span class=\"field\">y</span>:<span class=\"vtype\">real</span>\
+        </caption>\n<thead><tr><th><span class=\"field\">x</span>:\
+        <span class=\"vtype\">int</span></th>"
+    ));
+    assert!(html.contains(
+        "<tr><td><span class=\"number\">-3</span></td>\
+        <td><span class=\"number\">4.0</span></td></tr>"
+    ));
+    // A map is a table of keys and values
+    assert!(html.contains(
+        "<tr><th><span class=\"key\">&lt;c&gt;</span></th>\
+        <td><span class=\"date\">2022-01-02</span></td></tr>"
+    ));
+    assert!(html.contains(
+        "<tr><th><span class=\"key\">1</span></th>\
+        <td><span class=\"str\">&lt;x&amp;lt;y&gt;</span></td></tr>"
+    ));
+    assert!(html.contains("<td>(<span class=\"ttype\">Red</span>)</td>"));
+    assert!(html.contains("<ol class=\"list\" start=\"0\">\n<li>"));
+    // Reals use the format's decimal places
+    let html = to_html(&uxo, &uxf::Format::new(2, 96, 3));
+    assert!(html.contains("<span class=\"number\">2.500</span>"));
+}
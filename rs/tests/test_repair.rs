@@ -0,0 +1,113 @@
+// Copyright © 2022 Mark Summerfield. All rights reserved.
+// License: GPLv3
+
+mod utils;
+
+use std::{cell::RefCell, rc::Rc};
+use utils::assert_event;
+use uxf::event::{Event, EventKind};
+use uxf::repair::{RepairAction, RepairPolicy};
+
+fn parse_events(
+    uxt: &str,
+    repair_policy: &RepairPolicy,
+) -> (anyhow::Result<uxf::Uxf>, Vec<Event>) {
+    let events = Rc::new(RefCell::new(Vec::<Event>::new()));
+    let reply = uxf::parse_policy(
+        uxt,
+        uxf::ParserOptions::default(),
+        repair_policy,
+        Some(Rc::new({
+            let events = Rc::clone(&events);
+            move |event| {
+                let mut events = events.borrow_mut();
+                events.push(event.clone());
+            }
+        })),
+    );
+    let events = events.borrow().clone();
+    (reply, events)
+}
+
+#[test]
+fn t_repair_default() {
+    let (uxo, events) =
+        parse_events("uxf 1\n[int 1.6]", &RepairPolicy::default());
+    assert_eq!(uxo.unwrap().to_string(), "uxf 1\n[int 2]\n");
+    assert_eq!(events.len(), 1);
+    assert_event(
+        &events[0],
+        EventKind::Repair,
+        486,
+        "-",
+        2,
+        "converted real 1.6 to int 2",
+    );
+    let (uxo, events) =
+        parse_events("uxf 1\n[real 3 <4.5>]", &RepairPolicy::default());
+    assert_eq!(uxo.unwrap().to_string(), "uxf 1\n[real 3.0\n4.5]\n");
+    assert_eq!(events.len(), 2);
+    assert_event(
+        &events[0],
+        EventKind::Repair,
+        486,
+        "-",
+        2,
+        "converted int 3 to real 3.0",
+    );
+    assert_event(
+        &events[1],
+        EventKind::Repair,
+        486,
+        "-",
+        2,
+        "converted str <4.5> to real 4.5",
+    );
+}
+
+#[test]
+fn t_repair_silent() {
+    let policy = RepairPolicy::new(RepairAction::Repair);
+    let (uxo, events) = parse_events("uxf 1\n[int 1.6 <7>]", &policy);
+    assert_eq!(uxo.unwrap().to_string(), "uxf 1\n[int 2\n7]\n");
+    assert!(events.is_empty());
+}
+
+#[test]
+fn t_repair_strict() {
+    let policy = RepairPolicy::strict();
+    let (uxo, events) = parse_events("uxf 1\n[int 1.6]", &policy);
+    assert_eq!(
+        uxo.unwrap_err().to_string(),
+        "E487:-:2:won't convert real 1.6 to int 2 since repairs are \
+        not permitted"
+    );
+    assert!(events.is_empty());
+    let (uxo, _) = parse_events("uxf 1\n[date <2022-10-07>]", &policy);
+    assert_eq!(
+        uxo.unwrap_err().to_string(),
+        "E487:-:2:won't convert str <2022-10-07> to date 2022-10-07 since \
+        repairs are not permitted"
+    );
+    let (uxo, events) = parse_events("uxf 1\n[int 1 ? 2]", &policy);
+    assert_eq!(uxo.unwrap().to_string(), "uxf 1\n[int 1\n?\n2]\n");
+    assert!(events.is_empty());
+}
+
+#[test]
+fn t_repair_mixed() {
+    let policy = RepairPolicy {
+        real_to_int: RepairAction::Fail,
+        ..RepairPolicy::default()
+    };
+    let (uxo, events) =
+        parse_events("uxf 1\n=Pair a:int b:real\n(Pair 1 2)", &policy);
+    assert_eq!(
+        uxo.unwrap().to_string(),
+        "uxf 1\n=Pair a:int b:real\n(Pair 1 2.0)\n"
+    );
+    assert_eq!(events.len(), 1);
+    let (uxo, _) =
+        parse_events("uxf 1\n=Pair a:int b:real\n(Pair 1.0 2)", &policy);
+    assert!(uxo.unwrap_err().to_string().starts_with("E487:-:3:"));
+}
@@ -3,7 +3,8 @@
 
 use uxf::field::make_fields;
 use uxf::table::Table;
-use uxf::tclass::{TClass, make_tclass};
+use uxf::tclass::{make_tclass, TClass};
+use uxf::uxf::{ParserOptions, Uxf};
 use uxf::value::Value;
 
 #[test]
@@ -55,6 +56,322 @@ fn t_make_tclass() {
     assert_eq!(tclass.to_string(), "=Point x:int y:int");
 }
 
+#[test]
+fn t_table_try_append() {
+    let tclass = make_tclass("=Point x:int y:real").unwrap();
+    let mut t = Table::new(tclass.clone(), "");
+    t.try_append(vec![1.into(), 2.5.into()], None).unwrap();
+    t.try_append(vec![Value::Null, Value::Null], None).unwrap();
+    let err = t.try_append(vec![1.into(), 2.into()], None).unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "E500:-:0:expected real, got int 2 at [2].y"
+    );
+    let err = t.try_append(vec![1.into()], None).unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "E736:-:0:rows for table of ttype Point must have exactly 2 \
+        values, got 1"
+    );
+    let mut uxo = Uxf::default();
+    let err =
+        t.try_append(vec![3.into(), 4.0.into()], Some(&uxo)).unwrap_err();
+    assert_eq!(err.to_string(), "E450:-:0:undefined ttype Point");
+    uxo.add_tclass(make_tclass("=Point x:int y:int").unwrap());
+    let err =
+        t.try_append(vec![3.into(), 4.0.into()], Some(&uxo)).unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "E457:-:0:table of ttype Point doesn't match its TClass definition"
+    );
+    uxo.add_tclass(tclass);
+    t.try_append(vec![3.into(), 4.0.into()], Some(&uxo)).unwrap();
+    assert_eq!(t.len(), 3);
+}
+
+static PEOPLE: &str = "uxf 1
+=Person id:int name:str city:str age
+=Order id:int person:int item:str
+[
+  (Person
+    1 <Ann> <Leeds> 30
+    2 <bob> <York> 25
+    3 <Cy> <leeds> ?
+    4 <Di> <York> 25.5
+  )
+  (Order
+    10 2 <pen>
+    11 1 <ink>
+    12 2 <pad>
+  )
+]
+";
+
+fn people() -> (Table, Table) {
+    let uxo = uxf::parse(PEOPLE).unwrap();
+    let lst = uxo.value().as_list().unwrap();
+    (lst[0].as_table().unwrap().clone(), lst[1].as_table().unwrap().clone())
+}
+
+#[test]
+fn t_table_sort_filter_project() {
+    let (mut people, _) = people();
+    people.sort_by_fields(&["city", "age"]).unwrap();
+    let ids: Vec<i64> =
+        people.iter().map(|r| r[0].as_int().unwrap()).collect();
+    assert_eq!(ids, [3, 1, 2, 4]); // null < numbers; city case-insensitive
+    people.sort_by_fields(&["name"]).unwrap();
+    let ids: Vec<i64> =
+        people.iter().map(|r| r[0].as_int().unwrap()).collect();
+    assert_eq!(ids, [1, 2, 3, 4]);
+    let err = people.sort_by_fields(&["nosuch"]).unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "E740:-:0:table of ttype Person has no field nosuch"
+    );
+    let yorkers = people.filter(|record| {
+        record.get("city").unwrap().as_str() == Some("York")
+    });
+    assert_eq!(yorkers.tclass(), people.tclass());
+    assert_eq!(yorkers.len(), 2);
+    let names = yorkers.project("Name", &["name", "id"]).unwrap();
+    assert_eq!(names.tclass().to_string(), "=Name name:str id:int");
+    assert_eq!(names.to_string(), "(Name <bob> 2\n<Di> 4)");
+    assert!(people.project("Name", &["name", "name"]).is_err());
+}
+
+#[test]
+fn t_table_group_by() {
+    let (people, orders) = people();
+    let by_city = people.group_by("city").unwrap();
+    assert_eq!(by_city.ktype(), "str");
+    assert_eq!(by_city.vtype(), "Person");
+    assert_eq!(by_city.len(), 3); // Leeds and leeds are distinct keys
+    let york = by_city.get(&"York".into()).unwrap().as_table().unwrap();
+    assert_eq!(
+        york.to_string(),
+        "(Person 2 <bob> <York> 25\n\
+                                  4 <Di> <York> 25.5)"
+    );
+    let by_person = orders.group_by("person").unwrap();
+    assert_eq!(by_person.ktype(), "int");
+    assert_eq!(
+        by_person.get(&2.into()).unwrap().as_table().unwrap().len(),
+        2
+    );
+    let err = people.group_by("age").unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "E741:-:0:can't group table of ttype Person by field age"
+    );
+}
+
+#[test]
+fn t_table_join() {
+    let (people, orders) = people();
+    let joined = people
+        .inner_join(&orders, "PersonOrder", &[("id", "person")])
+        .unwrap();
+    assert_eq!(
+        joined.tclass().to_string(),
+        "=PersonOrder id:int name:str city:str age order_id:int item:str"
+    );
+    assert_eq!(
+        joined.to_string(),
+        "(PersonOrder 1 <Ann> <Leeds> 30 11 <ink>\n\
+        2 <bob> <York> 25 10 <pen>\n2 <bob> <York> 25 12 <pad>)"
+    );
+    let joined = people
+        .left_join(&orders, "PersonOrder", &[("id", "person")])
+        .unwrap();
+    assert_eq!(joined.len(), 5);
+    assert_eq!(
+        joined.last().unwrap().to_vec(),
+        vec![
+            4.into(),
+            "Di".into(),
+            "York".into(),
+            25.5.into(),
+            Value::Null,
+            Value::Null
+        ]
+    );
+    // The joined table is correctly typed so will round-trip
+    let mut uxo = Uxf::new("", "");
+    uxo.add_tclass(joined.tclass().clone());
+    uxo.set_value(joined.into()).unwrap();
+    assert!(uxo.typecheck().is_empty());
+    let again = uxf::parse(&uxo.to_text()).unwrap();
+    assert_eq!(again, uxo);
+    assert!(people.inner_join(&orders, "X", &[]).is_err());
+    assert!(people.inner_join(&orders, "X", &[("id", "who")]).is_err());
+}
+
+#[test]
+fn t_table_join_nulls() {
+    // A null never matches, not even another null (Cy's age is null)
+    let (people, _) = people();
+    let joined =
+        people.inner_join(&people, "Same", &[("age", "age")]).unwrap();
+    let ids: Vec<i64> =
+        joined.iter().map(|r| r[0].as_int().unwrap()).collect();
+    assert_eq!(ids, [1, 2, 4]);
+    let joined =
+        people.left_join(&people, "Same", &[("age", "age")]).unwrap();
+    assert_eq!(joined.len(), 4);
+    assert_eq!(
+        joined[2].to_vec(),
+        vec![
+            3.into(),
+            "Cy".into(),
+            "leeds".into(),
+            Value::Null,
+            Value::Null,
+            Value::Null,
+            Value::Null
+        ]
+    );
+}
+
+#[test]
+fn t_table_key() {
+    let (mut people, mut orders) = people();
+    assert!(!people.is_keyed());
+    assert!(people.find_by(&1.into()).is_none());
+    people.set_key(&["id"], false).unwrap();
+    assert_eq!(people.key(), ["id"]);
+    assert_eq!(people.find_by(&3.into()).unwrap()[1].as_str(), Some("Cy"));
+    assert!(people.find_by(&5.into()).is_none());
+    let err = people
+        .append(vec![3.into(), "Ed".into(), "Hull".into(), Value::Null])
+        .unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "E744:-:0:table of ttype Person already has a record with key 3"
+    );
+    assert_eq!(people.len(), 4);
+    people
+        .append(vec![5.into(), "Ed".into(), "Hull".into(), Value::Null])
+        .unwrap();
+    assert_eq!(people.find_by(&5.into()).unwrap()[1].as_str(), Some("Ed"));
+    assert!(people.append_empty().unwrap_err().to_string().starts_with(
+        "E743:-:0:table of ttype Person has invalid null value for key \
+        field id"
+    ));
+    // Mutation invalidates the index which is rebuilt on demand
+    people.sort_by_fields(&["name"]).unwrap();
+    people.get_field_mut(0, "id").unwrap().clone_from(&50.into());
+    assert!(people.find_by(&1.into()).is_none());
+    assert_eq!(
+        people.find_by(&50.into()).unwrap()[1].as_str(),
+        Some("Ann")
+    );
+    people[1][0] = 50.into();
+    assert!(people.reindex().unwrap_err().to_string().starts_with("E744"));
+    // Composite keys
+    assert!(orders.set_key(&["person"], false).is_err());
+    assert!(!orders.is_keyed());
+    orders.set_key(&["person", "item"], false).unwrap();
+    assert!(orders.find_by(&2.into()).is_none());
+    let order = orders.find_by_keys(&[2.into(), "pad".into()]).unwrap();
+    assert_eq!(order[0], 12.into());
+}
+
+#[test]
+fn t_table_key_text() {
+    let uxt = "uxf 1\n=#<People\nkey: id> Person id:int name:str\n\
+               (Person 1 <Ann> 2 <Bob> 1 <Cy>)\n";
+    // Without TABLE_KEYS the TClass has a key but its tables aren't keyed
+    let uxo = uxf::parse(uxt).unwrap();
+    let table = uxo.value().as_table().unwrap();
+    assert!(!table.is_keyed());
+    assert_eq!(table.tclass().key(), ["id"]);
+    assert_eq!(table.tclass().comment(), "People");
+    assert!(uxo.to_text().starts_with(
+        "uxf 1\n=#<People\nkey: id> Person id:int name:str\n"
+    ));
+    let err = uxf::parse_options(uxt, ParserOptions::TABLE_KEYS, None)
+        .unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "E744:-:4:table of ttype Person already has a record with key 1"
+    );
+    // A declaration naming a field the TClass doesn't have is just a
+    // comment unless tables are being keyed
+    let bad = uxt.replace("key: id", "key: nosuch");
+    let uxo = uxf::parse(&bad).unwrap();
+    assert!(uxo.tclass("Person").unwrap().key().is_empty());
+    assert_eq!(
+        uxo.tclass("Person").unwrap().comment(),
+        "People\nkey: nosuch"
+    );
+    let err = uxf::parse_options(&bad, ParserOptions::TABLE_KEYS, None)
+        .unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "E740:-:0:table of ttype Person has no field nosuch"
+    );
+    // Declaring a key puts it in the text form
+    let mut table = table.clone();
+    table.truncate(2);
+    table.set_key(&["name"], true).unwrap();
+    assert_eq!(table.tclass().key(), ["name"]);
+    assert_eq!(table.tclass().comment(), "People");
+    assert!(table
+        .tclass()
+        .to_string()
+        .starts_with("=#<People\nkey: name> Person"));
+    let mut uxo = Uxf::new("", "");
+    uxo.add_tclass(table.tclass().clone());
+    uxo.set_value(table.into()).unwrap();
+    let uxo =
+        uxf::parse_options(&uxo.to_text(), ParserOptions::TABLE_KEYS, None)
+            .unwrap();
+    let table = uxo.value().as_table().unwrap();
+    assert_eq!(table.key(), ["name"]);
+    assert_eq!(table.find_by(&"Bob".into()).unwrap()[0], 2.into());
+}
+
+#[test]
+fn t_table_mutation() {
+    let (mut people, _) = people();
+    people.set_key(&["id"], false).unwrap();
+    let ed = vec![5.into(), "Ed".into(), "Hull".into(), 40.into()];
+    people.insert(0, ed.clone()).unwrap();
+    assert_eq!(people.find_by(&5.into()).unwrap()[1].as_str(), Some("Ed"));
+    assert!(people
+        .insert(1, ed.clone())
+        .unwrap_err()
+        .to_string()
+        .starts_with("E744:"));
+    assert_eq!(
+        people.insert(0, vec![6.into()]).unwrap_err().to_string(),
+        "E736:-:0:rows for table of ttype Person must have exactly 4 \
+        values, got 1"
+    );
+    assert!(people
+        .insert(7, ed.clone())
+        .unwrap_err()
+        .to_string()
+        .starts_with("E760:"));
+    assert_eq!(people.remove(0), Some(ed.clone()));
+    assert!(people.find_by(&5.into()).is_none());
+    people.swap(0, 3).unwrap();
+    assert_eq!(people[0][1].as_str(), Some("Di"));
+    people.sort_by(|a, b| {
+        a.get_str("name").unwrap().cmp(b.get_str("name").unwrap())
+    });
+    let names: Vec<&str> =
+        people.views().map(|v| v.get_str("name").unwrap()).collect();
+    assert_eq!(names, ["Ann", "Cy", "Di", "bob"]);
+    people.retain(|v| v.get_str("city").unwrap() != "York");
+    assert_eq!(people.len(), 2);
+    people.set_key(&[], false).unwrap();
+    people.extend([ed.clone(), ed]).unwrap();
+    people.dedup();
+    assert_eq!(people.len(), 3);
+}
+
 /*
 #[test]
 fn t_table_nested() {
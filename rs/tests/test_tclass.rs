@@ -33,7 +33,7 @@ fn t_tclass_new() {
     rec[1] = Value::Int(-17);
     rec[2] = Value::Str("Test data".to_string());
     assert_eq!(rec.len(), asize);
-    assert_eq!(rec[0].as_bool().unwrap(), true);
+    assert!(rec[0].as_bool().unwrap());
     assert_eq!(rec[1].as_int().unwrap(), -17);
     assert_eq!(rec[2].as_str().unwrap(), "Test data");
     assert!(rec[3].is_null());
@@ -199,6 +199,49 @@ fn t_tclassbuilder_invalid() {
     check_error(&err.to_string(), 336, "Filename");
 }
 
+#[test]
+fn t_tclass_enum() {
+    let state =
+        TClass::new_enum("State", &["Pending", "Active", "Finished"], "")
+            .unwrap();
+    assert!(state.is_enum());
+    assert!(state.is_fieldless());
+    assert_eq!(state.members(), ["Pending", "Active", "Finished"]);
+    assert!(state.has_member("Active"));
+    assert!(!state.has_member("State"));
+    assert_eq!(state.to_string(), "|State Pending Active Finished");
+    let tclasses = state.member_tclasses();
+    assert_eq!(tclasses.len(), 3);
+    assert!(tclasses.iter().all(|tclass| tclass.is_fieldless()));
+    let active = state.member("Active").unwrap();
+    assert_eq!(active.as_table().unwrap().ttype(), "Active");
+    assert_eq!(
+        state.member("Done").unwrap_err().to_string(),
+        "E529:-:0:Done is not a member of enum State"
+    );
+    let fieldless = TClass::new_fieldless("State", "").unwrap();
+    assert!(!fieldless.is_enum());
+    assert_ne!(state, fieldless);
+    let state2 = TClass::new_enum("State", &["Pending"], "note").unwrap();
+    assert_eq!(state2.to_string(), "|#<note> State Pending");
+    assert_eq!(
+        TClass::new_enum("State", &[], "").unwrap_err().to_string(),
+        "E525:-:0:enum State must have at least one member"
+    );
+    assert_eq!(
+        TClass::new_enum("State", &["A", "B", "A"], "")
+            .unwrap_err()
+            .to_string(),
+        "E527:-:0:enum State has duplicate member A"
+    );
+    assert!(TClass::new_enum("State", &["int"], "").is_err());
+    let mut tcb = TClassBuilder::new("Light", "");
+    tcb.is_enum = true;
+    tcb.append_member("Red");
+    tcb.append_member("Green");
+    assert_eq!(tcb.build().unwrap().members(), ["Red", "Green"]);
+}
+
 fn valid_fields() -> Vec<Field> {
     make_fields(&[
         ("CID", "int"),
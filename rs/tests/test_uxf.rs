@@ -4,7 +4,7 @@
 mod utils;
 
 use std::{cell::RefCell, rc::Rc};
-use utils::{assert_event, check_error};
+use utils::{assert_event, check_error, temp_dir};
 use uxf::event::{Event, EventKind};
 use uxf::field::make_fields;
 use uxf::list::List;
@@ -249,10 +249,10 @@ fn t_uxf_parse141() {
     )
     .unwrap();
     assert!(!&events.borrow().is_empty());
-    assert_eq!(*&events.borrow().len(), 1);
+    assert_eq!(events.borrow().len(), 1);
     let event = &events.borrow()[0].clone();
     assert_event(
-        &event,
+        event,
         EventKind::Warning,
         141,
         "-",
@@ -279,6 +279,23 @@ fn t_uxf_parse160() {
     check_error(&err.to_string(), 160, "c");
 }
 
+#[test]
+fn t_uxf_bare_words() {
+    // Bare words (e.g., no, int, date, str) only match whole words
+    for text in [
+        "uxf 1\n=T note strength\n[(T 1 2)]\n",
+        "uxf 1\n=T integer\n[(T 1)]\n",
+        "uxf 1\n=dates d\n[(dates 1)]\n",
+        "uxf 1\n=string a\n[(string 1)]\n",
+    ] {
+        assert_eq!(uxf::parse(text).unwrap().to_string(), text);
+    }
+    let err = uxf::parse("uxf 1\n[no1]\n").unwrap_err();
+    assert_eq!(err.to_string(), "E446:-:2:expected list vtype, got no1");
+    let err = uxf::parse("uxf 1\n[int_x]\n").unwrap_err();
+    assert_eq!(err.to_string(), "E446:-:2:expected list vtype, got int_x");
+}
+
 #[test]
 fn t_uxf_parse_ok() {
     let uxo = uxf::parse("uxf 1\n[]").unwrap();
@@ -334,3 +351,404 @@ fn t_uxf_parse_options_ok() {
     // TODO
 }
 */
+
+#[test]
+fn t_uxf_typecheck() {
+    let uxo =
+        uxf::parse("uxf 1\n=Point x:int y:int\n[Point (Point 1 2)]\n")
+            .unwrap();
+    assert!(uxo.typecheck().is_empty());
+    let mut uxo = uxf::Uxf::default();
+    let mut lst = List::new("int", "").unwrap();
+    lst.push(1.into());
+    lst.push("two".into());
+    let mut m = Map::new("str", "Point", "").unwrap();
+    m.insert("a".into(), 3.into());
+    lst.push(m.into());
+    let tclass = TClass::new(
+        "Pair",
+        make_fields(&[("a", "int"), ("b", "")]).unwrap(),
+        "",
+    )
+    .unwrap();
+    let mut t = Table::new(tclass, "");
+    t.append(vec![1.5.into(), Value::Null]).unwrap();
+    lst.push(t.into());
+    uxo.set_value(lst.into()).unwrap();
+    let events = uxo.typecheck();
+    assert_eq!(events.len(), 6);
+    let expected = [
+        (500, "expected int, got str <two> at [1]"),
+        (506, "expected int, got map at [2]"),
+        (446, "undefined ttype Point used at [2]"),
+        (500, "expected Point, got int 3 at [2]{<a>}"),
+        (506, "expected int, got table of ttype Pair at [3]"),
+        (500, "expected int, got real 1.5 at [3][0].a"),
+    ];
+    for (event, (code, message)) in events.iter().zip(expected.iter()) {
+        assert_event(event, EventKind::Warning, *code, "-", 0, message);
+    }
+}
+
+#[test]
+fn t_uxf_enums() {
+    let text = "uxf 1
+|State Pending Active Finished
+=Task name:str state:State args:list
+[
+  [State (Active) ? (Finished)]
+  (Task <t1> (Active) [] <t2> (Pending) [1 2 3] <t3> ? [])
+]
+";
+    // Enums are a language extension so need to be enabled
+    let err = uxf::parse(text).unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "E170:-:2:invalid character encountered, got '|'"
+    );
+    let uxo =
+        uxf::parse_options(text, uxf::ParserOptions::ENUMS, None).unwrap();
+    let state = uxo.tclass("State").unwrap();
+    assert!(state.is_enum());
+    assert_eq!(state.members(), ["Pending", "Active", "Finished"]);
+    assert!(uxo.tclass("Active").unwrap().is_fieldless());
+    assert!(uxo.typecheck().is_empty());
+    let out = uxo.to_text();
+    assert!(out.starts_with(
+        "uxf 1\n|State Pending Active Finished\n\
+        =Task name:str state:State args:list\n"
+    ));
+    let uxo2 =
+        uxf::parse_options(&out, uxf::ParserOptions::ENUMS, None).unwrap();
+    assert_eq!(uxo, uxo2);
+    assert_eq!(out, uxo2.to_text());
+    assert!(uxo
+        .to_string()
+        .starts_with("uxf 1\n|State Pending Active Finished\n=Task "));
+
+    for (bad, message) in [
+        (
+            "=Task state:State\n(Task (Done))\n",
+            "E506:-:4:expected State, got table of type Done",
+        ),
+        (
+            "=Task state:State\n(Task <Active>)\n",
+            "E500:-:4:expected State (one of: Pending Active Finished), \
+            got <Active>",
+        ),
+        (
+            "=Task state:State\n(Task (State))\n",
+            "E452:-:4:can't create a table of enum ttype State; use one \
+            of its members: Pending Active Finished",
+        ),
+        (
+            "=Done\n=Task state:State\n(Task (Done))\n",
+            "E506:-:5:expected State, got table of type Done",
+        ),
+    ] {
+        let text = format!("uxf 1\n|State Pending Active Finished\n{bad}");
+        let err =
+            uxf::parse_options(&text, uxf::ParserOptions::ENUMS, None)
+                .unwrap_err();
+        assert_eq!(err.to_string(), message);
+    }
+    let err = uxf::parse_options(
+        "uxf 1\n|State Pending:int\n[]\n",
+        uxf::ParserOptions::ENUMS,
+        None,
+    )
+    .unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "E521:-:2:enum members can't have vtypes, got Pending:int"
+    );
+
+    // Building programmatically
+    let state = TClass::new_enum("Light", &["Red", "Green"], "").unwrap();
+    let mut lst = List::new("Light", "").unwrap();
+    lst.push(state.member("Green").unwrap());
+    let mut uxo = Uxf::new("", "");
+    uxo.set_value(Value::from(lst)).unwrap();
+    uxo.add_tclass(state);
+    assert!(uxo.typecheck().is_empty());
+    assert_eq!(uxo.to_text(), "uxf 1\n|Light Red Green\n[Light (Green)]\n");
+}
+
+#[test]
+fn t_uxf_pseudo_vtypes() {
+    let text = "uxf 1
+=Reading station:key value:number note:scalar
+[
+  [number 1 2.5 -3 <4> <5.5>]
+  [key (:AB:) 2022-10-18 2022-10-18T12:00:00 7 <x>]
+  [scalar yes 1 2.5 <s> ?]
+  {str number <a> 1 <b> 2.0}
+  (Reading <north> 17 <ok> 9 -0.5 no)
+]
+";
+    let events = Rc::new(RefCell::new(Vec::<Event>::new()));
+    let uxo = uxf::parse_options(
+        text,
+        uxf::ParserOptions::PSEUDO_VTYPES,
+        Some(Rc::new({
+            let events = Rc::clone(&events);
+            move |event: &Event| events.borrow_mut().push(event.clone())
+        })),
+    )
+    .unwrap();
+    // Only the two strs in the number list are repaired
+    assert_eq!(events.borrow().len(), 2);
+    assert_eq!(events.borrow()[0].message, "converted str <4> to int 4");
+    let lst = uxo.value().as_list().unwrap();
+    let numbers = lst[0].as_list().unwrap();
+    assert_eq!(numbers.vtype(), "number");
+    assert_eq!(numbers[0], Value::Int(1)); // no int → real repair
+    assert_eq!(numbers[1], Value::Real(2.5));
+    assert_eq!(numbers[3], Value::Int(4));
+    assert_eq!(numbers[4], Value::Real(5.5));
+    assert!(uxo.typecheck().is_empty());
+    let out = uxo.to_text();
+    assert!(out.contains("[number\n"));
+    let parse = |text: &str| {
+        uxf::parse_options(text, uxf::ParserOptions::PSEUDO_VTYPES, None)
+    };
+    assert_eq!(parse(&out).unwrap(), uxo);
+
+    for (bad, message) in [
+        ("[number <x>]", "E488:-:2:expected number, got <x>"),
+        ("[number 2022-10-18]", "E500:-:2:expected number, got 2022-10-18"),
+        ("[key 1.5]", "E500:-:2:expected key, got 1.5"),
+        ("[key yes]", "E500:-:2:expected key, got yes"),
+        ("[scalar [1]]", "E506:-:2:expected scalar, got list"),
+        ("=T x:number\n(T [])", "E506:-:3:expected number, got list"),
+    ] {
+        let err = parse(&format!("uxf 1\n{bad}\n")).unwrap_err();
+        assert_eq!(err.to_string(), message);
+    }
+    // Pseudo-vtypes are only vtypes, so they may be (or prefix) names
+    let uxo = parse(
+        "uxf 1\n=T number keys:int number_of:int string\n(T 1 2 3 4)\n",
+    )
+    .unwrap();
+    assert_eq!(
+        uxo.tclass("T").unwrap().fieldnames(),
+        ["number", "keys", "number_of", "string"]
+    );
+
+    let mut lst = List::new("number", "").unwrap();
+    lst.try_push(Value::Int(1), None).unwrap();
+    lst.try_push(Value::Real(1.5), None).unwrap();
+    assert_eq!(
+        lst.try_push(Value::from("x"), None).unwrap_err().to_string(),
+        "E500:-:0:expected number, got str <x> at [2]"
+    );
+    assert_eq!(lst.len(), 2);
+
+    // A Uxf only accepts pseudo-vtypes if it is set to (as it is if
+    // parsed with the option) since otherwise its text wouldn't parse
+    let mut uxo = Uxf::new("", "");
+    uxo.set_value(Value::from(lst.clone())).unwrap();
+    let events = uxo.typecheck();
+    assert_eq!(events.len(), 3);
+    assert_eq!(events[0].code, 446);
+    assert_eq!(
+        events[0].message,
+        "undefined ttype number used at the top level"
+    );
+    assert!(lst.try_push(Value::Int(2), Some(&uxo)).is_err());
+    uxo.set_pseudo_vtypes(true);
+    assert!(uxo.typecheck().is_empty());
+    lst.try_push(Value::Int(2), Some(&uxo)).unwrap();
+    assert_eq!(uxo.to_string(), "uxf 1\n[number 1\n1.5]\n");
+    assert_eq!(parse(&uxo.to_text()).unwrap(), uxo);
+}
+
+#[test]
+fn t_uxf_pseudo_vtypes_compat() {
+    // Files that predate pseudo-vtypes may use their names as ttypes and
+    // fieldnames, with or without ParserOptions::PSEUDO_VTYPES
+    for options in
+        [uxf::ParserOptions::DEFAULT, uxf::ParserOptions::PSEUDO_VTYPES]
+    {
+        let text = "uxf 1\n=key name:str\n(key <a>)\n";
+        let uxo = uxf::parse_options(text, options, None).unwrap();
+        assert_eq!(uxo.value().as_table().unwrap().ttype(), "key");
+        assert_eq!(uxo.to_text(), text);
+
+        let text = "uxf 1\n=Pt key:int scalar:int\n(Pt 1 2)\n";
+        let uxo = uxf::parse_options(text, options, None).unwrap();
+        assert_eq!(
+            uxo.tclass("Pt").unwrap().fieldnames(),
+            ["key", "scalar"]
+        );
+        assert_eq!(uxo.to_text(), text);
+
+        // A ttype shadows the pseudo-vtype of the same name
+        let text = "uxf 1\n=number n:int\n[number (number 1)]\n";
+        let uxo = uxf::parse_options(text, options, None).unwrap();
+        assert!(uxo.typecheck().is_empty());
+        assert_eq!(uxo.to_text(), text);
+    }
+    // Without the option the pseudo-vtypes are undefined ttypes
+    let err = uxf::parse("uxf 1\n[number 1 2.5]\n").unwrap_err();
+    assert_eq!(err.to_string(), "E446:-:2:expected list vtype, got number");
+}
+
+#[test]
+fn t_uxf_write_options() {
+    use std::fs;
+    let dir = temp_dir("uxf_t_uxf_write_options");
+    let filename = dir.join("data.uxf");
+    let filename = filename.to_string_lossy();
+    let bak = format!("{filename}.bak");
+    let format = uxf::Format::default();
+    let uxo = uxf::parse("uxf 1\n[1]\n").unwrap();
+    uxo.write(&filename).unwrap();
+    assert_eq!(
+        fs::read_to_string(filename.as_ref()).unwrap(),
+        "uxf 1\n[1]\n"
+    );
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        fs::set_permissions(
+            filename.as_ref(),
+            fs::Permissions::from_mode(0o600),
+        )
+        .unwrap();
+    }
+    let uxo = uxf::parse("uxf 1\n[3]\n").unwrap();
+    uxo.write_options(&filename, &format, uxf::WriteOptions::BACKUP)
+        .unwrap();
+    assert_eq!(
+        fs::read_to_string(filename.as_ref()).unwrap(),
+        "uxf 1\n[3]\n"
+    );
+    assert_eq!(fs::read_to_string(&bak).unwrap(), "uxf 1\n[1]\n");
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        let mode = |name: &str| {
+            fs::metadata(name).unwrap().permissions().mode() & 0o777
+        };
+        assert_eq!(mode(&filename), 0o600);
+        uxo.write(&filename).unwrap();
+        assert_eq!(mode(&filename), 0o600);
+        fs::set_permissions(
+            filename.as_ref(),
+            fs::Permissions::from_mode(0o640),
+        )
+        .unwrap();
+        uxo.write(&filename).unwrap();
+        assert_eq!(mode(&filename), 0o640);
+        // With DEFAULT_PERMISSIONS the new file has default permissions
+        uxo.write_options(
+            &filename,
+            &format,
+            uxf::WriteOptions::DEFAULT_PERMISSIONS,
+        )
+        .unwrap();
+        assert_ne!(mode(&filename), 0o640);
+        #[cfg(target_os = "linux")]
+        {
+            // i.e., not the private mode used for the temporary file
+            let status = fs::read_to_string("/proc/self/status").unwrap();
+            let umask = status
+                .lines()
+                .find_map(|line| line.strip_prefix("Umask:"))
+                .unwrap();
+            let umask = u32::from_str_radix(umask.trim(), 8).unwrap();
+            assert_eq!(mode(&filename), 0o666 & !umask);
+        }
+        // Writing via a symlink replaces the file it links to
+        let link = dir.join("link.uxf");
+        std::os::unix::fs::symlink(filename.as_ref(), &link).unwrap();
+        let uxo = uxf::parse("uxf 1\n[4]\n").unwrap();
+        uxo.write(&link.to_string_lossy()).unwrap();
+        assert!(link.is_symlink());
+        assert_eq!(
+            fs::read_to_string(filename.as_ref()).unwrap(),
+            "uxf 1\n[4]\n"
+        );
+    }
+    let gz = dir.join("data.uxf.gz");
+    let gz = gz.to_string_lossy();
+    uxo.write(&gz).unwrap();
+    assert_eq!(uxf::parse(&gz).unwrap(), uxo);
+    // A failed write leaves no temporary file behind
+    let missing = dir.join("nosuchdir").join("data.uxf");
+    let err = uxo.write(&missing.to_string_lossy()).unwrap_err();
+    assert!(err.to_string().starts_with("E904:"));
+    assert!(!fs::read_dir(&dir).unwrap().any(|entry| entry
+        .unwrap()
+        .file_name()
+        .to_string_lossy()
+        .ends_with(".tmp")));
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn t_uxf_write_concurrent() {
+    use std::fs;
+    let dir = temp_dir("uxf_t_uxf_write_concurrent");
+    let filename = dir.join("data.uxf").to_string_lossy().to_string();
+    // Concurrent writes of the same file each use their own temporary
+    // file, so each succeeds and the last one wins
+    let threads: Vec<_> = (0..8)
+        .map(|i| {
+            let filename = filename.clone();
+            std::thread::spawn(move || {
+                let uxo = uxf::parse(&format!("uxf 1\n[{i}]\n")).unwrap();
+                uxo.write(&filename).unwrap();
+            })
+        })
+        .collect();
+    for thread in threads {
+        thread.join().unwrap();
+    }
+    let uxo = uxf::parse(&filename).unwrap();
+    assert_eq!(uxo.value().as_list().unwrap().len(), 1);
+    let names: Vec<String> = fs::read_dir(&dir)
+        .unwrap()
+        .map(|entry| {
+            entry.unwrap().file_name().to_string_lossy().to_string()
+        })
+        .collect();
+    assert_eq!(names, ["data.uxf"]);
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn t_uxf_send_sync() {
+    // A Uxf (and so every Value) can be shared between threads
+    fn assert_send_sync<T: Send + Sync>() {}
+    assert_send_sync::<Uxf>();
+    assert_send_sync::<uxf::value::Value>();
+    let uxo = uxf::parse_options(
+        "uxf 1\n=#<key: id> Person id:int name:str\n\
+        (Person 7 <Ann> 3 <Bob>)\n",
+        uxf::ParserOptions::TABLE_KEYS,
+        None,
+    )
+    .unwrap();
+    let uxo = std::sync::Arc::new(uxo);
+    let handle = {
+        let uxo = std::sync::Arc::clone(&uxo);
+        std::thread::spawn(move || {
+            let people = uxo.value().as_table().unwrap();
+            people.find_by(&3.into()).unwrap()[1].clone()
+        })
+    };
+    assert_eq!(handle.join().unwrap(), "Bob".into());
+}
+
+#[test]
+fn t_uxf_split_error() {
+    use uxf::util::split_error;
+    assert_eq!(
+        split_error("E450:a.uxf:2:expected: ttype"),
+        (450, "a.uxf", 2, "expected: ttype")
+    );
+    assert_eq!(split_error("a:b:c:d"), (0, "", 0, "a:b:c:d"));
+    assert_eq!(split_error("E1:x:y:z"), (0, "", 0, "E1:x:y:z"));
+}
@@ -1,7 +1,8 @@
 // Copyright © 2022 Mark Summerfield. All rights reserved.
 // License: GPLv3
 
-use uxf::value::Value;
+use uxf::list::List;
+use uxf::value::{Step, Value};
 
 #[test]
 fn t_single_value() {
@@ -31,3 +32,117 @@ fn t_visit() {
     // TODO
 }
 */
+
+fn values(ints: &[i64]) -> Value {
+    let mut lst = List::default();
+    lst.extend(ints.iter().map(|i| Value::from(*i)));
+    lst.into()
+}
+
+#[test]
+fn t_value_insert_remove_swap() {
+    let uxo = uxf::parse(
+        "uxf 1\n=Pair a b\n[[1 2] {<x> 1 <y> 2} (Pair 1 2 3 4)]\n",
+    )
+    .unwrap();
+    let mut lst = uxo.value().as_list().unwrap()[0].clone();
+    let mut m = uxo.value().as_list().unwrap()[1].clone();
+    let mut t = uxo.value().as_list().unwrap()[2].clone();
+    for v in [&mut lst, &mut t] {
+        v.insert_at(&Step::Index(0), values(&[0, 0])).unwrap();
+        v.swap_at(&0.into(), &1.into()).unwrap();
+        assert!(v.remove_at(&"x".into()).is_none());
+    }
+    assert_eq!(lst.to_string(), "[1\n[0\n0]\n2]");
+    assert_eq!(t.to_string(), "(Pair 1 2\n0 0\n3 4)");
+    assert_eq!(t.remove_at(&2.into()).unwrap().to_string(), "[3\n4]");
+    assert!(t
+        .insert_at(&Step::Index(0), 5.into())
+        .unwrap_err()
+        .to_string()
+        .starts_with("E762:"));
+    assert!(t
+        .insert_at(&Step::Index(0), values(&[5]))
+        .unwrap_err()
+        .to_string()
+        .starts_with("E736:"));
+    assert_eq!(m.insert_at(&"z".into(), 3.into()).unwrap(), None);
+    assert_eq!(m.insert_at(&"x".into(), 4.into()).unwrap(), Some(1.into()));
+    m.swap_at(&"x".into(), &"z".into()).unwrap();
+    assert_eq!(m.to_string(), "{<x> 3\n<y> 2\n<z> 4}");
+    assert_eq!(
+        m.swap_at(&"x".into(), &"w".into()).unwrap_err().to_string(),
+        "E761:-:0:no item with key <w> in map"
+    );
+    assert_eq!(m.remove_at(&"y".into()), Some(2.into()));
+    assert!(m
+        .insert_at(&0.into(), 1.into())
+        .unwrap_err()
+        .to_string()
+        .starts_with("E762:-:0:can't insert at Index(0) in a map"));
+}
+
+fn insertion_order(m: &Value) -> Vec<String> {
+    m.as_map()
+        .unwrap()
+        .keys_in(uxf::MapOrder::Insertion)
+        .iter()
+        .map(|key| key.to_string())
+        .collect()
+}
+
+#[test]
+fn t_value_retain_sort_dedup_extend() {
+    let uxo = uxf::parse(
+        "uxf 1\n=Pair a b\n[[3 1 2 2] {<y> 1 <x> 2 <z> 3} \
+        (Pair 3 4 1 2 1 2)]\n",
+    )
+    .unwrap();
+    let mut lst = uxo.value().as_list().unwrap()[0].clone();
+    let mut m = uxo.value().as_list().unwrap()[1].clone();
+    let mut t = uxo.value().as_list().unwrap()[2].clone();
+    // Every kind of collection is handled uniformly
+    for v in [&mut lst, &mut m, &mut t] {
+        v.sort_by(|_, a, _, b| a[0].cmp_key_order(&b[0])).unwrap();
+        v.dedup().unwrap();
+    }
+    assert_eq!(lst.to_string(), "[1\n2\n3]");
+    assert_eq!(m.to_string(), "{<x> 2\n<y> 1\n<z> 3}");
+    assert_eq!(insertion_order(&m), ["<y>", "<x>", "<z>"]); // by value
+    assert_eq!(t.to_string(), "(Pair 1 2\n3 4)");
+    // Maps can be sorted by key and retained by key
+    m.sort_by(|akey, _, bkey, _| match (akey, bkey) {
+        (Step::Key(a), Step::Key(b)) => b.cmp(a),
+        _ => unreachable!(),
+    })
+    .unwrap();
+    assert_eq!(insertion_order(&m), ["<z>", "<y>", "<x>"]);
+    m.retain(|step, _| step != &Step::Key("y".into())).unwrap();
+    assert_eq!(m.len(), Some(2));
+    lst.retain(|step, _| step != &Step::Index(1)).unwrap();
+    assert_eq!(lst.to_string(), "[1\n3]");
+    t.retain(|_, record| record[1].as_int() != Some(2)).unwrap();
+    assert_eq!(t.to_string(), "(Pair 3 4)");
+    lst.extend(values(&[5, 6])).unwrap();
+    assert_eq!(lst.to_string(), "[1\n3\n5\n6]");
+    let other = uxo.value().as_list().unwrap()[2].clone();
+    t.extend(other).unwrap();
+    assert_eq!(t.len(), Some(4));
+    m.extend(uxo.value().as_list().unwrap()[1].clone()).unwrap();
+    assert_eq!(m.to_string(), "{<x> 2\n<y> 1\n<z> 3}");
+    assert_eq!(
+        lst.extend(m.clone()).unwrap_err().to_string(),
+        "E762:-:0:can't extend a list with a map"
+    );
+    let mut scalar = Value::from(1);
+    assert!(scalar.dedup().unwrap_err().to_string().starts_with("E762:"));
+    let mut other = uxf::parse("uxf 1\n=Other a b\n(Other 1 2)\n")
+        .unwrap()
+        .value()
+        .clone();
+    assert_eq!(
+        other.extend(t).unwrap_err().to_string(),
+        "E763:-:0:can't extend a table of ttype Other with a table of \
+        ttype Pair"
+    );
+}
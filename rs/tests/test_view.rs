@@ -0,0 +1,101 @@
+// Copyright © 2022 Mark Summerfield. All rights reserved.
+// License: GPLv3
+
+use chrono::NaiveDate;
+use uxf::value::Value;
+
+static EVENTS: &str = "uxf 1
+=Event name:str when:date count:int tags:list
+(Event
+  <launch> 2022-10-01 3 [<a> <b>]
+  <review> 2022-11-15 ? []
+)
+";
+
+#[test]
+fn t_view_get() {
+    let uxo = uxf::parse(EVENTS).unwrap();
+    let table = uxo.value().as_table().unwrap();
+    let view = table.view(0).unwrap();
+    assert_eq!(view.get_str("name").unwrap(), "launch");
+    assert_eq!(
+        view.get_date("when").unwrap(),
+        NaiveDate::from_ymd(2022, 10, 1)
+    );
+    assert_eq!(view.get_int("count").unwrap(), 3);
+    assert_eq!(view.get_list("tags").unwrap().len(), 2);
+    assert_eq!(view.get("count"), Some(&Value::Int(3)));
+    assert!(view.get("nosuch").is_none());
+    assert_eq!(
+        view.get_real("count").unwrap_err().to_string(),
+        "E751:-:0:field count of table of ttype Event: expected real, \
+        got int 3"
+    );
+    assert_eq!(
+        view.get_int("nosuch").unwrap_err().to_string(),
+        "E740:-:0:table of ttype Event has no field nosuch"
+    );
+    let view = table.view(1).unwrap();
+    assert_eq!(
+        view.get_int("count").unwrap_err().to_string(),
+        "E751:-:0:field count of table of ttype Event: expected int, \
+        got null ?"
+    );
+    assert_eq!(view.get("count"), Some(&Value::Null));
+    assert!(table.view(2).is_none());
+    let names: Vec<&str> =
+        table.views().map(|v| v.get_str("name").unwrap()).collect();
+    assert_eq!(names, ["launch", "review"]);
+}
+
+#[test]
+fn t_view_mut() {
+    let mut uxo = uxf::parse(EVENTS).unwrap();
+    let table = uxo.value_mut().as_table_mut().unwrap();
+    {
+        let mut view = table.view_mut(0).unwrap();
+        *view.get_int_mut("count").unwrap() += 1;
+        view.get_str_mut("name").unwrap().push_str("ed");
+        view.get_list_mut("tags").unwrap().push("c".into());
+        assert_eq!(view.view().get_int("count").unwrap(), 4);
+        let old =
+            view.set("when", NaiveDate::from_ymd(2022, 10, 2)).unwrap();
+        assert_eq!(old, NaiveDate::from_ymd(2022, 10, 1).into());
+        assert!(view
+            .set("when", "tomorrow")
+            .unwrap_err()
+            .to_string()
+            .starts_with("E500:"));
+        assert!(view.get_real_mut("count").is_err());
+    }
+    {
+        let mut view = table.view_mut(1).unwrap();
+        assert!(view.get_int_mut("count").is_err());
+        view.set("count", 0).unwrap();
+        *view.get_int_mut("count").unwrap() = 7;
+        view.set("count", Value::Null).unwrap();
+    }
+    assert_eq!(
+        table.to_string(),
+        "(Event <launched> 2022-10-02 4 [<a>\n<b>\n<c>]\n\
+        <review> 2022-11-15 ? [])"
+    );
+}
+
+#[test]
+fn t_view_short_record() {
+    // A view of a record with fewer values than fields doesn't panic
+    let uxo = uxf::parse(EVENTS).unwrap();
+    let tclass = uxo.tclass("Event").unwrap();
+    let record = vec![Value::from("solo")];
+    let view = uxf::view::RecordView::new(tclass, &record);
+    assert_eq!(view.get_str("name").unwrap(), "solo");
+    assert!(view.get("count").is_none());
+    let err = "E752:-:0:record of ttype Event has no value for field count";
+    assert_eq!(view.get_int("count").unwrap_err().to_string(), err);
+    let mut record = record.clone();
+    let mut view = uxf::view::RecordViewMut::new(tclass, &mut record);
+    assert_eq!(view.get_int_mut("count").unwrap_err().to_string(), err);
+    assert_eq!(view.set("count", 1).unwrap_err().to_string(), err);
+    assert_eq!(view.set("name", "duo").unwrap(), "solo".into());
+}
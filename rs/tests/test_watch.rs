@@ -0,0 +1,173 @@
+// Copyright © 2022 Mark Summerfield. All rights reserved.
+// License: GPLv3
+
+#![cfg(feature = "watch")]
+
+mod utils;
+
+use std::{fs, rc::Rc, thread, time::Duration};
+use utils::temp_dir;
+use uxf::value::Step;
+use uxf::watch::{summarize, Summary, Watcher};
+
+#[test]
+fn t_watch_summarize() {
+    let old = uxf::parse(
+        "uxf 1\n=Point x y\n=Flag\n{<a> 1 <b> [1 2] <c> (Point 1 2 3 4)}\n",
+    )
+    .unwrap();
+    assert!(summarize(&old, &old).is_empty());
+    let new = uxf::parse(
+        "uxf 1 new\n=Point x y\n=Size w h\n\
+        {<a> 2 <b> [1] <c> (Point 1 5) <d> (Size 1 2)}\n",
+    )
+    .unwrap();
+    let summary = summarize(&old, &new);
+    assert_eq!(
+        summary,
+        Summary {
+            header_changed: true,
+            ttypes_added: vec!["Size".to_string()],
+            ttypes_removed: vec!["Flag".to_string()],
+            ttypes_changed: vec![],
+            added: vec![vec!["d".into()]],
+            removed: vec![
+                vec!["b".into(), Step::Index(1)],
+                vec!["c".into(), Step::Index(1)]
+            ],
+            changed: vec![
+                vec!["a".into()],
+                vec!["c".into(), Step::Index(0), Step::Field("y".into())],
+            ],
+        }
+    );
+    assert_eq!(
+        summary.to_string(),
+        "~ header\n+ =Size\n- =Flag\n+ <d>\n- <b>[1]\n- <c>[1]\n~ <a>\n\
+        ~ <c>[0].y\n"
+    );
+}
+
+#[test]
+fn t_watch_reload() {
+    let dir = temp_dir("uxf_t_watch_reload");
+    let main = dir.join("main.uxf");
+    let shapes = dir.join("shapes.uxf");
+    fs::write(&shapes, "uxf 1\n=Point x y\n[]\n").unwrap();
+    fs::write(&main, "uxf 1\n!shapes.uxf\n!complex\n[(Point 1 2)]\n")
+        .unwrap();
+    let mut watcher = Watcher::new(
+        &main.to_string_lossy(),
+        uxf::ParserOptions::DEFAULT,
+        Some(Rc::new(uxf::ignore_event)),
+    )
+    .unwrap();
+    assert_eq!(watcher.files().len(), 2);
+    assert!(watcher.files()[1].ends_with("shapes.uxf"));
+    fs::write(&main, "uxf 1\n!shapes.uxf\n[(Nosuch 1 2)]\n").unwrap();
+    let err = watcher.reload().unwrap_err();
+    assert!(err.to_string().starts_with("E450:"));
+    // The last good version is kept
+    assert_eq!(
+        watcher.uxf().to_string(),
+        "uxf 1\n!shapes.uxf\n!complex\n[(Point 1 2)]\n"
+    );
+    fs::write(&shapes, "uxf 1\n=Point x y z\n[]\n").unwrap();
+    fs::write(&main, "uxf 1\n!shapes.uxf\n[(Point 1 2 3)]\n").unwrap();
+    let summary = watcher.reload().unwrap();
+    assert_eq!(summary.ttypes_changed, vec!["Point".to_string()]);
+    assert_eq!(summary.ttypes_removed, vec!["Complex".to_string()]);
+    assert_eq!(summary.changed, vec![vec![Step::Index(0)]]);
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn t_watch_notify() {
+    let dir = temp_dir("uxf_t_watch_notify");
+    let main = dir.join("main.uxf");
+    let shapes = dir.join("shapes.uxf");
+    fs::write(&shapes, "uxf 1\n=Point x y\n[]\n").unwrap();
+    fs::write(&main, "uxf 1\n!shapes.uxf\n{<p> (Point 1 2)}\n").unwrap();
+    let mut watcher = Watcher::new(
+        &main.to_string_lossy(),
+        uxf::ParserOptions::DEFAULT,
+        Some(Rc::new(uxf::ignore_event)),
+    )
+    .unwrap();
+    let writer = {
+        let main = main.clone();
+        thread::spawn(move || {
+            thread::sleep(Duration::from_millis(200));
+            fs::write(&main, "uxf 1\n!shapes.uxf\n{<p> (Nosuch 1)}\n")
+                .unwrap();
+            thread::sleep(Duration::from_millis(200));
+            // Replace the file (as many editors do) rather than rewrite it
+            let temp = main.with_extension("tmp");
+            fs::write(&temp, "uxf 1\n!shapes.uxf\n{<p> (Point 1 3)}\n")
+                .unwrap();
+            fs::rename(&temp, &main).unwrap();
+        })
+    };
+    let mut replies = vec![];
+    watcher
+        .watch(|reply| {
+            replies.push(match reply {
+                Ok((_, summary)) => summary.to_string(),
+                Err(err) => format!("error {}", &err.to_string()[..4]),
+            });
+            replies.len() < 2
+        })
+        .unwrap();
+    writer.join().unwrap();
+    assert_eq!(
+        replies,
+        vec!["error E450".to_string(), "~ <p>[0].y\n".to_string()]
+    );
+    assert_eq!(
+        watcher.uxf().value().as_map().unwrap().to_string(),
+        "{<p> (Point 1 3)}"
+    );
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn t_watch_change_before_watch() {
+    let dir = temp_dir("uxf_t_watch_change_before_watch");
+    fs::create_dir_all(dir.join("sub")).unwrap();
+    let main = dir.join("main.uxf");
+    let shapes = dir.join("sub/shapes.uxf");
+    fs::write(&shapes, "uxf 1\n=Point x y\n[]\n").unwrap();
+    fs::write(&main, "uxf 1\n[1]\n").unwrap();
+    let mut watcher = Watcher::new(
+        &main.to_string_lossy(),
+        uxf::ParserOptions::DEFAULT,
+        Some(Rc::new(uxf::ignore_event)),
+    )
+    .unwrap();
+    // Changes made before watch() is called must not be missed
+    fs::write(&main, "uxf 1\n!sub/shapes.uxf\n[(Point 1 2)]\n").unwrap();
+    let mut replies = vec![];
+    watcher
+        .watch(|reply| {
+            replies.push(reply.unwrap().1.to_string());
+            false
+        })
+        .unwrap();
+    assert_eq!(watcher.files().len(), 2);
+    // Including changes to newly imported files in newly watched folders
+    fs::write(&shapes, "uxf 1\n=Point x y\n=Size w h\n[]\n").unwrap();
+    watcher
+        .watch(|reply| {
+            replies.push(match reply {
+                Ok((_, summary)) => summary.to_string(),
+                Err(err) => format!("error {}", &err.to_string()[..4]),
+            });
+            false
+        })
+        .unwrap();
+    assert_eq!(
+        replies,
+        vec!["+ =Point\n~ [0]\n".to_string(), "+ =Size\n".to_string()]
+    );
+    fs::remove_dir_all(&dir).unwrap();
+}
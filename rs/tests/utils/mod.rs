@@ -1,9 +1,20 @@
 // Copyright © 2022 Mark Summerfield. All rights reserved.
 // License: GPLv3
 
+use std::{fs, path::PathBuf};
 use uxf::consts::*;
 use uxf::event::{Event, EventKind};
 
+/// Returns a new empty temporary folder with the given name (removing any
+/// left over from a previous run).
+#[allow(dead_code)]
+pub fn temp_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(name);
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
 #[allow(dead_code)]
 pub fn assert_warning(event: &Event, code: u16, message: &str) {
     assert_event(event, EventKind::Warning, code, "-", 0, message);
@@ -30,6 +41,7 @@ pub fn assert_event(
     assert_eq!(event.message, message);
 }
 
+#[allow(dead_code)]
 pub fn check_error(err: &str, code: i32, name: &str) {
     match code {
         110 => assert_eq!(